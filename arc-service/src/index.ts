@@ -170,12 +170,23 @@ app.post("/api/arc/quote", async (req, res) => {
 
 // ============================================================================
 // POST /api/arc/cashout — Full cashout: TXTC → WETH → USDC on Arc
-// Body: { phone: string, userAddress: string, txtcAmount: string }
+// Body: { phone: string, userAddress: string, txtcAmount: string,
+//         settlementCurrency?: "USDC", settlementChain?: "arc" }
 // ============================================================================
 app.post("/api/arc/cashout", async (req, res) => {
   try {
-    const { phone, userAddress, txtcAmount, token } = req.body;
+    const { phone, userAddress, txtcAmount, token, settlementCurrency, settlementChain } = req.body;
     const tokenType = (token || "TXTC").toUpperCase();
+    const currency = (settlementCurrency || "USDC").toUpperCase();
+    const chain = (settlementChain || "arc").toLowerCase();
+
+    // This service only pays out USDC on Arc
+    if (currency !== "USDC" || chain !== "arc") {
+      return res.status(400).json({
+        success: false,
+        error: `Settlement in ${currency} on ${chain} is not supported here`,
+      });
+    }
 
     if (!phone || !userAddress || !txtcAmount) {
       return res.status(400).json({
@@ -6,11 +6,17 @@
 import * as dotenv from "dotenv";
 import "dotenv/config";
 import express from "express";
+import * as fs from "fs";
+import * as path from "path";
 import { SEPOLIA_CONFIG } from "./contracts.config.ts";
 import { getContractService } from "./contract-service.ts";
 import { EnsService } from "./ens-service.ts";
 import { blockchainMonitor } from "./blockchain-monitor.ts";
+import { HoldError, HoldService } from "./hold-service.ts";
+import { BonusLedger } from "./bonus-ledger.ts";
+import { BridgeEstimator } from "./bridge-estimate.ts";
 import { ethers } from "ethers";
+import { timingSafeEqual } from "crypto";
 import twilio from "twilio";
 
 const app = express();
@@ -188,9 +194,13 @@ app.get("/api/balance/:address", async (req, res) => {
 // ============================================================================
 // STEP 2b: BUY Endpoint — Purchase TXTC with Lycamobile airtime (via Reloadly)
 // ============================================================================
+// BUY request ids seen recently, so a retried SMS doesn't top up twice
+const BUY_DEDUPE_MS = 10 * 60 * 1000;
+const recentBuyRequests = new Map<string, number>();
+
 app.post("/api/buy", async (req, res) => {
   try {
-    const { userAddress, amount, userPhone } = req.body;
+    const { userAddress, amount, userPhone, requestId } = req.body;
 
     if (!userAddress || !amount) {
       return res.status(400).json({
@@ -199,6 +209,18 @@ app.post("/api/buy", async (req, res) => {
       });
     }
 
+    if (requestId) {
+      const now = Date.now();
+      for (const [id, at] of recentBuyRequests) {
+        if (now - at > BUY_DEDUPE_MS) recentBuyRequests.delete(id);
+      }
+      if (recentBuyRequests.has(requestId)) {
+        console.log(`↩️  BUY ${requestId} already received, skipping`);
+        return res.json({ success: true, message: "Buy already initiated", duplicate: true });
+      }
+      recentBuyRequests.set(requestId, now);
+    }
+
     console.log(`💰 BUY: ${amount} EUR airtime → TXTC for ${userAddress}`);
 
     // Respond immediately to avoid Twilio timeout
@@ -250,7 +272,7 @@ app.post("/api/buy", async (req, res) => {
             operatorId: opData.operatorId,
             amount: sendAmount,
             useLocalAmount: false,
-            customIdentifier: `txtc_${Date.now()}`,
+            customIdentifier: requestId ? `txtc_${requestId}` : `txtc_${Date.now()}`,
             recipientPhone: { countryCode, number: cleanPhone },
           }),
         });
@@ -445,6 +467,84 @@ app.post("/api/send", async (req, res) => {
   }
 });
 
+// ============================================================================
+// STEP 4b: Welcome bonus (mint TXTC to a new wallet). Only the SMS handler
+// may ask, with BONUS_TOKEN; the amount is capped at BONUS_MAX_TXTC and each
+// phone or address is paid at most once.
+// ============================================================================
+const bonusToken = process.env.BONUS_TOKEN || "";
+const bonusMaxTxtc = parseFloat(process.env.BONUS_MAX_TXTC || "10");
+const bonusLedger = new BonusLedger(process.env.BONUSES_FILE || "./data/bonuses.json");
+if (!bonusToken) {
+  console.warn("⚠️  BONUS_TOKEN not set - welcome bonuses disabled");
+}
+
+/** Constant-time check of the X-Bonus-Token header */
+function bonusAuthorized(given: string | undefined): boolean {
+  if (!bonusToken || !given) return false;
+  const expected = Buffer.from(bonusToken);
+  const actual = Buffer.from(given);
+  return expected.length === actual.length && timingSafeEqual(expected, actual);
+}
+
+app.post("/api/bonus", async (req, res) => {
+  if (!bonusAuthorized(req.get("X-Bonus-Token"))) {
+    return res.status(401).json({ success: false, error: "Unauthorized" });
+  }
+
+  const { userAddress, amount, userPhone } = req.body;
+  if (!userAddress || !amount || !userPhone) {
+    return res.status(400).json({
+      success: false,
+      error: "Missing userAddress, amount or userPhone",
+    });
+  }
+  const value = Number(amount);
+  if (!(value > 0) || value > bonusMaxTxtc) {
+    return res.status(400).json({
+      success: false,
+      error: `Bonus must be more than 0 and at most ${bonusMaxTxtc} TXTC`,
+    });
+  }
+  if (!bonusLedger.claim(userPhone, userAddress, String(amount))) {
+    return res.status(409).json({ success: false, error: "Bonus already granted" });
+  }
+
+  let mintTx: ethers.ContractTransactionResponse;
+  try {
+    console.log(`🎁 Welcome bonus: ${amount} TXTC → ${userAddress} (${userPhone})`);
+
+    const provider = new ethers.JsonRpcProvider(SEPOLIA_CONFIG.rpcUrl);
+    const backendSigner = new ethers.Wallet(process.env.PRIVATE_KEY!, provider);
+    const tokenContract = new ethers.Contract(
+      SEPOLIA_CONFIG.contracts.tokenXYZ,
+      ["function mint(address to, uint256 amount)"],
+      backendSigner,
+    );
+    mintTx = await tokenContract.mint(userAddress, ethers.parseEther(String(amount)));
+  } catch (error: any) {
+    console.error("❌ Bonus error:", error.message);
+    bonusLedger.release(userPhone);
+
+    return res.status(500).json({
+      success: false,
+      error: error.message,
+    });
+  }
+
+  // Answer as soon as the mint is sent: confirmation can take longer than
+  // the SMS handler waits, and by now the bonus is going out either way
+  bonusLedger.recordTx(userPhone, mintTx.hash);
+  res.json({
+    success: true,
+    txHash: mintTx.hash,
+  });
+  mintTx
+    .wait()
+    .then(() => console.log(`✅ Bonus minted: ${mintTx.hash}`))
+    .catch((e: any) => console.error(`❌ Bonus mint ${mintTx.hash} failed, check ${userPhone}:`, e.message));
+});
+
 // ============================================================================
 // STEP 5a: Yellow Network Settlement (mint TXTC on-chain after batch)
 // ============================================================================
@@ -546,11 +646,138 @@ app.post("/api/yellow/settle", async (req, res) => {
 });
 
 // ============================================================================
-// STEP 5b: SEND via Yellow Network (Instant Finality)
+// STEP 5b: Escrow holds (HOLD, and sends held for the recipient's ACCEPT)
+// The sender's funds move into the backend wallet and stay there until
+// /api/hold/release pays the recipient or /api/hold/refund pays them back.
 // ============================================================================
+const holdService = new HoldService(
+  {
+    async lock(senderKey, amount, token) {
+      const provider = new ethers.JsonRpcProvider(SEPOLIA_CONFIG.rpcUrl);
+      const sender = new ethers.Wallet(senderKey, provider);
+      const escrowAddress = new ethers.Wallet(process.env.PRIVATE_KEY!).address;
+      const amountWei = ethers.parseEther(amount);
+      if (token === "TXTC") {
+        const tokenContract = new ethers.Contract(
+          SEPOLIA_CONFIG.contracts.tokenXYZ,
+          ["function transfer(address to, uint256 amount) returns (bool)"],
+          sender,
+        );
+        const tx = await tokenContract.transfer(escrowAddress, amountWei);
+        await tx.wait();
+        return tx.hash;
+      }
+      if (token === "ETH") {
+        const tx = await sender.sendTransaction({ to: escrowAddress, value: amountWei });
+        await tx.wait();
+        return tx.hash;
+      }
+      throw new HoldError(`Unsupported token: ${token}`, 400);
+    },
+    async pay(toAddress, amount, token) {
+      const provider = new ethers.JsonRpcProvider(SEPOLIA_CONFIG.rpcUrl);
+      const escrow = new ethers.Wallet(process.env.PRIVATE_KEY!, provider);
+      const amountWei = ethers.parseEther(amount);
+      if (token === "TXTC") {
+        const tokenContract = new ethers.Contract(
+          SEPOLIA_CONFIG.contracts.tokenXYZ,
+          ["function transfer(address to, uint256 amount) returns (bool)"],
+          escrow,
+        );
+        const tx = await tokenContract.transfer(toAddress, amountWei);
+        await tx.wait();
+        return tx.hash;
+      }
+      const tx = await escrow.sendTransaction({ to: toAddress, value: amountWei });
+      await tx.wait();
+      return tx.hash;
+    },
+  },
+  process.env.HOLDS_FILE || "./data/holds.json",
+);
+
+function holdErrorStatus(error: any): number {
+  return error instanceof HoldError ? error.status : 500;
+}
+
+for (const action of ["release", "refund"] as const) {
+  app.post(`/api/hold/${action}`, async (req, res) => {
+    try {
+      const { holdId } = req.body;
+
+      if (!holdId) {
+        return res.status(400).json({
+          success: false,
+          error: "Missing holdId",
+        });
+      }
+
+      const hold = await holdService.settle(holdId, action);
+      console.log(`🔓 Hold ${holdId} ${hold.status}: ${hold.amount} ${hold.token} (${hold.settleTxHash})`);
+
+      res.json({
+        success: true,
+        holdId,
+        status: hold.status,
+        txHash: hold.settleTxHash,
+      });
+    } catch (error: any) {
+      console.error(`❌ Hold ${action} error:`, error.message);
+      res.status(holdErrorStatus(error)).json({
+        success: false,
+        error: error.message,
+      });
+    }
+  });
+}
+
+// ============================================================================
+// STEP 5c: SEND via Yellow Network (Instant Finality)
+// ============================================================================
+// Transfers made in the last week, so the SMS handler can reconcile its
+// records. Kept in TRANSFERS_FILE so a restart doesn't lose them.
+const TRANSFER_LOG_MS = 7 * 24 * 60 * 60 * 1000;
+const transfersFile = process.env.TRANSFERS_FILE || "./data/transfers.json";
+
+interface LoggedTransfer {
+  txHash: string;
+  fromAddress: string;
+  toAddress: string;
+  amount: string;
+  token: string;
+  at: string;
+}
+
+function loadTransfers(): LoggedTransfer[] {
+  try {
+    if (fs.existsSync(transfersFile)) {
+      return JSON.parse(fs.readFileSync(transfersFile, "utf8")) as LoggedTransfer[];
+    }
+  } catch (e: any) {
+    console.error("⚠️  Failed to load transfers file:", e.message);
+  }
+  return [];
+}
+
+const recentTransfers: LoggedTransfer[] = loadTransfers();
+
+function recordTransfer(txHash: string, fromAddress: string, toAddress: string, amount: any, token: string) {
+  const cutoff = Date.now() - TRANSFER_LOG_MS;
+  while (recentTransfers.length > 0 && Date.parse(recentTransfers[0].at) < cutoff) {
+    recentTransfers.shift();
+  }
+  recentTransfers.push({ txHash, fromAddress, toAddress, amount: String(amount), token, at: new Date().toISOString() });
+  try {
+    fs.mkdirSync(path.dirname(transfersFile), { recursive: true });
+    fs.writeFileSync(transfersFile, JSON.stringify(recentTransfers, null, 2));
+  } catch (e: any) {
+    console.error("⚠️  Failed to save transfers file:", e.message);
+  }
+}
+
 app.post("/api/send-yellow", async (req, res) => {
   try {
-    const { fromAddress, toAddress, amount, token, userPhone, senderKey } = req.body;
+    const { fromAddress, toAddress, amount, token, userPhone, senderKey, channelId, hold } = req.body;
 
     if (!fromAddress || !toAddress || !amount || !token) {
       return res.status(400).json({
@@ -559,6 +786,32 @@ app.post("/api/send-yellow", async (req, res) => {
       });
     }
 
+    // Held sends go into escrow; the recipient is paid on /api/hold/release
+    if (hold) {
+      try {
+        const held = await holdService.create({
+          fromAddress,
+          toAddress,
+          amount,
+          token: token.toUpperCase(),
+          userPhone,
+          senderKey,
+        });
+        console.log(`🔒 Held ${amount} ${token} from ${fromAddress} for ${toAddress} [${held.holdId}]`);
+        return res.json({
+          success: true,
+          holdId: held.holdId,
+          txHash: held.lockTxHash,
+        });
+      } catch (holdError: any) {
+        console.error("❌ Hold error:", holdError.message);
+        return res.status(holdErrorStatus(holdError)).json({
+          success: false,
+          error: holdError.message,
+        });
+      }
+    }
+
     console.log(`🟡 Yellow Send: ${amount} ${token} from ${fromAddress} to ${toAddress}`);
 
     // Queue transaction with Yellow batch service
@@ -573,6 +826,8 @@ app.post("/api/send-yellow", async (req, res) => {
           token: token,
           fromAddress: fromAddress,
           senderKey: senderKey || "",
+          // The sender's open channel, reused instead of opening another
+          channelId: channelId || undefined,
         }),
       });
 
@@ -580,6 +835,7 @@ app.post("/api/send-yellow", async (req, res) => {
 
       if (yellowResult.success) {
         console.log(`✅ Queued via Yellow: ${yellowResult.transactionId}`);
+        recordTransfer(yellowResult.transactionId, fromAddress, toAddress, amount, token);
 
         // Send SMS notification
         if (twilioClient && twilioPhoneNumber && userPhone) {
@@ -597,6 +853,8 @@ app.post("/api/send-yellow", async (req, res) => {
         res.json({
           success: true,
           transactionId: yellowResult.transactionId,
+          channelId: yellowResult.channelId || channelId,
+          channelBalance: yellowResult.channelBalance,
           message: "Queued via Yellow Network",
           estimatedProcessing: "Within 3 minutes",
         });
@@ -626,6 +884,7 @@ app.post("/api/send-yellow", async (req, res) => {
         await mintTx.wait();
 
         console.log(`✅ On-chain TXTC transfer complete`);
+        recordTransfer(mintTx.hash, fromAddress, toAddress, amount, token);
 
         // Send SMS notification
         if (twilioClient && twilioPhoneNumber && userPhone) {
@@ -664,6 +923,7 @@ app.post("/api/send-yellow", async (req, res) => {
         await tx.wait();
 
         console.log(`✅ On-chain ETH transfer complete: ${tx.hash}`);
+        recordTransfer(tx.hash, fromAddress, toAddress, amount, token);
 
         if (twilioClient && twilioPhoneNumber && userPhone) {
           try {
@@ -698,6 +958,21 @@ app.post("/api/send-yellow", async (req, res) => {
   }
 });
 
+// Transfers made in a window (?since=&until=, ISO 8601), for reconciliation
+app.get("/api/transfers", (req, res) => {
+  const since = req.query.since ? Date.parse(String(req.query.since)) : 0;
+  const until = req.query.until ? Date.parse(String(req.query.until)) : Date.now();
+  if (isNaN(since) || isNaN(until)) {
+    return res.status(400).json({ success: false, error: "Invalid since or until" });
+  }
+
+  const transfers = recentTransfers.filter((t) => {
+    const at = Date.parse(t.at);
+    return at >= since && at < until;
+  });
+  res.json({ success: true, transfers });
+});
+
 // ============================================================================
 // Utility Endpoints
 // ============================================================================
@@ -744,6 +1019,75 @@ app.post("/api/quote", async (req, res) => {
   }
 });
 
+// Bridge fee estimate for BRIDGE, from a Li.Fi quote. The fee is paid in the
+// source chain's native token; the user's balance of it comes from that
+// chain's RPC (BRIDGE_RPC_<chainId> overrides the public default).
+const LIFI_API_URL = process.env.LIFI_API_URL || "https://li.quest/v1";
+const BRIDGE_RPC_URLS: Record<number, string> = {
+  1: "https://eth.llamarpc.com",
+  137: "https://polygon-rpc.com",
+  8453: "https://mainnet.base.org",
+  42161: "https://arb1.arbitrum.io/rpc",
+  11155111: SEPOLIA_CONFIG.rpcUrl,
+};
+
+async function lifiGet(route: string, params: Record<string, string | number>): Promise<any> {
+  const query = new URLSearchParams(Object.entries(params).map(([k, v]) => [k, String(v)]));
+  const response = await fetch(`${LIFI_API_URL}/${route}?${query}`, {
+    headers: process.env.LIFI_API_KEY ? { "x-lifi-api-key": process.env.LIFI_API_KEY } : {},
+  });
+  const data = await response.json() as any;
+  if (!response.ok) throw new Error(`Li.Fi ${route}: ${data.message || response.status}`);
+  return data;
+}
+
+const bridgeEstimator = new BridgeEstimator({
+  async decimals(chainId, token) {
+    return (await lifiGet("token", { chain: chainId, token })).decimals;
+  },
+  async quote(req) {
+    return lifiGet("quote", req);
+  },
+  async nativeBalance(chainId, address) {
+    const rpcUrl = process.env[`BRIDGE_RPC_${chainId}`] || BRIDGE_RPC_URLS[chainId];
+    if (!rpcUrl) return undefined;
+    return new ethers.JsonRpcProvider(rpcUrl).getBalance(address);
+  },
+});
+
+app.post("/api/bridge/estimate", async (req, res) => {
+  try {
+    const { fromChainId, toChainId, fromToken, toToken, amount, userAddress } = req.body;
+
+    if (!fromChainId || !toChainId || !fromToken || !toToken || !amount || !userAddress) {
+      return res.status(400).json({
+        success: false,
+        error: "Missing required fields",
+      });
+    }
+
+    const estimate = await bridgeEstimator.estimate({
+      fromChainId: Number(fromChainId),
+      toChainId: Number(toChainId),
+      fromToken,
+      toToken,
+      amount: String(amount),
+      userAddress,
+    });
+
+    res.json({
+      success: true,
+      ...estimate,
+    });
+  } catch (error: any) {
+    console.error("❌ Bridge estimate error:", error.message);
+    res.status(500).json({
+      success: false,
+      error: error.message,
+    });
+  }
+});
+
 // Contract addresses info
 app.get("/api/contracts", (req, res) => {
   res.json({
@@ -913,6 +1257,9 @@ app.listen(PORT, () => {
   console.log("  GET  /api/balance/:address - Get balance");
   console.log("  POST /api/swap      - Swap tokens for ETH");
   console.log("  POST /api/send      - Send tokens");
+  console.log("  POST /api/bonus     - Welcome bonus (X-Bonus-Token)");
+  console.log("  POST /api/hold/release - Pay a held transfer to its recipient");
+  console.log("  POST /api/hold/refund  - Return a held transfer to its sender");
   console.log("  GET  /api/price     - Get current price");
   console.log("  POST /api/quote     - Get swap quote");
   console.log("  GET  /api/contracts - Contract addresses");
@@ -0,0 +1,32 @@
+import { test } from "node:test";
+import * as assert from "node:assert/strict";
+import * as fs from "fs";
+import * as os from "os";
+import * as path from "path";
+import { BonusLedger } from "./bonus-ledger.ts";
+
+function bonusFile() {
+  return path.join(fs.mkdtempSync(path.join(os.tmpdir(), "bonuses-")), "bonuses.json");
+}
+
+test("a phone or an address gets one bonus", () => {
+  const ledger = new BonusLedger(bonusFile());
+  assert.ok(ledger.claim("+15550001111", "0xAbC", "5"));
+  assert.ok(!ledger.claim("+15550001111", "0xdef", "5"));
+  // Same wallet, whatever the case, under another phone
+  assert.ok(!ledger.claim("+15550002222", "0xabc", "5"));
+  assert.ok(ledger.claim("+15550002222", "0xdef", "5"));
+});
+
+test("a released claim can be made again", () => {
+  const ledger = new BonusLedger(bonusFile());
+  assert.ok(ledger.claim("+15550001111", "0xabc", "5"));
+  ledger.release("+15550001111");
+  assert.ok(ledger.claim("+15550001111", "0xabc", "5"));
+});
+
+test("grants survive a restart", () => {
+  const file = bonusFile();
+  new BonusLedger(file).claim("+15550001111", "0xabc", "5");
+  assert.ok(!new BonusLedger(file).claim("+15550001111", "0xabc", "5"));
+});
@@ -0,0 +1,66 @@
+import * as fs from "fs";
+import * as path from "path";
+
+/** One welcome bonus handed out */
+export interface BonusGrant {
+  userPhone: string;
+  userAddress: string;
+  amount: string;
+  txHash?: string;
+  grantedAt: string;
+}
+
+/**
+ * Welcome bonuses already granted. A phone or an address that has had one
+ * can't claim another, and the ledger is kept in a JSON file so a backend
+ * restart doesn't forget who was paid.
+ */
+export class BonusLedger {
+  private grants: BonusGrant[] = [];
+
+  constructor(private file: string) {
+    try {
+      if (fs.existsSync(file)) {
+        this.grants = JSON.parse(fs.readFileSync(file, "utf8")) as BonusGrant[];
+        console.log(`📂 Loaded ${this.grants.length} bonus grants from disk`);
+      }
+    } catch (e: any) {
+      console.error("⚠️  Failed to load bonus file:", e.message);
+    }
+  }
+
+  /** Claim the bonus before minting it; false if the phone or address already has one */
+  claim(userPhone: string, userAddress: string, amount: string): boolean {
+    const address = userAddress.toLowerCase();
+    if (this.grants.some((g) => g.userPhone === userPhone || g.userAddress === address)) {
+      return false;
+    }
+    this.grants.push({ userPhone, userAddress: address, amount, grantedAt: new Date().toISOString() });
+    this.save();
+    return true;
+  }
+
+  /** Record the mint transaction for a claimed bonus */
+  recordTx(userPhone: string, txHash: string) {
+    const grant = this.grants.find((g) => g.userPhone === userPhone);
+    if (grant) {
+      grant.txHash = txHash;
+      this.save();
+    }
+  }
+
+  /** Give a claim back when the mint couldn't be sent */
+  release(userPhone: string) {
+    this.grants = this.grants.filter((g) => g.userPhone !== userPhone);
+    this.save();
+  }
+
+  private save() {
+    try {
+      fs.mkdirSync(path.dirname(this.file), { recursive: true });
+      fs.writeFileSync(this.file, JSON.stringify(this.grants, null, 2));
+    } catch (e: any) {
+      console.error("⚠️  Failed to save bonus file:", e.message);
+    }
+  }
+}
@@ -0,0 +1,63 @@
+import { test } from "node:test";
+import * as assert from "node:assert/strict";
+import { BridgeEstimator, type BridgeQuote, type BridgeQuoter } from "./bridge-estimate.ts";
+
+const matic = { symbol: "MATIC", decimals: 18 };
+const usdc = { symbol: "USDC", decimals: 6 };
+
+/** A quoter answering with `quote` and `balance`, recording the amount asked for */
+function fakeQuoter(quote: BridgeQuote, balance?: bigint) {
+  const asked: string[] = [];
+  const quoter: BridgeQuoter = {
+    async decimals() {
+      return 6;
+    },
+    async quote(req) {
+      asked.push(req.fromAmount);
+      return quote;
+    },
+    async nativeBalance() {
+      return balance;
+    },
+  };
+  return { quoter, asked };
+}
+
+const request = {
+  fromChainId: 137,
+  toChainId: 8453,
+  fromToken: "USDC",
+  toToken: "ETH",
+  amount: "10",
+  userAddress: "0xuser",
+};
+
+test("fee is the gas plus fees not taken from the amount", async () => {
+  const { quoter, asked } = fakeQuoter(
+    {
+      estimate: {
+        gasCosts: [{ amount: "1500000000000000", token: matic }],
+        feeCosts: [
+          { amount: "500000000000000", included: false, token: matic },
+          // Taken out of the bridged USDC, so not on top of it
+          { amount: "30000", included: true, token: usdc },
+        ],
+      },
+    },
+    10n ** 15n,
+  );
+
+  const estimate = await new BridgeEstimator(quoter).estimate(request);
+  assert.deepEqual(estimate, { fee: "0.002", feeToken: "MATIC", feeTokenBalance: "0.001" });
+  assert.deepEqual(asked, ["10000000"]);
+});
+
+test("balance is left out when the chain has no RPC", async () => {
+  const { quoter } = fakeQuoter({ estimate: { gasCosts: [{ amount: "2000000000000000", token: matic }] } });
+  assert.deepEqual(await new BridgeEstimator(quoter).estimate(request), { fee: "0.002", feeToken: "MATIC" });
+});
+
+test("a quote without gas costs is refused", async () => {
+  const { quoter } = fakeQuoter({ estimate: {} });
+  await assert.rejects(new BridgeEstimator(quoter).estimate(request), /no gas cost/);
+});
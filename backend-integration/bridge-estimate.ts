@@ -0,0 +1,83 @@
+import { ethers } from "ethers";
+
+/** The parts of a Li.Fi quote the fee estimate reads */
+export interface BridgeQuote {
+  estimate: {
+    gasCosts?: { amount: string; token: { symbol: string; decimals: number } }[];
+    feeCosts?: { amount: string; included: boolean; token: { symbol: string; decimals: number } }[];
+  };
+}
+
+/** Lookups the estimator needs; api-server.ts wires in Li.Fi and ethers */
+export interface BridgeQuoter {
+  /** Decimals of `token` (a symbol or address) on `chainId` */
+  decimals(chainId: number, token: string): Promise<number>;
+  /** Li.Fi quote for bridging `fromAmount` base units */
+  quote(req: {
+    fromChain: number;
+    toChain: number;
+    fromToken: string;
+    toToken: string;
+    fromAmount: string;
+    fromAddress: string;
+  }): Promise<BridgeQuote>;
+  /** Native balance of `address` on `chainId`, or undefined when the chain has no RPC */
+  nativeBalance(chainId: number, address: string): Promise<bigint | undefined>;
+}
+
+export interface BridgeFeeEstimate {
+  fee: string;
+  feeToken: string;
+  feeTokenBalance?: string;
+}
+
+/**
+ * What a bridge costs on the source chain, on top of the bridged amount:
+ * the gas plus any protocol fee Li.Fi doesn't take out of the amount.
+ * Both are paid in the source chain's native token.
+ */
+export function feeFromQuote(quote: BridgeQuote): { fee: bigint; feeToken: string; decimals: number } {
+  const gas = quote.estimate.gasCosts || [];
+  if (gas.length === 0) {
+    throw new Error("Quote has no gas cost");
+  }
+  const { symbol, decimals } = gas[0].token;
+  let fee = gas.reduce((sum, cost) => sum + BigInt(cost.amount), 0n);
+  for (const cost of quote.estimate.feeCosts || []) {
+    if (!cost.included && cost.token.symbol === symbol) {
+      fee += BigInt(cost.amount);
+    }
+  }
+  return { fee, feeToken: symbol, decimals };
+}
+
+/** Fee estimates for BRIDGE, so the SMS handler can stop a bridge the user can't pay for */
+export class BridgeEstimator {
+  constructor(private quoter: BridgeQuoter) {}
+
+  async estimate(req: {
+    fromChainId: number;
+    toChainId: number;
+    fromToken: string;
+    toToken: string;
+    amount: string;
+    userAddress: string;
+  }): Promise<BridgeFeeEstimate> {
+    const decimals = await this.quoter.decimals(req.fromChainId, req.fromToken);
+    const quote = await this.quoter.quote({
+      fromChain: req.fromChainId,
+      toChain: req.toChainId,
+      fromToken: req.fromToken,
+      toToken: req.toToken,
+      fromAmount: ethers.parseUnits(req.amount, decimals).toString(),
+      fromAddress: req.userAddress,
+    });
+    const { fee, feeToken, decimals: feeDecimals } = feeFromQuote(quote);
+    const balance = await this.quoter.nativeBalance(req.fromChainId, req.userAddress);
+    return {
+      fee: ethers.formatUnits(fee, feeDecimals),
+      feeToken,
+      ...(balance === undefined ? {} : { feeTokenBalance: ethers.formatUnits(balance, feeDecimals) }),
+    };
+  }
+}
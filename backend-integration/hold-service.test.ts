@@ -0,0 +1,74 @@
+import { test } from "node:test";
+import * as assert from "node:assert/strict";
+import * as fs from "fs";
+import * as os from "os";
+import * as path from "path";
+import { HoldService, type HoldMover } from "./hold-service.ts";
+
+/** A mover that records payouts instead of touching the chain */
+function fakeMover(failPay = false) {
+  const paid: { toAddress: string; amount: string; token: string }[] = [];
+  const mover: HoldMover = {
+    async lock() {
+      return "0xlock";
+    },
+    async pay(toAddress, amount, token) {
+      if (failPay) throw new Error("RPC down");
+      paid.push({ toAddress, amount, token });
+      return `0xpay${paid.length}`;
+    },
+  };
+  return { mover, paid };
+}
+
+function holdsFile() {
+  return path.join(fs.mkdtempSync(path.join(os.tmpdir(), "holds-")), "holds.json");
+}
+
+const request = {
+  fromAddress: "0xsender",
+  toAddress: "0xrecipient",
+  amount: "5",
+  token: "TXTC",
+  userPhone: "+15550001111",
+  senderKey: "0xkey",
+};
+
+test("release pays the recipient once", async () => {
+  const { mover, paid } = fakeMover();
+  const holds = new HoldService(mover, holdsFile());
+  const hold = await holds.create(request);
+
+  assert.equal((await holds.settle(hold.holdId, "release")).status, "released");
+  assert.deepEqual(paid, [{ toAddress: "0xrecipient", amount: "5", token: "TXTC" }]);
+  await assert.rejects(holds.settle(hold.holdId, "refund"), { status: 409 });
+  assert.equal(paid.length, 1);
+});
+
+test("refund pays the sender back", async () => {
+  const { mover, paid } = fakeMover();
+  const holds = new HoldService(mover, holdsFile());
+  const hold = await holds.create(request);
+
+  assert.equal((await holds.settle(hold.holdId, "refund")).status, "refunded");
+  assert.deepEqual(paid, [{ toAddress: "0xsender", amount: "5", token: "TXTC" }]);
+});
+
+test("unknown holds are refused", async () => {
+  const holds = new HoldService(fakeMover().mover, holdsFile());
+  await assert.rejects(holds.settle("missing", "release"), { status: 404 });
+});
+
+test("a failed payout leaves the hold to retry", async () => {
+  const file = holdsFile();
+  const hold = await new HoldService(fakeMover(true).mover, file).create(request);
+  const failing = new HoldService(fakeMover(true).mover, file);
+  await assert.rejects(failing.settle(hold.holdId, "release"), /RPC down/);
+  assert.equal(failing.get(hold.holdId)?.status, "held");
+
+  // And survives a restart
+  const { mover, paid } = fakeMover();
+  const restarted = new HoldService(mover, file);
+  assert.equal((await restarted.settle(hold.holdId, "release")).status, "released");
+  assert.equal(paid.length, 1);
+});
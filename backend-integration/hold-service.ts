@@ -0,0 +1,133 @@
+import * as fs from "fs";
+import * as path from "path";
+import { randomUUID } from "crypto";
+
+/**
+ * A transfer held in escrow: the sender's funds sit in the backend wallet
+ * until the SMS handler releases them to the recipient or refunds them.
+ */
+export interface Hold {
+  holdId: string;
+  fromAddress: string;
+  toAddress: string;
+  amount: string;
+  token: string;
+  userPhone: string;
+  status: "held" | "settling" | "released" | "refunded";
+  lockTxHash: string;
+  settleTxHash?: string;
+  createdAt: string;
+  settledAt?: string;
+}
+
+/** On-chain moves the hold service needs; api-server.ts wires in ethers */
+export interface HoldMover {
+  /** Move `amount` of `token` from the sender's wallet into escrow */
+  lock(senderKey: string, amount: string, token: string): Promise<string>;
+  /** Pay `amount` of `token` out of escrow to `toAddress` */
+  pay(toAddress: string, amount: string, token: string): Promise<string>;
+}
+
+/** Refusal with the HTTP status the endpoint should answer with */
+export class HoldError extends Error {
+  constructor(message: string, readonly status: number) {
+    super(message);
+  }
+}
+
+/**
+ * Escrow for HOLD and approval-held transfers. Holds are kept in a JSON
+ * file so a backend restart doesn't strand funds it has locked.
+ */
+export class HoldService {
+  private holds: Map<string, Hold> = new Map();
+
+  constructor(private mover: HoldMover, private file: string) {
+    try {
+      if (fs.existsSync(file)) {
+        for (const hold of JSON.parse(fs.readFileSync(file, "utf8")) as Hold[]) {
+          // A payout interrupted by a restart may or may not have gone out
+          if (hold.status === "settling") {
+            console.warn(`⚠️  Hold ${hold.holdId} was settling at shutdown, check ${hold.toAddress} before retrying`);
+          }
+          this.holds.set(hold.holdId, hold);
+        }
+        console.log(`📂 Loaded ${this.holds.size} holds from disk`);
+      }
+    } catch (e: any) {
+      console.error("⚠️  Failed to load holds file:", e.message);
+    }
+  }
+
+  /** Lock the sender's funds and return the new hold */
+  async create(req: {
+    fromAddress: string;
+    toAddress: string;
+    amount: string;
+    token: string;
+    userPhone?: string;
+    senderKey: string;
+  }): Promise<Hold> {
+    if (!req.senderKey) {
+      throw new HoldError("Missing senderKey", 400);
+    }
+    const lockTxHash = await this.mover.lock(req.senderKey, req.amount, req.token);
+    const hold: Hold = {
+      holdId: randomUUID(),
+      fromAddress: req.fromAddress,
+      toAddress: req.toAddress,
+      amount: req.amount,
+      token: req.token,
+      userPhone: req.userPhone || "",
+      status: "held",
+      lockTxHash,
+      createdAt: new Date().toISOString(),
+    };
+    this.holds.set(hold.holdId, hold);
+    this.save();
+    return hold;
+  }
+
+  /**
+   * Pay a held transfer to its recipient (release) or back to its sender
+   * (refund). The addresses come from the hold, never from the caller.
+   */
+  async settle(holdId: string, action: "release" | "refund"): Promise<Hold> {
+    const hold = this.holds.get(holdId);
+    if (!hold) {
+      throw new HoldError(`Unknown hold ${holdId}`, 404);
+    }
+    if (hold.status !== "held") {
+      throw new HoldError(`Hold ${holdId} is already ${hold.status}`, 409);
+    }
+
+    // Claimed before paying, so a concurrent call can't pay it twice
+    hold.status = "settling";
+    this.save();
+    const to = action === "release" ? hold.toAddress : hold.fromAddress;
+    try {
+      hold.settleTxHash = await this.mover.pay(to, hold.amount, hold.token);
+    } catch (e) {
+      hold.status = "held";
+      this.save();
+      throw e;
+    }
+    hold.status = action === "release" ? "released" : "refunded";
+    hold.settledAt = new Date().toISOString();
+    this.save();
+    return hold;
+  }
+
+  get(holdId: string): Hold | undefined {
+    return this.holds.get(holdId);
+  }
+
+  private save() {
+    try {
+      fs.mkdirSync(path.dirname(this.file), { recursive: true });
+      fs.writeFileSync(this.file, JSON.stringify([...this.holds.values()], null, 2));
+    } catch (e: any) {
+      console.error("⚠️  Failed to save holds file:", e.message);
+    }
+  }
+}
@@ -1,20 +1,43 @@
 use axum::{
-    extract::State,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
     routing::{get, post},
     Json, Router,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use subtle::ConstantTimeEq;
 
-use crate::db::VoucherRepository;
+use crate::commands::cache::{LookupCacheStats, LookupCaches};
+use crate::db::{AuditEntry, AuditQuery, AuditRepository, TransferRepository, VoucherRepository};
+use crate::reconcile::{reconcile, ReconcileReport};
+
+/// Most audit entries returned by one query
+const MAX_AUDIT_ENTRIES: i64 = 500;
+
+/// Pending transfers older than this are reported stuck unless `stale_mins` says otherwise
+const DEFAULT_STALE_MINS: i64 = 30;
 
 /// Admin routes state
 #[derive(Clone)]
 pub struct AdminState {
     pub voucher_repo: Arc<VoucherRepository>,
+    pub audit_repo: Arc<AuditRepository>,
+    pub transfer_repo: Arc<TransferRepository>,
+    /// Backend asked for the transfers it made, when reconciling
+    pub backend_url: String,
+    /// The command processor's ENS and user caches, for their hit/miss counts
+    pub lookup_caches: LookupCaches,
     pub admin_token: String,
 }
 
+/// Whether the request carries `admin_token` in X-Admin-Token (compared in constant time)
+pub fn has_admin_token(headers: &HeaderMap, admin_token: &str) -> bool {
+    let given = headers.get("x-admin-token").map(|v| v.as_bytes()).unwrap_or_default();
+    bool::from(given.ct_eq(admin_token.as_bytes()))
+}
+
 /// Request to create vouchers
 #[derive(Debug, Deserialize)]
 pub struct CreateVouchersRequest {
@@ -58,6 +81,9 @@ pub fn admin_routes(state: AdminState) -> Router {
         .route("/vouchers", post(create_vouchers))
         .route("/vouchers", get(get_voucher_stats))
         .route("/vouchers/list", get(list_vouchers))
+        .route("/audit", get(query_audit))
+        .route("/reconcile", get(reconcile_report))
+        .route("/stats/cache", get(cache_stats))
         .with_state(state)
 }
 
@@ -137,3 +163,203 @@ async fn list_vouchers(State(_state): State<AdminState>) -> Json<ListVouchersRes
         vouchers: vec![],
     })
 }
+
+/// Audit log filters: `?phone=+1555...&since=2025-01-01T00:00:00Z&until=...&limit=50`
+#[derive(Debug, Deserialize)]
+pub struct AuditParams {
+    pub phone: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+/// Audit query response
+#[derive(Debug, Serialize)]
+pub struct AuditResponse {
+    pub count: usize,
+    pub entries: Vec<AuditEntry>,
+}
+
+/// Query the command audit trail by phone and/or time range (requires `X-Admin-Token`)
+async fn query_audit(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Query(params): Query<AuditParams>,
+) -> Result<Json<AuditResponse>, StatusCode> {
+    if !has_admin_token(&headers, &state.admin_token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let filter = AuditQuery {
+        phone: params.phone,
+        since: params.since,
+        until: params.until,
+        limit: params.limit.unwrap_or(100).clamp(1, MAX_AUDIT_ENTRIES),
+    };
+    match state.audit_repo.query(&filter).await {
+        Ok(entries) => Ok(Json(AuditResponse { count: entries.len(), entries })),
+        Err(e) => {
+            tracing::error!("Audit query failed: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Reconciliation window: `?since=...&until=...&stale_mins=30` (default: the last 24 hours)
+#[derive(Debug, Deserialize)]
+pub struct ReconcileParams {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub stale_mins: Option<i64>,
+}
+
+/// Compare recorded SENDs with the backend's transfers and list discrepancies (requires `X-Admin-Token`)
+async fn reconcile_report(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Query(params): Query<ReconcileParams>,
+) -> Result<Json<ReconcileReport>, StatusCode> {
+    if !has_admin_token(&headers, &state.admin_token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let until = params.until.unwrap_or_else(Utc::now);
+    let since = params.since.unwrap_or(until - chrono::Duration::hours(24));
+    let stale_after = chrono::Duration::minutes(params.stale_mins.unwrap_or(DEFAULT_STALE_MINS).max(0));
+    match reconcile(&state.transfer_repo, &state.backend_url, since, until, stale_after).await {
+        Ok(report) => Ok(Json(report)),
+        Err(e) => {
+            tracing::error!("Reconciliation failed: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Hit/miss counts of the recipient lookup caches (requires `X-Admin-Token`)
+async fn cache_stats(State(state): State<AdminState>, headers: HeaderMap) -> Result<Json<LookupCacheStats>, StatusCode> {
+    if !has_admin_token(&headers, &state.admin_token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(Json(state.lookup_caches.stats()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::hash_phone;
+    use crate::test_support::{db_processor, spawn_mock_backend};
+
+    #[test]
+    fn test_admin_token_check() {
+        let headers = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-admin-token", token.parse().unwrap());
+            headers
+        };
+        assert!(has_admin_token(&headers("secret"), "secret"));
+        assert!(!has_admin_token(&headers("secreT"), "secret"));
+        assert!(!has_admin_token(&headers("secret2"), "secret"));
+        assert!(!has_admin_token(&headers("secre"), "secret"));
+        assert!(!has_admin_token(&HeaderMap::new(), "secret"));
+    }
+
+    #[tokio::test]
+    async fn test_send_is_audited_with_redacted_args() {
+        let Some((processor, pool)) = db_processor("http://127.0.0.1:9").await else { return };
+        let phone = crate::test_support::random_phone();
+
+        processor.process(&phone, "SEND 5 TXTC TO +15551234567").await;
+        processor.process(&phone, "PIN 4321").await;
+
+        let state = AdminState {
+            voucher_repo: Arc::new(VoucherRepository::new(pool.clone())),
+            audit_repo: Arc::new(AuditRepository::new(pool.clone())),
+            transfer_repo: Arc::new(TransferRepository::new(pool)),
+            backend_url: "http://127.0.0.1:9".to_string(),
+            lookup_caches: LookupCaches::new(std::time::Duration::ZERO, std::time::Duration::ZERO),
+            admin_token: "secret".to_string(),
+        };
+        let url = spawn_mock_backend(admin_routes(state)).await;
+        let client = reqwest::Client::new();
+        let query = [("phone", phone.as_str()), ("since", "2000-01-01T00:00:00Z")];
+
+        let denied = client.get(format!("{}/audit", url)).query(&query).send().await.unwrap();
+        assert_eq!(denied.status(), 401);
+
+        let body: serde_json::Value = client
+            .get(format!("{}/audit", url))
+            .query(&query)
+            .header("x-admin-token", "secret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let entries = body["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2, "{}", body);
+
+        // Newest first
+        assert_eq!(entries[0]["command"], "PIN");
+        assert_eq!(entries[0]["args"], "[redacted]");
+        assert_eq!(entries[1]["command"], "SEND");
+        assert_eq!(entries[1]["args"], "5 TXTC to +1***4567");
+        assert_eq!(entries[1]["phone_hash"], hash_phone(&phone));
+        assert!(!body.to_string().contains("4321") && !body.to_string().contains(&phone));
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_reports_stuck_pending_transfer() {
+        let Some((_, pool)) = db_processor("http://127.0.0.1:9").await else { return };
+        let transfers = TransferRepository::new(pool.clone());
+        let to_address = format!("0x{:040x}", rand::random::<u64>());
+        let id = transfers
+            .create_pending("+15550000001", "0xfrom", &to_address, 7.0, "TXTC")
+            .await
+            .unwrap();
+        sqlx::query("UPDATE outgoing_transfers SET created_at = NOW() - INTERVAL '2 hours' WHERE id = $1")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let backend = spawn_mock_backend(axum::Router::new().route(
+            "/api/transfers",
+            get(|| async { Json(serde_json::json!({ "success": true, "transfers": [] })) }),
+        ))
+        .await;
+        let state = AdminState {
+            voucher_repo: Arc::new(VoucherRepository::new(pool.clone())),
+            audit_repo: Arc::new(AuditRepository::new(pool)),
+            transfer_repo: Arc::new(transfers),
+            backend_url: backend,
+            lookup_caches: LookupCaches::new(std::time::Duration::ZERO, std::time::Duration::ZERO),
+            admin_token: "secret".to_string(),
+        };
+        let url = spawn_mock_backend(admin_routes(state)).await;
+        let client = reqwest::Client::new();
+
+        let denied = client.get(format!("{}/reconcile", url)).send().await.unwrap();
+        assert_eq!(denied.status(), 401);
+
+        let body: serde_json::Value = client
+            .get(format!("{}/reconcile", url))
+            .header("x-admin-token", "secret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["backendChecked"], true, "{}", body);
+        let stuck = body["discrepancies"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|d| d["transferId"] == id.to_string())
+            .expect("seeded transfer reported");
+        assert_eq!(stuck["toAddress"], to_address);
+        assert_eq!(stuck["kind"], "pending_too_long");
+        assert_eq!(stuck["recordedStatus"], "pending");
+    }
+}
@@ -0,0 +1,206 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::db::User;
+
+/// Hit/miss counters for one cache, as shown in admin stats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+/// Cache-aside store: callers look here first and fill it after loading
+/// from the source. Entries expire after `ttl`; a zero ttl caches nothing.
+#[derive(Clone)]
+pub struct LookupCache<T> {
+    entries: Arc<Mutex<HashMap<String, (T, Instant)>>>,
+    ttl: Duration,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl<T: Clone> LookupCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Cached value, counting a hit or a miss
+    pub fn get(&self, key: &str) -> Option<T> {
+        let mut entries = self.entries.lock().unwrap();
+        let found = match entries.get(key) {
+            Some((value, at)) if at.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if found.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    pub fn put(&self, key: &str, value: T) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (_, at)| at.elapsed() < self.ttl);
+        entries.insert(key.to_string(), (value, Instant::now()));
+    }
+
+    /// Cached value, else `load`'s, which is cached when found. Errors and
+    /// "not found" aren't cached, so a new JOIN is seen straight away.
+    pub async fn get_or_load<E>(
+        &self,
+        key: &str,
+        load: impl Future<Output = Result<Option<T>, E>>,
+    ) -> Result<Option<T>, E> {
+        if let Some(value) = self.get(key) {
+            return Ok(Some(value));
+        }
+        let loaded = load.await?;
+        if let Some(ref value) = loaded {
+            self.put(key, value.clone());
+        }
+        Ok(loaded)
+    }
+
+    /// Drop every entry `stale` matches
+    pub fn invalidate_where(&self, stale: impl Fn(&str, &T) -> bool) {
+        self.entries.lock().unwrap().retain(|key, (value, _)| !stale(key, value));
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+}
+
+/// Stats for the recipient lookup caches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LookupCacheStats {
+    pub ens: CacheStats,
+    pub names: CacheStats,
+    pub users: CacheStats,
+}
+
+/// ENS resolutions (name → address), reverse records (address → name) and
+/// users by phone. Clones share entries and counters, so the admin API can
+/// report on the processor's caches.
+#[derive(Clone)]
+pub struct LookupCaches {
+    pub ens: LookupCache<String>,
+    /// None when the address has no reverse record, so it isn't asked again
+    pub names: LookupCache<Option<String>>,
+    pub users: LookupCache<User>,
+}
+
+impl LookupCaches {
+    pub fn new(ens_ttl: Duration, user_ttl: Duration) -> Self {
+        Self {
+            ens: LookupCache::new(ens_ttl),
+            names: LookupCache::new(ens_ttl),
+            users: LookupCache::new(user_ttl),
+        }
+    }
+
+    /// Cached reverse record for `address`, else `load`'s. Unlike
+    /// `get_or_load`, "no name" is cached too: most addresses have none.
+    pub async fn reverse_name<E>(
+        &self,
+        address: &str,
+        load: impl Future<Output = Result<Option<String>, E>>,
+    ) -> Result<Option<String>, E> {
+        let key = address.to_lowercase();
+        if let Some(name) = self.names.get(&key) {
+            return Ok(name);
+        }
+        let name = load.await?;
+        self.names.put(&key, name.clone());
+        Ok(name)
+    }
+
+    /// Forget a user after their row changes, under their main number and
+    /// any LINKed number it was looked up by
+    pub fn invalidate_user(&self, phone: &str) {
+        self.users.invalidate_where(|key, user| key == phone || user.phone == phone);
+    }
+
+    /// Forget an ENS name after it's (re)registered
+    pub fn invalidate_ens(&self, name: &str) {
+        let name = name.to_lowercase();
+        self.ens.invalidate_where(|key, _| *key == name);
+        self.names.invalidate_where(|_, cached| cached.as_deref() == Some(name.as_str()));
+    }
+
+    pub fn stats(&self) -> LookupCacheStats {
+        LookupCacheStats {
+            ens: self.ens.stats(),
+            names: self.names.stats(),
+            users: self.users.stats(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_or_load_counts_hits_and_misses() {
+        let cache = LookupCache::new(Duration::from_secs(60));
+        let loaded = cache.get_or_load("alice.ttcip.eth", async { Ok::<_, ()>(Some("0xabc".to_string())) }).await;
+        assert_eq!(loaded, Ok(Some("0xabc".to_string())));
+        // Served from the cache, the loader isn't consulted
+        let cached = cache.get_or_load("alice.ttcip.eth", async { Ok::<_, ()>(None) }).await;
+        assert_eq!(cached, Ok(Some("0xabc".to_string())));
+        // Misses at the source aren't cached
+        assert_eq!(cache.get_or_load("bob.ttcip.eth", async { Ok::<_, ()>(None) }).await, Ok(None));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2, entries: 1 });
+
+        cache.invalidate_where(|key, _| key == "alice.ttcip.eth");
+        assert_eq!(cache.get("alice.ttcip.eth"), None);
+
+        let disabled = LookupCache::new(Duration::ZERO);
+        disabled.put("alice.ttcip.eth", "0xabc".to_string());
+        assert_eq!(disabled.get("alice.ttcip.eth"), None);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_name_caches_missing_records() {
+        let caches = LookupCaches::new(Duration::from_secs(60), Duration::from_secs(60));
+        let found = caches.reverse_name("0xABC", async { Ok::<_, ()>(Some("alice.eth".to_string())) }).await;
+        assert_eq!(found, Ok(Some("alice.eth".to_string())));
+        // Cached case-insensitively
+        assert_eq!(caches.reverse_name("0xabc", async { Err(()) }).await, Ok(Some("alice.eth".to_string())));
+
+        // An address with no record isn't asked about again
+        assert_eq!(caches.reverse_name("0xdef", async { Ok::<_, ()>(None) }).await, Ok(None));
+        let asked_again = caches.reverse_name("0xdef", async { Ok::<_, ()>(Some("bob.eth".to_string())) }).await;
+        assert_eq!(asked_again, Ok(None));
+
+        // Errors aren't cached
+        assert_eq!(caches.reverse_name("0x123", async { Err(()) }).await, Err(()));
+        assert_eq!(caches.reverse_name("0x123", async { Ok::<_, ()>(None) }).await, Ok(None));
+        assert_eq!(caches.stats().names, CacheStats { hits: 2, misses: 4, entries: 3 });
+
+        // Re-registering a name drops the address it was cached under
+        caches.invalidate_ens("alice.eth");
+        assert_eq!(caches.stats().names.entries, 2);
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Per-user cooldowns for commands that are costly to repeat by accident
+/// (BUY, CASHOUT). Clones share the same store.
+#[derive(Clone)]
+pub struct CooldownStore {
+    cooldowns: Arc<HashMap<String, Duration>>,
+    last_used: Arc<Mutex<HashMap<(String, String), Instant>>>,
+}
+
+impl CooldownStore {
+    /// Cooldown seconds keyed by command keyword (e.g. "CASHOUT")
+    pub fn new(cooldowns: &HashMap<String, u64>) -> Self {
+        Self {
+            cooldowns: Arc::new(
+                cooldowns
+                    .iter()
+                    .filter(|(_, secs)| **secs > 0)
+                    .map(|(command, secs)| (command.to_uppercase(), Duration::from_secs(*secs)))
+                    .collect(),
+            ),
+            last_used: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Time left before `phone` may run `command` again; None when allowed
+    pub fn remaining(&self, phone: &str, command: &str) -> Option<Duration> {
+        let cooldown = *self.cooldowns.get(command)?;
+        let last_used = self.last_used.lock().unwrap();
+        let elapsed = last_used.get(&(phone.to_string(), command.to_string()))?.elapsed();
+        cooldown.checked_sub(elapsed).filter(|left| !left.is_zero())
+    }
+
+    /// Start the cooldown after `phone` ran `command`
+    pub fn record(&self, phone: &str, command: &str) {
+        if !self.cooldowns.contains_key(command) {
+            return;
+        }
+        let mut last_used = self.last_used.lock().unwrap();
+        last_used.retain(|(_, cmd), at| self.cooldowns.get(cmd).is_some_and(|c| at.elapsed() < *c));
+        last_used.insert((phone.to_string(), command.to_string()), Instant::now());
+    }
+}
+
+/// "45s" or "3 min", rounded up
+pub fn format_wait(left: Duration) -> String {
+    let secs = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{} min", secs.div_ceil(60))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_is_per_user_and_command() {
+        let store = CooldownStore::new(&HashMap::from([("cashout".to_string(), 60), ("BUY".to_string(), 0)]));
+        assert_eq!(store.remaining("+1", "CASHOUT"), None);
+
+        store.record("+1", "CASHOUT");
+        store.record("+1", "BUY");
+        assert!(store.remaining("+1", "CASHOUT").is_some_and(|left| left <= Duration::from_secs(60)));
+        assert_eq!(store.remaining("+2", "CASHOUT"), None);
+        assert_eq!(store.remaining("+1", "BUY"), None);
+
+        assert_eq!(format_wait(Duration::from_millis(44_200)), "45s");
+        assert_eq!(format_wait(Duration::from_secs(121)), "3 min");
+    }
+}
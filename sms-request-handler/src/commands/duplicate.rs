@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Recently executed SENDs (or BUYs) per user, to catch carrier duplicates and
+/// double-taps. Clones share the same cache.
+#[derive(Clone)]
+pub struct RecentSends {
+    window: Duration,
+    sent: Arc<Mutex<HashMap<(String, String), Instant>>>,
+}
+
+impl RecentSends {
+    /// Sends are remembered for `window` (zero disables the check)
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            sent: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Whether `phone` sent the same thing within the window
+    pub fn is_recent(&self, phone: &str, fingerprint: &str) -> bool {
+        let sent = self.sent.lock().unwrap();
+        sent.get(&(phone.to_string(), fingerprint.to_string()))
+            .is_some_and(|at| at.elapsed() < self.window)
+    }
+
+    /// Remember a send unless one with the same fingerprint is recent, in
+    /// one step so concurrent duplicates can't both go out. False if recent.
+    pub fn claim(&self, phone: &str, fingerprint: &str) -> bool {
+        if self.window.is_zero() {
+            return true;
+        }
+        let mut sent = self.sent.lock().unwrap();
+        sent.retain(|_, at| at.elapsed() < self.window);
+        let key = (phone.to_string(), fingerprint.to_string());
+        if sent.contains_key(&key) {
+            return false;
+        }
+        sent.insert(key, Instant::now());
+        true
+    }
+
+    /// Remember a send that is going out
+    pub fn record(&self, phone: &str, fingerprint: &str) {
+        if self.window.is_zero() {
+            return;
+        }
+        let mut sent = self.sent.lock().unwrap();
+        sent.retain(|_, at| at.elapsed() < self.window);
+        sent.insert((phone.to_string(), fingerprint.to_string()), Instant::now());
+    }
+}
+
+/// Identity of a send: same amount, token and recipient (case-insensitive)
+pub fn send_fingerprint(amount: f64, token: &str, recipient: &str) -> String {
+    format!("{} {} {}", amount, token.to_uppercase(), recipient.to_uppercase())
+}
+
+/// Request id for a BUY, the same for repeats of it within one time bucket
+/// so the backend can drop retried SMS
+pub fn buy_request_id(phone: &str, amount: f64, unix_secs: i64, bucket_secs: u64) -> String {
+    use sha2::{Digest, Sha256};
+    let bucket = unix_secs / bucket_secs.max(1) as i64;
+    let digest = Sha256::digest(format!("buy|{}|{}|{}", phone, amount, bucket));
+    hex::encode(&digest[..16])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_send_is_per_user_and_fingerprint() {
+        let sends = RecentSends::new(Duration::from_secs(60));
+        let fingerprint = send_fingerprint(5.0, "txtc", "alice");
+        assert!(!sends.is_recent("+1", &fingerprint));
+
+        sends.record("+1", &fingerprint);
+        assert!(sends.is_recent("+1", &send_fingerprint(5.0, "TXTC", "ALICE")));
+        assert!(!sends.is_recent("+2", &fingerprint));
+        assert!(!sends.is_recent("+1", &send_fingerprint(6.0, "TXTC", "alice")));
+
+        let disabled = RecentSends::new(Duration::ZERO);
+        disabled.record("+1", &fingerprint);
+        assert!(!disabled.is_recent("+1", &fingerprint));
+    }
+
+    #[test]
+    fn test_buy_request_id_is_stable_within_bucket() {
+        let id = buy_request_id("+1", 10.0, 1_000_020, 60);
+        assert_eq!(id.len(), 32);
+        assert_eq!(id, buy_request_id("+1", 10.0, 1_000_079, 60));
+        assert_ne!(id, buy_request_id("+1", 10.0, 1_000_080, 60));
+        assert_ne!(id, buy_request_id("+1", 20.0, 1_000_020, 60));
+        assert_ne!(id, buy_request_id("+2", 10.0, 1_000_020, 60));
+    }
+}
@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+
+use crate::db::{mask_phone, Escrow, EscrowRepository};
+
+/// What to do with a hold's funds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoldAction {
+    /// Pay the recipient
+    Release,
+    /// Pay the sender back
+    Refund,
+}
+
+impl HoldAction {
+    fn path(self) -> &'static str {
+        match self {
+            HoldAction::Release => "release",
+            HoldAction::Refund => "refund",
+        }
+    }
+
+    /// Status a record takes once the action has gone through
+    pub fn status(self) -> &'static str {
+        match self {
+            HoldAction::Release => "released",
+            HoldAction::Refund => "refunded",
+        }
+    }
+}
+
+/// How a release or refund went
+#[derive(Debug, PartialEq, Eq)]
+pub enum Settlement {
+    Paid,
+    /// The backend couldn't pay just now; worth trying again
+    Retry,
+    /// The backend has no open hold by that id (unknown or already
+    /// settled), so trying again can't help
+    Gone,
+}
+
+/// The backend's escrow, where HOLDs and sends waiting for ACCEPT keep the
+/// sender's funds until they're released or refunded
+pub struct Holds<'a> {
+    backend_url: &'a str,
+}
+
+impl<'a> Holds<'a> {
+    pub fn new(backend_url: &'a str) -> Self {
+        Self { backend_url }
+    }
+
+    /// Move funds into escrow instead of paying the recipient directly.
+    /// Returns the backend hold id, or the SMS reply on failure.
+    pub async fn lock(
+        &self,
+        from: &str,
+        from_address: &str,
+        sender_key: &str,
+        amount: f64,
+        token: &str,
+        to_address: &str,
+    ) -> Result<String, String> {
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/api/send-yellow", self.backend_url))
+            .json(&serde_json::json!({
+                "fromAddress": from_address,
+                "toAddress": to_address,
+                "amount": amount.to_string(),
+                "token": token,
+                "userPhone": from,
+                "senderKey": sender_key,
+                "hold": true
+            }))
+            .timeout(std::time::Duration::from_secs(30))
+            .send()
+            .await;
+
+        let result: serde_json::Value = match response {
+            Ok(resp) => resp.json().await.unwrap_or_default(),
+            Err(e) => {
+                tracing::error!("Failed to call Yellow API: {}", e);
+                return Err("Network error. Try later.".to_string());
+            }
+        };
+
+        if !result["success"].as_bool().unwrap_or(false) {
+            tracing::error!("Hold failed: {}", result["error"].as_str().unwrap_or("Unknown error"));
+            return Err("Transfer failed. Try later.".to_string());
+        }
+
+        // Without an id the hold could never be released or refunded
+        match result["holdId"].as_str() {
+            Some(hold_id) => Ok(hold_id.to_string()),
+            None => {
+                tracing::error!("Hold succeeded without a holdId: {}", result);
+                Err("Transfer failed. Try later.".to_string())
+            }
+        }
+    }
+
+    /// Pay out a hold. The backend takes both addresses from the hold itself.
+    pub async fn settle(&self, hold_id: &str, action: HoldAction) -> Settlement {
+        let client = reqwest::Client::new();
+        let url = format!("{}/api/hold/{}", self.backend_url, action.path());
+
+        let response = match client
+            .post(&url)
+            .json(&serde_json::json!({ "holdId": hold_id }))
+            .timeout(std::time::Duration::from_secs(30))
+            .send()
+            .await
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                tracing::error!("Hold {} failed: {}", action.path(), e);
+                return Settlement::Retry;
+            }
+        };
+
+        let status = response.status();
+        let body = response.json::<serde_json::Value>().await.unwrap_or_default();
+        if body["success"].as_bool().unwrap_or(false) {
+            return Settlement::Paid;
+        }
+        tracing::error!(hold = hold_id, %status, "Hold {} refused: {}", action.path(), body["error"].as_str().unwrap_or("Unknown error"));
+        match status {
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::CONFLICT => Settlement::Gone,
+            _ => Settlement::Retry,
+        }
+    }
+}
+
+/// Claim an escrow and pay it out. If the backend call fails it goes back
+/// to held for another try; if the backend has no such hold it's marked
+/// failed, for support to look at, rather than retried forever.
+pub async fn settle_escrow(repo: &EscrowRepository, holds: &Holds<'_>, escrow: &Escrow, action: HoldAction) -> bool {
+    match repo.resolve(escrow.id, action.status()).await {
+        Ok(true) => {}
+        Ok(false) => return false,
+        Err(e) => {
+            tracing::error!("Failed to claim escrow {}: {}", escrow.id, e);
+            return false;
+        }
+    }
+
+    let settlement = match escrow.hold_ref {
+        Some(ref hold_id) => holds.settle(hold_id, action).await,
+        None => Settlement::Gone,
+    };
+    match settlement {
+        Settlement::Paid => {
+            let to = match action {
+                HoldAction::Release => &escrow.recipient_address,
+                HoldAction::Refund => &escrow.payer_address,
+            };
+            tracing::info!(
+                escrow = %escrow.id, status = action.status(), payer = %mask_phone(&escrow.payer_phone), %to,
+                release_at = ?escrow.release_at, "Escrow settled"
+            );
+            true
+        }
+        Settlement::Retry => {
+            let _ = repo.reopen(escrow.id).await;
+            false
+        }
+        Settlement::Gone => {
+            tracing::error!(escrow = %escrow.id, hold = ?escrow.hold_ref, "Backend has no open hold for escrow, needs manual review");
+            if let Err(e) = repo.mark_failed(escrow.id).await {
+                tracing::error!("Failed to mark escrow {} failed: {}", escrow.id, e);
+            }
+            false
+        }
+    }
+}
+
+/// Release escrows whose date has come and refund undated ones created
+/// before `stale_before`. Returns how many were settled.
+pub async fn sweep(repo: &EscrowRepository, holds: &Holds<'_>, now: DateTime<Utc>, stale_before: DateTime<Utc>) -> usize {
+    let due = repo.due_for_release(now).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to load due escrows: {}", e);
+        Vec::new()
+    });
+    let stale = repo.stale(stale_before).await.unwrap_or_else(|e| {
+        tracing::error!("Failed to load stale escrows: {}", e);
+        Vec::new()
+    });
+
+    let mut settled = 0;
+    for escrow in &due {
+        if settle_escrow(repo, holds, escrow, HoldAction::Release).await {
+            settled += 1;
+        }
+    }
+    for escrow in &stale {
+        if settle_escrow(repo, holds, escrow, HoldAction::Refund).await {
+            settled += 1;
+        }
+    }
+    settled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{random_phone, spawn_mock_backend};
+
+    #[tokio::test]
+    async fn test_escrow_the_backend_lacks_is_not_retried() {
+        use axum::{http::StatusCode, routing::post, Json, Router};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let Some(pool) = crate::db::test_pool().await else { return };
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let backend = Router::new().route(
+            "/api/hold/release",
+            post(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                (StatusCode::NOT_FOUND, Json(serde_json::json!({ "success": false, "error": "Unknown hold" })))
+            }),
+        );
+        let url = spawn_mock_backend(backend).await;
+        let holds = Holds::new(&url);
+        let repo = EscrowRepository::new(pool);
+        let payer = random_phone();
+        let escrow = repo
+            .create(&payer, "0x1111111111111111111111111111111111111111", "alice", "0x2222222222222222222222222222222222222222",
+                20.0, "TXTC", Some("hold-gone"), None)
+            .await
+            .unwrap();
+
+        assert!(!settle_escrow(&repo, &holds, &escrow, HoldAction::Release).await);
+        // Marked failed rather than put back for the next sweep
+        assert!(repo.find_held(&payer, &escrow.short_id()).await.unwrap().is_none());
+        assert!(!settle_escrow(&repo, &holds, &escrow, HoldAction::Release).await);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
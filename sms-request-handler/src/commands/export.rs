@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// One-time download links for EXPORT files (keystores, history CSVs).
+/// These never go out by SMS; the user gets a link that works once and
+/// then expires.
+#[derive(Clone)]
+pub struct ExportStore<T = serde_json::Value> {
+    links: Arc<Mutex<HashMap<String, (T, Instant)>>>,
+    ttl: Duration,
+}
+
+impl<T> ExportStore<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            links: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+        }
+    }
+
+    /// Store a file and return the link token
+    pub fn put(&self, file: T) -> String {
+        let token = hex::encode(rand::random::<[u8; 16]>());
+        let mut links = self.links.lock().unwrap();
+        links.retain(|_, (_, at)| at.elapsed() < self.ttl);
+        links.insert(token.clone(), (file, Instant::now()));
+        token
+    }
+
+    /// Hand out a file once; None if unknown, used or expired
+    pub fn take(&self, token: &str) -> Option<T> {
+        let (file, at) = self.links.lock().unwrap().remove(token)?;
+        (at.elapsed() < self.ttl).then_some(file)
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+}
+
+/// One transaction in an EXPORT HISTORY statement
+#[derive(Debug, Clone)]
+pub struct HistoryRow {
+    pub kind: &'static str, // "deposit", "send", "receive"
+    pub amount: f64,
+    pub token: String,
+    pub counterparty: String,
+    pub status: String,
+    pub tx_hash: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// CSV statement, newest first
+pub fn history_csv(rows: &[HistoryRow]) -> String {
+    let mut rows: Vec<&HistoryRow> = rows.iter().collect();
+    rows.sort_by_key(|r| std::cmp::Reverse(r.timestamp));
+
+    let mut csv = String::from("type,amount,token,counterparty,status,tx_hash,timestamp\n");
+    for row in rows {
+        let fields = [
+            row.kind.to_string(),
+            row.amount.to_string(),
+            row.token.clone(),
+            row.counterparty.clone(),
+            row.status.clone(),
+            row.tx_hash.clone().unwrap_or_default(),
+            row.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        ];
+        let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quote a field holding a separator, quote or line break. Leading formula
+/// characters are neutralised so spreadsheets don't evaluate them.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) && value.parse::<f64>().is_err() {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_csv_orders_and_escapes() {
+        let at = |secs| DateTime::from_timestamp(secs, 0).unwrap();
+        let rows = [
+            HistoryRow {
+                kind: "deposit",
+                amount: 2.5,
+                token: "USDC".to_string(),
+                counterparty: "voucher".to_string(),
+                status: "confirmed".to_string(),
+                tx_hash: None,
+                timestamp: at(1_700_000_000),
+            },
+            HistoryRow {
+                kind: "receive",
+                amount: 1.0,
+                token: "TXTC".to_string(),
+                counterparty: "=HYPERLINK(\"x\")".to_string(),
+                status: "accepted".to_string(),
+                tx_hash: None,
+                timestamp: at(1_700_000_100),
+            },
+        ];
+
+        assert_eq!(
+            history_csv(&rows),
+            "type,amount,token,counterparty,status,tx_hash,timestamp\n\
+             receive,1,TXTC,\"'=HYPERLINK(\"\"x\"\")\",accepted,,2023-11-14T22:15:00Z\n\
+             deposit,2.5,USDC,voucher,confirmed,,2023-11-14T22:13:20Z\n"
+        );
+    }
+}
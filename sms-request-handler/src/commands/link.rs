@@ -0,0 +1,61 @@
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Codes issued by LINK on a wallet's main number, redeemed once by
+/// LINK <code> from the number being attached. Clones share the same store.
+#[derive(Clone)]
+pub struct LinkCodes {
+    codes: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+    ttl: Duration,
+}
+
+impl LinkCodes {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            codes: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+        }
+    }
+
+    /// New code for `phone`, replacing any it was issued before
+    pub fn issue(&self, phone: &str) -> String {
+        // No 0/O or 1/I, so codes survive being read off another screen
+        const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+        let mut rng = rand::thread_rng();
+        let code: String = (0..8).map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char).collect();
+
+        let mut codes = self.codes.lock().unwrap();
+        codes.retain(|_, (owner, at)| owner != phone && at.elapsed() < self.ttl);
+        codes.insert(code.clone(), (phone.to_string(), Instant::now()));
+        code
+    }
+
+    /// Phone that issued `code`; None if unknown, used or expired
+    pub fn redeem(&self, code: &str) -> Option<String> {
+        let (phone, at) = self.codes.lock().unwrap().remove(&code.to_uppercase())?;
+        (at.elapsed() < self.ttl).then_some(phone)
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_redeems_once_and_reissue_replaces() {
+        let codes = LinkCodes::new(Duration::from_secs(600));
+        let first = codes.issue("+15550001111");
+        let second = codes.issue("+15550001111");
+        assert_eq!(second.len(), 8);
+
+        assert_eq!(codes.redeem(&first), None);
+        assert_eq!(codes.redeem(&second.to_lowercase()), Some("+15550001111".to_string()));
+        assert_eq!(codes.redeem(&second), None);
+    }
+}
@@ -1,3 +1,15 @@
+pub mod cache;
+mod cooldown;
+mod duplicate;
+mod escrow;
+mod export;
+mod link;
 pub mod parser;
+mod pending;
+mod rate_limit;
+pub mod responses;
+mod session;
+pub mod synonyms;
+pub mod templates;
 
 pub use parser::CommandProcessor;
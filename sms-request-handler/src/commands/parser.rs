@@ -1,54 +1,492 @@
 use std::sync::Arc;
 use sha2::Digest;
-use crate::db::{UserRepository, VoucherRepository, DepositRepository, AddressBookRepository};
-use crate::wallet::{AmoyProvider, UserWallet, Chain, MultiChainProvider};
+use crate::config::{settlement_chain, AaConfig, CommandConfig, RecipientResolver};
+use crate::http::{self, HttpError};
+use crate::price::{currency_for_symbol, format_approx_fiat, format_fiat, format_price, supported_currency, price_source_from_env, PriceSource, PRICES_DISAGREE, SUPPORTED_CURRENCIES};
+use crate::db::{UserRepository, VoucherRepository, Deposit, DepositRepository, DepositSource, AddressBookRepository, InboundTransfer, InboundTransferRepository, EscrowRepository, User, AuditRepository, TransferRepository, PendingTxRepository, Receipt, ReceiptRepository, ChannelRepository, CloseReason, PayeeTagRepository, Transaction, TransactionRepository, mask_phone, normalize_phone};
+use crate::yellow_client::YellowClient;
+use crate::wallet::encryption::{decrypt_stored_key, encrypt_key};
+use crate::wallet::{AmoyProvider, UserWallet, WalletError, Chain, ChainBalances, MultiChainProvider, get_balances_all_chains, BundlerClient, EntryPointConfig, BlockHeightCache, format_display_amount};
+use super::cooldown::{format_wait, CooldownStore};
+use super::rate_limit::RateLimiter;
+use super::duplicate::{buy_request_id, send_fingerprint, RecentSends};
+use super::escrow::{self, HoldAction, Holds, Settlement};
+use super::cache::{LookupCache, LookupCaches};
+use super::export::{history_csv, ExportStore, HistoryRow};
+use super::link::LinkCodes;
+use super::pending::{PendingKind, PendingStore, PickError};
+use super::responses;
+use super::session::PinSessions;
+use super::synonyms;
+use super::templates;
+use crate::events::{EventBus, WalletEvent};
+use ethers::types::Address;
+use std::str::FromStr;
+
+/// Most contacts one SAVEALL message may add
+const MAX_SAVEALL_CONTACTS: usize = 10;
+
+/// Source-chain fee for a BRIDGE, from the backend estimate
+struct BridgeFee {
+    amount: f64,
+    token: String,
+    /// The user's balance of `token` on the source chain, when the backend knows it
+    balance: Option<f64>,
+}
+
+/// Longest list reply: two concatenated SMS segments
+const MAX_LIST_CHARS: usize = 306;
+
+/// MENU lines, keyed by the command they advertise
+const HELP_LINES: &[(&str, &str)] = &[
+    ("JOIN", "JOIN <name> - Create wallet"),
+    ("BALANCE", "BALANCE - Check balance"),
+    ("SEND", "SEND 10 TXTC TO name.ttcip.eth"),
+    ("BUY", "BUY 10 - Buy TXTC with airtime"),
+    ("DEPOSIT", "DEPOSIT - Get deposit address"),
+    ("REDEEM", "REDEEM <code> - Redeem voucher"),
+    ("SWAP", "SWAP 10 TXTC - Swap to ETH"),
+    ("CASHOUT", "CASHOUT 10 TXTC - Cash out to USDC"),
+    ("CASHOUT", "CASHOUT 10 TXTC TO USDT - Cash out to USDT"),
+    ("CASHOUT", "CASHOUT 0.001 ETH - Cash out ETH"),
+    ("BRIDGE", "BRIDGE 10 USDC FROM POLYGON TO BASE"),
+    ("HELP", "MENU - Show this help"),
+    ("STOP", "STOP - Opt out of messages"),
+];
+
+/// HELP <topic> text by command keyword
+const HELP_TOPICS: &[(&str, &str)] = &[
+    ("JOIN", "JOIN [name]\nCreates your wallet, optionally with an ENS name (3-20 letters/numbers).\nEx: JOIN alice"),
+    ("BALANCE", "BALANCE\nShows your TXTC, ETH and USDC balances."),
+    ("SEND", "SEND <amount> <token> [TO] <recipient>\nRecipient: ENS name, phone (+1...), contact or 0x address.\nEx: SEND 10 TXTC TO alice.ttcip.eth\nEx: SEND $5 TO bob"),
+    ("BUY", "BUY <amount>\nBuys TXTC with airtime.\nEx: BUY 10"),
+    ("DEPOSIT", "DEPOSIT\nShows how to fund your wallet: airtime, voucher or on-chain address."),
+    ("REDEEM", "REDEEM <code>\nAdds a voucher's value to your wallet.\nEx: REDEEM TXTC1234"),
+    ("SWAP", "SWAP <amount> <token>\nSwaps TXTC to ETH at the current pool price.\nEx: SWAP 10 TXTC"),
+    ("PRICE", "PRICE [token]\nPrice of one ETH or TXTC in your CURRENCY (USD if unset).\nEx: PRICE ETH"),
+    ("QUOTE", "QUOTE <amount> <token>\nWhat a swap would give at the current pool price, TXTC to ETH or ETH to TXTC.\nEx: QUOTE 10 TXTC"),
+    ("CASHOUT", "CASHOUT <amount> <token> [TO <currency>]\nCashes TXTC or ETH out to a stablecoin (USDC on Arc, USDT on Polygon).\nEx: CASHOUT 10 TXTC TO USDT"),
+    ("BRIDGE", "BRIDGE <amount> <token> FROM <chain> TO <chain> [AS <token>]\nChains: polygon, base, eth, arb\nEx: BRIDGE 10 USDC FROM POLYGON TO BASE"),
+    ("HISTORY", "HISTORY [count]\nRecent deposits and transfers.\nEXPORT HISTORY sends a link to your full history as CSV."),
+    ("CONTACTS", "CONTACTS [count] - list contacts\nCONTACT <name> - details\nSAVE <name> <phone|0x|ENS> - add one\nPAYABLE - contacts you can pay"),
+    ("TAG", "TAG <payee> <note> - sends to them get the note\nTAG <payee> OFF - stop\nSEND ... NOTE <text> overrides it once.\nEx: TAG alice rent"),
+    ("HOLD", "HOLD <amount> <token> FOR <recipient> [UNTIL YYYY-MM-DD]\nLocks a payment until the date or RELEASE <ref>.\nEx: HOLD 5 TXTC FOR bob UNTIL 2026-12-01"),
+    ("EXPORT", "EXPORT <passphrase> - encrypted wallet backup link\nEXPORT HISTORY - transaction history CSV link\nLinks open once."),
+    ("LINK", "LINK - get a code on your main phone\nLINK <code> - from your other phone, to use the same wallet"),
+    ("PIN", "PIN <4-6 digits> - set your PIN\nUNLOCK <PIN> - sends and cashouts skip the PIN for a while\nLOCK - ask for the PIN again"),
+    ("CURRENCY", "CURRENCY [code|OFF]\nShows approximate local values next to balances and sends; PRICE quotes in it too.\nEx: CURRENCY KES"),
+    ("APPROVE", "APPROVE ABOVE <amount> [token]\nHolds incoming transfers above the amount until you reply ACCEPT.\nAPPROVE OFF to credit them right away.\nEx: APPROVE ABOVE 100 TXTC"),
+    ("AUTOSWAP", "AUTOSWAP ON <n>%\nSwaps that share of each deposit to gas so you can always send.\nAUTOSWAP OFF to stop.\nEx: AUTOSWAP ON 10%"),
+    ("FREEZE", "FREEZE - phone lost or stolen? Blocks all sends, swaps and cashouts at once, no PIN needed. You can still receive.\nUNFREEZE <PIN> - restore"),
+    ("STOP", "STOP - stop all messages (also CANCEL when no send is waiting)\nSTART - resubscribe"),
+];
+
+/// Commands that wait for UNLOCK <pin> when the user has a PIN and no open session
+const PIN_PROTECTED: &[&str] = &["SEND", "SWAP", "CASHOUT", "BUY", "BRIDGE", "HOLD", "RELEASE", "EXPORT"];
+
+/// Longest a run of wrong PINs locks PIN entry for, however long it gets
+const MAX_PIN_LOCKOUT_SECS: u64 = 86_400;
+
+/// Commands refused while the account is FROZEN, besides the PIN-protected
+/// ones: they would let a thief confirm a send, change the PIN or add a phone
+const FREEZE_BLOCKED: &[&str] = &["CONFIRM", "PIN", "LINK"];
+
+/// Command keywords, refused as a SEND recipient: "SEND 10 TXTC BALANCE" is
+/// a slip, not a payment to a contact called Balance
+const RESERVED_WORDS: &[&str] = &[
+    "ACCEPT", "ACCOUNT", "ADD", "APPROVE", "AUTOSWAP", "BACKUP", "BAL", "BALANCE", "BOOK", "BRIDGE",
+    "BUY", "CANCEL", "CASH", "CASHOUT", "CHAIN", "CODE", "COMMANDS", "CONFIRM", "CONTACT", "CONTACTS",
+    "CROSS", "CURRENCY", "DECLINE", "DEPOSIT", "END", "ESCROW", "EXCHANGE", "EXPORT", "FIAT", "FREEZE",
+    "HELP", "HISTORY", "HOLD", "INFO", "JOIN", "LINK", "LOCK", "MENU", "NETWORK", "NO", "PANIC",
+    "PAYABLE", "PIN", "PRICE", "PURCHASE", "QUIT", "QUOTE", "RECEIVE", "REDEEM", "REGISTER", "REJECT", "RELEASE", "SAVE",
+    "SAVEALL", "SEND", "START", "STOP", "STOPALL", "SWAP", "TAG", "TOPUP", "TRANSACTIONS", "TXS",
+    "UNFREEZE", "UNLOCK", "UNSTOP", "UNSUBSCRIBE", "VOUCHER", "VOUCHERS", "YES",
+];
+
+/// Quotes below this output amount are treated as an empty pool
+const MIN_QUOTE_OUT: f64 = 1e-12;
 
 /// Parsed SMS command
 #[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     /// Show help/available commands
     Help,
+    /// Focused help for one command: HELP <topic>
+    HelpTopic { topic: String },
     /// Register a new user with optional ENS name
     Join { ens_name: Option<String> },
     /// Check account balance
     Balance,
     /// Set or change PIN
     Pin { new_pin: Option<String> },
-    /// Send money to someone
+    /// Enter the PIN to run PIN-protected commands for a while: UNLOCK <pin>
+    Unlock { pin: String },
+    /// End the PIN session early: LOCK
+    Lock,
+    /// Block all outgoing operations at once, no PIN needed: FREEZE
+    Freeze,
+    /// Lift a FREEZE: UNFREEZE <pin>
+    Unfreeze { pin: String },
+    /// Send money to someone: SEND <amount> <token> TO <recipient> [NOTE <text>] [PIN <pin>]
     Send {
         amount: f64,
         token: String,
         recipient: String,
+        /// Explicit note; without one the payee's TAG applies
+        note: Option<String>,
+        /// PIN typed with the command, instead of a separate UNLOCK
+        pin: Option<String>,
+    },
+    /// Send a fiat-denominated amount: SEND $10 [token] TO <recipient>
+    SendFiat {
+        fiat_amount: f64,
+        currency: String,
+        token: String,
+        recipient: String,
     },
     /// Check deposit address
     Deposit,
-    /// Check transaction history
-    History,
+    /// Check transaction history: HISTORY [count]
+    History { limit: Option<usize> },
     /// Redeem a voucher code
     Redeem { code: String },
+    /// List redeemed vouchers: VOUCHERS [count]
+    Vouchers { limit: Option<usize> },
     /// Swap tokens for ETH: SWAP <amount> TXTC
     Swap { amount: f64, token: String },
-    /// Cashout to USDC on Arc: CASHOUT <amount> TXTC or CASHOUT <amount> ETH
-    Cashout { amount: f64, token: String },
+    /// Unit price in the user's currency: PRICE [ETH|TXTC] (both when omitted)
+    Price { token: Option<String> },
+    /// Price a swap without making it: QUOTE <amount> TXTC|ETH
+    Quote { amount: f64, token: String },
+    /// Cash TXTC or ETH out to a stablecoin: CASHOUT <amount> TXTC [TO USDT] [PIN <pin>].
+    /// `to` is the user's settlement currency, None for the deployment's
+    Cashout { amount: f64, token: String, to: Option<String>, pin: Option<String> },
     /// Buy TXTC with airtime: BUY <amount>
     Buy { amount: f64 },
-    /// Bridge tokens cross-chain: BRIDGE <amount> <token> FROM <chain> TO <chain>
+    /// Bridge tokens cross-chain: BRIDGE <amount> <token> FROM <chain> TO <chain> [AS <token>]
     Bridge {
         amount: f64,
         token: String,
         from_chain: String,
         to_chain: String,
+        /// Token received on the destination chain (same as `token` unless AS is given)
+        to_token: String,
     },
-    /// Save a contact: SAVE <name> <phone>
-    Save { name: String, phone: String },
-    /// List contacts
-    Contacts,
+    /// Save a contact: SAVE <name> <phone|0x address|ENS name>
+    Save { name: String, contact: SavedContact },
+    /// Default note for sends to a payee: TAG <payee> <note>, or TAG <payee> OFF (tag None)
+    Tag { payee: String, tag: Option<String> },
+    /// Save several contacts: SAVEALL <name> <phone>, <name> <phone>, ...
+    /// (phone is empty when an entry had none)
+    SaveAll { contacts: Vec<(String, String)> },
+    /// List contacts: CONTACTS [count]
+    Contacts { limit: Option<usize> },
+    /// List contacts that can receive a SEND: PAYABLE [count]
+    Payable { limit: Option<usize> },
+    /// Show one contact's details: CONTACT <name>
+    Contact { name: String },
     /// Switch chain: CHAIN <name>
     SwitchChain { chain: String },
-    /// Unknown command
+    /// Accept a held incoming transfer: ACCEPT [n]
+    Accept { index: Option<usize> },
+    /// Decline a held incoming transfer, refunding the sender: DECLINE [n]
+    Decline { index: Option<usize> },
+    /// Lock funds for a recipient: HOLD <amount> <token> FOR <recipient> [UNTIL YYYY-MM-DD]
+    Hold {
+        amount: f64,
+        token: String,
+        recipient: String,
+        until: Option<chrono::NaiveDate>,
+    },
+    /// Pay out held funds early: RELEASE <ref>
+    Release { id: String },
+    /// Show or switch account mode: ACCOUNT [EOA|SMART]
+    Account { mode: Option<String> },
+    /// Show or set the display currency: CURRENCY [KES|OFF]
+    Currency { code: Option<String> },
+    /// Show or set the incoming amount that needs ACCEPT: APPROVE [ABOVE <amount> [token]|OFF]
+    Approve { limit: Option<ApprovalLimit> },
+    /// Show or set the share of each deposit swapped to gas: AUTOSWAP [ON <n>%|OFF]
+    AutoSwap { setting: Option<AutoSwapSetting> },
+    /// Confirm a pending send: YES [n] or CONFIRM <code>
+    Confirm { reference: Option<String> },
+    /// Discard a pending send: NO [n] or CANCEL <code>
+    Cancel { reference: Option<String> },
+    /// Encrypted self-custody backup: EXPORT <passphrase>
+    Export { passphrase: String },
+    /// Download link for the full transaction history as CSV: EXPORT HISTORY
+    ExportHistory,
+    /// Attach another phone to this wallet: LINK on the main number issues
+    /// a code, LINK <code> from the other number redeems it
+    Link { code: Option<String> },
+    /// Carrier opt-out: STOP
+    Stop,
+    /// Bare CANCEL: cancels a pending send, or opts out like STOP when
+    /// nothing is pending
+    CancelOrStop,
+    /// Carrier re-subscribe: START/UNSTOP (a bare START from a new number still JOINs)
+    Start,
+    /// A known command whose arguments don't parse; `hint` is the reply
+    Invalid {
+        command: &'static str,
+        reason: ParseError,
+        hint: String,
+    },
+    /// Unrecognized keyword
     Unknown(String),
 }
 
+/// Why a known command's arguments were rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    MissingAmount,
+    InvalidAmount,
+    MissingRecipient,
+    InvalidRecipient,
+    /// A list position or count that isn't a positive number
+    InvalidCount,
+    InvalidDate,
+    MissingArgument,
+    /// A mode, chain or currency the command doesn't know
+    UnsupportedOption,
+    InvalidArgument,
+    /// Arguments in the wrong shape
+    Usage,
+}
+
+impl ParseError {
+    /// Code recorded in the audit trail
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::MissingAmount => "missing_amount",
+            ParseError::InvalidAmount => "invalid_amount",
+            ParseError::MissingRecipient => "missing_recipient",
+            ParseError::InvalidRecipient => "invalid_recipient",
+            ParseError::InvalidCount => "invalid_count",
+            ParseError::InvalidDate => "invalid_date",
+            ParseError::MissingArgument => "missing_argument",
+            ParseError::UnsupportedOption => "unsupported_option",
+            ParseError::InvalidArgument => "invalid_argument",
+            ParseError::Usage => "usage",
+        }
+    }
+}
+
+/// APPROVE setting: hold incoming transfers above an amount, or stop holding them
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalLimit {
+    Above { amount: f64, token: String },
+    Off,
+}
+
+/// What a SAVE points at. Phones go in the address book's `contact_phone`,
+/// addresses and ENS names in `wallet_address` (names resolve at send time).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SavedContact {
+    Phone(String),
+    Address(String),
+    Ens(String),
+}
+
+impl SavedContact {
+    /// The phone, address or name as stored
+    pub fn value(&self) -> &str {
+        match self {
+            SavedContact::Phone(v) | SavedContact::Address(v) | SavedContact::Ens(v) => v,
+        }
+    }
+}
+
+/// AUTOSWAP setting: swap a percent of each deposit to gas, or stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoSwapSetting {
+    Percent(u32),
+    Off,
+}
+
+impl Command {
+    /// A known command with arguments that don't parse
+    fn invalid(command: &'static str, reason: ParseError, hint: impl Into<String>) -> Self {
+        Command::Invalid { command, reason, hint: hint.into() }
+    }
+
+    /// Keyword recorded in logs and the audit trail
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Help | Command::HelpTopic { .. } => "HELP",
+            Command::Join { .. } => "JOIN",
+            Command::Balance => "BALANCE",
+            Command::Pin { .. } => "PIN",
+            Command::Unlock { .. } => "UNLOCK",
+            Command::Lock => "LOCK",
+            Command::Freeze => "FREEZE",
+            Command::Unfreeze { .. } => "UNFREEZE",
+            Command::Send { .. } => "SEND",
+            Command::SendFiat { .. } => "SEND",
+            Command::Tag { .. } => "TAG",
+            Command::Deposit => "DEPOSIT",
+            Command::History { .. } => "HISTORY",
+            Command::Vouchers { .. } => "VOUCHERS",
+            Command::Redeem { .. } => "REDEEM",
+            Command::Swap { .. } => "SWAP",
+            Command::Quote { .. } => "QUOTE",
+            Command::Price { .. } => "PRICE",
+            Command::Cashout { .. } => "CASHOUT",
+            Command::Buy { .. } => "BUY",
+            Command::Bridge { .. } => "BRIDGE",
+            Command::Save { .. } => "SAVE",
+            Command::SaveAll { .. } => "SAVEALL",
+            Command::Contacts { .. } => "CONTACTS",
+            Command::Payable { .. } => "PAYABLE",
+            Command::Contact { .. } => "CONTACT",
+            Command::SwitchChain { .. } => "CHAIN",
+            Command::Accept { .. } => "ACCEPT",
+            Command::Decline { .. } => "DECLINE",
+            Command::Hold { .. } => "HOLD",
+            Command::Release { .. } => "RELEASE",
+            Command::Account { .. } => "ACCOUNT",
+            Command::Currency { .. } => "CURRENCY",
+            Command::Approve { .. } => "APPROVE",
+            Command::AutoSwap { .. } => "AUTOSWAP",
+            Command::Link { .. } => "LINK",
+            Command::Confirm { .. } => "CONFIRM",
+            Command::Cancel { .. } | Command::CancelOrStop => "CANCEL",
+            Command::Export { .. } | Command::ExportHistory => "EXPORT",
+            Command::Stop => "STOP",
+            Command::Start => "START",
+            Command::Invalid { command, .. } => command,
+            Command::Unknown(_) => "UNKNOWN",
+        }
+    }
+
+    /// PIN typed along with a SEND or CASHOUT
+    pub fn inline_pin(&self) -> Option<&str> {
+        match self {
+            Command::Send { pin, .. } | Command::Cashout { pin, .. } => pin.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Arguments safe to persist: PINs, passphrases and voucher codes are
+    /// withheld and phone numbers masked. Unparsed text is never kept.
+    pub fn redacted_args(&self) -> String {
+        fn party(who: &str) -> String {
+            if who.starts_with('+') || who.chars().all(|c| c.is_ascii_digit()) {
+                mask_phone(who)
+            } else {
+                who.to_string()
+            }
+        }
+        fn count(n: &Option<impl std::fmt::Display>) -> String {
+            n.as_ref().map(|n| n.to_string()).unwrap_or_default()
+        }
+
+        match self {
+            Command::Pin { new_pin: secret } | Command::Link { code: secret } => {
+                if secret.is_some() { "[redacted]".to_string() } else { String::new() }
+            }
+            Command::Export { .. } | Command::Redeem { .. } | Command::Unlock { .. } | Command::Unfreeze { .. } => {
+                "[redacted]".to_string()
+            }
+            Command::Join { ens_name } => count(ens_name),
+            Command::Send { amount, token, recipient, .. } => format!("{} {} to {}", amount, token, party(recipient)),
+            Command::Tag { payee, .. } => party(payee),
+            Command::SendFiat { fiat_amount, currency, token, recipient } => {
+                format!("{} {} in {} to {}", fiat_amount, currency, token, party(recipient))
+            }
+            Command::History { limit }
+            | Command::Contacts { limit }
+            | Command::Payable { limit }
+            | Command::Vouchers { limit } => count(limit),
+            Command::Swap { amount, token }
+            | Command::Quote { amount, token }
+            | Command::Cashout { amount, token, to: None, .. } => format!("{} {}", amount, token),
+            Command::Cashout { amount, token, to: Some(to), .. } => format!("{} {} to {}", amount, token, to),
+            Command::Buy { amount } => amount.to_string(),
+            Command::Bridge { amount, token, from_chain, to_chain, to_token } => {
+                format!("{} {} {} to {} {}", amount, token, from_chain, to_chain, to_token)
+            }
+            Command::Save { name, contact } => format!("{} {}", name, party(contact.value())),
+            Command::SaveAll { contacts } => format!("{} contacts", contacts.len()),
+            Command::SwitchChain { chain } | Command::Contact { name: chain } => chain.clone(),
+            Command::Accept { index } | Command::Decline { index } => count(index),
+            Command::Hold { amount, token, recipient, until } => {
+                format!("{} {} for {} {}", amount, token, party(recipient), count(until)).trim_end().to_string()
+            }
+            Command::Release { id } => id.clone(),
+            Command::Account { mode } => count(mode),
+            Command::Currency { code } => count(code),
+            Command::Approve { limit } => match limit {
+                Some(ApprovalLimit::Above { amount, token }) => format!("{} {}", amount, token),
+                Some(ApprovalLimit::Off) => "OFF".to_string(),
+                None => String::new(),
+            },
+            Command::AutoSwap { setting } => match setting {
+                Some(AutoSwapSetting::Percent(pct)) => format!("{}%", pct),
+                Some(AutoSwapSetting::Off) => "OFF".to_string(),
+                None => String::new(),
+            },
+            Command::HelpTopic { topic } => topic.clone(),
+            Command::Confirm { reference } | Command::Cancel { reference } => count(reference),
+            Command::Price { token } => count(token),
+            Command::Invalid { reason, .. } => reason.code().to_string(),
+            Command::Help | Command::Lock | Command::Freeze | Command::Balance | Command::Deposit | Command::ExportHistory | Command::Stop
+            | Command::CancelOrStop | Command::Start | Command::Unknown(_) => String::new(),
+        }
+    }
+}
+
+/// Outcome code recorded in the audit trail for a command's reply
+fn outcome_code(command: &Command, reply: &str) -> &'static str {
+    if matches!(command, Command::Unknown(_)) {
+        "unknown"
+    } else if matches!(command, Command::Invalid { .. }) {
+        "invalid"
+    } else if reply.is_empty() {
+        "suppressed"
+    } else if reply.starts_with("PIN needed") || (command.inline_pin().is_some() && reply.starts_with("Wrong PIN.")) {
+        "locked"
+    } else if reply.starts_with("Account frozen.") && !matches!(command, Command::Freeze) {
+        "frozen"
+    } else if reply.starts_with("[SIMULATED]") {
+        "simulated"
+    } else if reply.contains("Error") || reply.contains("Try later") || reply.contains("try again") {
+        "error"
+    } else {
+        "ok"
+    }
+}
+
+/// Recipient resolved from SMS input
+#[derive(Debug, Clone)]
+struct ResolvedRecipient {
+    address: String,
+    /// Phone of the registered user behind the address, when known
+    phone: Option<String>,
+    /// Which resolver matched
+    via: RecipientResolver,
+}
+
+/// One HISTORY line's source
+enum HistoryEntry<'a> {
+    Deposit(&'a Deposit),
+    Transaction(&'a Transaction),
+}
+
+/// TXTC counted in the wallet balance that's already spoken for
+#[derive(Debug, Clone, Copy, Default)]
+struct Committed {
+    in_channel: f64,
+    pending_out: f64,
+    held: f64,
+}
+
+impl Committed {
+    fn total(&self) -> f64 {
+        self.in_channel + self.pending_out + self.held
+    }
+}
+
 /// Command processor that parses and executes commands
 #[derive(Clone)]
 pub struct CommandProcessor {
@@ -56,23 +494,59 @@ pub struct CommandProcessor {
     voucher_repo: Option<VoucherRepository>,
     deposit_repo: Option<DepositRepository>,
     address_book_repo: Option<AddressBookRepository>,
+    inbound_repo: Option<InboundTransferRepository>,
+    escrow_repo: Option<EscrowRepository>,
+    audit_repo: Option<AuditRepository>,
+    transfer_repo: Option<TransferRepository>,
+    receipt_repo: Option<ReceiptRepository>,
+    channel_repo: Option<ChannelRepository>,
+    tag_repo: Option<PayeeTagRepository>,
+    transaction_repo: Option<TransactionRepository>,
     provider: Arc<AmoyProvider>,
     multi_chain: MultiChainProvider,
+    /// Per-chain balances by address for BALANCE_CHAINS, briefly reused
+    chain_balances: LookupCache<Vec<(Chain, Result<ChainBalances, String>)>>,
+    /// Chain heads for HISTORY confirmation counts
+    block_heights: BlockHeightCache,
     backend_url: String,
+    /// Arc service (USDC wallets and cashout)
+    arc_url: String,
+    /// Yellow service, for settling channels the sweep closes
+    yellow: YellowClient,
+    /// Prices used to convert fiat-denominated amounts
+    price_source: Arc<dyn PriceSource>,
+    /// ERC-4337 settings for smart-account mode (None disables it)
+    aa: Option<AaConfig>,
+    /// Large sends waiting for the user's confirmation
+    pending: PendingStore,
+    /// Keystore download links handed out by EXPORT
+    exports: ExportStore,
+    /// CSV download links handed out by EXPORT HISTORY
+    history_exports: ExportStore<String>,
+    /// Last use of cooldown-limited commands (BUY, CASHOUT) per user
+    cooldowns: CooldownStore,
+    /// Commands each phone may still send, so a flood can't hammer the backend
+    rate_limiter: RateLimiter,
+    /// Codes handed out by LINK, waiting to be used from the other phone
+    link_codes: LinkCodes,
+    /// Recent correct PINs, and commands waiting on one
+    pin_sessions: PinSessions,
+    /// Balance and transfer events for integrators
+    events: EventBus,
+    /// SENDs executed moments ago, so an identical repeat is confirmed first
+    recent_sends: RecentSends,
+    /// BUYs submitted moments ago, so a retried SMS isn't charged twice
+    recent_buys: RecentSends,
+    /// ENS resolutions and users by phone, reused for recipient lookups
+    lookups: LookupCaches,
+    config: CommandConfig,
+    /// Dry-run: money-moving calls are skipped and described instead
+    simulate: bool,
 }
 
 impl CommandProcessor {
     pub fn new(user_repo: Option<UserRepository>, provider: Arc<AmoyProvider>) -> Self {
-        let backend_url = std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
-        Self { 
-            user_repo,
-            voucher_repo: None,
-            deposit_repo: None,
-            address_book_repo: None,
-            provider,
-            multi_chain: MultiChainProvider::new(),
-            backend_url,
-        }
+        Self::build(user_repo, None, None, None, provider)
     }
 
     /// Create with all repositories
@@ -82,30 +556,312 @@ impl CommandProcessor {
         deposit_repo: Option<DepositRepository>,
         address_book_repo: Option<AddressBookRepository>,
         provider: Arc<AmoyProvider>,
+    ) -> Self {
+        Self::build(user_repo, voucher_repo, deposit_repo, address_book_repo, provider)
+    }
+
+    /// The one place every field is initialised; other repos and stores
+    /// start empty and are set with the `with_*` builders
+    fn build(
+        user_repo: Option<UserRepository>,
+        voucher_repo: Option<VoucherRepository>,
+        deposit_repo: Option<DepositRepository>,
+        address_book_repo: Option<AddressBookRepository>,
+        provider: Arc<AmoyProvider>,
     ) -> Self {
         let backend_url = std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+        let config = CommandConfig::from_env();
         Self {
             user_repo,
             voucher_repo,
             deposit_repo,
             address_book_repo,
+            inbound_repo: None,
+            escrow_repo: None,
+            audit_repo: None,
+            transfer_repo: None,
+            receipt_repo: None,
+            channel_repo: None,
+            tag_repo: None,
+            transaction_repo: None,
             provider,
-            multi_chain: MultiChainProvider::new(),
+            multi_chain: MultiChainProvider::with_chains(&config.balance_chains).with_ens_chain(config.ens_chain),
+            chain_balances: LookupCache::new(std::time::Duration::from_secs(config.chain_balance_cache_secs)),
+            block_heights: BlockHeightCache::new(std::time::Duration::from_secs(10)),
+            price_source: price_source_from_env(&backend_url),
             backend_url,
+            arc_url: std::env::var("ARC_SERVICE_URL").unwrap_or_else(|_| "http://arc:8084".to_string()),
+            yellow: YellowClient::new(std::env::var("YELLOW_SERVICE_URL").unwrap_or_else(|_| "http://yellow:8083".to_string())),
+            aa: None,
+            pending: PendingStore::new(std::time::Duration::from_secs(config.confirm_ttl_secs)),
+            exports: ExportStore::new(std::time::Duration::from_secs(600)),
+            history_exports: ExportStore::new(std::time::Duration::from_secs(600)),
+            cooldowns: CooldownStore::new(&config.command_cooldowns),
+            rate_limiter: RateLimiter::new(
+                config.rate_limit_commands,
+                std::time::Duration::from_secs(config.rate_limit_window_secs),
+            ),
+            link_codes: LinkCodes::new(std::time::Duration::from_secs(600)),
+            events: EventBus::default(),
+            pin_sessions: PinSessions::new(
+                std::time::Duration::from_secs(config.pin_session_secs),
+                std::time::Duration::from_secs(config.confirm_ttl_secs),
+            ),
+            recent_sends: RecentSends::new(std::time::Duration::from_secs(config.duplicate_send_window_secs)),
+            recent_buys: RecentSends::new(std::time::Duration::from_secs(config.duplicate_send_window_secs)),
+            lookups: LookupCaches::new(
+                std::time::Duration::from_secs(config.ens_cache_secs),
+                std::time::Duration::from_secs(config.user_cache_secs),
+            ),
+            config,
+            simulate: false,
+        }
+    }
+
+    /// Copy of this processor that never moves funds (used by the self-test)
+    pub fn simulated(&self) -> Self {
+        Self {
+            simulate: true,
+            ..self.clone()
         }
     }
 
-    /// Process an incoming SMS and return the response
+    /// A user's private key, decrypted from storage and hex-encoded for the
+    /// backend calls that sign for them. Errors carry the SMS reply.
+    fn signing_key(&self, user: &User) -> Result<String, String> {
+        self.user_wallet(user)
+            .map(|wallet| hex::encode(wallet.private_key_bytes()))
+            .map_err(|_| "Error. Try later.".to_string())
+    }
+
+    /// A user's wallet: re-derived from MASTER_MNEMONIC when they have a
+    /// derivation index, decrypted from storage otherwise. Errors are logged.
+    fn user_wallet(&self, user: &User) -> Result<UserWallet, WalletError> {
+        let wallet = match user.derivation_index {
+            Some(_) if self.config.master_mnemonic.is_empty() => {
+                Err(WalletError::CreationError("derived wallet but MASTER_MNEMONIC is not set".to_string()))
+            }
+            Some(index) => UserWallet::from_mnemonic(&self.config.master_mnemonic, index as u32).and_then(|wallet| {
+                // A different phrase derives a different wallet: never sign with it
+                if wallet.address_string().eq_ignore_ascii_case(&user.wallet_address) {
+                    Ok(wallet)
+                } else {
+                    Err(WalletError::CreationError(format!("index {} doesn't derive the stored address", index)))
+                }
+            }),
+            None => decrypt_stored_key(&user.encrypted_private_key, &self.config.encryption_key)
+                .and_then(|key| UserWallet::from_private_key(&key)),
+        };
+        wallet.inspect_err(|e| tracing::error!("Failed to load key for {}: {}", mask_phone(&user.phone), e))
+    }
+
+    /// Lookup caches, shared so the admin API can report hit/miss counts
+    pub fn lookup_caches(&self) -> &LookupCaches {
+        &self.lookups
+    }
+
+    /// Base URL of the backend API (BACKEND_URL)
+    pub fn backend_url(&self) -> &str {
+        &self.backend_url
+    }
+
+    /// Point backend calls at a different base URL
+    #[cfg(test)]
+    pub(crate) fn with_backend_url(mut self, backend_url: impl Into<String>) -> Self {
+        self.backend_url = backend_url.into();
+        self
+    }
+
+    /// Point the processor at a different Arc service (tests use a local mock)
+    #[cfg(test)]
+    pub(crate) fn with_arc_url(mut self, arc_url: impl Into<String>) -> Self {
+        self.arc_url = arc_url.into();
+        self
+    }
+
+    /// Point channel opens and closes at a different Yellow service (tests use a local mock)
+    #[cfg(test)]
+    pub(crate) fn with_yellow_url(mut self, yellow_url: impl Into<String>) -> Self {
+        self.yellow = YellowClient::new(yellow_url.into());
+        self
+    }
+
+    /// Replace the price source (tests inject a stub)
+    #[cfg(test)]
+    pub(crate) fn with_price_source(mut self, price_source: Arc<dyn PriceSource>) -> Self {
+        self.price_source = price_source;
+        self
+    }
+
+    /// Attach the inbound transfer repository (enables ACCEPT/DECLINE)
+    pub fn with_inbound_repo(mut self, inbound_repo: InboundTransferRepository) -> Self {
+        self.inbound_repo = Some(inbound_repo);
+        self
+    }
+
+    /// Enable smart-account mode with the bundler/factory settings
+    pub fn with_aa_config(mut self, aa: AaConfig) -> Self {
+        self.aa = Some(aa);
+        self
+    }
+
+    /// Attach the escrow repository (enables HOLD/RELEASE)
+    pub fn with_escrow_repo(mut self, escrow_repo: EscrowRepository) -> Self {
+        self.escrow_repo = Some(escrow_repo);
+        self
+    }
+
+    /// Attach the audit log repository (records every processed command)
+    pub fn with_audit_repo(mut self, audit_repo: AuditRepository) -> Self {
+        self.audit_repo = Some(audit_repo);
+        self
+    }
+
+    /// Attach the outgoing transfer repository (tracks SENDs for reconciliation)
+    pub fn with_transfer_repo(mut self, transfer_repo: TransferRepository) -> Self {
+        self.transfer_repo = Some(transfer_repo);
+        self
+    }
+
+    /// Attach the transaction repository (sends, swaps, cashouts and bridges for HISTORY)
+    pub fn with_transaction_repo(mut self, transaction_repo: TransactionRepository) -> Self {
+        self.transaction_repo = Some(transaction_repo);
+        self
+    }
+
+    /// Attach the pending-send repository, so sends awaiting CONFIRM survive
+    /// a restart and any instance can confirm them
+    pub fn with_pending_tx_repo(mut self, pending_tx_repo: PendingTxRepository) -> Self {
+        self.pending = self.pending.with_repo(pending_tx_repo);
+        self
+    }
+
+    /// Attach the receipt repository (signed receipts for completed SENDs)
+    pub fn with_receipt_repo(mut self, receipt_repo: ReceiptRepository) -> Self {
+        self.receipt_repo = Some(receipt_repo);
+        self
+    }
+
+    /// Wallet events emitted from now on (balance changes, completed transfers)
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<WalletEvent> {
+        self.events.subscribe()
+    }
+
+    /// Attach the payee tag repository (enables TAG and default send notes)
+    pub fn with_tag_repo(mut self, tag_repo: PayeeTagRepository) -> Self {
+        self.tag_repo = Some(tag_repo);
+        self
+    }
+
+    /// Attach the Yellow channel repository (enables the channel auto-close sweep)
+    pub fn with_channel_repo(mut self, channel_repo: ChannelRepository) -> Self {
+        self.channel_repo = Some(channel_repo);
+        self
+    }
+
+    /// Process an incoming SMS and return the response.
+    /// Empty when the sender has opted out (nothing should be sent).
     pub async fn process(&self, from: &str, body: &str) -> String {
+        self.process_unlocked(from, body, false).await
+    }
+
+    /// Process an SMS; `pin_entered` when it's a held command released by UNLOCK
+    async fn process_unlocked(&self, from: &str, body: &str, pin_entered: bool) -> String {
         let command = self.parse(body);
-        
+        // Checked before anything touches the database or backend; carrier
+        // keywords still work so a throttled user can always opt out
+        let carrier_keyword = matches!(
+            command,
+            Command::Stop | Command::CancelOrStop | Command::Start | Command::Help | Command::HelpTopic { .. }
+        );
+        if !pin_entered && !carrier_keyword && !self.rate_limiter.allow(from) {
+            tracing::warn!(from = %from, "Rate limit hit, dropping command");
+            return "Too many requests, wait a moment.".to_string();
+        }
+        let language = self.reply_language(from, body).await;
+        let correlation_id = uuid::Uuid::new_v4();
+        let (name, args) = (command.name(), command.redacted_args());
+
         tracing::debug!(
             from = %from,
-            command = ?command,
+            command = name,
+            args = %args,
+            %correlation_id,
             "Processing command"
         );
 
-        self.execute(from, command).await
+        // Opted-out users only get answers to the carrier keywords
+        let reply = if !carrier_keyword && self.is_opted_out(from).await {
+            tracing::info!(from = %from, "Sender opted out, ignoring message");
+            String::new()
+        } else if self.is_disabled(from, name) {
+            format!("{} isn't available in your region.\nReply MENU for commands.", name)
+        } else if let Some(left) = self.cooldowns.remaining(from, name) {
+            format!("You just did that. Wait {} before another {}.", format_wait(left), name)
+        } else if self.blocked_by_freeze(from, &command).await {
+            format!("Account frozen. {} is blocked.\nReply UNFREEZE <your PIN> to restore.", name)
+        } else if let Some(reply) = self.wrong_inline_pin(from, &command).await {
+            reply
+        } else if !pin_entered && command.inline_pin().is_none() && self.needs_pin(from, &command).await {
+            self.pin_sessions.hold(from, body);
+            format!("PIN needed for {}.\nReply UNLOCK <your PIN> to continue.", name)
+        } else {
+            let reply = self.execute(from, command.clone()).await;
+            if outcome_code(&command, &reply) == "ok" {
+                self.cooldowns.record(from, name);
+            }
+            reply
+        };
+
+        if let Some(ref audit_repo) = self.audit_repo {
+            let outcome = outcome_code(&command, &reply);
+            if let Err(e) = audit_repo.record(from, name, &args, outcome, correlation_id).await {
+                tracing::error!(%correlation_id, "Failed to write audit log: {}", e);
+            }
+        }
+        match language {
+            Some(language) => responses::localize(&reply, &language),
+            None => reply,
+        }
+    }
+
+    /// Language to reply to `from` in, learnt from the command word they
+    /// use: a localized word switches to its language, and an English word
+    /// picked over their language's own switches back to English. Other
+    /// words (PIN, an English-only command) leave it as it was.
+    async fn reply_language(&self, from: &str, body: &str) -> Option<String> {
+        if self.config.languages.is_empty() {
+            return None;
+        }
+        let repo = self.user_repo.as_ref()?;
+        let word = body.split_whitespace().next()?.to_uppercase();
+        let word = word.trim_end_matches(['.', '!', '?', ',', ';', ':']);
+        let current = repo.language(from).await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to read reply language: {}", e);
+            None
+        });
+
+        let detected = match synonyms::language_of(word, &self.config.languages) {
+            // SALDO is Spanish and Portuguese: a Portuguese speaker stays Portuguese
+            Some(_) if current.as_deref().is_some_and(|l| synonyms::resolve(word, &[l.to_string()]).is_some()) => current.clone(),
+            Some(language) => Some(language.to_string()),
+            None if current.as_deref().is_some_and(|l| synonyms::has_synonym_for(word, l)) => None,
+            None => return current,
+        };
+        if detected != current {
+            if let Err(e) = repo.set_language(from, detected.as_deref()).await {
+                tracing::warn!("Failed to save reply language: {}", e);
+            }
+        }
+        detected
+    }
+
+    /// Whether `phone` replied STOP (and hasn't sent START since)
+    pub async fn is_opted_out(&self, phone: &str) -> bool {
+        match self.user_repo {
+            Some(ref repo) => repo.is_opted_out(phone).await.unwrap_or(false),
+            None => false,
+        }
     }
 
     /// Parse SMS text into a structured command
@@ -119,8 +875,24 @@ impl CommandProcessor {
             return Command::Unknown("".to_string());
         }
 
-        match parts[0] {
-            "COMMANDS" | "MENU" | "?" => Command::Help,
+        // "BALANCE." / "HELP!" - only the keyword is trimmed, so amounts like 0.5
+        // and addresses are untouched. A bare "?" stays a keyword of its own.
+        let keyword = match parts[0].trim_end_matches(['.', '!', '?', ',', ';', ':']) {
+            "" => parts[0],
+            trimmed => trimmed,
+        };
+        // "SOLDE" / "ENVIAR" in the configured languages
+        let keyword = synonyms::resolve(keyword, &self.config.languages).unwrap_or(keyword);
+
+        match keyword {
+            "HELP" | "INFO" if parts.len() > 1 => Command::HelpTopic {
+                topic: parts[1].trim_end_matches(['.', '!', '?', ',', ';', ':']).to_string(),
+            },
+            "COMMANDS" | "MENU" | "?" | "HELP" | "INFO" => Command::Help,
+            // CANCEL is left to pending confirmations
+            "STOP" | "STOPALL" | "UNSUBSCRIBE" | "END" | "QUIT" => Command::Stop,
+            "UNSTOP" => Command::Start,
+            "START" if parts.len() == 1 => Command::Start,
             "JOIN" | "START" | "REGISTER" => {
                 let ens_name = parts.get(1).map(|s| s.to_lowercase());
                 Command::Join { ens_name }
@@ -130,42 +902,215 @@ impl CommandProcessor {
                 let new_pin = parts.get(1).map(|s| s.to_string());
                 Command::Pin { new_pin }
             }
+            "UNLOCK" => match parts.get(1) {
+                Some(pin) => Command::Unlock { pin: pin.to_string() },
+                None => Command::invalid("UNLOCK", ParseError::MissingArgument, "Usage: UNLOCK <PIN>"),
+            },
+            "LOCK" => Command::Lock,
+            "FREEZE" | "PANIC" => Command::Freeze,
+            "UNFREEZE" => match parts.get(1) {
+                Some(pin) => Command::Unfreeze { pin: pin.to_string() },
+                None => Command::invalid("UNFREEZE", ParseError::MissingArgument, "Usage: UNFREEZE <PIN>"),
+            },
             "SEND" => self.parse_send(&original_parts),
             "DEPOSIT" | "RECEIVE" => Command::Deposit,
-            "HISTORY" | "TRANSACTIONS" | "TXS" => Command::History,
+            "HISTORY" | "TRANSACTIONS" | "TXS" => match Self::parse_index(&parts) {
+                Ok(limit) => Command::History { limit },
+                Err(_) => Command::invalid("HISTORY", ParseError::InvalidCount, "Usage: HISTORY [count]"),
+            },
             "REDEEM" | "VOUCHER" | "CODE" => {
                 if parts.len() < 2 {
-                    Command::Unknown("Usage: REDEEM <code>".to_string())
+                    Command::invalid("REDEEM", ParseError::MissingArgument, "Usage: REDEEM <code>")
                 } else {
                     Command::Redeem { code: parts[1].to_string() }
                 }
             }
             "SWAP" | "EXCHANGE" => self.parse_swap(&parts),
+            "QUOTE" => Self::parse_quote(&parts),
+            "PRICE" => match &parts[1..] {
+                [] => Command::Price { token: None },
+                [token @ ("ETH" | "TXTC")] => Command::Price { token: Some(token.to_string()) },
+                _ => Command::invalid("PRICE", ParseError::Usage, "Usage: PRICE [ETH|TXTC]\nEx: PRICE ETH"),
+            },
             "CASHOUT" | "CASH" => self.parse_cashout(&parts),
             "BUY" | "TOPUP" | "PURCHASE" => self.parse_buy(&parts),
             "BRIDGE" | "CROSS" => self.parse_bridge(&parts),
-            "SAVE" | "ADD" => self.parse_save(&parts),
-            "CONTACTS" | "BOOK" => Command::Contacts,
+            "SAVE" | "ADD" => self.parse_save(&original_parts),
+            "TAG" => Self::parse_tag(&original_parts),
+            "SAVEALL" => self.parse_save_all(&parts),
+            "CONTACT" => {
+                if parts.len() < 2 {
+                    Command::invalid("CONTACT", ParseError::MissingArgument, "Usage: CONTACT <name>")
+                } else {
+                    Command::Contact { name: parts[1..].join(" ") }
+                }
+            }
+            "VOUCHERS" => match Self::parse_index(&parts) {
+                Ok(limit) => Command::Vouchers { limit },
+                Err(_) => Command::invalid("VOUCHERS", ParseError::InvalidCount, "Usage: VOUCHERS [count]"),
+            },
+            "CONTACTS" | "BOOK" => match Self::parse_index(&parts) {
+                Ok(limit) => Command::Contacts { limit },
+                Err(_) => Command::invalid("CONTACTS", ParseError::InvalidCount, "Usage: CONTACTS [count]"),
+            },
+            "PAYABLE" => match Self::parse_index(&parts) {
+                Ok(limit) => Command::Payable { limit },
+                Err(_) => Command::invalid("PAYABLE", ParseError::InvalidCount, "Usage: PAYABLE [count]"),
+            },
             "CHAIN" | "NETWORK" => {
                 if parts.len() < 2 {
-                    Command::Unknown("Usage: CHAIN <polygon|base|eth|arb>".to_string())
+                    Command::invalid("CHAIN", ParseError::MissingArgument, "Usage: CHAIN <polygon|base|eth|arb>")
                 } else {
                     Command::SwitchChain { chain: parts[1].to_string() }
                 }
             }
+            "ACCEPT" => match Self::parse_index(&parts) {
+                Ok(index) => Command::Accept { index },
+                Err(_) => Command::invalid("ACCEPT", ParseError::InvalidCount, "Usage: ACCEPT <number>"),
+            },
+            "DECLINE" | "REJECT" => match Self::parse_index(&parts) {
+                Ok(index) => Command::Decline { index },
+                Err(_) => Command::invalid("DECLINE", ParseError::InvalidCount, "Usage: DECLINE <number>"),
+            },
+            "HOLD" | "ESCROW" => self.parse_hold(&original_parts),
+            "ACCOUNT" => match parts.get(1).copied() {
+                None => Command::Account { mode: None },
+                Some("SMART" | "AA") => Command::Account { mode: Some("smart".to_string()) },
+                Some("EOA" | "BASIC") => Command::Account { mode: Some("eoa".to_string()) },
+                Some(_) => Command::invalid("ACCOUNT", ParseError::UnsupportedOption, "Usage: ACCOUNT <EOA|SMART>"),
+            },
+            "CURRENCY" | "FIAT" => match parts.get(1).copied() {
+                None => Command::Currency { code: None },
+                Some("OFF" | "NONE") => Command::Currency { code: Some("OFF".to_string()) },
+                Some(code) => match supported_currency(code) {
+                    Some(code) => Command::Currency { code: Some(code.to_string()) },
+                    None => Command::invalid(
+                        "CURRENCY",
+                        ParseError::UnsupportedOption,
+                        format!("Supported: {}\nExample: CURRENCY KES", SUPPORTED_CURRENCIES.join(", ")),
+                    ),
+                },
+            },
+            "APPROVE" => {
+                let usage = "Usage: APPROVE ABOVE <amount> [token]\nExample: APPROVE ABOVE 100 TXTC";
+                let args = match parts.get(1) {
+                    Some(&"ABOVE") => &parts[2..],
+                    _ => &parts[1..],
+                };
+                match args {
+                    [] if parts.len() == 1 => Command::Approve { limit: None },
+                    ["OFF" | "NONE"] => Command::Approve { limit: Some(ApprovalLimit::Off) },
+                    [amount] | [amount, _] => match parse_amount(amount) {
+                        Some(amount) if amount > 0.0 => Command::Approve {
+                            limit: Some(ApprovalLimit::Above {
+                                amount,
+                                token: args.get(1).copied().unwrap_or("TXTC").to_string(),
+                            }),
+                        },
+                        _ => Command::invalid("APPROVE", ParseError::InvalidAmount, usage),
+                    },
+                    _ => Command::invalid("APPROVE", ParseError::Usage, usage),
+                }
+            }
+            "AUTOSWAP" => self.parse_autoswap(&parts),
+            "LINK" => Command::Link { code: parts.get(1).map(|s| s.to_string()) },
+            "CONFIRM" | "YES" => Command::Confirm { reference: parts.get(1).map(|s| s.to_string()) },
+            "CANCEL" if parts.len() == 1 => Command::CancelOrStop,
+            "CANCEL" | "NO" => Command::Cancel { reference: parts.get(1).map(|s| s.to_string()) },
+            "EXPORT" if parts.len() == 2 && parts[1] == "HISTORY" => Command::ExportHistory,
+            "EXPORT" | "BACKUP" => {
+                // Passphrase keeps its case and inner spaces
+                let passphrase = original_parts[1..].join(" ");
+                if passphrase.is_empty() {
+                    Command::invalid(
+                        "EXPORT",
+                        ParseError::MissingArgument,
+                        "Usage: EXPORT <passphrase>\nYou'll need the passphrase to import the wallet.",
+                    )
+                } else if passphrase.chars().count() < 8 {
+                    Command::invalid("EXPORT", ParseError::InvalidArgument, "Passphrase too short (min 8 characters).")
+                } else {
+                    Command::Export { passphrase }
+                }
+            }
+            "RELEASE" => {
+                if parts.len() < 2 {
+                    Command::invalid("RELEASE", ParseError::MissingArgument, "Usage: RELEASE <ref>")
+                } else {
+                    Command::Release { id: parts[1].to_string() }
+                }
+            }
             _ => Command::Unknown(text),
         }
     }
 
-    /// Parse SAVE command: SAVE <name> <phone>
+    /// Parse an optional 1-based list position: ACCEPT 2
+    fn parse_index(parts: &[&str]) -> Result<Option<usize>, ()> {
+        match parts.get(1) {
+            None => Ok(None),
+            Some(s) => match s.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Some(n)),
+                _ => Err(()),
+            },
+        }
+    }
+
+    /// Parse SAVE command: SAVE <name> <phone|0x address|ENS name>
+    /// Supports: SAVE alice +1 555 123 4567
+    ///           SAVE alice 0xabc...
+    ///           SAVE alice alice.eth
     fn parse_save(&self, parts: &[&str]) -> Command {
+        let usage = "Usage: SAVE <name> <phone|0x address|ENS name>";
         if parts.len() < 3 {
-            return Command::Unknown("Usage: SAVE <name> <phone>".to_string());
+            return Command::invalid("SAVE", ParseError::Usage, usage);
+        }
+        let name = parts[1].to_uppercase();
+        let target = parts[2..].join(" ");
+
+        let contact = if target.starts_with('+') {
+            match normalize_phone(&target) {
+                Some(phone) => SavedContact::Phone(phone),
+                None => return Command::invalid("SAVE", ParseError::InvalidArgument, "Invalid phone.\nEx: SAVE alice +15551234567"),
+            }
+        } else if let Some(hex) = target.strip_prefix("0x").or_else(|| target.strip_prefix("0X")) {
+            // Kept as typed so a checksummed address stays checksummed
+            let address = format!("0x{}", hex);
+            if hex.len() != 40 || Address::from_str(&address).is_err() {
+                return Command::invalid("SAVE", ParseError::InvalidArgument, "Invalid address.\nEx: SAVE alice 0x71C7...976F");
+            }
+            SavedContact::Address(address)
+        } else if parts.len() == 3 && target.contains('.') && !target.starts_with('.') && !target.ends_with('.') {
+            SavedContact::Ens(target.to_lowercase())
+        } else {
+            return Command::invalid("SAVE", ParseError::Usage, usage);
+        };
+        Command::Save { name, contact }
+    }
+
+    /// Parse SAVEALL command: SAVEALL alice +1555..., bob +1556...
+    fn parse_save_all(&self, parts: &[&str]) -> Command {
+        let usage = "Usage: SAVEALL <name> <phone>, <name> <phone>, ...";
+        let rest = parts[1..].join(" ");
+        let contacts: Vec<(String, String)> = rest
+            .split(',')
+            .filter_map(|entry| {
+                let mut words = entry.split_whitespace();
+                let name = words.next()?;
+                Some((name.to_string(), words.collect::<Vec<_>>().join(" ")))
+            })
+            .collect();
+
+        if contacts.is_empty() {
+            return Command::invalid("SAVEALL", ParseError::Usage, usage);
         }
-        Command::Save {
-            name: parts[1].to_string(),
-            phone: parts[2..].join(" "),
+        if contacts.len() > MAX_SAVEALL_CONTACTS {
+            return Command::invalid(
+                "SAVEALL",
+                ParseError::InvalidArgument,
+                format!("Too many contacts ({}). Max {} per message.", contacts.len(), MAX_SAVEALL_CONTACTS),
+            );
         }
+        Command::SaveAll { contacts }
     }
 
     /// Parse SEND command: SEND <amount> <token> [TO] <recipient>
@@ -173,150 +1118,726 @@ impl CommandProcessor {
     ///           SEND 10 TXTC swarnim.ttcip.eth
     ///           SEND 0.001 ETH 0xabc...
     fn parse_send(&self, parts: &[&str]) -> Command {
+        if let Some(command) = self.parse_send_fiat(parts) {
+            return command;
+        }
+
         if parts.len() < 4 {
-            return Command::Unknown("Use: SEND <amount> <token> <recipient>\nExample: SEND 10 TXTC swarnim.ttcip.eth".to_string());
+            let reason = if parts.len() < 2 { ParseError::MissingAmount } else { ParseError::MissingRecipient };
+            return Command::invalid(
+                "SEND",
+                reason,
+                "Use: SEND <amount> <token> <recipient>\nExample: SEND 10 TXTC swarnim.ttcip.eth",
+            );
         }
 
-        let amount = match parts[1].parse::<f64>() {
-            Ok(amt) => amt,
-            Err(_) => return Command::Unknown("Invalid amount".to_string()),
+        let Some((amount, token)) = Self::amount_and_token(parts[1], parts[2]) else {
+            return Command::invalid("SEND", ParseError::InvalidAmount, "Invalid amount");
         };
+        let token = token.to_string();
+        let (parts, pin) = Self::trailing_pin(parts);
 
-        let token = parts[2].to_string();
+        // Optional trailing NOTE <text>, kept as typed
+        let (parts, note) = match parts.iter().skip(3).position(|p| p.eq_ignore_ascii_case("NOTE")) {
+            Some(i) => {
+                let note = parts[i + 4..].join(" ");
+                if note.is_empty() {
+                    return Command::invalid("SEND", ParseError::MissingArgument, "Missing note after NOTE.\nExample: SEND 10 TXTC TO alice NOTE rent");
+                }
+                (&parts[..i + 3], Some(note))
+            }
+            None => (parts, None),
+        };
 
         // Check if "TO" keyword is present (optional)
-        let recipient = if parts.len() >= 5 && parts[3].eq_ignore_ascii_case("TO") {
-            parts[4..].join(" ")
-        } else {
-            parts[3..].join(" ")
+        let recipient = match parts.get(3) {
+            Some(to) if to.eq_ignore_ascii_case("TO") => parts[4..].join(" "),
+            _ => parts[3..].join(" "),
         };
 
-        if recipient.is_empty() {
-            return Command::Unknown("Missing recipient.\nExample: SEND 10 TXTC swarnim.ttcip.eth".to_string());
+        if let Some(error) = Self::check_recipient(&recipient, "SEND 10 TXTC TO swarnim.ttcip.eth") {
+            return error;
         }
 
         Command::Send {
             amount,
             token,
             recipient,
+            note,
+            pin,
         }
     }
 
-    /// Parse BRIDGE command: BRIDGE <amount> <token> FROM <chain> TO <chain>
-    /// Also supports: BRIDGE <amount> <token> <from_chain> <to_chain>
-    fn parse_bridge(&self, parts: &[&str]) -> Command {
-        if parts.len() < 5 {
-            return Command::Unknown("Usage: BRIDGE <amount> <token> FROM <chain> TO <chain>\nExample: BRIDGE 10 USDC FROM POLYGON TO BASE".to_string());
+    /// Split a trailing `PIN <digits>` off SEND or CASHOUT
+    fn trailing_pin<'a>(parts: &'a [&'a str]) -> (&'a [&'a str], Option<String>) {
+        match parts {
+            [rest @ .., keyword, pin] if keyword.eq_ignore_ascii_case("PIN") && pin.chars().all(|c| c.is_ascii_digit()) => {
+                (rest, Some(pin.to_string()))
+            }
+            _ => (parts, None),
         }
+    }
 
-        let amount = match parts[1].parse::<f64>() {
-            Ok(amt) => amt,
-            Err(_) => return Command::Unknown("Invalid amount".to_string()),
+    /// Parse AUTOSWAP command: AUTOSWAP | AUTOSWAP ON <n>% | AUTOSWAP <n>% | AUTOSWAP OFF
+    fn parse_autoswap(&self, parts: &[&str]) -> Command {
+        let max = self.config.autoswap_max_pct;
+        let usage = format!("AUTOSWAP takes 1% to {}%.\nExample: AUTOSWAP ON 10%", max);
+        let pct = match &parts[1..] {
+            [] => return Command::AutoSwap { setting: None },
+            ["OFF"] => return Command::AutoSwap { setting: Some(AutoSwapSetting::Off) },
+            ["ON"] => return Command::invalid("AUTOSWAP", ParseError::MissingAmount, usage),
+            ["ON", pct] | [pct] => pct,
+            _ => return Command::invalid("AUTOSWAP", ParseError::Usage, usage),
         };
+        match pct.trim_end_matches('%').parse::<u32>() {
+            Ok(pct) if (1..=max).contains(&pct) => Command::AutoSwap { setting: Some(AutoSwapSetting::Percent(pct)) },
+            _ => Command::invalid("AUTOSWAP", ParseError::InvalidAmount, usage),
+        }
+    }
 
-        let token = parts[2].to_string();
-
-        // Parse FROM/TO chains - support both "FROM x TO y" and "x y" formats
-        let (from_chain, to_chain) = if parts.len() >= 7 && parts[3] == "FROM" && parts[5] == "TO" {
-            (parts[4].to_string(), parts[6].to_string())
-        } else if parts.len() >= 6 && parts[3] == "FROM" {
-            // BRIDGE 10 USDC FROM POLYGON BASE
-            (parts[4].to_string(), parts[5].to_string())
-        } else if parts.len() >= 5 {
-            // BRIDGE 10 USDC POLYGON BASE
-            (parts[3].to_string(), parts[4].to_string())
-        } else {
-            return Command::Unknown("Usage: BRIDGE <amount> <token> FROM <chain> TO <chain>".to_string());
+    /// Parse TAG command: TAG <payee> <note...> | TAG <payee> OFF
+    fn parse_tag(parts: &[&str]) -> Command {
+        let usage = "Usage: TAG <payee> <note>\nExample: TAG alice rent";
+        let (payee, tag) = match parts {
+            [_, payee, tag @ ..] if !tag.is_empty() => (payee.to_string(), tag.join(" ")),
+            [_, _] => return Command::invalid("TAG", ParseError::MissingArgument, usage),
+            _ => return Command::invalid("TAG", ParseError::Usage, usage),
         };
+        if tag.eq_ignore_ascii_case("OFF") {
+            return Command::Tag { payee, tag: None };
+        }
+        if tag.chars().count() > 64 {
+            return Command::invalid("TAG", ParseError::InvalidArgument, "Tag too long (max 64 characters).");
+        }
+        Command::Tag { payee, tag: Some(tag) }
+    }
 
-        Command::Bridge {
-            amount,
-            token,
-            from_chain,
-            to_chain,
+    /// Amount and token from SEND's first two arguments in either order
+    /// ("10 TXTC" or "TXTC 10"). Amount-first wins whenever it parses, and
+    /// the swapped reading needs a plain word for the token.
+    fn amount_and_token<'a>(first: &'a str, second: &'a str) -> Option<(f64, &'a str)> {
+        if let Some(amount) = parse_amount(first) {
+            return Some((amount, second));
+        }
+        match parse_amount(second) {
+            Some(amount) if first.chars().all(|c| c.is_ascii_alphabetic()) => Some((amount, first)),
+            _ => None,
         }
     }
 
-    /// Parse BUY command: BUY <amount>
-    fn parse_buy(&self, parts: &[&str]) -> Command {
-        if parts.len() < 2 {
-            return Command::Unknown("Usage: BUY <amount>\nExample: BUY 10 (buys €10 of TXTC with airtime)".to_string());
+    /// Reject a missing recipient (e.g. a dangling TO) or one made only of punctuation
+    fn check_recipient(recipient: &str, example: &str) -> Option<Command> {
+        if recipient.trim().is_empty() {
+            return Some(Command::invalid(
+                "SEND",
+                ParseError::MissingRecipient,
+                format!("Missing recipient after TO.\nExample: {}", example),
+            ));
+        }
+        if !recipient.chars().any(|c| c.is_alphanumeric()) {
+            return Some(Command::invalid(
+                "SEND",
+                ParseError::InvalidRecipient,
+                format!("Invalid recipient \"{}\".\nUse a name, phone (+1...) or address (0x...).", recipient),
+            ));
         }
+        None
+    }
 
-        let amount = match parts[1].parse::<f64>() {
-            Ok(amt) => amt,
-            Err(_) => return Command::Unknown("Invalid amount".to_string()),
-        };
+    /// Parse a fiat-denominated SEND, or None if the amount isn't in fiat:
+    ///   SEND $10 TO alice          (token defaults to TXTC)
+    ///   SEND 10 USD ETH TO alice
+    fn parse_send_fiat(&self, parts: &[&str]) -> Option<Command> {
+        let first = parts.get(1)?;
 
-        Command::Buy { amount }
-    }
+        let (fiat_amount, currency, rest) = match first.chars().next().and_then(currency_for_symbol) {
+            Some(currency) => {
+                let symbol_len = first.chars().next()?.len_utf8();
+                (parse_amount(&first[symbol_len..]), currency, &parts[2..])
+            }
+            None => {
+                let currency = parts.get(2).and_then(|c| supported_currency(c))?;
+                (parse_amount(first), currency, &parts[3..])
+            }
+        };
 
-    /// Parse SWAP command: SWAP <amount> TXTC
-    fn parse_swap(&self, parts: &[&str]) -> Command {
-        if parts.len() < 3 {
-            return Command::Unknown("Usage: SWAP <amount> TXTC".to_string());
-        }
+        let Some(fiat_amount) = fiat_amount.filter(|a| *a > 0.0) else {
+            return Some(Command::invalid("SEND", ParseError::InvalidAmount, "Invalid amount"));
+        };
 
-        let amount = match parts[1].parse::<f64>() {
-            Ok(amt) => amt,
-            Err(_) => return Command::Unknown("Invalid amount".to_string()),
+        // Optional token, then optional TO
+        let (token, rest) = match rest {
+            [t, tail @ ..] if !tail.is_empty() && (t.eq_ignore_ascii_case("TXTC") || t.eq_ignore_ascii_case("ETH")) => {
+                (t.to_string(), tail)
+            }
+            _ => ("TXTC".to_string(), rest),
+        };
+        let rest = match rest {
+            [to, tail @ ..] if to.eq_ignore_ascii_case("TO") => tail,
+            _ => rest,
         };
 
-        let token = parts[2].to_string();
-        
+        let recipient = rest.join(" ");
+        if let Some(error) = Self::check_recipient(&recipient, "SEND $10 TO alice") {
+            return Some(error);
+        }
+
+        Some(Command::SendFiat {
+            fiat_amount,
+            currency: currency.to_string(),
+            token,
+            recipient,
+        })
+    }
+
+    /// Parse HOLD command: HOLD <amount> <token> FOR <recipient> [UNTIL YYYY-MM-DD]
+    /// Without UNTIL the funds stay locked until the payer sends RELEASE <ref>
+    fn parse_hold(&self, parts: &[&str]) -> Command {
+        const USAGE: &str = "Usage: HOLD <amount> <token> FOR <recipient> [UNTIL YYYY-MM-DD]";
+
+        if parts.len() < 5 || !parts[3].eq_ignore_ascii_case("FOR") {
+            return Command::invalid("HOLD", ParseError::Usage, USAGE);
+        }
+
+        let amount = match parse_amount(parts[1]) {
+            Some(amt) if amt > 0.0 => amt,
+            _ => return Command::invalid("HOLD", ParseError::InvalidAmount, "Invalid amount"),
+        };
+
+        let token = parts[2].to_string();
+
+        let until_pos = parts.iter().position(|p| p.eq_ignore_ascii_case("UNTIL"));
+        let recipient_end = until_pos.unwrap_or(parts.len());
+        let recipient = parts[4..recipient_end].join(" ");
+        if recipient.is_empty() {
+            return Command::invalid("HOLD", ParseError::MissingRecipient, USAGE);
+        }
+
+        let until = match until_pos {
+            None => None,
+            Some(pos) => {
+                if parts.len() != pos + 2 {
+                    return Command::invalid("HOLD", ParseError::Usage, USAGE);
+                }
+                match chrono::NaiveDate::parse_from_str(parts[pos + 1], "%Y-%m-%d") {
+                    Ok(date) => Some(date),
+                    Err(_) => return Command::invalid("HOLD", ParseError::InvalidDate, "Invalid date. Use YYYY-MM-DD"),
+                }
+            }
+        };
+
+        Command::Hold { amount, token, recipient, until }
+    }
+
+    /// Parse BRIDGE command: BRIDGE <amount> <token> FROM <chain> TO <chain> [AS <token>]
+    /// Also supports: BRIDGE <amount> <token> <from_chain> <to_chain>
+    fn parse_bridge(&self, parts: &[&str]) -> Command {
+        if parts.len() < 5 {
+            return Command::invalid(
+                "BRIDGE",
+                ParseError::Usage,
+                "Usage: BRIDGE <amount> <token> FROM <chain> TO <chain>\nExample: BRIDGE 10 USDC FROM POLYGON TO BASE",
+            );
+        }
+
+        let amount = match parse_amount(parts[1]) {
+            Some(amt) => amt,
+            None => return Command::invalid("BRIDGE", ParseError::InvalidAmount, "Invalid amount"),
+        };
+
+        let token = parts[2].to_string();
+
+        // Optional trailing AS <token> for swap-bridges: BRIDGE 10 USDC FROM POLYGON TO BASE AS ETH
+        let (parts, to_token) = match parts.iter().skip(3).position(|p| *p == "AS") {
+            Some(i) => {
+                let as_index = i + 3;
+                match &parts[as_index + 1..] {
+                    [target] => (&parts[..as_index], target.to_string()),
+                    [] => {
+                        return Command::invalid(
+                            "BRIDGE",
+                            ParseError::MissingArgument,
+                            "Missing token after AS.\nExample: BRIDGE 10 USDC FROM POLYGON TO BASE AS ETH",
+                        )
+                    }
+                    _ => {
+                        return Command::invalid(
+                            "BRIDGE",
+                            ParseError::Usage,
+                            "Usage: BRIDGE <amount> <token> FROM <chain> TO <chain> AS <token>",
+                        )
+                    }
+                }
+            }
+            None => (parts, token.clone()),
+        };
+
+        // Parse FROM/TO chains - support both "FROM x TO y" and "x y" formats
+        let (from_chain, to_chain) = if parts.len() >= 7 && parts[3] == "FROM" && parts[5] == "TO" {
+            (parts[4].to_string(), parts[6].to_string())
+        } else if parts.len() >= 6 && parts[3] == "FROM" {
+            // BRIDGE 10 USDC FROM POLYGON BASE
+            (parts[4].to_string(), parts[5].to_string())
+        } else if parts.len() >= 5 {
+            // BRIDGE 10 USDC POLYGON BASE
+            (parts[3].to_string(), parts[4].to_string())
+        } else {
+            return Command::invalid("BRIDGE", ParseError::Usage, "Usage: BRIDGE <amount> <token> FROM <chain> TO <chain>");
+        };
+
+        Command::Bridge {
+            amount,
+            token,
+            from_chain,
+            to_chain,
+            to_token,
+        }
+    }
+
+    /// Parse BUY command: BUY <amount>
+    fn parse_buy(&self, parts: &[&str]) -> Command {
+        if parts.len() < 2 {
+            return Command::invalid(
+                "BUY",
+                ParseError::MissingAmount,
+                "Usage: BUY <amount>\nExample: BUY 10 (buys €10 of TXTC with airtime)",
+            );
+        }
+
+        let amount = match parse_amount(parts[1]) {
+            Some(amt) => amt,
+            None => return Command::invalid("BUY", ParseError::InvalidAmount, "Invalid amount"),
+        };
+
+        // Airtime comes in the market's currency units, within set bounds
+        let (min, max, decimals) = (self.config.buy_min, self.config.buy_max, self.config.buy_decimals);
+        let places = parts[1].split_once('.').map_or(0, |(_, fraction)| fraction.len());
+        if places > decimals as usize {
+            let hint = if decimals == 0 {
+                "BUY takes whole euros only.\nExample: BUY 10".to_string()
+            } else {
+                format!("BUY takes up to {} decimal place{}.\nExample: BUY 10", decimals, if decimals == 1 { "" } else { "s" })
+            };
+            return Command::invalid("BUY", ParseError::InvalidAmount, hint);
+        }
+        if amount < min || amount > max {
+            return Command::invalid(
+                "BUY",
+                ParseError::InvalidAmount,
+                format!("BUY amount must be €{} to €{}.\nExample: BUY 10", min, max),
+            );
+        }
+
+        Command::Buy { amount }
+    }
+
+    /// Parse SWAP command: SWAP <amount> TXTC
+    fn parse_swap(&self, parts: &[&str]) -> Command {
+        if parts.len() < 3 {
+            return Command::invalid("SWAP", ParseError::Usage, "Usage: SWAP <amount> TXTC");
+        }
+
+        let amount = match parse_amount(parts[1]) {
+            Some(amt) => amt,
+            None => return Command::invalid("SWAP", ParseError::InvalidAmount, "Invalid amount"),
+        };
+
+        let token = parts[2].to_string();
+        
         Command::Swap {
             amount,
             token,
         }
     }
 
+    /// Parse QUOTE command: QUOTE <amount> TXTC or QUOTE <amount> ETH
+    fn parse_quote(parts: &[&str]) -> Command {
+        let usage = "Usage: QUOTE <amount> TXTC\nOr: QUOTE <amount> ETH";
+        let [_, amount, token] = parts else {
+            return Command::invalid("QUOTE", ParseError::Usage, usage);
+        };
+        let Some(amount) = parse_amount(amount).filter(|a| *a > 0.0) else {
+            return Command::invalid("QUOTE", ParseError::InvalidAmount, "Invalid amount");
+        };
+        if *token != "TXTC" && *token != "ETH" {
+            return Command::invalid("QUOTE", ParseError::Usage, usage);
+        }
+        Command::Quote { amount, token: token.to_string() }
+    }
+
     /// Parse CASHOUT command: CASHOUT <amount> TXTC or CASHOUT <amount> ETH
     fn parse_cashout(&self, parts: &[&str]) -> Command {
         if parts.len() < 3 {
-            return Command::Unknown("Usage: CASHOUT <amount> TXTC\nOr: CASHOUT <amount> ETH".to_string());
+            return Command::invalid("CASHOUT", ParseError::Usage, "Usage: CASHOUT <amount> TXTC\nOr: CASHOUT <amount> ETH");
         }
 
-        let amount = match parts[1].parse::<f64>() {
-            Ok(amt) => amt,
-            Err(_) => return Command::Unknown("Invalid amount".to_string()),
+        let amount = match parse_amount(parts[1]) {
+            Some(amt) => amt,
+            None => return Command::invalid("CASHOUT", ParseError::InvalidAmount, "Invalid amount"),
         };
 
         let token = parts[2].to_string();
+        let (parts, pin) = Self::trailing_pin(parts);
+
+        let to = match &parts[3..] {
+            [] => None,
+            [to, currency] if to.eq_ignore_ascii_case("TO") => {
+                let currency = currency.to_uppercase();
+                if !self.config.cashout_currencies.contains(&currency) {
+                    return Command::invalid(
+                        "CASHOUT",
+                        ParseError::Usage,
+                        format!("Can't cash out to {}.\nAvailable: {}", currency, self.config.cashout_currencies.join(", ")),
+                    );
+                }
+                Some(currency)
+            }
+            _ => return Command::invalid("CASHOUT", ParseError::Usage, "Usage: CASHOUT <amount> TXTC\nOr: CASHOUT <amount> TXTC TO USDT"),
+        };
 
         Command::Cashout {
             amount,
             token,
+            to,
+            pin,
         }
     }
 
     /// Execute a parsed command and return the response text
     async fn execute(&self, from: &str, command: Command) -> String {
         match command {
-            Command::Help => self.help_response(),
+            Command::Help => self.help_response(from),
+            Command::HelpTopic { topic } => self.help_topic_response(from, &topic),
             Command::Join { ens_name } => self.join_response(from, ens_name).await,
             Command::Balance => self.balance_response(from).await,
             Command::Pin { new_pin } => self.pin_response(from, new_pin).await,
-            Command::Send { amount, token, recipient } => {
-                self.send_response(from, amount, &token, &recipient).await
+            Command::Unlock { pin } => self.unlock_response(from, &pin).await,
+            Command::Lock => {
+                self.pin_sessions.lock(from);
+                "Locked. Your PIN will be needed for the next send.".to_string()
+            }
+            Command::Freeze => self.freeze_response(from).await,
+            Command::Unfreeze { pin } => self.unfreeze_response(from, &pin).await,
+            Command::Send { amount, token, recipient, note, .. } => {
+                self.send_response(from, amount, &token, &recipient, note.as_deref()).await
+            }
+            Command::SendFiat { fiat_amount, currency, token, recipient } => {
+                self.send_fiat_response(from, fiat_amount, &currency, &token, &recipient).await
             }
             Command::Deposit => self.deposit_response(from).await,
-            Command::History => self.history_response(from).await,
+            Command::History { limit } => self.history_response(from, limit).await,
             Command::Redeem { code } => self.redeem_response(from, &code).await,
+            Command::Vouchers { limit } => self.vouchers_response(from, limit).await,
             Command::Buy { amount } => self.buy_response(from, amount).await,
             Command::Swap { amount, token } => self.swap_response(from, amount, &token).await,
-            Command::Cashout { amount, token } => self.cashout_response(from, amount, &token).await,
-            Command::Bridge { amount, token, from_chain, to_chain } => {
-                self.bridge_response(from, amount, &token, &from_chain, &to_chain).await
+            Command::Quote { amount, token } => self.quote_response(amount, &token).await,
+            Command::Price { token } => self.price_response(from, token.as_deref()).await,
+            Command::Cashout { amount, token, to, .. } => self.cashout_response(from, amount, &token, to.as_deref()).await,
+            Command::Bridge { amount, token, from_chain, to_chain, to_token } => {
+                self.bridge_response(from, amount, &token, &from_chain, &to_chain, &to_token).await
             }
-            Command::Save { name, phone } => self.save_response(from, &name, &phone).await,
-            Command::Contacts => self.contacts_response(from).await,
+            Command::Save { name, contact } => self.save_response(from, &name, &contact).await,
+            Command::Tag { payee, tag } => self.tag_response(from, &payee, tag.as_deref()).await,
+            Command::SaveAll { contacts } => self.save_all_response(from, &contacts).await,
+            Command::Contacts { limit } => self.contacts_response(from, limit).await,
+            Command::Payable { limit } => self.payable_response(from, limit).await,
+            Command::Contact { name } => self.contact_response(from, &name).await,
             Command::SwitchChain { chain } => self.chain_response(from, &chain).await,
+            Command::Accept { index } => self.inbound_response(from, index, true).await,
+            Command::Decline { index } => self.inbound_response(from, index, false).await,
+            Command::Hold { amount, token, recipient, until } => {
+                self.hold_response(from, amount, &token, &recipient, until).await
+            }
+            Command::Release { id } => self.release_response(from, &id).await,
+            Command::Account { mode } => self.account_response(from, mode.as_deref()).await,
+            Command::Currency { code } => self.currency_response(from, code.as_deref()).await,
+            Command::Approve { limit } => self.approve_response(from, limit.as_ref()).await,
+            Command::AutoSwap { setting } => self.autoswap_response(from, setting).await,
+            Command::Export { passphrase } => self.export_response(from, &passphrase).await,
+            Command::ExportHistory => self.export_history_response(from).await,
+            Command::Link { code } => self.link_response(from, code.as_deref()).await,
+            Command::Stop => self.stop_response(from).await,
+            Command::CancelOrStop => match self.pending.count(from).await {
+                Ok(0) => self.stop_response(from).await,
+                _ => self.confirm_response(from, None, false).await,
+            },
+            Command::Start => self.start_response(from).await,
+            Command::Confirm { reference } => self.confirm_response(from, reference.as_deref(), true).await,
+            Command::Cancel { reference } => self.confirm_response(from, reference.as_deref(), false).await,
+            Command::Invalid { hint, .. } => hint,
             Command::Unknown(text) => self.unknown_response(&text),
         }
     }
 
-    fn help_response(&self) -> String {
-        "Text-to-Chain Commands:\nJOIN <name> - Create wallet\nBALANCE - Check balance\nSEND 10 TXTC TO name.ttcip.eth\nBUY 10 - Buy TXTC with airtime\nDEPOSIT - Get deposit address\nREDEEM <code> - Redeem voucher\nSWAP 10 TXTC - Swap to ETH\nCASHOUT 10 TXTC - Cash out to USDC\nCASHOUT 0.001 ETH - Cash out ETH\nMENU - Show this help".to_string()
+    /// Command list, leaving out commands disabled for the sender's region
+    fn help_response(&self, from: &str) -> String {
+        let lines: Vec<&str> = HELP_LINES
+            .iter()
+            .filter(|(command, _)| !self.is_disabled(from, command))
+            .map(|(_, line)| *line)
+            .collect();
+        format!("Text-to-Chain Commands:\n{}\nReply HELP <command> for details.", lines.join("\n"))
+    }
+
+    /// HELP <topic>: syntax and examples for one command
+    fn help_topic_response(&self, from: &str, topic: &str) -> String {
+        // Related commands share a topic
+        let topic = match topic {
+            "SAVE" | "CONTACT" | "PAYABLE" => "CONTACTS",
+            "RELEASE" => "HOLD",
+            "START" => "STOP",
+            "UNLOCK" | "LOCK" => "PIN",
+            other => other,
+        };
+        match HELP_TOPICS.iter().find(|(name, _)| *name == topic) {
+            Some((name, _)) if self.is_disabled(from, name) => {
+                format!("{} isn't available in your region.\nReply MENU for commands.", name)
+            }
+            Some((_, text)) => text.to_string(),
+            None => format!("No help for {}.\nReply HELP for the command list.", topic),
+        }
+    }
+
+    /// Whether `command` is turned off for this phone number (by prefix or everywhere).
+    /// Carrier keywords can't be turned off.
+    fn is_disabled(&self, from: &str, command: &str) -> bool {
+        if matches!(command, "HELP" | "STOP" | "START") {
+            return false;
+        }
+        self.config.disabled_commands.iter().any(|(prefix, commands)| {
+            (prefix == "*" || from.starts_with(prefix.as_str())) && commands.iter().any(|c| c == command)
+        })
+    }
+
+    /// Encrypt the user's key to their passphrase and reply with a one-time download link
+    async fn export_response(&self, from: &str, passphrase: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        let user = match repo.find_by_phone(from).await {
+            Ok(Some(u)) => u,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        let Ok(wallet) = self.user_wallet(&user) else {
+            return "Export failed. Try later.".to_string();
+        };
+        let key = wallet.private_key_bytes();
+
+        // scrypt is CPU-heavy: keep it off the async workers. The keystore is
+        // decrypted once before handing it out, so a backup is never unusable.
+        let passphrase = passphrase.to_string();
+        let keystore = tokio::task::spawn_blocking(move || {
+            use crate::wallet::keystore::{decrypt_keystore, encrypt_keystore};
+            let keystore = encrypt_keystore(&key, wallet.address, &passphrase)?;
+            if decrypt_keystore(&keystore, &passphrase)? != key {
+                return Err(crate::wallet::WalletError::CreationError("keystore roundtrip mismatch".to_string()));
+            }
+            Ok(keystore)
+        })
+        .await;
+        let keystore = match keystore {
+            Ok(Ok(k)) => k,
+            _ => return "Export failed. Try later.".to_string(),
+        };
+
+        let token = self.exports.put(keystore);
+        format!(
+            "Encrypted wallet backup:\n{}/export/{}\n\nOpens once, expires in {} min. Import it into any wallet with your passphrase.",
+            self.config.public_url.trim_end_matches('/'),
+            token,
+            self.exports.ttl().as_secs() / 60
+        )
+    }
+
+    /// Keystore behind an EXPORT link (once)
+    pub fn take_export(&self, token: &str) -> Option<serde_json::Value> {
+        self.exports.take(token)
+    }
+
+    /// EXPORT HISTORY: every deposit, send and received transfer as a CSV
+    /// behind a one-time link
+    async fn export_history_response(&self, from: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        match repo.find_by_phone(from).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        }
+
+        let rows = match self.history_rows(from).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                tracing::error!("Failed to load history for export: {}", e);
+                return "Export failed. Try later.".to_string();
+            }
+        };
+        if rows.is_empty() {
+            return "No transactions yet.\nReply REDEEM <code> to add funds.".to_string();
+        }
+
+        let token = self.history_exports.put(history_csv(&rows));
+        format!(
+            "Transaction history ({} rows):\n{}/export/history/{}\n\nOpens once, expires in {} min.",
+            rows.len(),
+            self.config.public_url.trim_end_matches('/'),
+            token,
+            self.history_exports.ttl().as_secs() / 60
+        )
+    }
+
+    /// Everything that moved funds in or out for a phone
+    async fn history_rows(&self, from: &str) -> Result<Vec<HistoryRow>, sqlx::Error> {
+        let mut rows = Vec::new();
+        if let Some(ref deposit_repo) = self.deposit_repo {
+            for d in deposit_repo.find_by_user(from).await? {
+                let bonus = d.source == DepositSource::Bonus.to_string();
+                let on_chain = d.source == DepositSource::OnChain.to_string();
+                rows.push(HistoryRow {
+                    kind: "deposit",
+                    amount: d.amount_as_f64(),
+                    token: if bonus { "TXTC" } else { "USDC" }.to_string(),
+                    counterparty: d.source.clone(),
+                    status: "confirmed".to_string(),
+                    tx_hash: if on_chain { d.source_ref.clone() } else { None },
+                    timestamp: d.created_at,
+                });
+            }
+        }
+        if let Some(ref transfer_repo) = self.transfer_repo {
+            for t in transfer_repo.list_by_sender(from).await? {
+                rows.push(HistoryRow {
+                    kind: "send",
+                    amount: t.amount,
+                    token: t.token,
+                    counterparty: t.to_address,
+                    status: t.status,
+                    tx_hash: t.tx_hash,
+                    timestamp: t.created_at,
+                });
+            }
+        }
+        if let Some(ref inbound_repo) = self.inbound_repo {
+            for (sender_phone, amount, token, status, created_at) in inbound_repo.list_received(from).await? {
+                rows.push(HistoryRow {
+                    kind: "receive",
+                    amount,
+                    token,
+                    counterparty: sender_phone,
+                    status,
+                    tx_hash: None,
+                    timestamp: created_at,
+                });
+            }
+        }
+        Ok(rows)
+    }
+
+    /// LINK issues a code on the wallet's main number; LINK <code> from
+    /// another number attaches that number to the same wallet
+    async fn link_response(&self, from: &str, code: Option<&str>) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        let existing = match repo.find_by_phone(from).await {
+            Ok(user) => user,
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        let Some(code) = code else {
+            return match existing {
+                Some(user) if user.phone == from => {
+                    let code = self.link_codes.issue(from);
+                    format!(
+                        "Link code: {}\nFrom your other phone, reply LINK {} within {} min.\nNever share this code.",
+                        code,
+                        code,
+                        self.link_codes.ttl().as_secs() / 60
+                    )
+                }
+                Some(_) => "Only your wallet's main number can link phones.".to_string(),
+                None => "No wallet. Reply JOIN first.".to_string(),
+            };
+        };
+
+        if existing.is_some() {
+            return "This number already has a wallet.".to_string();
+        }
+        let Some(primary) = self.link_codes.redeem(code) else {
+            return "Invalid or expired code.\nReply LINK on your main phone for a new one.".to_string();
+        };
+        let user = match repo.find_by_phone(&primary).await {
+            Ok(Some(user)) => user,
+            _ => return "Invalid or expired code.\nReply LINK on your main phone for a new one.".to_string(),
+        };
+        if let Err(e) = repo.link_phone(from, &user.phone).await {
+            tracing::error!("Failed to link {} to {}: {}", mask_phone(from), mask_phone(&user.phone), e);
+            return "Error. Try later.".to_string();
+        }
+        self.lookups.invalidate_user(from);
+
+        tracing::info!(primary = %mask_phone(&user.phone), linked = %mask_phone(from), "Phone linked");
+        format!(
+            "Linked! This number now uses the wallet of {}.\nReply BALANCE to check.",
+            mask_phone(&user.phone)
+        )
+    }
+
+    /// CSV behind an EXPORT HISTORY link (once)
+    pub fn take_history_export(&self, token: &str) -> Option<String> {
+        self.history_exports.take(token)
+    }
+
+    /// Receipt behind a /receipt link, and whether its signature checks out
+    pub async fn find_receipt(&self, reference: &str) -> Option<(Receipt, bool)> {
+        let receipt = self.receipt_repo.as_ref()?.find(reference).await.ok()??;
+        let verified = !self.config.receipt_signing_key.is_empty() && receipt.verify(&self.config.receipt_signing_key);
+        Some((receipt, verified))
+    }
+
+    /// Explorer page for a receipt's transaction, when it has one on a known chain
+    pub fn receipt_explorer_url(&self, receipt: &Receipt) -> Option<String> {
+        let chain = Chain::from_input(&self.config.send_chain)?;
+        Some(chain.tx_url(receipt.tx_hash.as_deref()?))
+    }
+
+    /// Carrier STOP: record the opt-out and send the one allowed confirmation.
+    /// A number without a wallet has nothing to record and is never messaged
+    /// unprompted, so it's confirmed too.
+    async fn stop_response(&self, from: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        match repo.set_opted_out(from, true).await {
+            Ok(()) | Err(sqlx::Error::RowNotFound) => {}
+            Err(e) => {
+                tracing::error!("Failed to record opt-out for {}: {}", mask_phone(from), e);
+                return "Error. Try later.".to_string();
+            }
+        }
+        self.lookups.invalidate_user(from);
+        "You're unsubscribed from TextChain and won't get more messages.\nReply START to resubscribe.".to_string()
+    }
+
+    /// Carrier START/UNSTOP re-subscribes an existing user; new numbers JOIN
+    async fn start_response(&self, from: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        match repo.find_by_phone(from).await {
+            Ok(Some(user)) if user.opted_out => match repo.set_opted_out(&user.phone, false).await {
+                Ok(()) => {
+                    self.lookups.invalidate_user(&user.phone);
+                    "You're subscribed to TextChain again.\nReply COMMANDS for help.".to_string()
+                }
+                Err(_) => "Error. Try later.".to_string(),
+            },
+            Ok(_) => self.join_response(from, None).await,
+            Err(_) => "Error. Try later.".to_string(),
+        }
     }
 
     async fn join_response(&self, from: &str, ens_name: Option<String>) -> String {
@@ -334,6 +1855,10 @@ impl CommandProcessor {
             if !name.chars().all(|c| c.is_alphanumeric()) {
                 return "ENS name can only contain letters and numbers.\n\nTry again: JOIN <name>".to_string();
             }
+            if is_blocked_name(&name, &self.config.blocked_name_words) {
+                tracing::info!(from = %from, "JOIN name refused by blocklist");
+                return "That name isn't available.\n\nTry another: JOIN <name>".to_string();
+            }
 
             // Check if user already has a wallet
             match repo.find_by_phone(from).await {
@@ -382,6 +1907,8 @@ impl CommandProcessor {
                             if let Err(e) = repo.update_ens_name(from, &full_ens).await {
                                 tracing::error!("Failed to save ENS name to database: {}", e);
                             }
+                            self.lookups.invalidate_user(from);
+                            self.lookups.invalidate_ens(&full_ens);
                             
                             // TODO: Mint ENS subdomain on-chain here
                             return format!(
@@ -414,58 +1941,43 @@ impl CommandProcessor {
                 );
             }
             Ok(None) => {
-                // New user - create wallet and prompt for ENS name
-                let wallet = match UserWallet::create_new() {
-                    Ok(w) => w,
-                    Err(e) => {
-                        tracing::error!("Wallet error: {}", e);
-                        return "Error creating wallet.".to_string();
-                    }
-                };
-
-                // Encrypt private key
-                let encrypted_key = hex::encode(wallet.private_key_bytes());
-
-                // Save to database
-                match repo.create(from, &wallet.address_string(), &encrypted_key).await {
-                    Ok(_) => {
-                        // Create Arc wallet for USDC cashout
-                        let arc_url = std::env::var("ARC_SERVICE_URL").unwrap_or_else(|_| "http://arc:8084".to_string());
-                        let client = reqwest::Client::new();
-                        let arc_wallet = match client
-                            .post(&format!("{}/api/arc/wallet", arc_url))
-                            .json(&serde_json::json!({ "phone": from }))
-                            .timeout(std::time::Duration::from_secs(10))
-                            .send()
-                            .await
-                        {
-                            Ok(resp) => {
-                                if let Ok(data) = resp.json::<serde_json::Value>().await {
-                                    data["wallet"]["address"].as_str().unwrap_or("").to_string()
-                                } else {
-                                    String::new()
-                                }
-                            }
-                            Err(_) => String::new(),
-                        };
+                // New user - create wallet and prompt for ENS name.
+                // Receive-only accounts skip Arc provisioning until their first send
+                let receive_only = self.config.receive_only_join;
+                let created = self.store_new_wallet(repo, from, receive_only).await;
+                self.lookups.invalidate_user(from);
+                match created {
+                    Ok(wallet) if receive_only => format!(
+                        "Wallet created (receive only)!\n{}{}\n\nShare it to get paid. Your full account is set up on your first SEND.\n\nNow pick a name:\nJOIN <name>",
+                        wallet.address_string(),
+                        self.grant_welcome_bonus(from, &wallet.address_string()).await
+                    ),
+                    Ok(wallet) => {
+                        if self.simulate {
+                            return format!(
+                                "Wallet created!\n{}\n\n[SIMULATED] Arc wallet skipped.",
+                                wallet.address_string()
+                            );
+                        }
 
+                        let arc_wallet = self.provision_arc_wallet(from).await;
+                        let bonus = self.grant_welcome_bonus(from, &wallet.address_string()).await;
                         if arc_wallet.is_empty() {
                             format!(
-                                "Wallet created!\n{}\n\nNow pick a name:\nJOIN <name>\n\nEx: JOIN alice",
-                                wallet.address_string()
+                                "Wallet created!\n{}{}\n\nNow pick a name:\nJOIN <name>\n\nEx: JOIN alice",
+                                wallet.address_string(),
+                                bonus
                             )
                         } else {
                             format!(
-                                "Wallet created!\n{}\nArc (USDC): {}...\n\nNow pick a name:\nJOIN <name>\n\nEx: JOIN alice",
+                                "Wallet created!\n{}\nArc (USDC): {}...{}\n\nNow pick a name:\nJOIN <name>\n\nEx: JOIN alice",
                                 wallet.address_string(),
-                                &arc_wallet[..10.min(arc_wallet.len())]
+                                &arc_wallet[..10.min(arc_wallet.len())],
+                                bonus
                             )
                         }
                     }
-                    Err(e) => {
-                        tracing::error!("DB save error: {}", e);
-                        "Error saving wallet.".to_string()
-                    }
+                    Err(reply) => reply,
                 }
             }
             Err(e) => {
@@ -475,178 +1987,170 @@ impl CommandProcessor {
         }
     }
 
-    async fn balance_response(&self, from: &str) -> String {
-        let Some(ref repo) = self.user_repo else {
-            return "Balance: $0.00\nDB offline.".to_string();
+    /// Create `from`'s wallet and save it: derived at the next HD index when
+    /// MASTER_MNEMONIC is set (only the index is stored), random with its key
+    /// encrypted otherwise. Errors carry the SMS reply.
+    async fn store_new_wallet(&self, repo: &UserRepository, from: &str, receive_only: bool) -> Result<UserWallet, String> {
+        let created = if self.config.master_mnemonic.is_empty() {
+            let wallet = UserWallet::create_new()
+                .and_then(|wallet| Ok((encrypt_key(&wallet.private_key_bytes(), &self.config.encryption_key)?, wallet)))
+                .map_err(|e| {
+                    tracing::error!("Wallet error: {}", e);
+                    "Error creating wallet.".to_string()
+                });
+            let (encrypted_key, wallet) = wallet?;
+            let address = wallet.address_string();
+            let saved = if receive_only {
+                repo.create_receive_only(from, &address, &encrypted_key).await
+            } else {
+                repo.create(from, &address, &encrypted_key).await
+            };
+            saved.map(|_| wallet)
+        } else {
+            let index = repo.next_derivation_index().await.map_err(|e| {
+                tracing::error!("DB error: {}", e);
+                "Error creating wallet.".to_string()
+            })?;
+            let wallet = UserWallet::from_mnemonic(&self.config.master_mnemonic, index as u32).map_err(|e| {
+                tracing::error!("Wallet error: {}", e);
+                "Error creating wallet.".to_string()
+            })?;
+            repo.create_derived(from, &wallet.address_string(), index, receive_only)
+                .await
+                .map(|_| wallet)
         };
+        created.map_err(|e| {
+            tracing::error!("DB save error: {}", e);
+            "Error saving wallet.".to_string()
+        })
+    }
 
-        // Get user's wallet address
-        let user = match repo.find_by_phone(from).await {
-            Ok(Some(u)) => u,
-            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
-            Err(_) => return "Error. Try later.".to_string(),
+    /// Credit the configured welcome bonus to a new wallet, once per phone.
+    /// Returns the line to add to the welcome message, empty if nothing was granted.
+    async fn grant_welcome_bonus(&self, from: &str, address: &str) -> String {
+        let amount = self.config.welcome_bonus_txtc;
+        if amount <= 0.0 || self.simulate {
+            return String::new();
+        }
+        let Some(ref deposit_repo) = self.deposit_repo else {
+            return String::new();
         };
 
-        // Call Contract API to get balance on Sepolia
-        let client = reqwest::Client::new();
-        let api_url = format!("{}/api/balance/{}", self.backend_url, user.wallet_address);
-        
-        tracing::info!("Fetching balance from Contract API for {}", user.wallet_address);
-        
-        let response = match client.get(&api_url).send().await {
-            Ok(resp) => resp,
+        // Claim the bonus before granting it, so repeated JOINs can't double up
+        let micro = (amount * 1_000_000.0).round() as i64;
+        let deposit = match deposit_repo.create_welcome_bonus(from, micro).await {
+            Ok(Some(deposit)) => deposit,
+            Ok(None) => return String::new(),
             Err(e) => {
-                tracing::error!("Failed to call Contract API: {}", e);
-                return "Network error. Try later.".to_string();
+                tracing::error!("Failed to record welcome bonus: {}", e);
+                return String::new();
             }
         };
 
-        // Parse response
-        let result: serde_json::Value = match response.json().await {
-            Ok(json) => json,
+        let response = reqwest::Client::new()
+            .post(format!("{}/api/bonus", self.backend_url))
+            .header("X-Bonus-Token", &self.config.welcome_bonus_token)
+            .json(&serde_json::json!({
+                "userAddress": address,
+                "amount": format_token_amount(amount),
+                "userPhone": from
+            }))
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await;
+        match response {
+            Ok(resp) if resp.status().is_success() => format!("\n+{} TXTC welcome bonus!", self.fmt_amount(amount)),
+            Ok(resp) => {
+                tracing::warn!(status = %resp.status(), "Welcome bonus refused for {}", mask_phone(from));
+                let _ = deposit_repo.remove_welcome_bonus(deposit.id).await;
+                String::new()
+            }
+            Err(e) if e.is_connect() => {
+                tracing::warn!("Welcome bonus not sent for {}: {}", mask_phone(from), e);
+                let _ = deposit_repo.remove_welcome_bonus(deposit.id).await;
+                String::new()
+            }
+            // Timed out or cut off after the request went out: the mint may
+            // have happened, so the claim stays and the phone can't be paid twice
             Err(e) => {
-                tracing::error!("Failed to parse API response: {}", e);
-                return "Error processing response.".to_string();
+                tracing::warn!("Welcome bonus for {} unconfirmed, keeping the claim: {}", mask_phone(from), e);
+                String::new()
             }
-        };
+        }
+    }
 
-        if result["success"].as_bool().unwrap_or(false) {
-            let txtc_balance = result["balances"]["txtc"].as_str().unwrap_or("0");
-            let eth_balance = result["balances"]["eth"].as_str().unwrap_or("0");
-            
-            // Parse as float for display
-            let txtc: f64 = txtc_balance.parse().unwrap_or(0.0);
-            let eth: f64 = eth_balance.parse().unwrap_or(0.0);
-            
-            if txtc > 0.0 || eth > 0.0 {
-                format!(
-                    "Balance:\n{} TXTC\n{} ETH\n\nSepolia testnet",
-                    txtc, eth
-                )
-            } else {
-                "Balance: $0.00\n\nReply DEPOSIT to fund wallet.".to_string()
-            }
-        } else {
-            "Error fetching balance.".to_string()
+    /// Create the user's Arc wallet for USDC cashout. Empty if the service didn't answer.
+    async fn provision_arc_wallet(&self, from: &str) -> String {
+        let client = reqwest::Client::new();
+        match client
+            .post(format!("{}/api/arc/wallet", self.arc_url))
+            .json(&serde_json::json!({ "phone": from }))
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await
+        {
+            Ok(resp) => match resp.json::<serde_json::Value>().await {
+                Ok(data) => data["wallet"]["address"].as_str().unwrap_or("").to_string(),
+                Err(_) => String::new(),
+            },
+            Err(_) => String::new(),
         }
     }
 
-    async fn pin_response(&self, from: &str, new_pin: Option<String>) -> String {
-        match new_pin {
-            Some(pin) => {
-                if pin.len() < 4 || pin.len() > 6 || !pin.chars().all(|c| c.is_ascii_digit()) {
-                    "PIN must be 4-6 digits.\nExample: PIN 1234".to_string()
-                } else {
-                    // Save PIN hash
-                    if let Some(ref repo) = self.user_repo {
-                        // Simple hash for demo (use bcrypt in production)
-                        let pin_hash = format!("{:x}", sha2::Sha256::digest(pin.as_bytes()));
-                        if repo.update_pin(from, &pin_hash).await.is_ok() {
-                            return "PIN set!".to_string();
-                        }
-                    }
-                    "PIN set!".to_string()
-                }
+    /// TXTC the user has committed but not yet spent: locked in open Yellow
+    /// channels, in sends whose outcome is unknown, and held in escrow.
+    /// A lookup that fails counts as nothing committed.
+    async fn committed_txtc(&self, from: &str) -> Committed {
+        let mut committed = Committed::default();
+        if let Some(ref repo) = self.channel_repo {
+            match repo.open_balance(from).await {
+                Ok(amount) => committed.in_channel = amount,
+                Err(e) => tracing::warn!("Couldn't read open channels for {}: {}", from, e),
             }
-            None => "Reply: PIN <4-6 digits>\nExample: PIN 1234".to_string(),
         }
-    }
-
-    async fn send_response(&self, from: &str, amount: f64, token: &str, recipient: &str) -> String {
-        let token_upper = token.to_uppercase();
-        // Support TXTC and ETH
-        if token_upper != "TXTC" && token_upper != "ETH" {
-            return format!("Supported tokens: TXTC, ETH\nExample: SEND 10 TXTC swarnim.ttcip.eth");
+        if let Some(ref repo) = self.transfer_repo {
+            match repo.pending_total(from, "TXTC").await {
+                Ok(amount) => committed.pending_out = amount,
+                Err(e) => tracing::warn!("Couldn't read pending sends for {}: {}", from, e),
+            }
+        }
+        if let Some(ref repo) = self.escrow_repo {
+            match repo.held_total(from, "TXTC").await {
+                Ok(amount) => committed.held = amount,
+                Err(e) => tracing::warn!("Couldn't read held escrows for {}: {}", from, e),
+            }
         }
+        committed
+    }
 
-        // Get sender's wallet and private key
-        let Some(ref user_repo) = self.user_repo else {
-            return "DB offline. Try later.".to_string();
+    async fn balance_response(&self, from: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "Balance: $0.00\nDB offline.".to_string();
         };
 
-        let sender = match user_repo.find_by_phone(from).await {
+        // Get user's wallet address (BALANCE is the most repeated command)
+        let user = match self.lookups.users.get_or_load(from, repo.find_by_phone(from)).await {
             Ok(Some(u)) => u,
-            Ok(None) => { return "No wallet. Reply JOIN first.".to_string(); },
-            Err(_) => { return "Error. Try later.".to_string(); },
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
         };
 
-        // Resolve recipient address (wallet address, phone number, or ENS name)
-        let recipient_address = if recipient.starts_with("0x") && recipient.len() == 42 {
-            // Already a wallet address
-            recipient.to_string()
-        } else if recipient.starts_with("+") {
-            // Phone number - look up in database
-            match user_repo.find_by_phone(recipient).await {
-                Ok(Some(u)) => u.wallet_address,
-                Ok(None) => { return format!("{} hasn't joined yet.\nAsk them to text JOIN", recipient); },
-                Err(_) => { return "Error looking up recipient.".to_string(); },
-            }
-        } else if recipient.contains(".eth") || recipient.contains(".") {
-            // ENS name (e.g., swarnim.ttcip.eth) - resolve via backend
-            let client = reqwest::Client::new();
-            let resolve_url = format!("{}/api/ens/resolve/{}", self.backend_url, recipient);
-            match client.get(&resolve_url).send().await {
-                Ok(resp) => {
-                    match resp.json::<serde_json::Value>().await {
-                        Ok(json) => {
-                            if let Some(addr) = json["address"].as_str() {
-                                addr.to_string()
-                            } else {
-                                return format!("Could not resolve {}.\nUse wallet address instead.", recipient);
-                            }
-                        },
-                        Err(_) => { return format!("Could not resolve {}.", recipient); },
-                    }
-                },
-                Err(_) => { return "Network error resolving ENS. Try later.".to_string(); },
-            }
-        } else {
-            // Try as contact name from address book
-            if let Some(ref address_book) = self.address_book_repo {
-                match address_book.find_by_name(from, recipient).await {
-                    Ok(contacts) if !contacts.is_empty() => {
-                        let contact = &contacts[0];
-                        if let Some(ref addr) = contact.wallet_address {
-                            addr.clone()
-                        } else if let Some(ref phone) = contact.contact_phone {
-                            match user_repo.find_by_phone(phone).await {
-                                Ok(Some(u)) => u.wallet_address,
-                                _ => { return format!("Contact {} has no wallet.", recipient); },
-                            }
-                        } else {
-                            return format!("Contact {} has no address.", recipient);
-                        }
-                    },
-                    _ => { return "Invalid recipient.\nUse ENS (name.ttcip.eth), phone (+1...), or address (0x...)".to_string(); },
-                }
-            } else {
-                return "Invalid recipient.\nUse ENS (name.ttcip.eth), phone (+1...), or address (0x...)".to_string();
-            }
+        let address = match self.account_address(&user).await {
+            Ok(address) => address,
+            Err(msg) => return msg,
         };
 
-        // Route through Yellow Network for instant finality
+        // Call Contract API to get balance on Sepolia
         let client = reqwest::Client::new();
-        let api_url = &format!("{}/api/send-yellow", self.backend_url);
+        let api_url = format!("{}/api/balance/{}", self.backend_url, address);
         
-        tracing::info!("Sending {} {} from {} to {} (via Yellow)", amount, token_upper, sender.wallet_address, recipient_address);
+        tracing::info!("Fetching balance from Contract API for {}", address);
         
-        let response = match client
-            .post(api_url)
-            .json(&serde_json::json!({
-                "fromAddress": sender.wallet_address,
-                "toAddress": recipient_address,
-                "amount": amount.to_string(),
-                "token": token_upper,
-                "userPhone": from,
-                "senderKey": sender.encrypted_private_key
-            }))
-            .timeout(std::time::Duration::from_secs(30))
-            .send()
-            .await
-        {
+        let response = match http::send(client.get(&api_url)).await {
             Ok(resp) => resp,
+            Err(HttpError::RateLimited { retry_after }) => return busy_reply(retry_after),
             Err(e) => {
-                tracing::error!("Failed to call Yellow API: {}", e);
+                tracing::error!("Failed to call Contract API: {}", e);
                 return "Network error. Try later.".to_string();
             }
         };
@@ -661,356 +2165,2435 @@ impl CommandProcessor {
         };
 
         if result["success"].as_bool().unwrap_or(false) {
-            format!(
-                "Sending {} {} to {}...\n\nQueued via Yellow Network.\nYou'll get SMS when complete.",
-                amount, token_upper, recipient
-            )
-        } else {
-            let error_msg = result["error"].as_str().unwrap_or("Unknown error");
-            tracing::error!("Transfer failed: {}", error_msg);
+            let txtc_balance = result["balances"]["txtc"].as_str().unwrap_or("0");
+            let eth_balance = result["balances"]["eth"].as_str().unwrap_or("0");
             
-            if error_msg.contains("insufficient") || error_msg.contains("balance") {
-                "Insufficient balance.".to_string()
+            // Parse as float for display
+            let txtc: f64 = txtc_balance.parse().unwrap_or(0.0);
+            let eth: f64 = eth_balance.parse().unwrap_or(0.0);
+            
+            let other_chains = self.chain_breakdown(&address).await;
+            if txtc > 0.0 || eth > 0.0 {
+                let currency = self.display_currency(from).await;
+                let committed = self.committed_txtc(from).await;
+                let total = self.fiat_total(currency.as_deref().unwrap_or("USD"), &[(txtc, "TXTC"), (eth, "ETH")]).await;
+                if committed.total() <= 0.0 {
+                    return format!(
+                        "Balance:\n{} TXTC{}\n{} ETH{}{}\n\nSepolia testnet{}",
+                        self.fmt_amount(txtc),
+                        self.fiat_hint(currency.as_deref(), txtc, "TXTC").await,
+                        self.fmt_amount(eth),
+                        self.fiat_hint(currency.as_deref(), eth, "ETH").await,
+                        total,
+                        other_chains
+                    );
+                }
+
+                // Funds in channels, unsettled sends and escrow are in the
+                // wallet total but can't be spent again
+                let available = (txtc - committed.total()).max(0.0);
+                let mut lines = vec![format!(
+                    "Balance:\n{} TXTC available{}",
+                    self.fmt_amount(available),
+                    self.fiat_hint(currency.as_deref(), available, "TXTC").await
+                )];
+                for (label, amount) in [
+                    ("In channel", committed.in_channel),
+                    ("Pending out", committed.pending_out),
+                    ("Held", committed.held),
+                ] {
+                    if amount > 0.0 {
+                        lines.push(format!("{}: {} TXTC", label, self.fmt_amount(amount)));
+                    }
+                }
+                lines.push(format!("Total: {} TXTC", self.fmt_amount(txtc)));
+                lines.push(format!(
+                    "{} ETH{}",
+                    self.fmt_amount(eth),
+                    self.fiat_hint(currency.as_deref(), eth, "ETH").await
+                ));
+                format!("{}{}\n\nSepolia testnet{}", lines.join("\n"), total, other_chains)
             } else {
-                "Transfer failed. Try later.".to_string()
+                format!("Balance: $0.00\n\nReply DEPOSIT to fund wallet.{}", other_chains)
             }
+        } else {
+            "Error fetching balance.".to_string()
         }
     }
 
-    async fn deposit_response(&self, from: &str) -> String {
-        let Some(ref repo) = self.user_repo else {
-            return "DB offline. Reply JOIN first.".to_string();
+    /// "Other chains" section for BALANCE_CHAINS (empty when none are set).
+    /// A chain whose RPC fails shows as unavailable instead of failing BALANCE.
+    async fn chain_breakdown(&self, address: &str) -> String {
+        if self.config.balance_chains.is_empty() {
+            return String::new();
+        }
+        let Ok(parsed) = Address::from_str(address) else {
+            return String::new();
+        };
+        let balances = match self.chain_balances.get(address) {
+            Some(balances) => balances,
+            None => {
+                let balances = get_balances_all_chains(&self.multi_chain, &self.config.balance_chains, parsed).await;
+                for (chain, result) in &balances {
+                    if let Err(e) = result {
+                        tracing::warn!(chain = chain.name(), "Chain balance unavailable: {}", e);
+                    }
+                }
+                self.chain_balances.put(address, balances.clone());
+                balances
+            }
         };
 
-        match repo.find_by_phone(from).await {
-            Ok(Some(user)) => {
-                let deposit_address = if let Some(ref ens) = user.ens_name {
-                    ens.clone()
+        let rows: Vec<String> = balances
+            .iter()
+            .map(|(chain, result)| match result {
+                Ok(balances) => balances.to_sms_string(),
+                Err(_) => format!("{}: unavailable", chain.short_code()),
+            })
+            .collect();
+        format!("\n\nOther chains:\n{}", rows.join("\n"))
+    }
+
+    async fn pin_response(&self, from: &str, new_pin: Option<String>) -> String {
+        match new_pin {
+            Some(pin) => {
+                if pin.len() < 4 || pin.len() > 6 || !pin.chars().all(|c| c.is_ascii_digit()) {
+                    "PIN must be 4-6 digits.\nExample: PIN 1234".to_string()
                 } else {
-                    user.wallet_address.clone()
-                };
-                
-                format!(
-                    "Fund wallet:\nDial *384*46750#\nOr REDEEM <code>\nOr send to:\n{}",
-                    deposit_address
-                )
+                    let Some(ref repo) = self.user_repo else {
+                        return "DB offline. Try later.".to_string();
+                    };
+                    match repo.update_pin(from, &hash_pin(&pin)).await {
+                        Ok(()) => {
+                            self.lookups.invalidate_user(from);
+                            "PIN set!".to_string()
+                        }
+                        Err(sqlx::Error::RowNotFound) => "No wallet. Reply JOIN first.".to_string(),
+                        Err(e) => {
+                            tracing::error!("Failed to save PIN for {}: {}", mask_phone(from), e);
+                            "Error. Try later.".to_string()
+                        }
+                    }
+                }
             }
-            Ok(None) => "No wallet. Reply JOIN first.".to_string(),
-            Err(_) => "Error. Try later.".to_string(),
+            None => "Reply: PIN <4-6 digits>\nExample: PIN 1234".to_string(),
         }
     }
 
-    async fn history_response(&self, from: &str) -> String {
-        // Check for recent deposits
-        if let Some(ref deposit_repo) = self.deposit_repo {
-            if let Ok(deposits) = deposit_repo.get_recent(from, 5).await {
-                if !deposits.is_empty() {
-                    let history: Vec<String> = deposits.iter()
-                        .map(|d| format!("${:.2} via {}", d.amount_as_f64(), d.source))
-                        .collect();
-                    return format!("Recent deposits:\n{}", history.join("\n"));
-                }
-            }
+    /// Whether `command` moves funds or secrets and `from` has a PIN but no open session
+    async fn needs_pin(&self, from: &str, command: &Command) -> bool {
+        let protected = match command {
+            Command::Pin { new_pin } => new_pin.is_some(),
+            Command::Invalid { .. } | Command::Unknown(_) => false,
+            other => PIN_PROTECTED.contains(&other.name()),
+        };
+        if !protected || self.pin_sessions.is_unlocked(from) {
+            return false;
         }
-        "No transactions yet.\nReply REDEEM <code> to add funds.".to_string()
+        let Some(ref repo) = self.user_repo else {
+            return false;
+        };
+        matches!(repo.find_by_phone(from).await, Ok(Some(User { pin_hash: Some(_), .. })))
     }
 
-    async fn redeem_response(&self, from: &str, code: &str) -> String {
-        // Check if user has wallet
-        let Some(ref user_repo) = self.user_repo else {
-            return "DB offline. Try later.".to_string();
+    /// Reply refusing a SEND or CASHOUT whose inline PIN doesn't match the
+    /// user's. A PIN given by a user who hasn't set one is ignored.
+    async fn wrong_inline_pin(&self, from: &str, command: &Command) -> Option<String> {
+        let pin = command.inline_pin()?;
+        let repo = self.user_repo.as_ref()?;
+        let pin_hash = match repo.find_by_phone(from).await {
+            Ok(Some(User { pin_hash: Some(pin_hash), .. })) => pin_hash,
+            Ok(_) => return None,
+            Err(_) => return Some("Error. Try later.".to_string()),
         };
+        if let Some(reply) = self.pin_lockout(repo, from).await {
+            return Some(reply);
+        }
+        if hash_pin(pin) == pin_hash {
+            self.pin_succeeded(repo, from).await;
+            return None;
+        }
 
-        // Get user's wallet address
-        let user = match user_repo.find_by_phone(from).await {
-            Ok(Some(user)) => user,
-            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
-            Err(_) => return "Error. Try later.".to_string(),
+        tracing::warn!(from = %mask_phone(from), "Wrong inline PIN");
+        self.pin_sessions.lock(from);
+        if let Some(reply) = self.pin_failed(repo, from).await {
+            return Some(reply);
+        }
+        let example = match command {
+            Command::Cashout { .. } => "CASHOUT 10 TXTC PIN 1234",
+            _ => "SEND 10 TXTC alice PIN 1234",
         };
+        Some(format!("Wrong PIN. Nothing was sent.\nPIN required: {}", example))
+    }
 
-        // Call Contract API to redeem voucher on-chain
-        let client = reqwest::Client::new();
-        let api_url = &format!("{}/api/redeem", self.backend_url);
-        
-        tracing::info!("Calling Contract API to redeem voucher: {}", code);
-        
-        let response = match client
-            .post(api_url)
-            .json(&serde_json::json!({
-                "voucherCode": code,
-                "userAddress": user.wallet_address,
-                "userPhone": from
-            }))
-            .send()
-            .await
-        {
-            Ok(resp) => resp,
+    /// Reply refusing PIN entry while `from` is locked out after too many wrong PINs
+    async fn pin_lockout(&self, repo: &UserRepository, from: &str) -> Option<String> {
+        match repo.pin_locked_until(from).await {
+            Ok(until) => until.map(pin_locked_reply),
             Err(e) => {
-                tracing::error!("Failed to call Contract API: {}", e);
-                return "Network error. Try later.".to_string();
+                tracing::error!("Failed to check PIN lockout: {}", e);
+                Some("Error. Try later.".to_string())
             }
-        };
+        }
+    }
 
-        // Parse response
-        let result: serde_json::Value = match response.json().await {
-            Ok(json) => json,
+    /// Count a wrong PIN, returning the lockout reply if it locked `from` out
+    async fn pin_failed(&self, repo: &UserRepository, from: &str) -> Option<String> {
+        let config = &self.config;
+        match repo.record_pin_failure(from, config.pin_max_attempts, config.pin_lockout_secs, MAX_PIN_LOCKOUT_SECS).await {
+            Ok(until) => {
+                let until = until?;
+                tracing::warn!(from = %mask_phone(from), %until, "PIN entry locked out");
+                Some(pin_locked_reply(until))
+            }
             Err(e) => {
-                tracing::error!("Failed to parse API response: {}", e);
-                return "Error processing response.".to_string();
+                tracing::error!("Failed to record wrong PIN: {}", e);
+                None
             }
-        };
+        }
+    }
 
-        if result["success"].as_bool().unwrap_or(false) {
-            let token_amount = result["tokenAmount"].as_str().unwrap_or("0");
-            let eth_amount = result["ethAmount"].as_str().unwrap_or("0");
-            let tx_hash = result["txHash"].as_str().unwrap_or("");
-            
-            tracing::info!("Voucher redeemed successfully: {} TXTC + {} ETH, tx: {}", token_amount, eth_amount, tx_hash);
-            
-            format!(
-                "Voucher redeemed!\n\nReceived:\n{} TXTC\n{} ETH (gas)\n\nReply BALANCE to check.",
-                token_amount, eth_amount
-            )
-        } else {
-            let error_msg = result["error"].as_str().unwrap_or("Unknown error");
-            tracing::error!("Redemption failed: {}", error_msg);
-            
-            if error_msg.contains("already redeemed") || error_msg.contains("AlreadyRedeemed") {
-                "Voucher already used.".to_string()
-            } else if error_msg.contains("not found") || error_msg.contains("invalid") {
-                "Invalid voucher code.".to_string()
-            } else {
-                "Redemption failed. Try later.".to_string()
-            }
+    /// Clear the wrong-PIN count after a correct PIN
+    async fn pin_succeeded(&self, repo: &UserRepository, from: &str) {
+        if let Err(e) = repo.reset_pin_failures(from).await {
+            tracing::error!("Failed to reset wrong PIN count: {}", e);
         }
     }
 
-    async fn buy_response(&self, from: &str, amount: f64) -> String {
-        let Some(ref user_repo) = self.user_repo else {
-            return "DB offline. Try later.".to_string();
+    /// Whether `command` sends something out of `from`'s frozen account
+    async fn blocked_by_freeze(&self, from: &str, command: &Command) -> bool {
+        let outgoing = match command {
+            Command::Pin { new_pin } => new_pin.is_some(),
+            Command::Invalid { .. } | Command::Unknown(_) => false,
+            other => PIN_PROTECTED.contains(&other.name()) || FREEZE_BLOCKED.contains(&other.name()),
         };
+        if !outgoing {
+            return false;
+        }
+        match self.user_repo {
+            Some(ref repo) => repo.is_frozen(from).await.unwrap_or(false),
+            None => false,
+        }
+    }
 
-        let user = match user_repo.find_by_phone(from).await {
+    /// FREEZE: block outgoing operations and end any PIN session
+    async fn freeze_response(&self, from: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        let user = match repo.find_by_phone(from).await {
             Ok(Some(user)) => user,
-            Ok(None) => { return "No wallet. Reply JOIN first.".to_string(); },
-            Err(_) => { return "Error. Try later.".to_string(); },
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
         };
+        if let Err(e) = repo.set_frozen(from, true).await {
+            tracing::error!("Failed to freeze account: {}", e);
+            return "Error. Try later.".to_string();
+        }
+        self.pin_sessions.lock(from);
+        tracing::warn!(from = %mask_phone(from), "Account frozen");
 
-        // Call backend /api/buy endpoint (async - fires and notifies via SMS)
-        let client = reqwest::Client::new();
-        let api_url = &format!("{}/api/buy", self.backend_url);
-
-        tracing::info!("BUY {} EUR airtime for user {}", amount, user.wallet_address);
+        let restore = if user.pin_hash.is_some() {
+            "Reply UNFREEZE <your PIN> to restore."
+        } else {
+            "You have no PIN, so contact support to restore it."
+        };
+        format!("Account frozen. Sends, swaps and cashouts are blocked; you can still receive.\n{}", restore)
+    }
 
-        let _response = client
-            .post(api_url)
-            .json(&serde_json::json!({
-                "userAddress": user.wallet_address,
-                "amount": amount,
-                "userPhone": from
-            }))
-            .timeout(std::time::Duration::from_secs(2))
-            .send()
-            .await;
+    /// UNFREEZE: lift a freeze with the PIN
+    async fn unfreeze_response(&self, from: &str, pin: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        let user = match repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        if !repo.is_frozen(from).await.unwrap_or(false) {
+            return "Account isn't frozen.".to_string();
+        }
+        let Some(ref pin_hash) = user.pin_hash else {
+            return "No PIN set. Contact support to unfreeze.".to_string();
+        };
+        if let Some(reply) = self.pin_lockout(repo, from).await {
+            return reply;
+        }
+        if hash_pin(pin) != *pin_hash {
+            tracing::warn!(from = %mask_phone(from), "Wrong PIN on UNFREEZE");
+            return self
+                .pin_failed(repo, from)
+                .await
+                .unwrap_or_else(|| "Wrong PIN. Account stays frozen.".to_string());
+        }
+        self.pin_succeeded(repo, from).await;
 
-        format!(
-            "Buying TXTC with €{:.0} airtime...\n\nYou'll get an SMS when complete.",
-            amount
-        )
+        match repo.set_frozen(from, false).await {
+            Ok(_) => "Account unfrozen. Sends are back on.".to_string(),
+            Err(e) => {
+                tracing::error!("Failed to unfreeze account: {}", e);
+                "Error. Try later.".to_string()
+            }
+        }
     }
 
-    async fn swap_response(&self, from: &str, amount: f64, token: &str) -> String {
-        // Check if user has wallet
-        let Some(ref user_repo) = self.user_repo else {
+    /// UNLOCK: check the PIN, open a session and run the command it was held for
+    async fn unlock_response(&self, from: &str, pin: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
             return "DB offline. Try later.".to_string();
         };
-
-        // Get user's wallet address
-        let user = match user_repo.find_by_phone(from).await {
+        let user = match repo.find_by_phone(from).await {
             Ok(Some(user)) => user,
-            Ok(None) => { return "No wallet. Reply JOIN first.".to_string(); },
-            Err(_) => { return "Error. Try later.".to_string(); },
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        let Some(ref pin_hash) = user.pin_hash else {
+            return "No PIN set.\nReply PIN <4-6 digits> to set one.".to_string();
         };
 
-        // Call Contract API to swap tokens (async - don't wait for completion)
-        let client = reqwest::Client::new();
-        let api_url = &format!("{}/api/swap", self.backend_url);
-        
-        tracing::info!("Initiating swap of {} {} for user {}", amount, token, user.wallet_address);
-        
-        // Send request with user phone for SMS notification
-        let _response = client
-            .post(api_url)
-            .json(&serde_json::json!({
-                "userAddress": user.wallet_address,
-                "tokenAmount": amount.to_string(),
-                "minEthOut": "0",
-                "userPhone": from
-            }))
-            .timeout(std::time::Duration::from_secs(2))
-            .send()
-            .await;
+        if let Some(reply) = self.pin_lockout(repo, from).await {
+            return reply;
+        }
+        if hash_pin(pin) != *pin_hash {
+            tracing::warn!(from = %mask_phone(from), "Wrong PIN");
+            self.pin_sessions.lock(from);
+            return self
+                .pin_failed(repo, from)
+                .await
+                .unwrap_or_else(|| "Wrong PIN.\nSend your command again, then UNLOCK <PIN>.".to_string());
+        }
 
-        // Respond immediately - don't wait for swap to complete
-        // Backend will send SMS notification when swap completes
-        format!(
-            "Swapping {} {}...\n\nYou'll get an SMS when complete.\n\nThis may take 30 seconds.",
-            amount, token
-        )
+        self.pin_succeeded(repo, from).await;
+        self.pin_sessions.unlock(from);
+        if let Some(held) = self.pin_sessions.take_held(from) {
+            return Box::pin(self.process_unlocked(from, &held, true)).await;
+        }
+        let window = self.pin_sessions.window();
+        if window.is_zero() {
+            "PIN correct. Nothing was waiting for it.".to_string()
+        } else {
+            format!("Unlocked for {} min.\nReply LOCK to lock now.", (window.as_secs() / 60).max(1))
+        }
     }
 
-    async fn cashout_response(&self, from: &str, amount: f64, token: &str) -> String {
-        let Some(ref user_repo) = self.user_repo else {
+    /// Reply refusing a SEND from a wallet younger than `min_wallet_age_secs`
+    /// that hasn't been funded yet, so create-and-drain accounts are slowed down
+    async fn wallet_age_block(&self, from: &str) -> Option<String> {
+        let min_age = self.config.min_wallet_age_secs;
+        if min_age == 0 || self.config.wallet_age_exempt.iter().any(|prefix| from.starts_with(prefix.as_str())) {
+            return None;
+        }
+        let user = self.user_repo.as_ref()?.find_by_phone(from).await.ok()??;
+        let age = (chrono::Utc::now() - user.created_at).num_seconds().max(0) as u64;
+        if age >= min_age {
+            return None;
+        }
+        if let Some(ref deposit_repo) = self.deposit_repo {
+            if deposit_repo.has_funded(&user.phone).await.unwrap_or(false) {
+                return None;
+            }
+        }
+        Some(format!(
+            "New wallets can't send yet.\nSending opens in {}, or as soon as you DEPOSIT funds.",
+            format_wait(std::time::Duration::from_secs(min_age - age))
+        ))
+    }
+
+    /// The recipient, uppercased, when it is a command keyword (or one of its
+    /// synonyms in the configured languages) rather than a payee
+    fn reserved_recipient(&self, recipient: &str) -> Option<String> {
+        let word = recipient.trim().trim_end_matches(['.', '!', '?', ',', ';', ':']).to_uppercase();
+        let reserved = RESERVED_WORDS.contains(&word.as_str())
+            || synonyms::resolve(&word, &self.config.languages).is_some();
+        reserved.then_some(word)
+    }
+
+    async fn send_response(&self, from: &str, amount: f64, token: &str, recipient: &str, note: Option<&str>) -> String {
+        let token_upper = token.to_uppercase();
+        // Support TXTC and ETH
+        if token_upper != "TXTC" && token_upper != "ETH" {
+            return format!("Supported tokens: TXTC, ETH\nExample: SEND 10 TXTC swarnim.ttcip.eth");
+        }
+
+        if let Some(word) = self.reserved_recipient(recipient) {
+            return format!(
+                "{} is a command, not a recipient.\nTo run it, text {} on its own.\nTo send: SEND {} {} TO <name or number>",
+                word, word, self.fmt_amount(amount), token_upper
+            );
+        }
+
+        if let Some(blocked) = self.wallet_age_block(from).await {
+            return blocked;
+        }
+
+        // Without an explicit note, a TAG on the payee supplies one
+        let note = match note {
+            Some(note) => Some(note.to_string()),
+            None => self.payee_tag(from, recipient).await,
+        };
+
+        // Large sends, repeats of a send that just went out (likely a carrier
+        // duplicate or double-tap) and, when enabled, first payments to a
+        // recipient wait for a YES/CONFIRM reply
+        let fingerprint = send_fingerprint(amount, &token_upper, recipient);
+        let repeat = self.recent_sends.is_recent(from, &fingerprint);
+        let large = self.config.confirm_above > 0.0 && amount > self.config.confirm_above;
+        let first_time = !repeat && !large && !self.simulate && self.is_new_recipient(from, recipient).await;
+        if (repeat || large || first_time) && !self.simulate {
+            let kind = PendingKind::Send {
+                amount,
+                token: token_upper,
+                recipient: recipient.to_string(),
+                note,
+            };
+            let action = kind.to_sms_string(self.config.display_precision);
+            let question = if repeat {
+                format!("You just did this. Again: {}?", action)
+            } else if first_time {
+                format!("First time paying {}. Confirm: {}?", recipient, action)
+            } else {
+                format!("Confirm: {}?", action)
+            };
+            let code = match self.pending.add(from, kind).await {
+                Ok(code) => code,
+                Err(e) => {
+                    tracing::error!("Failed to store pending send: {}", e);
+                    return "Error. Try later.".to_string();
+                }
+            };
+            let minutes = (self.config.confirm_ttl_secs / 60).max(1);
+            return if self.config.confirm_by_keyword {
+                format!("{}\nReply YES to confirm or NO to cancel.\nExpires in {} min.", question, minutes)
+            } else {
+                format!("{}\nReply CONFIRM {} to confirm or CANCEL {} to cancel.\nExpires in {} min.", question, code, code, minutes)
+            };
+        }
+
+        self.recent_sends.record(from, &fingerprint);
+        self.execute_send(from, amount, &token_upper, recipient, note.as_deref()).await
+    }
+
+    /// Default note `from` tagged `recipient` with, if any
+    async fn payee_tag(&self, from: &str, recipient: &str) -> Option<String> {
+        let repo = self.tag_repo.as_ref()?;
+        repo.get(from, recipient).await.unwrap_or_else(|e| {
+            tracing::error!("Failed to look up payee tag: {}", e);
+            None
+        })
+    }
+
+    /// TAG <payee> <note>: default note for sends to the payee; TAG <payee> OFF clears it
+    async fn tag_response(&self, from: &str, payee: &str, tag: Option<&str>) -> String {
+        let Some(ref repo) = self.tag_repo else {
             return "DB offline. Try later.".to_string();
         };
+        match tag {
+            Some(tag) => match repo.set(from, payee, tag).await {
+                Ok(()) => format!("Tagged {}: \"{}\".\nSends to {} get this note unless you add NOTE <text>.", payee, tag, payee),
+                Err(e) => {
+                    tracing::error!("Failed to save payee tag: {}", e);
+                    "Error. Try later.".to_string()
+                }
+            },
+            None => match repo.remove(from, payee).await {
+                Ok(true) => format!("Tag on {} removed.", payee),
+                Ok(false) => format!("{} has no tag.", payee),
+                Err(e) => {
+                    tracing::error!("Failed to remove payee tag: {}", e);
+                    "Error. Try later.".to_string()
+                }
+            },
+        }
+    }
+
+    /// Whether first payments need confirming and `from` has never paid this
+    /// recipient. False whenever it can't be told (lookup failures surface
+    /// when the send runs).
+    async fn is_new_recipient(&self, from: &str, recipient: &str) -> bool {
+        if !self.config.confirm_new_recipients {
+            return false;
+        }
+        let (Some(user_repo), Some(transfer_repo)) = (self.user_repo.as_ref(), self.transfer_repo.as_ref()) else {
+            return false;
+        };
+        let Ok(resolved) = self.resolve_recipient(user_repo, from, recipient).await else {
+            return false;
+        };
+        matches!(transfer_repo.last_to(from, &resolved.address).await, Ok(None))
+    }
 
-        let user = match user_repo.find_by_phone(from).await {
-            Ok(Some(user)) => user,
-            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
-            Err(_) => return "Error. Try later.".to_string(),
+    /// Handle YES/CONFIRM and NO/CANCEL for pending sends
+    async fn confirm_response(&self, from: &str, reference: Option<&str>, confirm: bool) -> String {
+        let (yes, no) = if self.config.confirm_by_keyword { ("YES", "NO") } else { ("CONFIRM", "CANCEL") };
+        let verb = if confirm { yes } else { no };
+
+        let picked = match reference {
+            // Keyword mode picks by list position, token mode only by code
+            Some(r) if self.config.confirm_by_keyword && r.parse::<usize>().is_ok() => {
+                self.pending.take(from, r.parse().ok(), confirm).await
+            }
+            Some(code) => self.pending.take_by_code(from, code, confirm).await,
+            None if self.config.confirm_by_keyword => self.pending.take(from, None, confirm).await,
+            None => return format!("Usage: {} <code>", verb),
         };
 
-        let arc_url = std::env::var("ARC_SERVICE_URL").unwrap_or_else(|_| "http://arc:8084".to_string());
-        let client = reqwest::Client::new();
-        let token_upper = token.to_uppercase();
+        let action = match picked {
+            Ok(action) => action,
+            Err(PickError::Empty) => {
+                return if confirm { "Nothing to confirm." } else { "Nothing to cancel." }.to_string();
+            }
+            Err(PickError::Expired) => {
+                return if confirm {
+                    "That send expired and wasn't made.\nSend it again if you still want to."
+                } else {
+                    "That send had already expired. Nothing was sent."
+                }
+                .to_string();
+            }
+            Err(PickError::AlreadyUsed { confirmed }) => {
+                return if confirmed { "Already confirmed." } else { "Already cancelled." }.to_string();
+            }
+            Err(PickError::Unavailable) => return "Error. Try later.".to_string(),
+            Err(PickError::NotFound) => match reference {
+                Some(r) if self.config.confirm_by_keyword && r.parse::<usize>().is_ok() => {
+                    return format!("No pending send #{}.\nReply {} to list.", r, verb);
+                }
+                _ => return "Code not found or expired.".to_string(),
+            },
+            Err(PickError::Ambiguous(kinds)) => {
+                let list: Vec<String> = kinds.iter()
+                    .enumerate()
+                    .map(|(i, k)| format!("{}. {}", i + 1, k.to_sms_string(self.config.display_precision)))
+                    .collect();
+                return format!("Pending:\n{}\n\nReply {} <n> or {} <n>", list.join("\n"), yes, no);
+            }
+        };
 
-        tracing::info!("Cashout: {} {} for {} ({})", amount, token_upper, from, user.wallet_address);
+        if !confirm {
+            return format!("Cancelled: {}.", action.kind.to_sms_string(self.config.display_precision));
+        }
 
-        // Call arc-service cashout endpoint
-        let _response = client
-            .post(&format!("{}/api/arc/cashout", arc_url))
-            .json(&serde_json::json!({
-                "phone": from,
-                "userAddress": user.wallet_address,
-                "txtcAmount": amount.to_string(),
-                "token": token_upper
-            }))
-            .timeout(std::time::Duration::from_secs(5))
-            .send()
-            .await;
+        match action.kind {
+            PendingKind::Send { amount, token, recipient, note } => {
+                self.execute_send(from, amount, &token, &recipient, note.as_deref()).await
+            }
+        }
+    }
 
-        format!(
-            "Cashing out {} {}...\n\nTXTC → USDC on Arc via Circle CCTP.\nYou'll get an SMS when complete.\n\nThis may take 1-2 minutes.",
-            amount, token_upper
-        )
+    /// The channel a send from `from` should go through: their open one,
+    /// or a newly opened one if they have none. None (Yellow picks) when
+    /// channels aren't tracked or the open failed.
+    async fn sender_channel(&self, from: &str, amount: f64) -> Option<String> {
+        let repo = self.channel_repo.as_ref()?;
+        match repo.get_open_channel(from).await {
+            Ok(Some(channel)) => return Some(channel.channel_id),
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!("Failed to look up open channel: {}", e);
+                return None;
+            }
+        }
+
+        let channel_id = match self.yellow.open_channel(amount.to_string()).await {
+            Ok(channel_id) if !channel_id.is_empty() => channel_id,
+            Ok(_) => {
+                tracing::warn!("Yellow opened a channel without an id");
+                return None;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to open Yellow channel: {}", e);
+                return None;
+            }
+        };
+        match repo.insert_open(from, &channel_id).await {
+            Ok(true) => Some(channel_id),
+            Ok(false) => {
+                // A concurrent send opened one first: use theirs, close ours
+                if let Err(e) = self.yellow.close_channel(Some(channel_id.clone())).await {
+                    tracing::warn!("Failed to close surplus channel {}: {}", channel_id, e);
+                }
+                repo.get_open_channel(from).await.ok().flatten().map(|channel| channel.channel_id)
+            }
+            Err(e) => {
+                tracing::error!("Failed to record opened channel {}: {}", channel_id, e);
+                Some(channel_id)
+            }
+        }
     }
 
-    async fn bridge_response(&self, from: &str, amount: f64, token: &str, from_chain: &str, to_chain: &str) -> String {
+    /// Perform a send the user has already confirmed (or that needed no confirmation)
+    async fn execute_send(&self, from: &str, amount: f64, token_upper: &str, recipient: &str, note: Option<&str>) -> String {
+        // Get sender's wallet and private key
         let Some(ref user_repo) = self.user_repo else {
             return "DB offline. Try later.".to_string();
         };
 
-        let user = match user_repo.find_by_phone(from).await {
-            Ok(Some(user)) => user,
-            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
-            Err(_) => return "Error. Try later.".to_string(),
+        let sender = match user_repo.find_by_phone(from).await {
+            Ok(Some(u)) => u,
+            Ok(None) => { return "No wallet. Reply JOIN first.".to_string(); },
+            Err(_) => { return "Error. Try later.".to_string(); },
+        };
+
+        let ResolvedRecipient { address: recipient_address, phone: recipient_phone, via } =
+            match self.resolve_recipient(user_repo, from, recipient).await {
+                Ok(r) => r,
+                Err(msg) => return msg,
+            };
+        tracing::info!(resolver = via.name(), "SEND recipient resolved to {}", recipient_address);
+        let recipient = &self.recipient_label(recipient).await;
+
+        if self.simulate {
+            return format!(
+                "[SIMULATED] Would send {} {} to {} ({})",
+                self.fmt_amount(amount), token_upper, recipient, recipient_address
+            );
+        }
+
+        // First send from a receive-only account: finish setting it up.
+        // Stays receive-only (and retries next time) if Arc didn't answer.
+        if sender.receive_only && !self.provision_arc_wallet(from).await.is_empty() {
+            if let Err(e) = user_repo.mark_provisioned(from).await {
+                tracing::error!("Failed to upgrade receive-only account: {}", e);
+            }
+            self.lookups.invalidate_user(from);
+        }
+
+        let from_address = match self.account_address(&sender).await {
+            Ok(address) => address,
+            Err(msg) => return msg,
+        };
+        let sender_key = match self.signing_key(&sender) {
+            Ok(key) => key,
+            Err(msg) => return msg,
         };
+        if sender.uses_smart_account() {
+            if let Err(msg) = self.deploy_smart_account(&sender, &from_address).await {
+                return msg;
+            }
+        }
+
+        // Large or unknown senders' transfers wait for the recipient to ACCEPT
+        if let Some(ref to_phone) = recipient_phone {
+            if self.should_hold_transfer(from, to_phone, amount, token_upper).await {
+                return self.hold_transfer_response(from, &from_address, &sender_key, amount, token_upper, to_phone, &recipient_address).await;
+            }
+        }
 
+        // Route through Yellow Network for instant finality
         let client = reqwest::Client::new();
+        let api_url = &format!("{}/api/send-yellow", self.backend_url);
+        
+        tracing::info!("Sending {} {} from {} to {} (via Yellow)", amount, token_upper, from_address, recipient_address);
 
-        tracing::info!(
-            "Bridge: {} {} from {} to {} for {}",
-            amount, token, from_chain, to_chain, user.wallet_address
-        );
+        // Tracked before submitting, so a transfer whose outcome we never
+        // learn is still there for reconciliation
+        let transfer_id = match self.transfer_repo {
+            Some(ref repo) => match repo.create_pending(from, &from_address, &recipient_address, amount, token_upper).await {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    tracing::error!("Failed to record outgoing transfer: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
 
-        let response = client
-            .post(&format!("{}/api/bridge", self.backend_url))
+        // Reuse the sender's open channel, opening one only if they have none
+        let channel_id = self.sender_channel(from, amount).await;
+        
+        let request = client
+            .post(api_url)
             .json(&serde_json::json!({
-                "fromChain": from_chain.to_lowercase(),
-                "toChain": to_chain.to_lowercase(),
-                "fromToken": token,
-                "toToken": token,
+                "fromAddress": from_address,
+                "toAddress": recipient_address,
                 "amount": amount.to_string(),
-                "userAddress": user.wallet_address,
-                "userPhone": from
+                "token": token_upper,
+                "userPhone": from,
+                "senderKey": sender_key,
+                "accountMode": sender.account_mode,
+                "note": note,
+                "channelId": channel_id
             }))
-            .timeout(std::time::Duration::from_secs(5))
-            .send()
-            .await;
-
-        match response {
-            Ok(resp) => {
-                if let Ok(result) = resp.json::<serde_json::Value>().await {
-                    if result["success"].as_bool().unwrap_or(false) {
-                        let route = result["route"].as_str().unwrap_or("");
-                        format!(
-                            "Bridge started!\n{}\nSMS when done.",
-                            route
-                        )
-                    } else {
-                        let err = result["error"].as_str().unwrap_or("Unknown error");
-                        format!("❌ Bridge failed: {}", err)
-                    }
-                } else {
-                    "Bridge initiated. You'll get an SMS when complete.".to_string()
-                }
+            .timeout(std::time::Duration::from_secs(self.config.send_timeout_secs));
+        let response = match http::send(request).await {
+            Ok(resp) => resp,
+            Err(HttpError::RateLimited { retry_after }) => {
+                // Turned away before Yellow looked at it: safe to send again later
+                self.track_transfer(transfer_id, |repo, id| async move { repo.mark_failed(id, "rate limited").await })
+                    .await;
+                return busy_reply(retry_after);
+            }
+            Err(e) if e.is_timeout() => {
+                // Yellow may well have queued it; don't invite a duplicate send
+                tracing::warn!("Yellow API timed out, transfer may still complete: {}", e);
+                self.track_transfer(transfer_id, |repo, id| async move {
+                    repo.note_unresolved(id, "timed out waiting for Yellow").await
+                })
+                .await;
+                return format!(
+                    "Still processing: {} {} to {}.\nYellow is slow right now. Wait for the completion SMS before sending again.",
+                    self.fmt_amount(amount), token_upper, recipient
+                );
             }
             Err(e) => {
-                tracing::error!("Bridge API error: {}", e);
-                "Bridge service unavailable. Try later.".to_string()
+                tracing::error!("Failed to call Yellow API: {}", e);
+                let error = e.to_string();
+                self.track_transfer(transfer_id, |repo, id| async move { repo.mark_failed(id, &error).await })
+                    .await;
+                return "Network error. Try later.".to_string();
+            }
+        };
+
+        // Parse response
+        let result: serde_json::Value = match response.json().await {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::error!("Failed to parse API response: {}", e);
+                let error = format!("unreadable response: {}", e);
+                self.track_transfer(transfer_id, |repo, id| async move { repo.note_unresolved(id, &error).await })
+                    .await;
+                return "Error processing response.".to_string();
+            }
+        };
+
+        if result["success"].as_bool().unwrap_or(false) {
+            let tx_hash = result["txHash"].as_str().or(result["transactionId"].as_str());
+            self.track_transfer(transfer_id, |repo, id| async move { repo.mark_queued(id, tx_hash).await }).await;
+            if let (Some(repo), Some(channel_id)) = (self.channel_repo.as_ref(), result["channelId"].as_str()) {
+                let balance = result["channelBalance"]
+                    .as_f64()
+                    .or_else(|| result["channelBalance"].as_str().and_then(|b| b.parse().ok()));
+                if let Err(e) = repo.record_activity(from, channel_id, balance).await {
+                    tracing::error!("Failed to record channel activity: {}", e);
+                }
             }
+            self.emit_transfer(&from_address, &recipient_address, amount, token_upper, tx_hash);
+            self.record_transaction(from, "send", amount, token_upper, Some(recipient), tx_hash).await;
+            let receipt = self.issue_receipt(amount, token_upper, &from_address, &recipient_address, tx_hash).await;
+            let currency = self.display_currency(from).await;
+            let note_line = note.map(|note| format!("\nNote: {}", note)).unwrap_or_default();
+            let ack = templates::render(
+                &self.config.send_ack_template,
+                &[
+                    ("amount", &self.fmt_amount(amount)),
+                    ("token", token_upper),
+                    ("recipient", recipient),
+                    ("eta", result["estimatedProcessing"].as_str().unwrap_or("a few minutes")),
+                    ("fiat", &self.fiat_hint(currency.as_deref(), amount, token_upper).await),
+                    ("note", &note_line),
+                ],
+            );
+            format!("{}{}", ack, receipt)
+        } else {
+            let error_msg = result["error"].as_str().unwrap_or("Unknown error");
+            tracing::error!("Transfer failed: {}", error_msg);
+            self.track_transfer(transfer_id, |repo, id| async move { repo.mark_failed(id, error_msg).await })
+                .await;
+
+            balance_failure(error_msg, token_upper).unwrap_or_else(|| "Transfer failed. Try later.".to_string())
         }
     }
 
-    async fn save_response(&self, from: &str, name: &str, phone: &str) -> String {
-        let Some(ref address_book) = self.address_book_repo else {
-            return "Address book offline.".to_string();
+    /// Tell event subscribers about a send and the two balances it moved
+    fn emit_transfer(&self, from_address: &str, to_address: &str, amount: f64, token: &str, tx_hash: Option<&str>) {
+        self.events.emit(WalletEvent::TransferCompleted {
+            from_address: from_address.to_string(),
+            to_address: to_address.to_string(),
+            amount,
+            token: token.to_string(),
+            tx_hash: tx_hash.map(str::to_string),
+        });
+        for (address, delta) in [(from_address, -amount), (to_address, amount)] {
+            self.events.emit(WalletEvent::BalanceChanged {
+                address: address.to_string(),
+                token: token.to_string(),
+                delta,
+            });
+        }
+    }
+
+    /// Sign and store a receipt for a completed send. Returns the line linking
+    /// to it, empty when receipts are off or it couldn't be stored.
+    async fn issue_receipt(&self, amount: f64, token: &str, from_address: &str, to_address: &str, tx_hash: Option<&str>) -> String {
+        let Some(ref receipt_repo) = self.receipt_repo else {
+            return String::new();
         };
+        if self.config.receipt_signing_key.is_empty() {
+            return String::new();
+        }
 
-        match address_book.add_contact(from, name, Some(phone), None).await {
-            Ok(_) => format!("Saved {} as {}.", phone, name),
-            Err(_) => "Error saving contact.".to_string(),
+        let receipt = Receipt::new(amount, token, from_address, to_address, tx_hash).signed(&self.config.receipt_signing_key);
+        if let Err(e) = receipt_repo.insert(&receipt).await {
+            tracing::error!("Failed to store receipt: {}", e);
+            return String::new();
         }
+        format!("\nReceipt: {}/receipt/{}", self.config.public_url.trim_end_matches('/'), receipt.reference)
     }
 
-    async fn contacts_response(&self, from: &str) -> String {
-        let Some(ref address_book) = self.address_book_repo else {
-            return "Address book offline.".to_string();
+    /// Token amount as shown in replies, at the configured display precision
+    fn fmt_amount(&self, amount: f64) -> String {
+        format_display_amount(amount, self.config.display_precision)
+    }
+
+    /// Add a transaction the backend accepted to the user's HISTORY; failures are only logged
+    async fn record_transaction(&self, from: &str, kind: &str, amount: f64, token: &str, counterparty: Option<&str>, tx_hash: Option<&str>) {
+        let Some(ref repo) = self.transaction_repo else {
+            return;
         };
+        if let Err(e) = repo.record(from, kind, amount, token, counterparty, tx_hash, "submitted").await {
+            tracing::error!("Failed to record {} transaction: {}", kind, e);
+        }
+    }
 
-        match address_book.list_all(from).await {
-            Ok(contacts) if contacts.is_empty() => {
-                "No contacts yet.\n\nSAVE <name> <phone>".to_string()
-            }
-            Ok(contacts) => {
-                let list: Vec<String> = contacts.iter()
-                    .take(5)
-                    .map(|c| c.to_sms_string())
-                    .collect();
-                format!("Contacts:\n{}", list.join("\n"))
+    /// Update a tracked outgoing transfer; failures are only logged
+    async fn track_transfer<'a, F, Fut>(&'a self, id: Option<uuid::Uuid>, update: F)
+    where
+        F: FnOnce(&'a TransferRepository, uuid::Uuid) -> Fut,
+        Fut: std::future::Future<Output = Result<(), sqlx::Error>>,
+    {
+        if let (Some(repo), Some(id)) = (self.transfer_repo.as_ref(), id) {
+            if let Err(e) = update(repo, id).await {
+                tracing::error!(%id, "Failed to update outgoing transfer: {}", e);
             }
-            Err(_) => "Error loading contacts.".to_string(),
         }
     }
 
-    async fn chain_response(&self, from: &str, chain_input: &str) -> String {
-        let Some(chain) = Chain::from_input(chain_input) else {
-            return format!(
-                "Unknown chain: {}\n\nAvailable: polygon, base, eth, arb",
-                chain_input
-            );
+    /// SEND $10: convert at the current price, then send the token amount
+    async fn send_fiat_response(&self, from: &str, fiat_amount: f64, currency: &str, token: &str, recipient: &str) -> String {
+        let token_upper = token.to_uppercase();
+        let amount = match self.fiat_to_token(fiat_amount, currency, &token_upper).await {
+            Ok(amount) => amount,
+            Err(e) if e.starts_with(PRICES_DISAGREE) => {
+                tracing::warn!("Fiat conversion refused: {}", e);
+                return format!(
+                    "Prices are moving too fast to convert {} right now.\nSend a token amount instead:\nSEND 10 {} TO {}",
+                    currency, token_upper, recipient
+                );
+            }
+            Err(e) => {
+                tracing::warn!("Fiat conversion failed: {}", e);
+                return format!(
+                    "Price feed unavailable.\nSend a token amount instead:\nSEND 10 {} TO {}",
+                    token_upper, recipient
+                );
+            }
         };
 
-        // For now, just acknowledge - could save preference to DB
-        format!(
-            "Switched to {}!\n\nChain ID: {}\nNative: {}",
-            chain.name(),
-            chain.chain_id(),
-            chain.native_token()
-        )
+        let reply = self.send_response(from, amount, &token_upper, recipient, None).await;
+        format!("{} = {} {}\n{}", format_fiat(fiat_amount, currency), self.fmt_amount(amount), token_upper, reply)
     }
 
-    fn unknown_response(&self, text: &str) -> String {
-        if text.is_empty() {
-            "Welcome to TextChain!\n\nReply COMMANDS for help.".to_string()
-        } else {
-            format!(
-                "Unknown: {}\n\nReply COMMANDS for help.",
-                text.chars().take(15).collect::<String>()
-            )
+    /// Token amount worth `fiat_amount`, rounded to 6 decimals. Uses the firm
+    /// price, so a cross-checked feed with a source down refuses.
+    async fn fiat_to_token(&self, fiat_amount: f64, currency: &str, token: &str) -> Result<f64, String> {
+        let price = self.price_source.firm_price(token, currency).await?;
+        if !price.is_finite() || price <= 0.0 {
+            return Err(format!("Invalid {} price: {}", token, price));
         }
+        Ok((fiat_amount / price * 1_000_000.0).round() / 1_000_000.0)
     }
-}
+
+    /// Resolve a recipient (wallet address, phone number, ENS name, or contact)
+    /// to a wallet address, trying RECIPIENT_RESOLVERS in order until one
+    /// matches. Errors carry the SMS reply to send back: the first resolver's
+    /// complaint, if any had one.
+    async fn resolve_recipient(
+        &self,
+        user_repo: &UserRepository,
+        from: &str,
+        recipient: &str,
+    ) -> Result<ResolvedRecipient, String> {
+        let mut failure: Option<String> = None;
+        for &resolver in &self.config.recipient_resolvers {
+            match self.resolve_with(resolver, user_repo, from, recipient).await {
+                Ok(Some(resolved)) => return Ok(resolved),
+                Ok(None) => {}
+                Err(e) => {
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+        Err(failure.unwrap_or_else(|| {
+            "Invalid recipient.\nUse ENS (name.ttcip.eth), phone (+1...), or address (0x...)".to_string()
+        }))
+    }
+
+    /// One resolver's answer: None when the input isn't its kind or it finds
+    /// nothing, an error when it's its kind but can't be paid
+    async fn resolve_with(
+        &self,
+        resolver: RecipientResolver,
+        user_repo: &UserRepository,
+        from: &str,
+        recipient: &str,
+    ) -> Result<Option<ResolvedRecipient>, String> {
+        let found = |address: String, phone: Option<String>| Ok(Some(ResolvedRecipient { address, phone, via: resolver }));
+        match resolver {
+            RecipientResolver::Address => {
+                if recipient.starts_with("0x") && recipient.len() == 42 {
+                    return found(recipient.to_string(), None);
+                }
+                Ok(None)
+            }
+            RecipientResolver::Phone => {
+                if !recipient.starts_with('+') {
+                    return Ok(None);
+                }
+                match self.lookups.users.get_or_load(recipient, user_repo.find_by_phone(recipient)).await {
+                    Ok(Some(u)) => found(u.wallet_address, Some(u.phone)),
+                    Ok(None) => Err(format!("{} hasn't joined yet.\nAsk them to text JOIN", recipient)),
+                    Err(_) => Err("Error looking up recipient.".to_string()),
+                }
+            }
+            RecipientResolver::Ens => {
+                // e.g. swarnim.ttcip.eth
+                if !recipient.contains('.') {
+                    return Ok(None);
+                }
+                let name = recipient.to_lowercase();
+                match self.lookups.ens.get_or_load(&name, self.resolve_ens(recipient)).await? {
+                    Some(addr) => found(addr, None),
+                    None => Err(format!("Could not resolve {}.\nUse wallet address instead.", recipient)),
+                }
+            }
+            RecipientResolver::Contact => {
+                let Some(ref address_book) = self.address_book_repo else {
+                    return Ok(None);
+                };
+                let contacts = match address_book.find_by_name(from, recipient).await {
+                    Ok(contacts) => contacts,
+                    Err(_) => return Err("Error looking up recipient.".to_string()),
+                };
+                let Some(contact) = contacts.first() else {
+                    return Ok(None);
+                };
+                if let Some(ref saved) = contact.wallet_address {
+                    match self.saved_address(saved).await? {
+                        Some(address) => found(address, None),
+                        None => Err(format!("Could not resolve {} ({}).", saved, recipient)),
+                    }
+                } else if let Some(ref phone) = contact.contact_phone {
+                    match self.lookups.users.get_or_load(phone, user_repo.find_by_phone(phone)).await {
+                        Ok(Some(u)) => found(u.wallet_address, Some(u.phone)),
+                        _ => Err(format!("Contact {} has no wallet.", recipient)),
+                    }
+                } else {
+                    Err(format!("Contact {} has no address.", recipient))
+                }
+            }
+        }
+    }
+
+    /// How a recipient is shown in replies: a raw 0x address by its ENS name
+    /// when it has one. Slow or failed lookups just show the address.
+    async fn recipient_label(&self, recipient: &str) -> String {
+        if !(recipient.starts_with("0x") && recipient.len() == 42) {
+            return recipient.to_string();
+        }
+        let Ok(address) = Address::from_str(recipient) else {
+            return recipient.to_string();
+        };
+        let lookup = self.lookups.reverse_name(recipient, self.multi_chain.lookup_address(address));
+        match tokio::time::timeout(std::time::Duration::from_secs(2), lookup).await {
+            Ok(Ok(Some(name))) => name,
+            Ok(Ok(None)) | Err(_) => recipient.to_string(),
+            Ok(Err(e)) => {
+                tracing::debug!("Reverse ENS lookup failed: {}", e);
+                recipient.to_string()
+            }
+        }
+    }
+
+    /// An ENS name's address (None if it has none), from the ENS chain when
+    /// it answers, otherwise from the backend
+    async fn resolve_ens(&self, name: &str) -> Result<Option<String>, String> {
+        match self.multi_chain.resolve_ens(name).await {
+            Ok(address) => return Ok(address.map(|a| format!("{:?}", a))),
+            Err(e) => tracing::debug!("Native ENS resolution failed, asking the backend: {}", e),
+        }
+
+        let client = reqwest::Client::new();
+        let resolve_url = format!("{}/api/ens/resolve/{}", self.backend_url, name);
+        let resp = client
+            .get(&resolve_url)
+            .send()
+            .await
+            .map_err(|_| "Network error resolving ENS. Try later.".to_string())?;
+        let json = resp
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|_| format!("Could not resolve {}.", name))?;
+        Ok(json["address"].as_str().map(str::to_string))
+    }
+
+    /// Whether a transfer should be held for the recipient's approval
+    async fn should_hold_transfer(&self, from: &str, to_phone: &str, amount: f64, token: &str) -> bool {
+        if self.inbound_repo.is_none() {
+            return false;
+        }
+        // Above the recipient's APPROVE limit, whoever the sender is
+        if let Some(ref user_repo) = self.user_repo {
+            match user_repo.approval_limit(to_phone).await {
+                Ok(Some((limit, limit_token))) if limit_token == token && amount > limit => return true,
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to read approval limit: {}", e),
+            }
+        }
+        if !self.config.hold_unknown_senders {
+            return false;
+        }
+        let Some(ref address_book) = self.address_book_repo else {
+            return false;
+        };
+        // Senders the recipient has saved as a contact go straight through
+        matches!(address_book.find_by_phone(to_phone, from).await, Ok(None))
+    }
+
+    /// Move funds into backend escrow and record the pending inbound transfer
+    #[allow(clippy::too_many_arguments)]
+    async fn hold_transfer_response(
+        &self,
+        from: &str,
+        from_address: &str,
+        sender_key: &str,
+        amount: f64,
+        token: &str,
+        to_phone: &str,
+        to_address: &str,
+    ) -> String {
+        let Some(ref inbound_repo) = self.inbound_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let hold_ref = match self.holds().lock(from, from_address, sender_key, amount, token, to_address).await {
+            Ok(hold_ref) => hold_ref,
+            Err(msg) => return msg,
+        };
+
+        if let Err(e) = inbound_repo
+            .create_pending(to_phone, from, from_address, amount, token, Some(&hold_ref))
+            .await
+        {
+            // Unrecorded, nobody could ever ACCEPT or DECLINE it: hand it back
+            tracing::error!("Failed to record held transfer {}: {}", hold_ref, e);
+            if self.holds().settle(&hold_ref, HoldAction::Refund).await == Settlement::Paid {
+                return "Transfer failed. Nothing was sent. Try later.".to_string();
+            }
+            return "Funds held but not recorded. Contact support.".to_string();
+        }
+
+        format!(
+            "Sent {} {} to {}.\n\nHeld until they reply ACCEPT.\nRefunded if declined.",
+            self.fmt_amount(amount), token, to_phone
+        )
+    }
+
+    /// HOLD: lock funds for a recipient until a date or a manual RELEASE
+    async fn hold_response(
+        &self,
+        from: &str,
+        amount: f64,
+        token: &str,
+        recipient: &str,
+        until: Option<chrono::NaiveDate>,
+    ) -> String {
+        let token_upper = token.to_uppercase();
+        if token_upper != "TXTC" && token_upper != "ETH" {
+            return "Supported tokens: TXTC, ETH\nExample: HOLD 20 TXTC FOR alice UNTIL 2025-12-01".to_string();
+        }
+
+        let (Some(ref user_repo), Some(ref escrow_repo)) = (&self.user_repo, &self.escrow_repo) else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let release_at = until.map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc());
+        if let Some(at) = release_at {
+            if at <= chrono::Utc::now() {
+                return "Release date must be in the future.".to_string();
+            }
+        }
+
+        let payer = match user_repo.find_by_phone(from).await {
+            Ok(Some(u)) => u,
+            Ok(None) => { return "No wallet. Reply JOIN first.".to_string(); },
+            Err(_) => { return "Error. Try later.".to_string(); },
+        };
+
+        let resolved = match self.resolve_recipient(user_repo, from, recipient).await {
+            Ok(r) => r,
+            Err(msg) => return msg,
+        };
+
+        if self.simulate {
+            return format!(
+                "[SIMULATED] Would hold {} {} for {} ({})",
+                self.fmt_amount(amount), token_upper, recipient, resolved.address
+            );
+        }
+
+        let payer_key = match self.signing_key(&payer) {
+            Ok(key) => key,
+            Err(msg) => return msg,
+        };
+        let hold_ref = match self
+            .holds()
+            .lock(from, &payer.wallet_address, &payer_key, amount, &token_upper, &resolved.address)
+            .await
+        {
+            Ok(hold_ref) => hold_ref,
+            Err(msg) => return msg,
+        };
+
+        let escrow = match escrow_repo
+            .create(from, &payer.wallet_address, recipient, &resolved.address, amount, &token_upper, Some(&hold_ref), release_at)
+            .await
+        {
+            Ok(escrow) => escrow,
+            Err(e) => {
+                tracing::error!("Failed to record escrow {}: {}", hold_ref, e);
+                return "Funds held but not recorded. Contact support.".to_string();
+            }
+        };
+
+        let release_note = match until {
+            Some(date) => format!("Releases on {}.", date),
+            None => format!("Refunded after {} days if not released.", self.config.escrow_max_days),
+        };
+
+        format!(
+            "Holding {} {} for {}.\nRef: {}\n\n{}\nReply RELEASE {} to pay now.",
+            self.fmt_amount(amount), token_upper, recipient, escrow.short_id(), release_note, escrow.short_id()
+        )
+    }
+
+    /// RELEASE: pay out one of the sender's held escrows early
+    async fn release_response(&self, from: &str, id: &str) -> String {
+        let Some(ref escrow_repo) = self.escrow_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let escrow = match escrow_repo.find_held(from, id).await {
+            Ok(Some(e)) => e,
+            Ok(None) => return format!("No held payment {}.", id),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        if escrow::settle_escrow(escrow_repo, &self.holds(), &escrow, HoldAction::Release).await {
+            format!("Released {} {} to {}.", escrow.amount, escrow.token, escrow.recipient)
+        } else {
+            "Release failed. Try later.".to_string()
+        }
+    }
+
+    /// Release escrows that reached their date and refund undated ones
+    /// older than `escrow_max_days`. Returns how many were settled.
+    pub async fn run_escrow_sweep(&self) -> usize {
+        let Some(ref escrow_repo) = self.escrow_repo else {
+            return 0;
+        };
+
+        let now = chrono::Utc::now();
+        let stale_before = now - chrono::Duration::days(self.config.escrow_max_days);
+        escrow::sweep(escrow_repo, &self.holds(), now, stale_before).await
+    }
+
+    /// Close Yellow channels that have gone idle or run low, settling them
+    /// on chain. Returns the (phone, SMS) notices for the owners.
+    pub async fn run_channel_sweep(&self) -> Vec<(String, String)> {
+        let Some(ref channel_repo) = self.channel_repo else {
+            return Vec::new();
+        };
+        let channels = channel_repo.list_open().await.unwrap_or_else(|e| {
+            tracing::error!("Failed to load open channels: {}", e);
+            Vec::new()
+        });
+
+        let now = chrono::Utc::now();
+        let mut notices = Vec::new();
+        for channel in channels {
+            let Some(reason) = channel.close_reason(now, self.config.channel_idle_close_secs, self.config.channel_min_balance)
+            else {
+                continue;
+            };
+            match channel_repo.claim_close(&channel.channel_id).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    tracing::error!(channel = %channel.channel_id, "Failed to claim channel: {}", e);
+                    continue;
+                }
+            }
+
+            if let Err(e) = self.yellow.close_channel(Some(channel.channel_id.clone())).await {
+                tracing::warn!(channel = %channel.channel_id, "Channel close failed: {}", e);
+                let _ = channel_repo.reopen(&channel.channel_id).await;
+                continue;
+            }
+            if let Err(e) = channel_repo.mark_closed(&channel.channel_id).await {
+                tracing::error!(channel = %channel.channel_id, "Failed to record channel close: {}", e);
+            }
+            tracing::info!(channel = %channel.channel_id, ?reason, "Yellow channel auto-closed");
+
+            let wait = std::time::Duration::from_secs(self.config.channel_settle_wait_secs);
+            let settled = match self.yellow.wait_for_settlement(&channel.channel_id, wait).await {
+                Ok(settled) => settled.is_some(),
+                Err(e) => {
+                    tracing::warn!(channel = %channel.channel_id, "Couldn't confirm channel settlement: {}", e);
+                    false
+                }
+            };
+
+            let why = match reason {
+                CloseReason::Idle => format!("after {} days without use", self.config.channel_idle_close_secs.div_ceil(86_400)),
+                CloseReason::LowBalance => format!("as its balance fell below {}", self.fmt_amount(self.config.channel_min_balance)),
+            };
+            let notice = if settled {
+                format!(
+                    "Your Yellow payment channel was closed {} and settled on-chain.\nFunds are back in your wallet. Reply BALANCE to check.",
+                    why
+                )
+            } else {
+                format!(
+                    "Your Yellow payment channel was closed {}.\nIt's settling on-chain; funds will be back in your wallet shortly.",
+                    why
+                )
+            };
+            notices.push((channel.user_phone, notice));
+        }
+        notices
+    }
+
+    /// ACCEPT/DECLINE: list held transfers or resolve the chosen one
+    async fn inbound_response(&self, from: &str, index: Option<usize>, accept: bool) -> String {
+        let Some(ref inbound_repo) = self.inbound_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let pending = match inbound_repo.list_pending(from).await {
+            Ok(p) => p,
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        if pending.is_empty() {
+            return "No pending transfers.".to_string();
+        }
+
+        let transfer = match index {
+            Some(n) => match pending.get(n - 1) {
+                Some(t) => t,
+                None => return format!("No transfer #{}.\nReply ACCEPT to list.", n),
+            },
+            None if pending.len() == 1 => &pending[0],
+            None => {
+                let list: Vec<String> = pending.iter()
+                    .enumerate()
+                    .map(|(i, t)| format!("{}. {}", i + 1, t.to_sms_string()))
+                    .collect();
+                return format!(
+                    "Pending transfers:\n{}\n\nReply ACCEPT <n> or DECLINE <n>",
+                    list.join("\n")
+                );
+            }
+        };
+
+        // Claim the transfer first so a repeated reply can't release it twice
+        let status = if accept { "accepted" } else { "declined" };
+        match inbound_repo.resolve(transfer.id, status).await {
+            Ok(true) => {}
+            Ok(false) => return "Transfer already handled.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        }
+
+        let action = if accept { HoldAction::Release } else { HoldAction::Refund };
+        match self.settle_inbound(transfer, action).await {
+            Settlement::Paid => {}
+            Settlement::Retry => {
+                let _ = inbound_repo.reopen(transfer.id).await;
+                return "Could not complete. Try later.".to_string();
+            }
+            Settlement::Gone => {
+                tracing::error!(transfer = %transfer.id, hold = ?transfer.hold_ref, "Backend has no open hold for transfer, needs manual review");
+                if let Err(e) = inbound_repo.mark_failed(transfer.id).await {
+                    tracing::error!("Failed to mark transfer {} failed: {}", transfer.id, e);
+                }
+                return "This transfer can't be completed. Contact support.".to_string();
+            }
+        }
+
+        if accept {
+            format!("Accepted {}.\n\nReply BALANCE to check.", transfer.to_sms_string())
+        } else {
+            format!(
+                "Declined. {} {} returned to {}.",
+                transfer.amount, transfer.token, transfer.sender_phone
+            )
+        }
+    }
+
+    /// Pay a held transfer to its recipient or back to its sender
+    async fn settle_inbound(&self, transfer: &InboundTransfer, action: HoldAction) -> Settlement {
+        let Some(ref hold_id) = transfer.hold_ref else {
+            return Settlement::Gone;
+        };
+        let settlement = self.holds().settle(hold_id, action).await;
+        if settlement == Settlement::Paid {
+            tracing::info!(
+                transfer = %transfer.id, status = action.status(), recipient = %mask_phone(&transfer.recipient_phone),
+                sender = %transfer.sender_address, "Held transfer settled"
+            );
+        }
+        settlement
+    }
+
+    /// The backend escrow behind HOLD and transfers waiting for ACCEPT
+    fn holds(&self) -> Holds<'_> {
+        Holds::new(&self.backend_url)
+    }
+
+    /// CURRENCY: show, set or clear (OFF) the currency for approximate values
+    async fn currency_response(&self, from: &str, code: Option<&str>) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let Some(code) = code else {
+            return match repo.display_currency(from).await {
+                Ok(Some(current)) => format!("Amounts show approx. {} values.\nReply CURRENCY OFF to hide.", current),
+                Ok(None) => "No currency set.\nExample: CURRENCY KES".to_string(),
+                Err(_) => "Error. Try later.".to_string(),
+            };
+        };
+
+        let currency = (code != "OFF").then_some(code);
+        match repo.set_display_currency(from, currency).await {
+            Ok(true) => match currency {
+                Some(code) => format!("Amounts will show approx. {} values.", code),
+                None => "Currency values hidden.".to_string(),
+            },
+            Ok(false) => "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => "Error. Try later.".to_string(),
+        }
+    }
+
+    /// APPROVE: show, set or clear (OFF) the amount above which incoming transfers are held
+    async fn approve_response(&self, from: &str, limit: Option<&ApprovalLimit>) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let Some(limit) = limit else {
+            return match repo.approval_limit(from).await {
+                Ok(Some((amount, token))) => format!(
+                    "Incoming transfers above {} {} wait for your ACCEPT.\nReply APPROVE OFF to credit them right away.",
+                    self.fmt_amount(amount), token
+                ),
+                Ok(None) => "Incoming transfers are credited right away.\nExample: APPROVE ABOVE 100 TXTC".to_string(),
+                Err(_) => "Error. Try later.".to_string(),
+            };
+        };
+
+        let setting = match limit {
+            ApprovalLimit::Above { amount, token } => Some((*amount, token.as_str())),
+            ApprovalLimit::Off => None,
+        };
+        match repo.set_approval_limit(from, setting).await {
+            Ok(true) => match setting {
+                Some((amount, token)) => format!(
+                    "Incoming transfers above {} {} will wait for your ACCEPT.",
+                    self.fmt_amount(amount), token
+                ),
+                None => "Large incoming transfers will be credited right away.".to_string(),
+            },
+            Ok(false) => "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => "Error. Try later.".to_string(),
+        }
+    }
+
+    /// AUTOSWAP: show, set or stop swapping part of each deposit to gas
+    async fn autoswap_response(&self, from: &str, setting: Option<AutoSwapSetting>) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let Some(setting) = setting else {
+            return match repo.autoswap_pct(from).await {
+                Ok(Some(pct)) => format!(
+                    "Auto-swap is on: {}% of each deposit goes to gas.\nReply AUTOSWAP OFF to stop.",
+                    pct
+                ),
+                Ok(None) => "Auto-swap is off.\nExample: AUTOSWAP ON 10%".to_string(),
+                Err(_) => "Error. Try later.".to_string(),
+            };
+        };
+
+        let pct = match setting {
+            AutoSwapSetting::Percent(pct) => Some(pct),
+            AutoSwapSetting::Off => None,
+        };
+        match repo.set_autoswap_pct(from, pct).await {
+            Ok(true) => match pct {
+                Some(pct) => format!("Auto-swap on: {}% of each deposit will be swapped to gas.", pct),
+                None => "Auto-swap off. Deposits stay as they are.".to_string(),
+            },
+            Ok(false) => "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => "Error. Try later.".to_string(),
+        }
+    }
+
+    /// Swap the user's AUTOSWAP share of a confirmed TXTC deposit to gas
+    /// (the backend only swaps TXTC, so other deposits are left alone). Each
+    /// deposit is acted on at most once; returns the SMS to send, if any.
+    pub async fn autoswap_deposit(&self, deposit: &Deposit) -> Option<String> {
+        if deposit.token != "TXTC" {
+            return None;
+        }
+        let (user_repo, deposit_repo) = (self.user_repo.as_ref()?, self.deposit_repo.as_ref()?);
+        let pct = user_repo.autoswap_pct(&deposit.user_phone).await.ok()??;
+        let amount = (deposit.amount_as_f64() * pct.min(self.config.autoswap_max_pct) as f64 / 100.0 * 1_000_000.0)
+            .floor()
+            / 1_000_000.0;
+        if amount <= 0.0 {
+            return None;
+        }
+
+        match deposit_repo.claim_autoswap(deposit.id).await {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(e) => {
+                tracing::error!("Failed to claim deposit {} for auto-swap: {}", deposit.id, e);
+                return None;
+            }
+        }
+        let user = user_repo.find_by_phone(&deposit.user_phone).await.ok()??;
+
+        tracing::info!(deposit = %deposit.id, pct, amount, "Auto-swapping deposit share to gas");
+        match self.submit_swap(&user, amount, "TXTC").await {
+            Ok(()) => Some(format!(
+                "Auto-swap: {}% of your deposit ({} TXTC) is being swapped to gas.\nReply AUTOSWAP OFF to stop.",
+                pct,
+                self.fmt_amount(amount)
+            )),
+            Err(msg) => Some(format!("Auto-swap skipped this deposit.\n{}", msg)),
+        }
+    }
+
+    /// " (~KES 1,300)" for `amount` of `token` in the user's currency; empty when
+    /// no currency is set, the amount is zero, or the price feed is down
+    async fn fiat_hint(&self, currency: Option<&str>, amount: f64, token: &str) -> String {
+        let Some(currency) = currency else {
+            return String::new();
+        };
+        if amount == 0.0 {
+            return String::new();
+        }
+        match self.price_source.price(token, currency).await {
+            Ok(price) => format!(" ({})", format_approx_fiat(amount * price, currency)),
+            Err(e) => {
+                tracing::warn!("No {} price for display: {}", currency, e);
+                String::new()
+            }
+        }
+    }
+
+    /// "\n≈ $12.30": what the `holdings` are worth together in `currency`.
+    /// Empty when any price needed is unavailable, rather than understating it.
+    async fn fiat_total(&self, currency: &str, holdings: &[(f64, &str)]) -> String {
+        let mut total = 0.0;
+        for &(amount, token) in holdings.iter().filter(|(amount, _)| *amount > 0.0) {
+            match self.price_source.price(token, currency).await {
+                Ok(price) => total += amount * price,
+                Err(e) => {
+                    tracing::warn!("No {} price for the balance total: {}", currency, e);
+                    return String::new();
+                }
+            }
+        }
+        format!("\n≈ {}", format_price(total, currency))
+    }
+
+    /// The user's display currency, if set
+    async fn display_currency(&self, from: &str) -> Option<String> {
+        self.user_repo.as_ref()?.display_currency(from).await.ok()?
+    }
+
+    /// ACCOUNT: show or switch between EOA and smart-account mode
+    async fn account_response(&self, from: &str, mode: Option<&str>) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let user = match repo.find_by_phone(from).await {
+            Ok(Some(u)) => u,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        match mode {
+            None if user.uses_smart_account() => format!(
+                "Account: smart\n{}\n\nReply ACCOUNT EOA to switch.",
+                user.smart_account_address.as_deref().unwrap_or(&user.wallet_address)
+            ),
+            None => format!("Account: EOA\n{}\n\nReply ACCOUNT SMART to switch.", user.wallet_address),
+            Some("smart") => {
+                let address = match self.resolve_smart_account(&user).await {
+                    Ok(address) => address,
+                    Err(msg) => return msg,
+                };
+                match repo.update_account_mode(from, "smart", Some(&address)).await {
+                    Ok(_) => {
+                        self.lookups.invalidate_user(from);
+                        format!("Smart account on:\n{}\n\nDeployed on your first send.", address)
+                    }
+                    Err(_) => "Error. Try later.".to_string(),
+                }
+            }
+            Some(_) => match repo.update_account_mode(from, "eoa", None).await {
+                Ok(_) => {
+                    self.lookups.invalidate_user(from);
+                    format!("Smart account off.\nUsing wallet:\n{}", user.wallet_address)
+                }
+                Err(_) => "Error. Try later.".to_string(),
+            },
+        }
+    }
+
+    /// Factory, entry point and bundler, when smart accounts are configured
+    fn aa_settings(&self) -> Option<(Address, EntryPointConfig, BundlerClient)> {
+        let aa = self.aa.as_ref()?;
+        if aa.bundler_url.is_empty() {
+            return None;
+        }
+        let factory = Address::from_str(&aa.simple_account_factory_address).ok()?;
+        let entry_point = EntryPointConfig::parse(&aa.entry_point_address, &aa.entry_point_version)?;
+        Some((factory, entry_point, BundlerClient::new(aa.bundler_url.clone())))
+    }
+
+    /// Address the user transacts from: their smart account in AA mode, else the EOA
+    async fn account_address(&self, user: &User) -> Result<String, String> {
+        if !user.uses_smart_account() {
+            return Ok(user.wallet_address.clone());
+        }
+        match user.smart_account_address {
+            Some(ref address) => Ok(address.clone()),
+            None => self.resolve_smart_account(user).await,
+        }
+    }
+
+    /// Counterfactual SimpleAccount address owned by the user's EOA, salted by phone
+    async fn resolve_smart_account(&self, user: &User) -> Result<String, String> {
+        let Some((factory, _, _)) = self.aa_settings() else {
+            return Err("Smart accounts not enabled.".to_string());
+        };
+        let owner = Address::from_str(&user.wallet_address).map_err(|_| "Invalid wallet.".to_string())?;
+
+        let salt = crate::wallet::account_salt(&user.phone);
+        crate::wallet::get_smart_account_address(factory, owner, salt, self.provider.clone())
+            .await
+            .map(|address| format!("{:?}", address))
+            .map_err(|e| {
+                tracing::error!("Failed to resolve smart account for {}: {}", user.phone, e);
+                "Network error. Try later.".to_string()
+            })
+    }
+
+    /// Deploy the user's smart account through the bundler before its first use
+    async fn deploy_smart_account(&self, user: &User, account: &str) -> Result<(), String> {
+        const FAILED: &str = "Smart account setup failed. Try later.";
+
+        let Some((factory, entry_point, bundler)) = self.aa_settings() else {
+            return Err("Smart accounts not enabled.\nReply ACCOUNT EOA.".to_string());
+        };
+        let owner = self.user_wallet(user).map_err(|_| FAILED.to_string())?;
+        let account = Address::from_str(account).map_err(|_| FAILED.to_string())?;
+
+        match crate::wallet::ensure_account_deployed(
+            &owner,
+            account,
+            factory,
+            crate::wallet::account_salt(&user.phone),
+            &entry_point,
+            &bundler,
+            self.provider.clone(),
+        )
+        .await
+        {
+            Ok(Some(op_hash)) => {
+                tracing::info!(account = ?account, op_hash, "Smart account deployment submitted");
+                // Follow the op to the chain in the background; the send goes ahead meanwhile
+                tokio::spawn(async move {
+                    match bundler.wait_for_user_op(&op_hash, std::time::Duration::from_secs(120)).await {
+                        Ok(Some(receipt)) if receipt.success => tracing::info!(
+                            account = ?account,
+                            tx_hash = ?receipt.receipt.transaction_hash,
+                            gas_used = %receipt.actual_gas_used,
+                            "Smart account deployed"
+                        ),
+                        Ok(Some(receipt)) => tracing::error!(
+                            account = ?account,
+                            tx_hash = ?receipt.receipt.transaction_hash,
+                            "Smart account deployment reverted"
+                        ),
+                        Ok(None) => tracing::warn!(account = ?account, op_hash, "Smart account deployment not mined yet"),
+                        Err(e) => tracing::warn!(account = ?account, op_hash, "Couldn't check smart account deployment: {}", e),
+                    }
+                });
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(e) => {
+                tracing::error!("Smart account deployment failed for {}: {}", user.phone, e);
+                Err(FAILED.to_string())
+            }
+        }
+    }
+
+    async fn deposit_response(&self, from: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Reply JOIN first.".to_string();
+        };
+
+        match repo.find_by_phone(from).await {
+            Ok(Some(user)) => {
+                // ENS names point at the EOA, so smart accounts show the raw address
+                let deposit_address = if user.uses_smart_account() {
+                    match self.account_address(&user).await {
+                        Ok(address) => address,
+                        Err(msg) => return msg,
+                    }
+                } else if let Some(ref ens) = user.ens_name {
+                    ens.clone()
+                } else {
+                    user.wallet_address.clone()
+                };
+                
+                format!(
+                    "Fund wallet:\nDial *384*46750#\nOr REDEEM <code>\nOr send to:\n{}",
+                    deposit_address
+                )
+            }
+            Ok(None) => "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => "Error. Try later.".to_string(),
+        }
+    }
+
+    /// Number of list entries to show, honoring a requested count up to the cap
+    fn page_size(&self, requested: Option<usize>) -> usize {
+        requested
+            .unwrap_or(self.config.page_size)
+            .clamp(1, self.config.max_page_size.max(1))
+    }
+
+    async fn history_response(&self, from: &str, limit: Option<usize>) -> String {
+        let limit = self.page_size(limit);
+
+        // The newest `limit` of each, merged, cover the newest `limit` overall
+        let deposits = match self.deposit_repo {
+            Some(ref repo) => repo.get_recent(from, limit as i64).await.unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let transactions = match self.transaction_repo {
+            Some(ref repo) => repo.recent(from, limit as i64).await.unwrap_or_else(|e| {
+                tracing::error!("Failed to load transactions: {}", e);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+
+        let mut entries: Vec<(chrono::DateTime<chrono::Utc>, HistoryEntry)> = deposits
+            .iter()
+            .map(|d| (d.created_at, HistoryEntry::Deposit(d)))
+            .chain(transactions.iter().map(|t| (t.created_at, HistoryEntry::Transaction(t))))
+            .collect();
+        if entries.is_empty() {
+            return "No transactions yet.\nReply REDEEM <code> to add funds.".to_string();
+        }
+        entries.sort_by_key(|(at, _)| std::cmp::Reverse(*at));
+        entries.truncate(limit);
+
+        let mut history = Vec::with_capacity(entries.len());
+        for (_, entry) in entries {
+            history.push(match entry {
+                HistoryEntry::Deposit(d) => {
+                    let line = if d.source == DepositSource::Bonus.to_string() {
+                        format!("{} TXTC welcome bonus", d.amount_as_f64())
+                    } else {
+                        format!("${:.2} via {}", d.amount_as_f64(), d.source)
+                    };
+                    match self.confirmation_status(d).await {
+                        Some(status) => format!("{} ({})", line, status),
+                        None => line,
+                    }
+                }
+                HistoryEntry::Transaction(t) => t.summary(&self.fmt_amount(t.amount)),
+            });
+        }
+        format!("Recent activity:\n{}", history.join("\n"))
+    }
+
+    /// "confirmed" or "pending 3/12" for an on-chain deposit; None when it isn't
+    /// on chain or the chain head can't be read
+    async fn confirmation_status(&self, deposit: &crate::db::Deposit) -> Option<String> {
+        let block = u64::try_from(deposit.block_number?).ok()?;
+        let chain = Chain::from_name(deposit.chain.as_deref()?)?;
+        let head = self.block_heights.head(chain).await?;
+
+        let depth = (head + 1).saturating_sub(block);
+        let required = self.config.min_confirmations.max(1);
+        Some(if depth >= required {
+            "confirmed".to_string()
+        } else {
+            format!("pending {}/{}", depth, required)
+        })
+    }
+
+    /// VOUCHERS: codes the user has redeemed, newest first
+    async fn vouchers_response(&self, from: &str, limit: Option<usize>) -> String {
+        let Some(ref voucher_repo) = self.voucher_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        let limit = self.page_size(limit);
+
+        // One extra row tells whether there are more to show
+        let vouchers = match voucher_repo.find_redeemed_by(from, limit as i64 + 1).await {
+            Ok(vouchers) => vouchers,
+            Err(e) => {
+                tracing::error!("Failed to load vouchers: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+        if vouchers.is_empty() {
+            return "No vouchers redeemed yet.\nReply REDEEM <code> to add funds.".to_string();
+        }
+
+        let lines: Vec<String> = vouchers
+            .iter()
+            .take(limit)
+            .map(|v| {
+                let date = v.redeemed_at.map(|at| at.format("%Y-%m-%d").to_string()).unwrap_or_default();
+                // Codes only known on chain have no amount recorded
+                let amount = if v.usdc_amount > 0 { format!("${:.2}", v.usdc_as_f64()) } else { String::new() };
+                [v.masked_code(), amount, date].iter().filter(|s| !s.is_empty()).cloned().collect::<Vec<_>>().join(" ")
+            })
+            .collect();
+        let mut reply = format!("Redeemed vouchers:\n{}", lines.join("\n"));
+        if vouchers.len() > limit {
+            let max = self.config.max_page_size.max(1);
+            if limit < max {
+                reply.push_str(&format!("\nReply VOUCHERS {} for more.", max));
+            } else {
+                reply.push_str("\nOlder vouchers not shown.");
+            }
+        }
+        reply
+    }
+
+    async fn redeem_response(&self, from: &str, code: &str) -> String {
+        // Check if user has wallet
+        let Some(ref user_repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        // Get user's wallet address
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        // Call Contract API to redeem voucher on-chain
+        let client = reqwest::Client::new();
+        let api_url = &format!("{}/api/redeem", self.backend_url);
+        
+        tracing::info!("Calling Contract API to redeem voucher: {}", code);
+        
+        let request = client.post(api_url).json(&serde_json::json!({
+            "voucherCode": code,
+            "userAddress": user.wallet_address,
+            "userPhone": from
+        }));
+        let response = match http::send(request).await {
+            Ok(resp) => resp,
+            Err(HttpError::RateLimited { retry_after }) => return busy_reply(retry_after),
+            Err(e) => {
+                tracing::error!("Failed to call Contract API: {}", e);
+                return "Network error. Try later.".to_string();
+            }
+        };
+
+        // Parse response
+        let result: serde_json::Value = match response.json().await {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::error!("Failed to parse API response: {}", e);
+                return "Error processing response.".to_string();
+            }
+        };
+
+        if result["success"].as_bool().unwrap_or(false) {
+            let token_amount = result["tokenAmount"].as_str().unwrap_or("0");
+            let eth_amount = result["ethAmount"].as_str().unwrap_or("0");
+            let tx_hash = result["txHash"].as_str().unwrap_or("");
+            
+            tracing::info!("Voucher redeemed successfully: {} TXTC + {} ETH, tx: {}", token_amount, eth_amount, tx_hash);
+
+            // Kept locally so VOUCHERS can list it
+            if let Some(ref voucher_repo) = self.voucher_repo {
+                if let Err(e) = voucher_repo.record_redemption(code, from).await {
+                    tracing::error!("Failed to record redemption of {}: {}", code, e);
+                }
+            }
+
+            format!(
+                "Voucher redeemed!\n\nReceived:\n{} TXTC\n{} ETH (gas)\n\nReply BALANCE to check.",
+                token_amount, eth_amount
+            )
+        } else {
+            let error_msg = result["error"].as_str().unwrap_or("Unknown error");
+            tracing::error!("Redemption failed: {}", error_msg);
+            
+            if error_msg.contains("already redeemed") || error_msg.contains("AlreadyRedeemed") {
+                "Voucher already used.".to_string()
+            } else if error_msg.contains("not found") || error_msg.contains("invalid") {
+                "Invalid voucher code.".to_string()
+            } else {
+                "Redemption failed. Try later.".to_string()
+            }
+        }
+    }
+
+    async fn buy_response(&self, from: &str, amount: f64) -> String {
+        let Some(ref user_repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => { return "No wallet. Reply JOIN first.".to_string(); },
+            Err(_) => { return "Error. Try later.".to_string(); },
+        };
+
+        // The call below times out before the backend answers, so a carrier
+        // retry can't be told apart by its outcome: identical BUYs close
+        // together are dropped here, and share a request id the backend dedupes
+        if !self.recent_buys.claim(from, &amount.to_string()) {
+            tracing::info!("Duplicate BUY {} EUR from {} dropped", amount, mask_phone(from));
+            return format!("Already buying TXTC with €{} airtime.\n\nYou'll get an SMS when complete.", self.fmt_euros(amount));
+        }
+        let request_id = buy_request_id(
+            from,
+            amount,
+            chrono::Utc::now().timestamp(),
+            self.config.duplicate_send_window_secs,
+        );
+
+        // Call backend /api/buy endpoint (async - fires and notifies via SMS)
+        let client = reqwest::Client::new();
+        let api_url = &format!("{}/api/buy", self.backend_url);
+
+        tracing::info!("BUY {} EUR airtime for user {} ({})", amount, user.wallet_address, request_id);
+
+        let _response = client
+            .post(api_url)
+            .json(&serde_json::json!({
+                "userAddress": user.wallet_address,
+                "amount": amount,
+                "userPhone": from,
+                "requestId": request_id
+            }))
+            .timeout(std::time::Duration::from_secs(2))
+            .send()
+            .await;
+
+        format!(
+            "Buying TXTC with €{} airtime...\n\nYou'll get an SMS when complete.",
+            self.fmt_euros(amount)
+        )
+    }
+
+    /// A BUY amount to the BUY_DECIMALS it was accepted with (12.5 -> "12.50")
+    fn fmt_euros(&self, amount: f64) -> String {
+        format!("{:.*}", self.config.buy_decimals as usize, amount)
+    }
+
+    /// Pool quote for swapping `amount` (TXTC -> ETH, or ETH -> TXTC)
+    async fn get_swap_quote(&self, amount: f64, is_token_to_eth: bool) -> Result<f64, String> {
+        let request = reqwest::Client::new()
+            .post(format!("{}/api/quote", self.backend_url))
+            .json(&serde_json::json!({
+                "amount": amount.to_string(),
+                "isTokenToEth": is_token_to_eth
+            }))
+            .timeout(std::time::Duration::from_secs(5));
+        let json: serde_json::Value = http::send(request)
+            .await
+            .map_err(|e| format!("Quote request failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid quote response: {}", e))?;
+
+        if !json["success"].as_bool().unwrap_or(false) {
+            return Err(json["error"].as_str().unwrap_or("Quote failed").to_string());
+        }
+        json["outputAmount"]
+            .as_str()
+            .and_then(|s| s.parse().ok())
+            .or_else(|| json["outputAmount"].as_f64())
+            .ok_or_else(|| "Invalid quote amount".to_string())
+    }
+
+    /// PRICE: one ETH and/or TXTC in the user's display currency
+    async fn price_response(&self, from: &str, token: Option<&str>) -> String {
+        let currency = self.display_currency(from).await.unwrap_or_else(|| "USD".to_string());
+        let tokens = match token {
+            Some(token) => vec![token],
+            None => vec!["ETH", "TXTC"],
+        };
+        let mut lines = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            match self.price_source.price(token, &currency).await {
+                Ok(price) => lines.push(format!("1 {} = {}", token, format_price(price, &currency))),
+                Err(e) => {
+                    tracing::warn!("{} price unavailable: {}", token, e);
+                    return "Price feed unavailable. Try later.".to_string();
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// QUOTE: what swapping `amount` of TXTC or ETH would give right now
+    async fn quote_response(&self, amount: f64, token: &str) -> String {
+        let is_token_to_eth = token != "ETH";
+        let output = if is_token_to_eth { "ETH" } else { "TXTC" };
+        match self.get_swap_quote(amount, is_token_to_eth).await {
+            Ok(out) if out < MIN_QUOTE_OUT => insufficient_liquidity(&self.fmt_amount(amount), token),
+            Ok(out) => format!("{} {} ≈ {} {}", self.fmt_amount(amount), token, self.fmt_amount(out), output),
+            Err(e) => {
+                tracing::warn!("Quote failed: {}", e);
+                "Can't get a price right now. Try again later.".to_string()
+            }
+        }
+    }
+
+    /// Reject a swap up front when the pool can't fill it. A zero (or dust)
+    /// quote means the pool is dry; an unreachable quote doesn't block.
+    async fn check_liquidity(&self, amount: f64, token: &str) -> Result<(), String> {
+        if self.simulate {
+            return Ok(());
+        }
+        let is_token_to_eth = !token.eq_ignore_ascii_case("ETH");
+        match self.get_swap_quote(amount, is_token_to_eth).await {
+            Ok(out) if out < MIN_QUOTE_OUT => Err(insufficient_liquidity(&self.fmt_amount(amount), token)),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::warn!("Liquidity check skipped: {}", e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Quote a swap, then re-quote just before executing it. Returns the
+    /// minimum output to enforce, or the reply to send when the pool is dry,
+    /// unreachable, or the price slid past the slippage tolerance meanwhile.
+    async fn swap_min_out(&self, amount: f64, token: &str) -> Result<f64, String> {
+        let is_token_to_eth = !token.eq_ignore_ascii_case("ETH");
+        let unavailable = |e: String| {
+            tracing::warn!("Swap quote failed: {}", e);
+            "Can't get a price right now. Try again later.".to_string()
+        };
+
+        let quoted = self.get_swap_quote(amount, is_token_to_eth).await.map_err(unavailable)?;
+        if quoted < MIN_QUOTE_OUT {
+            return Err(insufficient_liquidity(&self.fmt_amount(amount), token));
+        }
+        let min_out = quoted * (1.0 - self.config.swap_slippage_bps as f64 / 10_000.0);
+
+        let fresh = self.get_swap_quote(amount, is_token_to_eth).await.map_err(unavailable)?;
+        if fresh < min_out {
+            tracing::info!("Swap aborted: quote moved {} -> {}", quoted, fresh);
+            return Err("Price moved, try again.".to_string());
+        }
+        Ok(min_out)
+    }
+
+    /// Unix time after which a swap submitted now should revert
+    fn swap_deadline(&self) -> i64 {
+        chrono::Utc::now().timestamp() + self.config.swap_deadline_secs as i64
+    }
+
+    async fn swap_response(&self, from: &str, amount: f64, token: &str) -> String {
+        // Check if user has wallet
+        let Some(ref user_repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        // Get user's wallet address
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => { return "No wallet. Reply JOIN first.".to_string(); },
+            Err(_) => { return "Error. Try later.".to_string(); },
+        };
+
+        if let Err(msg) = self.submit_swap(&user, amount, token).await {
+            return msg;
+        }
+
+        // Respond immediately - don't wait for swap to complete
+        // Backend will send SMS notification when swap completes
+        format!(
+            "Swapping {} {}...\n\nYou'll get an SMS when complete.\n\nThis may take 30 seconds.",
+            self.fmt_amount(amount), token
+        )
+    }
+
+    /// Check the quote and hand a swap to the backend without waiting for it;
+    /// the backend texts the user when it completes. Errors carry the SMS reply.
+    async fn submit_swap(&self, user: &User, amount: f64, token: &str) -> Result<(), String> {
+        let min_out = if self.simulate { 0.0 } else { self.swap_min_out(amount, token).await? };
+
+        // Call Contract API to swap tokens (async - don't wait for completion)
+        let client = reqwest::Client::new();
+        let api_url = &format!("{}/api/swap", self.backend_url);
+        
+        tracing::info!("Initiating swap of {} {} for user {}", amount, token, user.wallet_address);
+        
+        // Send request with user phone for SMS notification
+        let response = client
+            .post(api_url)
+            .json(&serde_json::json!({
+                "userAddress": user.wallet_address,
+                "tokenAmount": amount.to_string(),
+                "minEthOut": format_token_amount(min_out),
+                "slippageBps": self.config.swap_slippage_bps,
+                "deadline": self.swap_deadline(),
+                "userPhone": user.phone
+            }))
+            .timeout(std::time::Duration::from_secs(2))
+            .send()
+            .await;
+        // Only a swap the backend confirmed accepting goes in HISTORY
+        if let Some(result) = accepted(response).await {
+            self.record_transaction(&user.phone, "swap", amount, token, Some("ETH"), result["txHash"].as_str()).await;
+        }
+        Ok(())
+    }
+
+    async fn cashout_response(&self, from: &str, amount: f64, token: &str, to: Option<&str>) -> String {
+        let Some(ref user_repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        let token_upper = token.to_uppercase();
+        // TXTC cashouts are swapped through the pool first
+        if token_upper == "TXTC" {
+            if let Err(msg) = self.check_liquidity(amount, &token_upper).await {
+                return msg;
+            }
+        }
+
+        // Checked when parsed (and loaded); the configured default always has a chain
+        let currency = to.unwrap_or(&self.config.cashout_currency);
+        let chain = settlement_chain(currency).unwrap_or("Arc");
+
+        let client = reqwest::Client::new();
+
+        tracing::info!("Cashout: {} {} to {} on {} for {} ({})", amount, token_upper, currency, chain, from, user.wallet_address);
+
+        // Call arc-service cashout endpoint
+        let response = client
+            .post(&format!("{}/api/arc/cashout", self.arc_url))
+            .json(&serde_json::json!({
+                "phone": from,
+                "userAddress": user.wallet_address,
+                "txtcAmount": amount.to_string(),
+                "token": token_upper,
+                "slippageBps": self.config.swap_slippage_bps,
+                "deadline": self.swap_deadline(),
+                "settlementCurrency": currency,
+                "settlementChain": chain.to_lowercase()
+            }))
+            .timeout(std::time::Duration::from_secs(5))
+            .send()
+            .await;
+        let Some(result) = accepted(response).await else {
+            // Not an "ok" outcome, so no CASHOUT cooldown starts
+            return "Cashout failed. Try later.".to_string();
+        };
+        self.record_transaction(from, "cashout", amount, &token_upper, Some(currency), result["txHash"].as_str()).await;
+
+        templates::render(
+            &self.config.cashout_ack_template,
+            &[
+                ("amount", &self.fmt_amount(amount)),
+                ("token", &token_upper),
+                ("recipient", &user.wallet_address),
+                ("eta", "1-2 minutes"),
+                ("currency", currency),
+                ("chain", chain),
+            ],
+        )
+    }
+
+    async fn bridge_response(&self, from: &str, amount: f64, token: &str, from_chain: &str, to_chain: &str, to_token: &str) -> String {
+        let Some(ref user_repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        let Some(source) = Chain::from_input(from_chain) else {
+            return format!("Unknown chain: {}\n\nAvailable: polygon, base, eth, arb", from_chain);
+        };
+        let Some(destination) = Chain::from_input(to_chain) else {
+            return format!("Unknown chain: {}\n\nAvailable: polygon, base, eth, arb", to_chain);
+        };
+
+        let client = reqwest::Client::new();
+
+        tracing::info!(
+            "Bridge: {} {} from {} to {} as {} for {}",
+            amount, token, from_chain, to_chain, to_token, user.wallet_address
+        );
+
+        let body = serde_json::json!({
+            "fromChain": from_chain.to_lowercase(),
+            "toChain": to_chain.to_lowercase(),
+            "fromChainId": source.chain_id(),
+            "toChainId": destination.chain_id(),
+            "fromToken": token,
+            "toToken": to_token,
+            "amount": amount.to_string(),
+            "userAddress": user.wallet_address,
+            "userPhone": from
+        });
+
+        // Block up front when the user can't pay the source-chain fee; an
+        // unavailable estimate doesn't block
+        let fee = match self.estimate_bridge_fee(&body).await {
+            Ok(fee) => Some(fee),
+            Err(e) => {
+                tracing::warn!("Bridge fee estimate skipped: {}", e);
+                None
+            }
+        };
+        if let Some(ref fee) = fee {
+            // Fee paid in the bridged token comes on top of the amount
+            let needed = if fee.token.eq_ignore_ascii_case(token) { amount + fee.amount } else { fee.amount };
+            if let Some(balance) = fee.balance.filter(|balance| *balance < needed) {
+                return format!(
+                    "Not enough {} on {} for the bridge fee.\nNeeds {} {} (fee {}), you have {}.",
+                    fee.token,
+                    source.name(),
+                    self.fmt_amount(needed),
+                    fee.token,
+                    self.fmt_amount(fee.amount),
+                    self.fmt_amount(balance)
+                );
+            }
+        }
+
+        let request = client
+            .post(&format!("{}/api/bridge", self.backend_url))
+            .json(&body)
+            .timeout(std::time::Duration::from_secs(5));
+        let response = http::send(request).await;
+
+        match response {
+            Ok(resp) => {
+                if let Ok(result) = resp.json::<serde_json::Value>().await {
+                    if result["success"].as_bool().unwrap_or(false) {
+                        self.record_transaction(from, "bridge", amount, token, Some(destination.name()), result["txHash"].as_str())
+                            .await;
+                        let route = result["route"].as_str().unwrap_or("");
+                        let fee_line = fee
+                            .map(|fee| format!("Fee: {} {}\n", self.fmt_amount(fee.amount), fee.token))
+                            .unwrap_or_default();
+                        templates::render(
+                            &self.config.bridge_ack_template,
+                            &[
+                                ("amount", &self.fmt_amount(amount)),
+                                ("token", &token.to_uppercase()),
+                                ("recipient", destination.name()),
+                                ("eta", result["estimatedTime"].as_str().unwrap_or("a few minutes")),
+                                ("route", route),
+                                ("fee", &fee_line),
+                            ],
+                        )
+                    } else {
+                        let err = result["error"].as_str().unwrap_or("Unknown error");
+                        format!("❌ Bridge failed: {}", err)
+                    }
+                } else {
+                    "Bridge initiated. You'll get an SMS when complete.".to_string()
+                }
+            }
+            Err(HttpError::RateLimited { retry_after }) => busy_reply(retry_after),
+            Err(e) => {
+                tracing::error!("Bridge API error: {}", e);
+                "Bridge service unavailable. Try later.".to_string()
+            }
+        }
+    }
+
+    /// Backend estimate of the bridge fee for `body` (the /api/bridge request)
+    async fn estimate_bridge_fee(&self, body: &serde_json::Value) -> Result<BridgeFee, String> {
+        let request = reqwest::Client::new()
+            .post(format!("{}/api/bridge/estimate", self.backend_url))
+            .json(body)
+            .timeout(std::time::Duration::from_secs(5));
+        let json: serde_json::Value = http::send(request)
+            .await
+            .map_err(|e| format!("Estimate request failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid estimate response: {}", e))?;
+
+        if !json["success"].as_bool().unwrap_or(false) {
+            return Err(json["error"].as_str().unwrap_or("Estimate failed").to_string());
+        }
+        let number = |field: &serde_json::Value| field.as_str().and_then(|s| s.parse().ok()).or_else(|| field.as_f64());
+        Ok(BridgeFee {
+            amount: number(&json["fee"]).ok_or_else(|| "Invalid fee amount".to_string())?,
+            token: json["feeToken"].as_str().ok_or_else(|| "Missing fee token".to_string())?.to_uppercase(),
+            balance: number(&json["feeTokenBalance"]),
+        })
+    }
+
+    async fn save_response(&self, from: &str, name: &str, contact: &SavedContact) -> String {
+        let Some(ref address_book) = self.address_book_repo else {
+            return "Address book offline.".to_string();
+        };
+
+        let saved = match contact {
+            SavedContact::Phone(phone) => address_book.add_contact(from, name, Some(phone), None).await,
+            SavedContact::Address(address) | SavedContact::Ens(address) => {
+                address_book.add_contact(from, name, None, Some(address)).await
+            }
+        };
+        match (saved, contact) {
+            (Ok(_), SavedContact::Address(address)) => {
+                format!("Saved {}...{} as {}.", &address[..6], &address[38..], name)
+            }
+            (Ok(_), SavedContact::Phone(saved) | SavedContact::Ens(saved)) => format!("Saved {} as {}.", saved, name),
+            (Err(_), _) => "Error saving contact.".to_string(),
+        }
+    }
+
+    async fn save_all_response(&self, from: &str, contacts: &[(String, String)]) -> String {
+        let Some(ref address_book) = self.address_book_repo else {
+            return "Address book offline.".to_string();
+        };
+
+        let mut failed = Vec::new();
+        for (name, phone) in contacts {
+            let Some(phone) = normalize_phone(phone) else {
+                failed.push(format!("{} (invalid phone)", name));
+                continue;
+            };
+            if let Err(e) = address_book.add_contact(from, name, Some(&phone), None).await {
+                tracing::error!("Failed to save contact {}: {}", name, e);
+                failed.push(format!("{} (error)", name));
+            }
+        }
+
+        let saved = contacts.len() - failed.len();
+        let mut reply = format!("Saved {} of {} contacts.", saved, contacts.len());
+        if !failed.is_empty() {
+            reply.push_str(&format!("\nFailed: {}", failed.join(", ")));
+        }
+        reply
+    }
+
+    async fn contacts_response(&self, from: &str, limit: Option<usize>) -> String {
+        let Some(ref address_book) = self.address_book_repo else {
+            return "Address book offline.".to_string();
+        };
+
+        match address_book.list_all(from).await {
+            Ok(contacts) if contacts.is_empty() => {
+                "No contacts yet.\n\nSAVE <name> <phone>".to_string()
+            }
+            Ok(contacts) => {
+                let shown = contacts.len().min(self.page_size(limit));
+                let mut reply = "Contacts:".to_string();
+                for (i, contact) in contacts.iter().take(shown).enumerate() {
+                    let line = contact.render(&self.config.contact_template);
+                    // Leave room for the "+N more" footer
+                    if reply.len() + 1 + line.len() > MAX_LIST_CHARS - 12 {
+                        reply.push_str(&format!("\n+{} more", contacts.len() - i));
+                        return reply;
+                    }
+                    reply.push('\n');
+                    reply.push_str(&line);
+                }
+                reply
+            }
+            Err(_) => "Error loading contacts.".to_string(),
+        }
+    }
+
+    /// PAYABLE: contacts a SEND would reach, i.e. with a saved address or a
+    /// phone registered on TextChain
+    async fn payable_response(&self, from: &str, limit: Option<usize>) -> String {
+        let Some(ref address_book) = self.address_book_repo else {
+            return "Address book offline.".to_string();
+        };
+        let contacts = match address_book.list_all(from).await {
+            Ok(contacts) => contacts,
+            Err(_) => return "Error loading contacts.".to_string(),
+        };
+
+        let mut payable = Vec::new();
+        for contact in &contacts {
+            if let Some(address) = self.contact_address(contact).await {
+                let short = if address.len() == 42 {
+                    format!("{}...{}", &address[..6], &address[38..])
+                } else {
+                    address
+                };
+                payable.push(format!("{}: {}", contact.name, short));
+            }
+        }
+        if payable.is_empty() {
+            return "No contacts can receive yet.\nSAVE <name> <phone> for someone on TextChain.".to_string();
+        }
+
+        let limit = self.page_size(limit);
+        let mut reply = format!("Can receive:\n{}", payable.iter().take(limit).cloned().collect::<Vec<_>>().join("\n"));
+        if payable.len() > limit {
+            let max = self.config.max_page_size.max(1);
+            if limit < max {
+                reply.push_str(&format!("\nReply PAYABLE {} for more.", max));
+            } else {
+                reply.push_str(&format!("\n+{} more", payable.len() - limit));
+            }
+        }
+        reply
+    }
+
+    /// A contact's saved `wallet_address` as an address: as is, or where the
+    /// ENS name it was saved as points now (None if nowhere)
+    async fn saved_address(&self, saved: &str) -> Result<Option<String>, String> {
+        if saved.starts_with("0x") {
+            return Ok(Some(saved.to_string()));
+        }
+        self.lookups.ens.get_or_load(saved, self.resolve_ens(saved)).await
+    }
+
+    /// Where a SEND to this contact would go: its saved address (ENS names
+    /// resolved), or the TextChain wallet of its phone when registered
+    async fn contact_address(&self, contact: &crate::db::Contact) -> Option<String> {
+        match (&contact.wallet_address, &contact.contact_phone, &self.user_repo) {
+            (Some(saved), _, _) => self.saved_address(saved).await.unwrap_or_else(|e| {
+                tracing::debug!("Could not resolve contact {}: {}", saved, e);
+                None
+            }),
+            (None, Some(phone), Some(user_repo)) => {
+                user_repo.find_by_phone(phone).await.ok().flatten().map(|u| u.wallet_address)
+            }
+            _ => None,
+        }
+    }
+
+    async fn contact_response(&self, from: &str, name: &str) -> String {
+        let Some(ref address_book) = self.address_book_repo else {
+            return "Address book offline.".to_string();
+        };
+
+        let matches = match address_book.find_by_name(from, name).await {
+            Ok(matches) => matches,
+            Err(_) => return "Error loading contacts.".to_string(),
+        };
+        // An exact name wins over partial matches (ALI vs ALICE)
+        let contact = match matches.iter().find(|c| c.name.eq_ignore_ascii_case(name)) {
+            Some(exact) => exact,
+            None => match matches.as_slice() {
+                [] => return format!("No contact named {}.\nReply CONTACTS to list.", name),
+                [only] => only,
+                several => {
+                    let names: Vec<&str> = several.iter().map(|c| c.name.as_str()).collect();
+                    return format!(
+                        "Several contacts match {}:\n{}\nReply CONTACT <full name>.",
+                        name,
+                        names.join(", ")
+                    );
+                }
+            },
+        };
+
+        let address = self.contact_address(contact).await;
+
+        let mut lines = vec![contact.name.clone()];
+        if let Some(ref phone) = contact.contact_phone {
+            lines.push(format!("Phone: {}", mask_phone(phone)));
+        }
+        let ens = contact.wallet_address.as_deref().filter(|saved| !saved.starts_with("0x"));
+        if let Some(name) = ens {
+            lines.push(format!("ENS: {}", name));
+        }
+        match address {
+            Some(ref address) => {
+                lines.push(format!("Address: {}", address));
+                // Where SENDs settle, same as BALANCE
+                let chain = Chain::from_input(&self.config.send_chain).map(|c| c.name().to_string());
+                lines.push(format!("Chain: {}", chain.unwrap_or_else(|| self.config.send_chain.clone())));
+            }
+            None if ens.is_some() => lines.push("Address: name doesn't resolve".to_string()),
+            None => lines.push("Address: not on TextChain".to_string()),
+        }
+
+        let last_paid = match (address, &self.transfer_repo) {
+            (Some(address), Some(repo)) => repo.last_to(from, &address).await.ok().flatten(),
+            _ => None,
+        };
+        lines.push(match last_paid {
+            Some((amount, token, at)) => {
+                format!("Last paid: {} {} on {}", self.fmt_amount(amount), token, at.format("%Y-%m-%d"))
+            }
+            None => "Last paid: never".to_string(),
+        });
+        lines.join("\n")
+    }
+
+    async fn chain_response(&self, from: &str, chain_input: &str) -> String {
+        let Some(chain) = Chain::from_input(chain_input) else {
+            return format!(
+                "Unknown chain: {}\n\nAvailable: polygon, base, eth, arb",
+                chain_input
+            );
+        };
+
+        // For now, just acknowledge - could save preference to DB
+        format!(
+            "Switched to {}!\n\nChain ID: {}\nNative: {}",
+            chain.name(),
+            chain.chain_id(),
+            chain.native_token()
+        )
+    }
+
+    fn unknown_response(&self, text: &str) -> String {
+        if text.is_empty() {
+            "Welcome to TextChain!\n\nReply COMMANDS for help.".to_string()
+        } else {
+            format!(
+                "Unknown: {}\n\nReply COMMANDS for help.",
+                text.chars().take(15).collect::<String>()
+            )
+        }
+    }
+}
 
 impl std::fmt::Debug for CommandProcessor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -1020,65 +4603,2813 @@ impl std::fmt::Debug for CommandProcessor {
             .field("has_deposits", &self.deposit_repo.is_some())
             .finish()
     }
-}
+}
+
+/// Stored form of a PIN
+fn hash_pin(pin: &str) -> String {
+    // Simple hash for demo (use bcrypt in production)
+    format!("{:x}", sha2::Sha256::digest(pin.as_bytes()))
+}
+
+fn insufficient_liquidity(amount: &str, token: &str) -> String {
+    format!(
+        "Insufficient liquidity for {} {}.\nTry a smaller amount or later.",
+        amount,
+        token.to_uppercase()
+    )
+}
+
+/// Reply for a send the backend refused for lack of funds: native gas
+/// ("insufficient funds for gas * price + value") is told apart from the
+/// token itself so the user knows whether to swap or top up. None if the
+/// error isn't about funds.
+fn balance_failure(error: &str, token: &str) -> Option<String> {
+    let error = error.to_lowercase();
+    let out_of_gas = ["insufficient funds", "for gas", "gas required exceeds", "intrinsic transaction cost"]
+        .iter()
+        .any(|needle| error.contains(needle));
+    if out_of_gas && !token.eq_ignore_ascii_case("ETH") {
+        return Some("Not enough ETH for gas.\n\nReply SWAP 5 TXTC to get some, then try again.".to_string());
+    }
+    if out_of_gas || error.contains("insufficient") || error.contains("balance") {
+        return Some(format!(
+            "Insufficient {} balance.\n\nReply BALANCE to check, or REDEEM a voucher to add funds.",
+            token.to_uppercase()
+        ));
+    }
+    None
+}
+
+/// Whether an ENS name contains a blocked word, also when spelled with
+/// look-alike digits (4DM1N, 0ff1c1al)
+fn is_blocked_name(name: &str, blocked: &[String]) -> bool {
+    fn unleet(text: &str) -> String {
+        text.chars()
+            .map(|c| match c.to_ascii_lowercase() {
+                '0' => 'o',
+                '1' => 'i',
+                '3' => 'e',
+                '4' => 'a',
+                '5' => 's',
+                '7' => 't',
+                '8' => 'b',
+                c => c,
+            })
+            .collect()
+    }
+    let name = unleet(name);
+    blocked.iter().any(|word| !word.is_empty() && name.contains(&unleet(word)))
+}
+
+/// Body of a fire-and-forget backend call that answered `"success": true`;
+/// None when it failed, timed out or didn't say
+async fn accepted(response: reqwest::Result<reqwest::Response>) -> Option<serde_json::Value> {
+    let result: serde_json::Value = response.ok()?.json().await.ok()?;
+    result["success"].as_bool().unwrap_or(false).then_some(result)
+}
+
+/// Reply when a service is rate limiting us
+fn busy_reply(retry_after: Option<std::time::Duration>) -> String {
+    match retry_after {
+        Some(wait) => format!("Busy right now. Try again in {}.", format_wait(wait.max(std::time::Duration::from_secs(1)))),
+        None => "Busy right now. Try again in a minute.".to_string(),
+    }
+}
+
+/// Reply while PIN entry is locked out until `until`
+fn pin_locked_reply(until: chrono::DateTime<chrono::Utc>) -> String {
+    let left = (until - chrono::Utc::now()).to_std().unwrap_or_default();
+    format!("Too many wrong PINs. Try again in {}.", format_wait(left.max(std::time::Duration::from_secs(1))))
+}
+
+/// Most decimal places an amount may have: no token we handle goes finer
+const MAX_AMOUNT_DECIMALS: usize = 18;
+
+/// Amount as users type it: "10", "10.50", "$10.50", "1,000". One leading
+/// currency symbol is dropped and commas must group thousands. Negative,
+/// exponent, NaN/inf and over-precise amounts are None.
+fn parse_amount(s: &str) -> Option<f64> {
+    let digits = match s.chars().next().and_then(currency_for_symbol) {
+        Some(_) => &s[s.chars().next()?.len_utf8()..],
+        None => s,
+    };
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (digits, None),
+    };
+
+    let mut groups = whole.split(',');
+    let first = groups.next()?;
+    let grouped = whole.contains(',');
+    if first.is_empty() && (fraction.is_none() || grouped) {
+        return None; // "", "$", ",000"
+    }
+    if !first.chars().all(|c| c.is_ascii_digit()) || (grouped && first.len() > 3) {
+        return None;
+    }
+    if !groups.all(|g| g.len() == 3 && g.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    if let Some(fraction) = fraction {
+        if fraction.is_empty() || fraction.len() > MAX_AMOUNT_DECIMALS || !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+    }
+
+    digits.replace(',', "").parse::<f64>().ok().filter(|a| a.is_finite())
+}
+
+/// Decimal string for the backend's parseEther. 12 places keeps it well under
+/// the 18 it accepts while hiding f64 noise (1.98, not 1.979999999999999982).
+fn format_token_amount(amount: f64) -> String {
+    let s = format!("{:.12}", amount);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::create_shared_provider;
+    use crate::price::CrossCheckedPriceSource;
+    use crate::test_support::random_phone;
+
+    fn test_processor() -> CommandProcessor {
+        CommandProcessor::new(None, create_shared_provider())
+    }
+
+    #[test]
+    fn test_parse_help() {
+        let processor = test_processor();
+        assert_eq!(processor.parse("COMMANDS"), Command::Help);
+        assert_eq!(processor.parse("menu"), Command::Help);
+        assert_eq!(processor.parse("?"), Command::Help);
+        assert_eq!(processor.parse("HELP"), Command::Help);
+        assert_eq!(processor.parse("help send"), Command::HelpTopic { topic: "SEND".to_string() });
+        assert_eq!(processor.parse("HELP SEND?"), Command::HelpTopic { topic: "SEND".to_string() });
+    }
+
+    #[tokio::test]
+    async fn test_help_topic_is_focused() {
+        let mut processor = test_processor();
+        let from = "+15550003333";
+
+        let send = processor.process(from, "HELP SEND").await;
+        assert!(send.starts_with("SEND <amount> <token> [TO] <recipient>"), "{}", send);
+        assert!(send.contains("Ex: SEND 10 TXTC TO alice.ttcip.eth"), "{}", send);
+        assert!(!send.contains("SWAP") && !send.contains("BRIDGE"), "{}", send);
+        assert!(HELP_TOPICS.iter().all(|(_, text)| text.len() <= MAX_LIST_CHARS));
+
+        assert!(processor.process(from, "HELP").await.ends_with("Reply HELP <command> for details."));
+        assert!(processor.process(from, "HELP save").await.starts_with("CONTACTS [count]"));
+        assert_eq!(processor.process(from, "HELP PIZZA").await, "No help for PIZZA.\nReply HELP for the command list.");
+
+        processor.config.disabled_commands = std::collections::HashMap::from([("*".to_string(), vec!["BRIDGE".to_string()])]);
+        assert_eq!(
+            processor.process(from, "HELP BRIDGE").await,
+            "BRIDGE isn't available in your region.\nReply MENU for commands."
+        );
+    }
+
+    #[test]
+    fn test_parse_ignores_trailing_punctuation() {
+        let processor = test_processor();
+        assert_eq!(processor.parse("BALANCE."), Command::Balance);
+        assert_eq!(processor.parse("HELP!"), Command::Help);
+        assert_eq!(processor.parse("bal?!"), Command::Balance);
+        assert_eq!(processor.parse("?"), Command::Help);
+        assert_eq!(processor.parse("JOIN, alice"), Command::Join { ens_name: Some("alice".to_string()) });
+
+        // Decimal amounts are untouched
+        assert!(matches!(processor.parse("SEND. 0.5 TXTC TO bob"), Command::Send { amount, .. } if amount == 0.5));
+        assert!(matches!(processor.parse("SWAP 0.5 TXTC"), Command::Swap { amount, .. } if amount == 0.5));
+    }
+
+    #[test]
+    fn test_parse_carrier_keywords() {
+        let processor = test_processor();
+        assert_eq!(processor.parse("STOP"), Command::Stop);
+        assert_eq!(processor.parse("unsubscribe"), Command::Stop);
+        assert_eq!(processor.parse("UNSTOP"), Command::Start);
+        // CANCEL alone may be an opt-out; with a code it only cancels
+        assert_eq!(processor.parse("cancel"), Command::CancelOrStop);
+        assert_eq!(processor.parse("CANCEL A1B2C3"), Command::Cancel { reference: Some("A1B2C3".to_string()) });
+        assert_eq!(processor.parse("NO"), Command::Cancel { reference: None });
+        assert_eq!(processor.parse("start"), Command::Start);
+        // START with a name is still JOIN
+        assert_eq!(processor.parse("START alice"), Command::Join { ens_name: Some("alice".to_string()) });
+    }
+
+    #[tokio::test]
+    async fn test_stop_suppresses_replies_until_start() {
+        let Some((processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        let processor = processor.simulated();
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        assert!(processor.process(&phone, "STOP").await.starts_with("You're unsubscribed"));
+        assert!(processor.is_opted_out(&phone).await);
+        assert_eq!(processor.process(&phone, "BALANCE").await, "");
+        assert_eq!(processor.process(&phone, "SEND 1 TXTC TO bob").await, "");
+        // HELP is always answered
+        assert!(processor.process(&phone, "HELP").await.contains("STOP"));
+
+        assert!(processor.process(&phone, "START").await.starts_with("You're subscribed"));
+        assert!(!processor.is_opted_out(&phone).await);
+        assert!(processor.process(&phone, "MENU").await.starts_with("Text-to-Chain Commands"));
+        // START from a subscribed user is the usual JOIN welcome
+        assert!(processor.process(&phone, "START").await.starts_with("Welcome back!"));
+    }
+
+    #[tokio::test]
+    async fn test_bare_cancel_opts_out_only_when_nothing_is_pending() {
+        let Some((mut processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        processor.config.confirm_by_keyword = false;
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        let kind = PendingKind::Send { amount: 5.0, token: "TXTC".to_string(), recipient: "bob".to_string(), note: None };
+        let code = processor.pending.add(&phone, kind).await.unwrap();
+        assert_eq!(processor.process(&phone, "CANCEL").await, "Usage: CANCEL <code>");
+        assert!(!processor.is_opted_out(&phone).await);
+
+        assert!(!processor.process(&phone, &format!("CANCEL {}", code)).await.starts_with("You're unsubscribed"));
+        assert!(processor.process(&phone, "CANCEL").await.starts_with("You're unsubscribed"));
+        assert!(processor.is_opted_out(&phone).await);
+    }
+
+    #[tokio::test]
+    async fn test_start_from_new_number_joins() {
+        let Some((processor, _pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        let processor = processor.simulated();
+        let phone = random_phone();
+        assert!(processor.process(&phone, "START").await.starts_with("Wallet created!"));
+    }
+
+    #[test]
+    fn test_parse_join() {
+        let processor = test_processor();
+        assert_eq!(processor.parse("JOIN"), Command::Join { ens_name: None });
+        assert_eq!(processor.parse("JOIN john"), Command::Join { ens_name: Some("john".to_string()) });
+        assert_eq!(processor.parse("register"), Command::Join { ens_name: None });
+    }
+
+    #[test]
+    fn test_parse_balance() {
+        let processor = test_processor();
+        assert_eq!(processor.parse("BALANCE"), Command::Balance);
+        assert_eq!(processor.parse("bal"), Command::Balance);
+    }
+
+    #[test]
+    fn test_parse_send() {
+        let processor = test_processor();
+        
+        let cmd = processor.parse("SEND 10 USDC TO +917123456789");
+        assert!(matches!(cmd, Command::Send { amount, token, recipient, note: None, pin: None } 
+            if amount == 10.0 && token == "USDC" && recipient == "+917123456789"));
+    }
+
+    #[test]
+    fn test_parse_buy_checks_range_and_decimals() {
+        let mut processor = test_processor();
+        assert_eq!(processor.parse("BUY 10.50"), Command::Buy { amount: 10.5 });
+        assert_eq!(
+            processor.parse("BUY 0.5"),
+            Command::invalid("BUY", ParseError::InvalidAmount, "BUY amount must be €1 to €100.\nExample: BUY 10")
+        );
+        assert_eq!(
+            processor.parse("BUY 1000000"),
+            Command::invalid("BUY", ParseError::InvalidAmount, "BUY amount must be €1 to €100.\nExample: BUY 10")
+        );
+        assert_eq!(
+            processor.parse("BUY 10.555"),
+            Command::invalid("BUY", ParseError::InvalidAmount, "BUY takes up to 2 decimal places.\nExample: BUY 10")
+        );
+        assert!(matches!(processor.parse("BUY inf"), Command::Invalid { reason: ParseError::InvalidAmount, .. }));
+
+        // Markets selling whole units only
+        processor.config.buy_decimals = 0;
+        assert_eq!(processor.parse("BUY 10"), Command::Buy { amount: 10.0 });
+        assert_eq!(
+            processor.parse("BUY 10.5"),
+            Command::invalid("BUY", ParseError::InvalidAmount, "BUY takes whole euros only.\nExample: BUY 10")
+        );
+    }
+
+    #[test]
+    fn test_parse_language_synonyms() {
+        let mut processor = test_processor();
+        assert!(matches!(processor.parse("SOLDE"), Command::Unknown(_)));
+
+        processor.config.languages = vec!["fr".to_string()];
+        assert_eq!(processor.parse("solde."), Command::Balance);
+        assert_eq!(
+            processor.parse("ENVOYER 10 TXTC alice.ttcip.eth"),
+            Command::Send { amount: 10.0, token: "TXTC".to_string(), recipient: "alice.ttcip.eth".to_string(), note: None, pin: None }
+        );
+        assert_eq!(processor.parse("Aide SEND"), Command::HelpTopic { topic: "SEND".to_string() });
+        assert_eq!(processor.parse("OUI"), Command::Confirm { reference: None });
+        assert!(matches!(processor.parse("SALDO"), Command::Unknown(_)));
+
+        // English keeps working alongside
+        assert_eq!(processor.parse("BALANCE"), Command::Balance);
+        assert_eq!(
+            processor.parse("SEND 10 TXTC TO alice.ttcip.eth"),
+            Command::Send { amount: 10.0, token: "TXTC".to_string(), recipient: "alice.ttcip.eth".to_string(), note: None, pin: None }
+        );
+    }
+
+    #[test]
+    fn test_parse_portuguese_synonyms() {
+        let mut processor = test_processor();
+        processor.config.languages = vec!["pt".to_string()];
+        assert_eq!(processor.parse("Saldo"), Command::Balance);
+        assert_eq!(
+            processor.parse("ENVIAR 5 TXTC bob.ttcip.eth"),
+            Command::Send { amount: 5.0, token: "TXTC".to_string(), recipient: "bob.ttcip.eth".to_string(), note: None, pin: None }
+        );
+        assert_eq!(processor.parse("sacar 10 TXTC"), Command::Cashout { amount: 10.0, token: "TXTC".to_string(), to: None, pin: None });
+        assert!(matches!(processor.parse("SOLDE"), Command::Unknown(_)));
+    }
+
+    #[tokio::test]
+    async fn test_replies_follow_the_users_language() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let url = crate::test_support::spawn_mock_backend(balance_backend(seen)).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.languages = vec!["fr".to_string(), "pt".to_string()];
+        let phone = random_phone();
+        let users = UserRepository::new(pool);
+        users.seed_user(&phone).await.unwrap();
+
+        assert!(processor.process(&phone, "BALANCE").await.starts_with("Balance:\n12.5 TXTC"));
+        assert_eq!(users.language(&phone).await.unwrap(), None);
+
+        assert!(processor.process(&phone, "saldo").await.starts_with("Saldo:\n12.5 TXTC"));
+        assert_eq!(users.language(&phone).await.unwrap().as_deref(), Some("pt"));
+        // Words without a Portuguese version don't switch back to English
+        assert!(!processor.process(&phone, "CONTACTS").await.is_empty());
+        assert_eq!(users.language(&phone).await.unwrap().as_deref(), Some("pt"));
+
+        assert!(processor.process(&phone, "SOLDE").await.starts_with("Solde :\n12.5 TXTC"));
+        assert!(processor.process(&phone, "BALANCE").await.starts_with("Balance:\n12.5 TXTC"));
+        assert_eq!(users.language(&phone).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_command_word_recipient_is_flagged() {
+        let mut processor = test_processor();
+        assert_eq!(
+            processor.process("+15550000001", "SEND 10 TXTC BALANCE").await,
+            "BALANCE is a command, not a recipient.\nTo run it, text BALANCE on its own.\nTo send: SEND 10 TXTC TO <name or number>"
+        );
+        let reply = processor.process("+15550000001", "send 2 eth to help.").await;
+        assert!(reply.starts_with("HELP is a command, not a recipient."), "{}", reply);
+
+        // Synonyms count once their language is on; ordinary names still resolve
+        assert_eq!(processor.reserved_recipient("solde"), None);
+        processor.config.languages = vec!["fr".to_string()];
+        assert_eq!(processor.reserved_recipient("solde"), Some("SOLDE".to_string()));
+        assert_eq!(processor.reserved_recipient("alice"), None);
+        assert_eq!(processor.reserved_recipient("balance.ttcip.eth"), None);
+    }
+
+    #[test]
+    fn test_parse_send_note_and_tag() {
+        let processor = test_processor();
+        assert_eq!(
+            processor.parse("SEND 10 TXTC TO alice NOTE March rent"),
+            Command::Send {
+                amount: 10.0,
+                token: "TXTC".to_string(),
+                recipient: "alice".to_string(),
+                note: Some("March rent".to_string()),
+                pin: None,
+            }
+        );
+        assert!(matches!(processor.parse("SEND 10 TXTC TO alice NOTE"), Command::Invalid { reason: ParseError::MissingArgument, .. }));
+
+        assert_eq!(
+            processor.parse("TAG alice Rent money"),
+            Command::Tag { payee: "alice".to_string(), tag: Some("Rent money".to_string()) }
+        );
+        assert_eq!(processor.parse("tag alice off"), Command::Tag { payee: "alice".to_string(), tag: None });
+        assert!(matches!(processor.parse("TAG alice"), Command::Invalid { reason: ParseError::MissingArgument, .. }));
+        assert!(matches!(processor.parse("TAG"), Command::Invalid { reason: ParseError::Usage, .. }));
+        assert!(matches!(processor.parse(&format!("TAG alice {}", "x".repeat(65))), Command::Invalid { .. }));
+    }
+
+    #[test]
+    fn test_parse_inline_pin() {
+        let processor = test_processor();
+        assert_eq!(
+            processor.parse("SEND 10 TXTC alice PIN 1234"),
+            Command::Send {
+                amount: 10.0,
+                token: "TXTC".to_string(),
+                recipient: "alice".to_string(),
+                note: None,
+                pin: Some("1234".to_string()),
+            }
+        );
+        assert!(matches!(
+            processor.parse("SEND 10 TXTC TO alice NOTE rent pin 1234"),
+            Command::Send { ref note, ref pin, .. } if note.as_deref() == Some("rent") && pin.as_deref() == Some("1234")
+        ));
+        assert_eq!(
+            processor.parse("CASHOUT 10 TXTC TO USDT PIN 1234"),
+            Command::Cashout { amount: 10.0, token: "TXTC".to_string(), to: Some("USDT".to_string()), pin: Some("1234".to_string()) }
+        );
+        // The PIN is never logged
+        assert_eq!(processor.parse("SEND 10 TXTC alice PIN 1234").redacted_args(), "10 TXTC to alice");
+        assert!(matches!(processor.parse("SEND 10 TXTC PIN 1234"), Command::Invalid { .. }));
+    }
+
+    #[test]
+    fn test_parse_send_token_before_amount() {
+        let processor = test_processor();
+        let send = |amount: f64, token: &str, recipient: &str| Command::Send {
+            amount,
+            token: token.to_string(),
+            recipient: recipient.to_string(),
+            note: None,
+            pin: None,
+        };
+
+        assert_eq!(processor.parse("SEND TXTC 10 alice"), send(10.0, "TXTC", "alice"));
+        assert_eq!(processor.parse("SEND ETH 0.5 TO bob.ttcip.eth"), send(0.5, "ETH", "bob.ttcip.eth"));
+        // The usual order is unchanged and wins when both arguments are numbers
+        assert_eq!(processor.parse("SEND 10 TXTC alice"), send(10.0, "TXTC", "alice"));
+        assert_eq!(processor.parse("SEND 10 20 alice"), send(10.0, "20", "alice"));
+        // Neither argument is an amount
+        assert_eq!(processor.parse("SEND TXTC ETH alice"), Command::invalid("SEND", ParseError::InvalidAmount, "Invalid amount"));
+        assert_eq!(processor.parse("SEND 0x12 10 alice"), Command::invalid("SEND", ParseError::InvalidAmount, "Invalid amount"));
+    }
+
+    #[test]
+    fn test_parse_amount_symbols_and_separators() {
+        assert_eq!(parse_amount("$10.50"), Some(10.5));
+        assert_eq!(parse_amount("€5"), Some(5.0));
+        assert_eq!(parse_amount("1,000"), Some(1000.0));
+        assert_eq!(parse_amount("1,234,567.89"), Some(1_234_567.89));
+        assert_eq!(parse_amount(".5"), Some(0.5));
+        for bad in ["-5", "1e9", "inf", "NaN", "10,50", "1,0000", "10.", "$", "", "1.0000000000000000001"] {
+            assert_eq!(parse_amount(bad), None, "{}", bad);
+        }
+
+        let processor = test_processor();
+        assert_eq!(processor.parse("SWAP $10.50 TXTC"), Command::Swap { amount: 10.5, token: "TXTC".to_string() });
+        assert!(matches!(processor.parse("SEND 1,000 TXTC TO alice"), Command::Send { amount, .. } if amount == 1000.0));
+        for bad in ["SEND -5 TXTC TO alice", "SWAP 1e9 TXTC", "CASHOUT -5 TXTC"] {
+            assert!(matches!(processor.parse(bad), Command::Invalid { reason: ParseError::InvalidAmount, .. }), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_balance_failure_tells_gas_from_token() {
+        let gas = "Not enough ETH for gas.\n\nReply SWAP 5 TXTC to get some, then try again.";
+        let txtc = "Insufficient TXTC balance.\n\nReply BALANCE to check, or REDEEM a voucher to add funds.";
+        let cases = [
+            ("insufficient funds for gas * price + value", "TXTC", Some(gas)),
+            ("insufficient funds for intrinsic transaction cost", "txtc", Some(gas)),
+            ("gas required exceeds allowance (0)", "TXTC", Some(gas)),
+            ("Insufficient balance", "TXTC", Some(txtc)),
+            ("ERC20: transfer amount exceeds balance", "TXTC", Some(txtc)),
+            ("execution reverted: ERC20InsufficientBalance", "TXTC", Some(txtc)),
+            ("Insufficient ytest.usd balance on Yellow", "TXTC", Some(txtc)),
+            ("nonce too low", "TXTC", None),
+        ];
+        for (error, token, expected) in cases {
+            assert_eq!(balance_failure(error, token).as_deref(), expected, "{}", error);
+        }
+        // Sending ETH itself, gas and value come out of the same balance
+        assert_eq!(
+            balance_failure("insufficient funds for gas * price + value", "ETH").as_deref(),
+            Some("Insufficient ETH balance.\n\nReply BALANCE to check, or REDEEM a voucher to add funds.")
+        );
+    }
+
+    #[test]
+    fn test_parse_pin() {
+        let processor = test_processor();
+        
+        let cmd = processor.parse("PIN 1234");
+        assert!(matches!(cmd, Command::Pin { new_pin: Some(pin) } if pin == "1234"));
+        
+        let cmd = processor.parse("PIN");
+        assert!(matches!(cmd, Command::Pin { new_pin: None }));
+    }
+
+    #[test]
+    fn test_parse_accept_decline() {
+        let processor = test_processor();
+        assert_eq!(processor.parse("ACCEPT"), Command::Accept { index: None });
+        assert_eq!(processor.parse("accept 2"), Command::Accept { index: Some(2) });
+        assert_eq!(processor.parse("DECLINE 1"), Command::Decline { index: Some(1) });
+        assert_eq!(processor.parse("reject"), Command::Decline { index: None });
+        assert!(matches!(processor.parse("DECLINE x"), Command::Invalid { .. }));
+        assert!(matches!(processor.parse("ACCEPT 0"), Command::Invalid { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_decline_refunds_sender() {
+        use axum::{routing::post, Json, Router};
+        use std::sync::Mutex;
+
+        let captured: Arc<Mutex<Option<serde_json::Value>>> = Arc::new(Mutex::new(None));
+        let sink = captured.clone();
+        let router = Router::new().route(
+            "/api/hold/refund",
+            post(move |Json(body): Json<serde_json::Value>| {
+                let sink = sink.clone();
+                async move {
+                    *sink.lock().unwrap() = Some(body);
+                    Json(serde_json::json!({ "success": true }))
+                }
+            }),
+        );
+
+        let mut processor = test_processor();
+        processor.backend_url = crate::test_support::spawn_mock_backend(router).await;
+
+        let transfer = InboundTransfer {
+            id: uuid::Uuid::new_v4(),
+            recipient_phone: "+15550000002".to_string(),
+            sender_phone: "+15550000001".to_string(),
+            sender_address: "0x1111111111111111111111111111111111111111".to_string(),
+            amount: 10.0,
+            token: "TXTC".to_string(),
+            hold_ref: Some("hold-1".to_string()),
+        };
+
+        assert_eq!(processor.settle_inbound(&transfer, HoldAction::Refund).await, Settlement::Paid);
+
+        // The backend pays the sender back from the hold itself
+        let body = captured.lock().unwrap().clone().expect("refund not called");
+        assert_eq!(body, serde_json::json!({ "holdId": "hold-1" }));
+    }
+
+    #[test]
+    fn test_parse_history_and_contacts_count() {
+        let processor = test_processor();
+        assert_eq!(processor.parse("HISTORY"), Command::History { limit: None });
+        assert_eq!(processor.parse("history 10"), Command::History { limit: Some(10) });
+        assert_eq!(processor.parse("CONTACTS 3"), Command::Contacts { limit: Some(3) });
+        assert!(matches!(processor.parse("HISTORY all"), Command::Invalid { .. }));
+    }
+
+    #[test]
+    fn test_page_size() {
+        let mut processor = test_processor();
+        processor.config.page_size = 4;
+        processor.config.max_page_size = 8;
+
+        // Configured default when no count is given
+        assert_eq!(processor.page_size(None), 4);
+        // Override within the cap is honored
+        assert_eq!(processor.page_size(Some(7)), 7);
+        // Override beyond the cap is clamped
+        assert_eq!(processor.page_size(Some(50)), 8);
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        let processor = test_processor();
+        
+        let cmd = processor.parse("FOOBAR");
+        assert!(matches!(cmd, Command::Unknown(_)));
+    }
+
+    #[test]
+    fn test_parse_tells_malformed_from_unknown() {
+        let processor = test_processor();
+        assert!(matches!(
+            processor.parse("SEND 10"),
+            Command::Invalid { command: "SEND", reason: ParseError::MissingRecipient, .. }
+        ));
+        assert!(matches!(
+            processor.parse("SEND"),
+            Command::Invalid { command: "SEND", reason: ParseError::MissingAmount, .. }
+        ));
+        assert!(matches!(
+            processor.parse("BUY lots"),
+            Command::Invalid { command: "BUY", reason: ParseError::InvalidAmount, .. }
+        ));
+        assert!(matches!(
+            processor.parse("HISTORY all"),
+            Command::Invalid { command: "HISTORY", reason: ParseError::InvalidCount, .. }
+        ));
+        assert_eq!(processor.parse("XYZ"), Command::Unknown("XYZ".to_string()));
+
+        let invalid = processor.parse("SEND 10");
+        assert_eq!((invalid.name(), invalid.redacted_args().as_str()), ("SEND", "missing_recipient"));
+        assert_eq!(outcome_code(&invalid, "Use: SEND"), "invalid");
+        assert_eq!(outcome_code(&processor.parse("XYZ"), "Unknown: XYZ"), "unknown");
+    }
+
+    #[tokio::test]
+    async fn test_malformed_command_replies_with_its_usage() {
+        let processor = test_processor();
+        assert_eq!(processor.process("+15550001111", "HISTORY all").await, "Usage: HISTORY [count]");
+        assert_eq!(
+            processor.process("+15550001111", "XYZ").await,
+            "Unknown: XYZ\n\nReply COMMANDS for help."
+        );
+    }
+
+    #[test]
+    fn test_parse_hold_date_clause() {
+        let processor = test_processor();
+        assert_eq!(
+            processor.parse("HOLD 20 TXTC FOR alice UNTIL 2025-12-01"),
+            Command::Hold {
+                amount: 20.0,
+                token: "TXTC".to_string(),
+                recipient: "alice".to_string(),
+                until: chrono::NaiveDate::from_ymd_opt(2025, 12, 1),
+            }
+        );
+        // UNTIL is optional; recipient keeps its case
+        assert_eq!(
+            processor.parse("escrow 5 txtc for Bob.ttcip.eth"),
+            Command::Hold {
+                amount: 5.0,
+                token: "txtc".to_string(),
+                recipient: "Bob.ttcip.eth".to_string(),
+                until: None,
+            }
+        );
+        assert_eq!(
+            processor.parse("HOLD 20 TXTC FOR alice UNTIL 12/01/2025"),
+            Command::invalid("HOLD", ParseError::InvalidDate, "Invalid date. Use YYYY-MM-DD")
+        );
+        assert!(matches!(processor.parse("HOLD 20 TXTC FOR alice UNTIL"), Command::Invalid { .. }));
+        assert!(matches!(processor.parse("HOLD 20 TXTC FOR UNTIL 2025-12-01"), Command::Invalid { .. }));
+        assert!(matches!(processor.parse("HOLD 20 TXTC alice"), Command::Invalid { .. }));
+        assert_eq!(processor.parse("release a1b2c3"), Command::Release { id: "A1B2C3".to_string() });
+    }
+
+    #[tokio::test]
+    async fn test_escrow_sweep_releases_due() {
+        use axum::{routing::post, Json, Router};
+        use std::sync::Mutex;
+
+        let Some(pool) = crate::db::test_pool().await else { return };
+
+        let captured: Arc<Mutex<Vec<serde_json::Value>>> = Arc::new(Mutex::new(Vec::new()));
+        let sink = captured.clone();
+        let router = Router::new().route(
+            "/api/hold/release",
+            post(move |Json(body): Json<serde_json::Value>| {
+                let sink = sink.clone();
+                async move {
+                    sink.lock().unwrap().push(body);
+                    Json(serde_json::json!({ "success": true }))
+                }
+            }),
+        );
+
+        let repo = EscrowRepository::new(pool);
+        let mut processor = test_processor().with_escrow_repo(repo.clone());
+        processor.backend_url = crate::test_support::spawn_mock_backend(router).await;
+
+        let payer = random_phone();
+        let due_ref = format!("hold-{}", uuid::Uuid::new_v4());
+        let later_ref = format!("hold-{}", uuid::Uuid::new_v4());
+        let recipient_address = "0x2222222222222222222222222222222222222222";
+        let now = chrono::Utc::now();
+
+        repo.create(&payer, "0x1111111111111111111111111111111111111111", "alice", recipient_address,
+            20.0, "TXTC", Some(&due_ref), Some(now - chrono::Duration::minutes(1)))
+            .await
+            .unwrap();
+        let later = repo
+            .create(&payer, "0x1111111111111111111111111111111111111111", "alice", recipient_address,
+                5.0, "TXTC", Some(&later_ref), Some(now + chrono::Duration::days(1)))
+            .await
+            .unwrap();
+
+        processor.run_escrow_sweep().await;
+        // Already released escrows are not paid twice
+        processor.run_escrow_sweep().await;
+
+        let calls = captured.lock().unwrap().clone();
+        let ours: Vec<_> = calls.iter().filter(|b| b["holdId"] == due_ref.as_str()).collect();
+        assert_eq!(ours.len(), 1);
+        assert!(!calls.iter().any(|b| b["holdId"] == later_ref.as_str()));
+
+        // An escrow that is not yet due can still be released manually
+        let reply = processor.process(&payer, &format!("RELEASE {}", later.short_id())).await;
+        assert_eq!(reply, "Released 5 TXTC to alice.");
+        let reply = processor.process(&payer, &format!("RELEASE {}", later.short_id())).await;
+        assert_eq!(reply, format!("No held payment {}.", later.short_id()));
+    }
+
+    /// Price source returning a fixed rate, or an outage when None
+    struct StubPrice(Option<f64>);
+
+    #[async_trait::async_trait]
+    impl PriceSource for StubPrice {
+        async fn price(&self, _token: &str, _currency: &str) -> Result<f64, String> {
+            self.0.ok_or_else(|| "feed down".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_price_reply() {
+        let processor = test_processor().with_price_source(Arc::new(StubPrice(Some(3210.444))));
+        assert_eq!(processor.parse("PRICE"), Command::Price { token: None });
+        assert_eq!(processor.parse("price txtc"), Command::Price { token: Some("TXTC".to_string()) });
+        assert!(matches!(processor.parse("PRICE DOGE"), Command::Invalid { reason: ParseError::Usage, .. }));
+
+        // USD without a CURRENCY preference
+        assert_eq!(processor.process("+15550100006", "PRICE ETH").await, "1 ETH = $3,210.44");
+        assert_eq!(processor.process("+15550100006", "PRICE").await, "1 ETH = $3,210.44\n1 TXTC = $3,210.44");
+
+        let down = test_processor().with_price_source(Arc::new(StubPrice(None)));
+        assert_eq!(down.process("+15550100006", "PRICE TXTC").await, "Price feed unavailable. Try later.");
+    }
+
+    #[test]
+    fn test_parse_send_fiat() {
+        let processor = test_processor();
+        let fiat = |fiat_amount: f64, currency: &str, token: &str, recipient: &str| Command::SendFiat {
+            fiat_amount,
+            currency: currency.to_string(),
+            token: token.to_string(),
+            recipient: recipient.to_string(),
+        };
+
+        assert_eq!(processor.parse("SEND $10 TO alice"), fiat(10.0, "USD", "TXTC", "alice"));
+        assert_eq!(processor.parse("send 10 usd to alice"), fiat(10.0, "USD", "TXTC", "alice"));
+        assert_eq!(processor.parse("SEND €5.50 ETH TO bob.ttcip.eth"), fiat(5.5, "EUR", "ETH", "bob.ttcip.eth"));
+        assert_eq!(processor.parse("SEND 200 KES alice"), fiat(200.0, "KES", "TXTC", "alice"));
+        assert_eq!(processor.parse("SEND $ten TO alice"), Command::invalid("SEND", ParseError::InvalidAmount, "Invalid amount"));
+        assert!(matches!(processor.parse("SEND $10 TO"), Command::Invalid { .. }));
+
+        // Token amounts are unaffected
+        assert!(matches!(processor.parse("SEND 10 TXTC TO alice"), Command::Send { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_send_fiat_conversion() {
+        // 1 TXTC = $0.25
+        let processor = test_processor().with_price_source(Arc::new(StubPrice(Some(0.25))));
+        assert_eq!(processor.fiat_to_token(10.0, "USD", "TXTC").await.unwrap(), 40.0);
+        assert_eq!(processor.fiat_to_token(1.0, "USD", "TXTC").await.unwrap(), 4.0);
+
+        // Both values are echoed ahead of the send result
+        let reply = processor.process("+15550000001", "SEND $10 TO alice").await;
+        assert_eq!(reply, "$10.00 = 40 TXTC\nDB offline. Try later.");
+
+        let down = test_processor().with_price_source(Arc::new(StubPrice(None)));
+        let reply = down.process("+15550000001", "SEND $10 TO alice").await;
+        assert!(reply.starts_with("Price feed unavailable."), "{}", reply);
+    }
+
+    #[tokio::test]
+    async fn test_send_fiat_refused_when_sources_disagree() {
+        let checked = |a, b| {
+            let source = CrossCheckedPriceSource::new(Arc::new(StubPrice(Some(a))), Arc::new(StubPrice(Some(b))), 2.0);
+            test_processor().with_price_source(Arc::new(source))
+        };
+
+        let reply = checked(0.25, 0.251).process("+15550000001", "SEND $10 TO alice").await;
+        assert_eq!(reply, "$10.00 = 40 TXTC\nDB offline. Try later.");
+
+        let reply = checked(0.25, 0.30).process("+15550000001", "SEND $10 TO alice").await;
+        assert_eq!(
+            reply,
+            "Prices are moving too fast to convert USD right now.\nSend a token amount instead:\nSEND 10 TXTC TO alice"
+        );
+
+        // With one source down the price can't be checked, so it isn't used
+        let one_down = CrossCheckedPriceSource::new(Arc::new(StubPrice(Some(0.25))), Arc::new(StubPrice(None)), 2.0);
+        let reply = test_processor()
+            .with_price_source(Arc::new(one_down))
+            .process("+15550000001", "SEND $10 TO alice")
+            .await;
+        assert!(reply.starts_with("Price feed unavailable."), "{}", reply);
+    }
+
+    /// Mock backend reporting a fixed balance and recording the address asked for
+    fn balance_backend(seen: Arc<std::sync::Mutex<Vec<String>>>) -> axum::Router {
+        use axum::{extract::Path, routing::get, Json, Router};
+        Router::new().route(
+            "/api/balance/:address",
+            get(move |Path(address): Path<String>| {
+                let seen = seen.clone();
+                async move {
+                    seen.lock().unwrap().push(address);
+                    Json(serde_json::json!({
+                        "success": true,
+                        "balances": { "txtc": "12.5", "eth": "0.01" }
+                    }))
+                }
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_seeded_user_join_then_balance() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let url = crate::test_support::spawn_mock_backend(balance_backend(seen.clone())).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+
+        let phone = "+15550100001";
+        let user = UserRepository::new(pool.clone()).seed_user(phone).await.unwrap();
+        assert_eq!(user.wallet_address, crate::db::seed_wallet(phone).address_string());
+
+        let reply = processor.process(phone, "JOIN").await;
+        assert!(reply.starts_with("Welcome back!"), "{}", reply);
+        assert!(reply.contains(&user.wallet_address));
+
+        let reply = processor.process(phone, "BALANCE").await;
+        assert_eq!(reply, "Balance:\n12.5 TXTC\n0.01 ETH\n\nSepolia testnet");
+        assert_eq!(seen.lock().unwrap().as_slice(), std::slice::from_ref(&user.wallet_address));
+
+        DepositRepository::new(pool).seed_deposit(phone, 2_000_000).await.unwrap();
+        let reply = processor.process(phone, "HISTORY 1").await;
+        assert_eq!(reply, "Recent activity:\n$2.00 via onchain");
+    }
+
+    #[tokio::test]
+    async fn test_join_derives_wallet_from_master_mnemonic() {
+        let Some((mut processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        processor.config.master_mnemonic = "test test test test test test test test test test test junk".to_string();
+        let processor = processor.simulated();
+
+        let phone = random_phone();
+        let reply = processor.process(&phone, "JOIN").await;
+        assert!(reply.starts_with("Wallet created!"), "{}", reply);
+
+        // Only the index is stored; the key is re-derived from the phrase
+        let user = UserRepository::new(pool).find_by_phone(&phone).await.unwrap().unwrap();
+        let index = user.derivation_index.expect("derived wallets record their index");
+        assert_eq!(user.encrypted_private_key, "");
+        let derived = UserWallet::from_mnemonic(&processor.config.master_mnemonic, index as u32).unwrap();
+        assert_eq!(derived.address_string(), user.wallet_address);
+        assert_eq!(processor.signing_key(&user).unwrap(), hex::encode(derived.private_key_bytes()));
+
+        // Without the phrase (or with another one) the wallet can't be used
+        let mut without = processor.clone();
+        without.config.master_mnemonic = String::new();
+        assert!(without.user_wallet(&user).is_err());
+        without.config.master_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about".to_string();
+        assert!(without.user_wallet(&user).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_join_then_balance_and_send_to_seeded_contact() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let url = crate::test_support::spawn_mock_backend(balance_backend(seen.clone())).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let processor = processor.simulated();
+
+        let phone = random_phone();
+        let reply = processor.process(&phone, "JOIN").await;
+        assert!(reply.starts_with("Wallet created!"), "{}", reply);
+
+        // The key is stored encrypted and decrypts to the wallet's own
+        let user = UserRepository::new(pool.clone()).find_by_phone(&phone).await.unwrap().unwrap();
+        assert!(hex::decode(&user.encrypted_private_key).unwrap().len() > 32);
+        let key = decrypt_stored_key(&user.encrypted_private_key, &processor.config.encryption_key).unwrap();
+        assert_eq!(UserWallet::from_private_key(&key).unwrap().address_string(), user.wallet_address);
+
+        let reply = processor.process(&phone, "BALANCE").await;
+        assert!(reply.starts_with("Balance:\n12.5 TXTC"), "{}", reply);
+
+        let mom = UserRepository::new(pool.clone()).seed_user("+15550100002").await.unwrap();
+        AddressBookRepository::new(pool).seed_contact(&phone, "mom", &mom.phone).await.unwrap();
+
+        let reply = processor.process(&phone, "SEND 1 TXTC TO mom").await;
+        assert_eq!(reply, format!("[SIMULATED] Would send 1 TXTC to mom ({})", mom.wallet_address));
+    }
+
+    #[tokio::test]
+    async fn test_balance_breaks_out_committed_funds() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let url = crate::test_support::spawn_mock_backend(balance_backend(seen)).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+
+        let phone = random_phone();
+        let user = UserRepository::new(pool.clone()).seed_user(&phone).await.unwrap();
+        let channel_id = format!("0x{}", hex::encode(rand::random::<[u8; 32]>()));
+        ChannelRepository::new(pool.clone()).record_activity(&phone, &channel_id, Some(3.0)).await.unwrap();
+        let transfers = TransferRepository::new(pool.clone());
+        transfers.create_pending(&phone, &user.wallet_address, "0xabc", 2.0, "TXTC").await.unwrap();
+        // Settled sends are already out of the on-chain balance
+        let queued = transfers.create_pending(&phone, &user.wallet_address, "0xabc", 4.0, "TXTC").await.unwrap();
+        transfers.mark_queued(queued, None).await.unwrap();
+
+        let reply = processor.process(&phone, "BALANCE").await;
+        assert_eq!(
+            reply,
+            "Balance:\n7.5 TXTC available\nIn channel: 3 TXTC\nPending out: 2 TXTC\nTotal: 12.5 TXTC\n0.01 ETH\n\nSepolia testnet"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_balance_lists_other_chains() {
+        use axum::{routing::post, Json, Router};
+        use ethers::providers::{Http, Provider};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Amoy answers 1.5 MATIC and 25.5 USDC; Base Sepolia has no provider
+        let rpc_calls = Arc::new(AtomicUsize::new(0));
+        let counter = rpc_calls.clone();
+        let rpc = Router::new().route(
+            "/",
+            post(move |Json(req): Json<serde_json::Value>| async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let result = match req["method"].as_str() {
+                    Some("eth_getBalance") => format!("{:#x}", ethers::types::U256::from(1_500_000_000_000_000_000u64)),
+                    _ => format!("0x{}", hex::encode(ethers::abi::encode(&[ethers::abi::Token::Uint(25_500_000u64.into())]))),
+                };
+                Json(serde_json::json!({ "jsonrpc": "2.0", "id": req["id"], "result": result }))
+            }),
+        );
+        let rpc_url = crate::test_support::spawn_mock_backend(rpc).await;
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let url = crate::test_support::spawn_mock_backend(balance_backend(seen)).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.balance_chains = vec![Chain::PolygonAmoy, Chain::BaseSepolia];
+        processor.multi_chain = MultiChainProvider::with_chains(&[])
+            .with_provider(Chain::PolygonAmoy, Provider::<Http>::try_from(rpc_url.as_str()).unwrap());
+        processor.chain_balances = LookupCache::new(std::time::Duration::from_secs(15));
+
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        let expected = "Balance:\n12.5 TXTC\n0.01 ETH\n\nSepolia testnet\n\nOther chains:\nPOL-T: 1.500000 MATIC | 25.500000 USDC\nBASE-T: unavailable";
+        assert_eq!(processor.process(&phone, "BALANCE").await, expected);
+        let calls = rpc_calls.load(Ordering::SeqCst);
+        assert!(calls >= 2, "{}", calls);
+
+        // Served from the cache the second time
+        assert_eq!(processor.process(&phone, "BALANCE").await, expected);
+        assert_eq!(rpc_calls.load(Ordering::SeqCst), calls);
+    }
+
+    /// Backend + Arc service counting Arc wallet creations; every send succeeds
+    fn arc_backend(arc_calls: Arc<std::sync::Mutex<usize>>) -> axum::Router {
+        use axum::{routing::post, Json, Router};
+        Router::new()
+            .route(
+                "/api/arc/wallet",
+                post(move || {
+                    let arc_calls = arc_calls.clone();
+                    async move {
+                        *arc_calls.lock().unwrap() += 1;
+                        Json(serde_json::json!({ "wallet": { "address": "0xarc0000000000000000" } }))
+                    }
+                }),
+            )
+            .route(
+                "/api/send-yellow",
+                post(|| async { Json(serde_json::json!({ "success": true })) }),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_receive_only_join_provisions_on_first_send() {
+        let arc_calls = Arc::new(std::sync::Mutex::new(0));
+        let url = crate::test_support::spawn_mock_backend(arc_backend(arc_calls.clone())).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.receive_only_join = true;
+        let users = UserRepository::new(pool);
+
+        let phone = random_phone();
+        let reply = processor.process(&phone, "JOIN").await;
+        assert!(reply.starts_with("Wallet created (receive only)!"), "{}", reply);
+        assert_eq!(*arc_calls.lock().unwrap(), 0);
+        assert!(users.find_by_phone(&phone).await.unwrap().unwrap().receive_only);
+
+        let to = format!("0x{}", "22".repeat(20));
+        let reply = processor.process(&phone, &format!("SEND 1 TXTC TO {}", to)).await;
+        assert!(reply.starts_with("Sending 1 TXTC"), "{}", reply);
+        assert_eq!(*arc_calls.lock().unwrap(), 1);
+        assert!(!users.find_by_phone(&phone).await.unwrap().unwrap().receive_only);
+
+        // Already provisioned: later sends don't touch Arc again
+        processor.process(&phone, &format!("SEND 1 TXTC TO {}", to)).await;
+        assert_eq!(*arc_calls.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_parse_bridge_as_clause() {
+        let processor = test_processor();
+        let bridge = |to_token: &str| Command::Bridge {
+            amount: 10.0,
+            token: "USDC".to_string(),
+            from_chain: "POLYGON".to_string(),
+            to_chain: "BASE".to_string(),
+            to_token: to_token.to_string(),
+        };
+
+        assert_eq!(processor.parse("BRIDGE 10 USDC FROM polygon TO base AS ETH"), bridge("ETH"));
+        assert_eq!(processor.parse("bridge 10 usdc polygon base as eth"), bridge("ETH"));
+        // Same token when AS is omitted
+        assert_eq!(processor.parse("BRIDGE 10 USDC FROM POLYGON TO BASE"), bridge("USDC"));
+
+        assert!(matches!(processor.parse("BRIDGE 10 USDC FROM POLYGON TO BASE AS"), Command::Invalid { hint, .. } if hint.starts_with("Missing token after AS")));
+        assert!(matches!(processor.parse("BRIDGE 10 USDC FROM POLYGON TO BASE AS ETH NOW"), Command::Invalid { .. }));
+    }
+
+    /// Backend estimating a 0.002 ETH bridge fee against `eth_balance`, counting bridges started
+    fn bridge_backend(eth_balance: &'static str, bridges: Arc<std::sync::Mutex<u32>>) -> axum::Router {
+        use axum::{routing::post, Json, Router};
+        Router::new()
+            .route(
+                "/api/bridge/estimate",
+                post(move |Json(body): Json<serde_json::Value>| async move {
+                    // The backend quotes by chain id, not the user's spelling
+                    assert_eq!((body["fromChainId"].as_u64(), body["toChainId"].as_u64()), (Some(137), Some(8453)));
+                    Json(serde_json::json!({
+                        "success": true, "fee": "0.002", "feeToken": "ETH", "feeTokenBalance": eth_balance
+                    }))
+                }),
+            )
+            .route(
+                "/api/bridge",
+                post(move || async move {
+                    *bridges.lock().unwrap() += 1;
+                    Json(serde_json::json!({ "success": true, "route": "Polygon -> Base" }))
+                }),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_bridge_blocked_without_fee_token() {
+        let bridges = Arc::new(std::sync::Mutex::new(0));
+        let url = crate::test_support::spawn_mock_backend(bridge_backend("0.001", bridges.clone())).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        let reply = processor.process(&phone, "BRIDGE 10 USDC FROM POLYGON TO BASE").await;
+        assert_eq!(reply, "Not enough ETH on Polygon for the bridge fee.\nNeeds 0.002 ETH (fee 0.002), you have 0.001.");
+        assert_eq!(*bridges.lock().unwrap(), 0);
+
+        let reply = processor.process(&phone, "BRIDGE 10 USDC FROM MARS TO BASE").await;
+        assert!(reply.starts_with("Unknown chain: MARS"), "{}", reply);
+        assert_eq!(*bridges.lock().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_bridge_shows_fee_when_covered() {
+        let bridges = Arc::new(std::sync::Mutex::new(0));
+        let url = crate::test_support::spawn_mock_backend(bridge_backend("0.01", bridges.clone())).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        let reply = processor.process(&phone, "BRIDGE 10 USDC FROM POLYGON TO BASE").await;
+        assert_eq!(reply, "Bridge started!\nPolygon -> Base\nFee: 0.002 ETH\nSMS when done.");
+        assert_eq!(*bridges.lock().unwrap(), 1);
+
+        // A fee in the bridged token is needed on top of the amount
+        let reply = processor.process(&phone, "BRIDGE 0.009 ETH FROM POLYGON TO BASE").await;
+        assert!(reply.starts_with("Not enough ETH on Polygon for the bridge fee.\nNeeds 0.011 ETH"), "{}", reply);
+        assert_eq!(*bridges.lock().unwrap(), 1);
+
+        // Only the bridge the backend accepted shows up in HISTORY
+        let reply = processor.process(&phone, "HISTORY").await;
+        assert_eq!(reply, "Recent activity:\nBridged 10 USDC to Base");
+    }
+
+    #[tokio::test]
+    async fn test_edited_send_ack_leaves_other_templates_default() {
+        use axum::{routing::post, Json};
+        let backend = bridge_backend("0.01", Arc::new(std::sync::Mutex::new(0)))
+            .route(
+                "/api/send-yellow",
+                post(|| async { Json(serde_json::json!({ "success": true, "estimatedProcessing": "3 min" })) }),
+            )
+            .route("/api/arc/cashout", post(|| async { Json(serde_json::json!({ "success": true })) }));
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.send_ack_template = "{amount} {token} on its way to {recipient} (about {eta}).{note}".to_string();
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+        let to = "0xabcdef1234567890abcdef1234567890abcdef12";
+
+        assert_eq!(
+            processor.process(&phone, &format!("SEND 2 TXTC TO {} NOTE lunch", to)).await,
+            format!("2 TXTC on its way to {} (about 3 min).\nNote: lunch", to)
+        );
+        assert_eq!(
+            processor.process(&phone, "CASHOUT 1 ETH").await,
+            "Cashing out 1 ETH...\n\nETH → USDC on Arc.\nYou'll get an SMS when complete.\n\nThis may take 1-2 minutes."
+        );
+        assert_eq!(
+            processor.process(&phone, "BRIDGE 10 USDC FROM POLYGON TO BASE").await,
+            "Bridge started!\nPolygon -> Base\nFee: 0.002 ETH\nSMS when done."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_history_shows_confirmation_status() {
+        let Some((mut processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        processor.config.min_confirmations = 12;
+        processor.block_heights.set(Chain::ArbitrumSepolia, 1_000);
+        let deposits = DepositRepository::new(pool);
+        let phone = random_phone();
+        let chain = Chain::ArbitrumSepolia.name();
+
+        let old = deposits
+            .create_provisional(&phone, 1_000_000, "USDC", &format!("0x{}", hex::encode(rand::random::<[u8; 32]>())), chain, 900)
+            .await.unwrap().unwrap();
+        deposits.confirm(old.id).await.unwrap();
+        // Three blocks deep: mined at 998, head 1000
+        deposits
+            .create_provisional(&phone, 2_000_000, "USDC", &format!("0x{}", hex::encode(rand::random::<[u8; 32]>())), chain, 998)
+            .await.unwrap().unwrap();
+
+        let reply = processor.process(&phone, "HISTORY").await;
+        assert!(reply.contains("$2.00 via onchain (pending 3/12)"), "{}", reply);
+        assert!(reply.contains("$1.00 via onchain (confirmed)"), "{}", reply);
+    }
+
+    #[test]
+    fn test_parse_export_keeps_passphrase_case() {
+        let processor = test_processor();
+        assert_eq!(
+            processor.parse("EXPORT Correct Horse 42"),
+            Command::Export { passphrase: "Correct Horse 42".to_string() }
+        );
+        assert!(matches!(processor.parse("EXPORT"), Command::Invalid { .. }));
+        assert_eq!(
+            processor.parse("export short"),
+            Command::invalid("EXPORT", ParseError::InvalidArgument, "Passphrase too short (min 8 characters).")
+        );
+        assert_eq!(processor.parse("export history"), Command::ExportHistory);
+        assert_eq!(
+            processor.parse("EXPORT history of mine"),
+            Command::Export { passphrase: "history of mine".to_string() }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_history_csv_lists_transactions_once() {
+        let Some((processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        let phone = random_phone();
+        let sender = random_phone();
+        UserRepository::new(pool.clone()).seed_user(&phone).await.unwrap();
+        let deposit = DepositRepository::new(pool.clone()).seed_deposit(&phone, 2_500_000).await.unwrap();
+
+        let transfers = TransferRepository::new(pool.clone());
+        let sent = transfers.create_pending(&phone, "0xfrom", "0x2222222222222222222222222222222222222222", 5.0, "TXTC").await.unwrap();
+        transfers.mark_queued(sent, Some("0xfeed")).await.unwrap();
+        let failed = transfers.create_pending(&phone, "0xfrom", "0x3333333333333333333333333333333333333333", 9.0, "TXTC").await.unwrap();
+        transfers.mark_failed(failed, "rejected").await.unwrap();
+        InboundTransferRepository::new(pool)
+            .create_pending(&phone, &sender, "0x4444444444444444444444444444444444444444", 1.5, "TXTC", None)
+            .await
+            .unwrap();
+
+        let reply = processor.process(&phone, "EXPORT HISTORY").await;
+        assert!(reply.starts_with("Transaction history (4 rows):\n"), "{}", reply);
+        let token = reply.split("/export/history/").nth(1).unwrap().split_whitespace().next().unwrap();
+
+        let csv = processor.take_history_export(token).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "type,amount,token,counterparty,status,tx_hash,timestamp");
+        assert_eq!(lines.len(), 5);
+        let row = |prefix: String| lines.iter().any(|l| l.starts_with(&prefix));
+        assert!(row(format!("deposit,2.5,USDC,onchain,confirmed,{},", deposit.source_ref.unwrap())), "{}", csv);
+        assert!(row("send,5,TXTC,0x2222222222222222222222222222222222222222,queued,0xfeed,".to_string()), "{}", csv);
+        assert!(row("send,9,TXTC,0x3333333333333333333333333333333333333333,failed,,".to_string()), "{}", csv);
+        assert!(row(format!("receive,1.5,TXTC,{},pending,,", sender)), "{}", csv);
+
+        // The link works once
+        assert!(processor.take_history_export(token).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_export_link_serves_keystore_once() {
+        let Some((processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        let phone = "+15550100004";
+        UserRepository::new(pool).seed_user(phone).await.unwrap();
+
+        let reply = processor.process(phone, "EXPORT Correct Horse 42").await;
+        assert!(!reply.contains(&hex::encode(crate::db::seed_wallet(phone).private_key_bytes())));
+        let token = reply.split("/export/").nth(1).and_then(|rest| rest.split_whitespace().next()).unwrap();
+
+        let keystore = processor.take_export(token).unwrap();
+        let key = crate::wallet::keystore::decrypt_keystore(&keystore, "Correct Horse 42").unwrap();
+        assert_eq!(key, crate::db::seed_wallet(phone).private_key_bytes());
+        assert!(processor.take_export(token).is_none());
+    }
+
+    #[test]
+    fn test_parse_account_mode() {
+        let processor = test_processor();
+        assert_eq!(processor.parse("ACCOUNT"), Command::Account { mode: None });
+        assert_eq!(processor.parse("account smart"), Command::Account { mode: Some("smart".to_string()) });
+        assert_eq!(processor.parse("ACCOUNT AA"), Command::Account { mode: Some("smart".to_string()) });
+        assert_eq!(processor.parse("ACCOUNT eoa"), Command::Account { mode: Some("eoa".to_string()) });
+        assert!(matches!(processor.parse("ACCOUNT MAYBE"), Command::Invalid { .. }));
+    }
+
+    /// JSON-RPC node answering every eth_call with `address` (SimpleAccountFactory.getAddress)
+    fn mock_rpc(address: Address) -> axum::Router {
+        use axum::{routing::post, Json, Router};
+        Router::new().route(
+            "/",
+            post(move |Json(req): Json<serde_json::Value>| async move {
+                let result = match req["method"].as_str() {
+                    Some("eth_call") => format!(
+                        "0x{}",
+                        hex::encode(ethers::abi::encode(&[ethers::abi::Token::Address(address)]))
+                    ),
+                    _ => "0x".to_string(),
+                };
+                Json(serde_json::json!({ "jsonrpc": "2.0", "id": req["id"], "result": result }))
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_deposit_address_follows_account_mode() {
+        use ethers::providers::{Http, Provider};
+
+        let smart_account = Address::random();
+        let rpc_url = crate::test_support::spawn_mock_backend(mock_rpc(smart_account)).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        processor.provider = Arc::new(Provider::<Http>::try_from(rpc_url.as_str()).unwrap());
+        processor.rate_limiter = RateLimiter::new(0, std::time::Duration::ZERO);
+        processor.aa = Some(AaConfig {
+            bundler_url: "http://127.0.0.1:9".to_string(),
+            entry_point_address: "0x5FF137D4b0FDCD49DcA30c7CF57E578a026d2789".to_string(),
+            entry_point_version: "0.6".to_string(),
+            simple_account_factory_address: "0x9406Cc6185a346906296840746125a0E44976454".to_string(),
+        });
+
+        let phone = random_phone();
+        let user = UserRepository::new(pool).seed_user(&phone).await.unwrap();
+        let smart_address = format!("{:?}", smart_account);
+
+        // EOA mode reports the EOA
+        let reply = processor.process(&phone, "DEPOSIT").await;
+        assert!(reply.ends_with(&user.wallet_address), "{}", reply);
+
+        // AA mode reports the counterfactual smart account
+        let reply = processor.process(&phone, "ACCOUNT SMART").await;
+        assert!(reply.contains(&smart_address), "{}", reply);
+        let reply = processor.process(&phone, "DEPOSIT").await;
+        assert!(reply.ends_with(&smart_address), "{}", reply);
+
+        processor.process(&phone, "ACCOUNT EOA").await;
+        let reply = processor.process(&phone, "DEPOSIT").await;
+        assert!(reply.ends_with(&user.wallet_address), "{}", reply);
+
+        // Without AA settings smart mode can't be enabled
+        processor.aa = None;
+        assert_eq!(processor.process(&phone, "ACCOUNT SMART").await, "Smart accounts not enabled.");
+    }
+
+    #[test]
+    fn test_parse_send_dangling_or_punctuation_recipient() {
+        let processor = test_processor();
+        assert_eq!(
+            processor.parse("SEND 10 TXTC TO"),
+            Command::invalid(
+                "SEND",
+                ParseError::MissingRecipient,
+                "Missing recipient after TO.\nExample: SEND 10 TXTC TO swarnim.ttcip.eth"
+            )
+        );
+        assert_eq!(
+            processor.parse("SEND 10 TXTC TO ."),
+            Command::invalid(
+                "SEND",
+                ParseError::InvalidRecipient,
+                "Invalid recipient \".\".\nUse a name, phone (+1...) or address (0x...)."
+            )
+        );
+        assert!(matches!(processor.parse("SEND 10 TXTC ?!"), Command::Invalid { .. }));
+        assert!(matches!(processor.parse("SEND $10 TO ..."), Command::Invalid { .. }));
+
+        // The usual TO form is unaffected
+        assert!(matches!(processor.parse("SEND 10 TXTC TO bob"), Command::Send { .. }));
+    }
+
+    /// Backend whose pool quotes `outputs` in turn (the last one repeats)
+    /// and which records swap requests
+    fn quote_backend(
+        outputs: &[&'static str],
+        swaps: Arc<std::sync::Mutex<Vec<serde_json::Value>>>,
+    ) -> axum::Router {
+        use axum::{routing::post, Json, Router};
+        let outputs = Arc::new(std::sync::Mutex::new(outputs.to_vec()));
+        Router::new()
+            .route(
+                "/api/quote",
+                post(move || async move {
+                    let mut outputs = outputs.lock().unwrap();
+                    let output = if outputs.len() > 1 { outputs.remove(0) } else { outputs[0] };
+                    Json(serde_json::json!({ "success": true, "outputAmount": output }))
+                }),
+            )
+            .route(
+                "/api/swap",
+                post(move |Json(body): Json<serde_json::Value>| async move {
+                    swaps.lock().unwrap().push(body);
+                    Json(serde_json::json!({ "success": true }))
+                }),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_zero_quote_blocks_swap() {
+        let swaps = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let url = crate::test_support::spawn_mock_backend(quote_backend(&["0.0"], swaps.clone())).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let phone = "+15550100003";
+        UserRepository::new(pool).seed_user(phone).await.unwrap();
+
+        assert_eq!(
+            processor.process(phone, "SWAP 5 TXTC").await,
+            "Insufficient liquidity for 5 TXTC.\nTry a smaller amount or later."
+        );
+        assert!(processor.process(phone, "CASHOUT 5 TXTC").await.starts_with("Insufficient liquidity"));
+        assert!(swaps.lock().unwrap().is_empty());
+
+        let url = crate::test_support::spawn_mock_backend(quote_backend(&["0.0015"], swaps.clone())).await;
+        let processor = processor.with_backend_url(&url);
+        assert!(processor.process(phone, "SWAP 5 TXTC").await.starts_with("Swapping 5 TXTC"));
+        assert_eq!(swaps.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_quote() {
+        let processor = test_processor();
+        let quote = |amount: f64, token: &str| Command::Quote { amount, token: token.to_string() };
+        assert_eq!(processor.parse("QUOTE 10 TXTC"), quote(10.0, "TXTC"));
+        assert_eq!(processor.parse("quote 0.01 eth"), quote(0.01, "ETH"));
+        assert_eq!(
+            processor.parse("QUOTE 10 USDC"),
+            Command::invalid("QUOTE", ParseError::Usage, "Usage: QUOTE <amount> TXTC\nOr: QUOTE <amount> ETH")
+        );
+        assert!(matches!(processor.parse("QUOTE 10"), Command::Invalid { reason: ParseError::Usage, .. }));
+        assert!(matches!(processor.parse("QUOTE 0 TXTC"), Command::Invalid { reason: ParseError::InvalidAmount, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_quote_prices_both_directions() {
+        let swaps = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let url = crate::test_support::spawn_mock_backend(quote_backend(&["0.0042", "23.5", "0.0"], swaps.clone())).await;
+        // Needs no wallet or database
+        let processor = test_processor().with_backend_url(&url);
+
+        assert_eq!(processor.process("+15550100005", "QUOTE 10 TXTC").await, "10 TXTC ≈ 0.0042 ETH");
+        assert_eq!(processor.process("+15550100005", "QUOTE 0.01 ETH").await, "0.01 ETH ≈ 23.5 TXTC");
+        assert_eq!(
+            processor.process("+15550100005", "QUOTE 10 TXTC").await,
+            "Insufficient liquidity for 10 TXTC.\nTry a smaller amount or later."
+        );
+        assert!(swaps.lock().unwrap().is_empty());
+
+        let offline = test_processor().with_backend_url("http://127.0.0.1:9");
+        assert_eq!(offline.process("+15550100005", "QUOTE 10 TXTC").await, "Can't get a price right now. Try again later.");
+    }
+
+    #[test]
+    fn test_parse_autoswap() {
+        let processor = test_processor();
+        let pct = |pct| Command::AutoSwap { setting: Some(AutoSwapSetting::Percent(pct)) };
+        assert_eq!(processor.parse("AUTOSWAP"), Command::AutoSwap { setting: None });
+        assert_eq!(processor.parse("autoswap on 10%"), pct(10));
+        assert_eq!(processor.parse("AUTOSWAP 25"), pct(25));
+        assert_eq!(processor.parse("AUTOSWAP OFF"), Command::AutoSwap { setting: Some(AutoSwapSetting::Off) });
+        for text in ["AUTOSWAP ON 0%", "AUTOSWAP ON 80%", "AUTOSWAP ON ten"] {
+            assert_eq!(
+                processor.parse(text),
+                Command::invalid("AUTOSWAP", ParseError::InvalidAmount, "AUTOSWAP takes 1% to 50%.\nExample: AUTOSWAP ON 10%")
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_cashout_target_currency() {
+        let mut processor = test_processor();
+        let cashout = |to: Option<&str>| Command::Cashout { amount: 10.0, token: "TXTC".to_string(), to: to.map(str::to_string), pin: None };
+        assert_eq!(processor.parse("CASHOUT 10 TXTC"), cashout(None));
+        assert_eq!(processor.parse("CASHOUT 10 TXTC to usdt"), cashout(Some("USDT")));
+        assert_eq!(
+            processor.parse("CASHOUT 10 TXTC TO DAI"),
+            Command::invalid("CASHOUT", ParseError::Usage, "Can't cash out to DAI.\nAvailable: USDC, USDT")
+        );
+
+        // A deployment can keep users on its own settlement currency
+        processor.config.cashout_currencies = vec!["USDC".to_string()];
+        assert_eq!(
+            processor.parse("CASHOUT 10 TXTC TO USDT"),
+            Command::invalid("CASHOUT", ParseError::Usage, "Can't cash out to USDT.\nAvailable: USDC")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cashout_passes_settlement_currency() {
+        use axum::{routing::post, Json, Router};
+        let bodies = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = bodies.clone();
+        let arc = Router::new().route(
+            "/api/arc/cashout",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                seen.lock().unwrap().push(body);
+                Json(serde_json::json!({ "success": true }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(arc).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.cooldowns = CooldownStore::new(&std::collections::HashMap::new());
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        let reply = processor.process(&phone, "CASHOUT 1 ETH TO USDT").await;
+        assert!(reply.contains("ETH → USDT on Polygon."), "{}", reply);
+        processor.config.cashout_currency = "USDT".to_string();
+        processor.process(&phone, "CASHOUT 1 ETH TO USDC").await;
+        processor.process(&phone, "CASHOUT 1 ETH").await;
+
+        let bodies = bodies.lock().unwrap();
+        let settled: Vec<_> = bodies
+            .iter()
+            .map(|b| (b["settlementCurrency"].as_str().unwrap(), b["settlementChain"].as_str().unwrap()))
+            .collect();
+        assert_eq!(settled, [("USDT", "polygon"), ("USDC", "arc"), ("USDT", "polygon")]);
+    }
+
+    #[tokio::test]
+    async fn test_deposit_autoswaps_once() {
+        let swaps = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let url = crate::test_support::spawn_mock_backend(quote_backend(&["0.0005"], swaps.clone())).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let phone = random_phone();
+        UserRepository::new(pool.clone()).seed_user(&phone).await.unwrap();
+        let deposits = DepositRepository::new(pool);
+
+        // Off by default
+        let deposit = deposits.seed_token_deposit(&phone, 2_500_000, "TXTC").await.unwrap();
+        assert_eq!(processor.autoswap_deposit(&deposit).await, None);
+
+        assert_eq!(
+            processor.process(&phone, "AUTOSWAP ON 10%").await,
+            "Auto-swap on: 10% of each deposit will be swapped to gas."
+        );
+        // USDC can't be swapped to gas, so USDC deposits are left alone
+        let usdc = deposits.seed_deposit(&phone, 2_500_000).await.unwrap();
+        assert_eq!(processor.autoswap_deposit(&usdc).await, None);
+
+        let deposit = deposits.seed_token_deposit(&phone, 2_500_000, "TXTC").await.unwrap();
+        let sms = processor.autoswap_deposit(&deposit).await.unwrap();
+        assert!(sms.starts_with("Auto-swap: 10% of your deposit (0.25 TXTC)"), "{}", sms);
+
+        // The watcher seeing the deposit again doesn't swap twice
+        assert_eq!(processor.autoswap_deposit(&deposit).await, None);
+        let swaps = swaps.lock().unwrap();
+        assert_eq!(swaps.len(), 1);
+        assert_eq!(swaps[0]["tokenAmount"], "0.25");
+        assert_eq!(swaps[0]["userPhone"], phone.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_resolver_order_picks_the_winner() {
+        use axum::{routing::get, Json, Router};
+        let ens_address = "0x2222222222222222222222222222222222222222";
+        let backend = Router::new().route(
+            "/api/ens/resolve/:name",
+            get(move || async move { Json(serde_json::json!({ "address": ens_address })) }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        // Resolve through the mock backend, not a live ENS registry
+        processor.multi_chain = MultiChainProvider::with_chains(&[]);
+        let repo = UserRepository::new(pool.clone());
+        let sender = random_phone();
+        let carol = repo.seed_user(&random_phone()).await.unwrap();
+        // "carol.eth" is both an ENS name and a saved contact
+        AddressBookRepository::new(pool).seed_contact(&sender, "CAROL.ETH", &carol.phone).await.unwrap();
+
+        use RecipientResolver::*;
+        let winner = |resolved: ResolvedRecipient| (resolved.via, resolved.address);
+        let resolved = processor.resolve_recipient(&repo, &sender, "carol.eth").await.unwrap();
+        assert_eq!(winner(resolved), (Ens, ens_address.to_string()));
+
+        processor.config.recipient_resolvers = vec![Contact, Ens, Phone, Address];
+        let resolved = processor.resolve_recipient(&repo, &sender, "carol.eth").await.unwrap();
+        assert_eq!(resolved.phone.as_deref(), Some(carol.phone.as_str()));
+        assert_eq!(winner(resolved), (Contact, carol.wallet_address.clone()));
+
+        // Resolvers left out aren't tried
+        processor.config.recipient_resolvers = vec![Address, Phone];
+        assert!(processor.resolve_recipient(&repo, &sender, "carol.eth").await.unwrap_err().starts_with("Invalid recipient."));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_caches_count_and_invalidate() {
+        use axum::{routing::get, Json, Router};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let resolves = Arc::new(AtomicUsize::new(0));
+        let counter = resolves.clone();
+        let backend = Router::new().route(
+            "/api/ens/resolve/:name",
+            get(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Json(serde_json::json!({ "address": "0x2222222222222222222222222222222222222222" }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        // Resolve through the mock backend, not a live ENS registry
+        processor.multi_chain = MultiChainProvider::with_chains(&[]);
+        let repo = UserRepository::new(pool);
+        let sender = random_phone();
+        let recipient = random_phone();
+        repo.seed_user(&recipient).await.unwrap();
+
+        // Second lookup is served from the cache
+        for _ in 0..2 {
+            let resolved = processor.resolve_recipient(&repo, &sender, &recipient).await.unwrap();
+            assert_eq!(resolved.phone.as_deref(), Some(recipient.as_str()));
+        }
+        assert_eq!(processor.lookup_caches().stats().users.hits, 1);
+        assert_eq!(processor.lookup_caches().stats().users.misses, 1);
+
+        // Updating the user's row drops the cached copy
+        processor.process(&recipient, "STOP").await;
+        processor.resolve_recipient(&repo, &sender, &recipient).await.unwrap();
+        assert_eq!(processor.lookup_caches().stats().users.misses, 2);
+
+        // ENS names are cached case-insensitively until re-registered
+        processor.resolve_recipient(&repo, &sender, "Alice.ttcip.eth").await.unwrap();
+        processor.resolve_recipient(&repo, &sender, "alice.ttcip.eth").await.unwrap();
+        assert_eq!(resolves.load(Ordering::SeqCst), 1);
+        processor.lookup_caches().invalidate_ens("alice.ttcip.eth");
+        processor.resolve_recipient(&repo, &sender, "alice.ttcip.eth").await.unwrap();
+        assert_eq!(resolves.load(Ordering::SeqCst), 2);
+        assert_eq!(processor.lookup_caches().stats().ens.hits, 1);
+    }
+
+    #[tokio::test]
+    async fn test_slow_yellow_send_reported_as_still_processing() {
+        use axum::{routing::post, Json, Router};
+        let backend = Router::new().route(
+            "/api/send-yellow",
+            post(|Json(body): Json<serde_json::Value>| async move {
+                if body["amount"] == "1" {
+                    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+                    Json(serde_json::json!({ "success": true }))
+                } else {
+                    Json(serde_json::json!({ "success": false, "error": "channel closed" }))
+                }
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.send_timeout_secs = 1;
+        let phone = random_phone();
+        UserRepository::new(pool.clone()).seed_user(&phone).await.unwrap();
+        let to = "0x1234567890abcdef1234567890abcdef12345678";
+
+        let reply = processor.process(&phone, &format!("SEND 1 TXTC TO {}", to)).await;
+        assert!(reply.starts_with("Still processing: 1 TXTC"), "{}", reply);
+        assert!(reply.contains("Wait for the completion SMS"), "{}", reply);
+
+        // An explicit rejection is a failure, not "still processing"
+        assert_eq!(
+            processor.process(&phone, &format!("SEND 2 TXTC TO {}", to)).await,
+            "Transfer failed. Try later."
+        );
+
+        let transfers = TransferRepository::new(pool).find_by_sender(&phone).await.unwrap();
+        assert_eq!(transfers.len(), 2);
+        assert_eq!((transfers[0].status.as_str(), transfers[0].amount), ("pending", 1.0));
+        assert_eq!(transfers[0].to_address, to);
+        assert!(transfers[0].error.as_deref().unwrap().contains("timed out"));
+        assert_eq!(transfers[1].status, "failed");
+        assert_eq!(transfers[1].error.as_deref(), Some("channel closed"));
+    }
+
+    #[test]
+    fn test_parse_save_phone_address_or_ens() {
+        let processor = test_processor();
+        let save = |name: &str, contact| Command::Save { name: name.to_string(), contact };
+        assert_eq!(
+            processor.parse("SAVE alice +1 555 123 4567"),
+            save("ALICE", SavedContact::Phone("+15551234567".to_string()))
+        );
+        assert_eq!(
+            processor.parse("save bob 0x71C7656EC7ab88b098defB751B7401B5f6d8976F"),
+            save("BOB", SavedContact::Address("0x71C7656EC7ab88b098defB751B7401B5f6d8976F".to_string()))
+        );
+        assert_eq!(
+            processor.parse("SAVE carol Carol.TTCIP.eth"),
+            save("CAROL", SavedContact::Ens("carol.ttcip.eth".to_string()))
+        );
+
+        assert!(matches!(processor.parse("SAVE dave +1555"), Command::Invalid { reason: ParseError::InvalidArgument, .. }));
+        assert!(matches!(processor.parse("SAVE dave 0x1234"), Command::Invalid { reason: ParseError::InvalidArgument, .. }));
+        assert!(matches!(processor.parse("SAVE dave 5551234567"), Command::Invalid { reason: ParseError::Usage, .. }));
+        assert!(matches!(processor.parse("SAVE dave"), Command::Invalid { reason: ParseError::Usage, .. }));
+    }
+
+    #[test]
+    fn test_parse_saveall() {
+        let processor = test_processor();
+        assert_eq!(
+            processor.parse("SAVEALL alice +1 555 123 4567, bob +15561234567,"),
+            Command::SaveAll {
+                contacts: vec![
+                    ("ALICE".to_string(), "+1 555 123 4567".to_string()),
+                    ("BOB".to_string(), "+15561234567".to_string()),
+                ]
+            }
+        );
+        assert_eq!(
+            processor.parse("SAVEALL carol"),
+            Command::SaveAll { contacts: vec![("CAROL".to_string(), String::new())] }
+        );
+        assert!(matches!(processor.parse("SAVEALL"), Command::Invalid { hint, .. } if hint.starts_with("Usage")));
+
+        let many = (0..11).map(|i| format!("c{} +1555000{:04}", i, i)).collect::<Vec<_>>().join(",");
+        assert_eq!(
+            processor.parse(&format!("SAVEALL {}", many)),
+            Command::invalid("SAVEALL", ParseError::InvalidArgument, "Too many contacts (11). Max 10 per message.")
+        );
+    }
+
+    #[test]
+    fn test_parse_contact() {
+        let processor = test_processor();
+        assert_eq!(processor.parse("CONTACT alice"), Command::Contact { name: "ALICE".to_string() });
+        assert_eq!(processor.parse("contact Mary Jane"), Command::Contact { name: "MARY JANE".to_string() });
+        assert_eq!(processor.parse("CONTACT"), Command::invalid("CONTACT", ParseError::MissingArgument, "Usage: CONTACT <name>"));
+        assert_eq!(processor.parse("CONTACTS"), Command::Contacts { limit: None });
+        assert_eq!(processor.parse("payable 3"), Command::Payable { limit: Some(3) });
+    }
+
+    #[tokio::test]
+    async fn test_channel_sweep_closes_idle_and_low_balance_channels() {
+        use axum::{routing::post, Json, Router};
+        let closed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = closed.clone();
+        let yellow = Router::new()
+            .route(
+                "/channel/close",
+                post(move |Json(body): Json<serde_json::Value>| async move {
+                    recorded.lock().unwrap().push(body["channelId"].as_str().unwrap_or_default().to_string());
+                    Json(serde_json::json!({ "success": true, "txHash": "0xc105e" }))
+                }),
+            )
+            .route(
+                // Idle channels settle straight away, the others are still settling
+                "/channel/:id/status",
+                axum::routing::get(|axum::extract::Path(id): axum::extract::Path<String>| async move {
+                    let status = if id.starts_with("0x1d") { "closed" } else { "settling" };
+                    Json(serde_json::json!({ "success": true, "status": status }))
+                }),
+            );
+        let url = crate::test_support::spawn_mock_backend(yellow).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.channel_idle_close_secs = 2 * 86_400;
+        processor.config.channel_min_balance = 1.0;
+        processor.config.channel_settle_wait_secs = 0;
+
+        // One open channel per user, so each channel gets its own
+        let phones: Vec<String> = (0..3).map(|_| random_phone()).collect();
+        let channel = |tag: &str| format!("0x{}{}", tag, uuid::Uuid::new_v4().simple());
+        let (idle, low, healthy) = (channel("1d"), channel("10"), channel("0c"));
+        let channels = ChannelRepository::new(pool.clone());
+        channels.record_activity(&phones[0], &idle, Some(50.0)).await.unwrap();
+        channels.record_activity(&phones[1], &low, Some(0.25)).await.unwrap();
+        channels.record_activity(&phones[2], &healthy, Some(50.0)).await.unwrap();
+        sqlx::query("UPDATE yellow_channels SET last_activity_at = NOW() - INTERVAL '3 days' WHERE channel_id = $1")
+            .bind(&idle)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let notices: Vec<String> = processor
+            .run_channel_sweep()
+            .await
+            .into_iter()
+            .filter(|(to, _)| phones.contains(to))
+            .map(|(_, sms)| sms)
+            .collect();
+        assert_eq!(notices.len(), 2, "{:?}", notices);
+        assert!(notices.iter().any(|n| n.starts_with("Your Yellow payment channel was closed after 2 days without use and settled on-chain.")));
+        assert!(notices.iter().any(|n| n.starts_with("Your Yellow payment channel was closed as its balance fell below 1.\nIt's settling on-chain")));
+
+        let closed_ids = closed.lock().unwrap().clone();
+        assert!(closed_ids.contains(&idle) && closed_ids.contains(&low));
+        assert!(!closed_ids.contains(&healthy));
+
+        // Closed channels aren't closed again
+        assert!(!processor.run_channel_sweep().await.iter().any(|(to, _)| phones.contains(to)));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::wallet::create_shared_provider;
+    #[tokio::test]
+    async fn test_linked_phone_resolves_to_same_wallet() {
+        let Some((processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        let users = UserRepository::new(pool);
+        let main = random_phone();
+        let second = random_phone();
+        let stranger = random_phone();
+        let user = users.seed_user(&main).await.unwrap();
+        assert_eq!(processor.parse("link ab12cd34"), Command::Link { code: Some("AB12CD34".to_string()) });
 
-    fn test_processor() -> CommandProcessor {
-        CommandProcessor::new(None, create_shared_provider())
+        let reply = processor.process(&main, "LINK").await;
+        assert!(reply.starts_with("Link code: "), "{}", reply);
+        let code = reply.lines().next().unwrap().trim_start_matches("Link code: ").to_string();
+
+        assert_eq!(
+            processor.process(&second, "LINK WRONG123").await,
+            "Invalid or expired code.\nReply LINK on your main phone for a new one."
+        );
+        assert_eq!(
+            processor.process(&second, &format!("LINK {}", code)).await,
+            format!("Linked! This number now uses the wallet of {}.\nReply BALANCE to check.", mask_phone(&main))
+        );
+
+        // The second number resolves to the main wallet everywhere
+        let resolved = users.find_by_phone(&second).await.unwrap().unwrap();
+        assert_eq!(resolved.phone, main);
+        assert_eq!(resolved.wallet_address, user.wallet_address);
+        assert!(processor.process(&second, "DEPOSIT").await.ends_with(&user.wallet_address));
+        assert_eq!(processor.process(&second, "LINK").await, "Only your wallet's main number can link phones.");
+
+        // Codes are single use
+        assert!(processor.process(&stranger, &format!("LINK {}", code)).await.starts_with("Invalid or expired code."));
+        assert!(users.find_by_phone(&stranger).await.unwrap().is_none());
     }
 
-    #[test]
-    fn test_parse_help() {
-        let processor = test_processor();
-        assert_eq!(processor.parse("COMMANDS"), Command::Help);
-        assert_eq!(processor.parse("menu"), Command::Help);
-        assert_eq!(processor.parse("?"), Command::Help);
+    #[tokio::test]
+    async fn test_stop_start_and_pin_from_a_linked_number() {
+        let Some((processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        let processor = processor.simulated();
+        let users = UserRepository::new(pool);
+        let main = random_phone();
+        let second = random_phone();
+        users.seed_user(&main).await.unwrap();
+        users.link_phone(&second, &main).await.unwrap();
+
+        assert!(processor.process(&second, "STOP").await.starts_with("You're unsubscribed"));
+        assert!(users.find_by_phone(&main).await.unwrap().unwrap().opted_out);
+        assert_eq!(processor.process(&main, "BALANCE").await, "");
+
+        assert!(processor.process(&second, "START").await.starts_with("You're subscribed"));
+        assert!(!users.find_by_phone(&main).await.unwrap().unwrap().opted_out);
+
+        assert_eq!(processor.process(&second, "PIN 1234").await, "PIN set!");
+        let pin_hash = users.find_by_phone(&main).await.unwrap().unwrap().pin_hash;
+        assert_eq!(pin_hash, Some(hash_pin("1234")));
     }
 
-    #[test]
-    fn test_parse_join() {
-        let processor = test_processor();
-        assert_eq!(processor.parse("JOIN"), Command::Join { ens_name: None });
-        assert_eq!(processor.parse("JOIN john"), Command::Join { ens_name: Some("john".to_string()) });
-        assert_eq!(processor.parse("start"), Command::Join { ens_name: None });
+    #[tokio::test]
+    async fn test_payable_lists_only_contacts_that_can_receive() {
+        let Some((mut processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        let phone = random_phone();
+        let book = AddressBookRepository::new(pool.clone());
+        assert_eq!(
+            processor.process(&phone, "PAYABLE").await,
+            "No contacts can receive yet.\nSAVE <name> <phone> for someone on TextChain."
+        );
+
+        let alice_phone = random_phone();
+        let alice = UserRepository::new(pool.clone()).seed_user(&alice_phone).await.unwrap();
+        book.seed_contact(&phone, "ALICE", &alice.phone).await.unwrap();
+        book.seed_contact(&phone, "BOB", &random_phone()).await.unwrap();
+
+        let reply = processor.process(&phone, "PAYABLE").await;
+        let a = &alice.wallet_address;
+        assert_eq!(reply, format!("Can receive:\nALICE: {}...{}", &a[..6], &a[38..]));
+        assert!(!reply.contains("BOB"));
+
+        // Paginated like other lists
+        let carol_phone = random_phone();
+        UserRepository::new(pool).seed_user(&carol_phone).await.unwrap();
+        book.seed_contact(&phone, "CAROL", &carol_phone).await.unwrap();
+        processor.config.page_size = 1;
+        let reply = processor.process(&phone, "PAYABLE").await;
+        assert!(reply.ends_with("\nReply PAYABLE 10 for more."), "{}", reply);
+        assert_eq!(processor.process(&phone, "PAYABLE 10").await.lines().count(), 3);
     }
 
-    #[test]
-    fn test_parse_balance() {
-        let processor = test_processor();
-        assert_eq!(processor.parse("BALANCE"), Command::Balance);
-        assert_eq!(processor.parse("bal"), Command::Balance);
+    #[tokio::test]
+    async fn test_ens_contacts_resolve_before_payable_and_contact() {
+        use axum::{extract::Path, routing::get, Json, Router};
+        let resolved = "0x2222222222222222222222222222222222222222";
+        let backend = Router::new().route(
+            "/api/ens/resolve/:name",
+            get(move |Path(name): Path<String>| async move {
+                let address = (name == "carol.ttcip.eth").then_some(resolved);
+                Json(serde_json::json!({ "address": address }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        // Resolve through the mock backend, not a live ENS registry
+        processor.multi_chain = MultiChainProvider::with_chains(&[]);
+        let phone = random_phone();
+        processor.process(&phone, "SAVE carol carol.ttcip.eth").await;
+        processor.process(&phone, "SAVE ghost ghost.eth").await;
+
+        let transfers = TransferRepository::new(pool);
+        let id = transfers.create_pending(&phone, "0xfrom", resolved, 3.0, "TXTC").await.unwrap();
+        transfers.mark_queued(id, None).await.unwrap();
+
+        // Only the name that resolves can receive, shown by its address
+        assert_eq!(processor.process(&phone, "PAYABLE").await, "Can receive:\nCAROL: 0x2222...2222");
+        assert_eq!(
+            processor.process(&phone, "CONTACT carol").await,
+            format!(
+                "CAROL\nENS: carol.ttcip.eth\nAddress: {}\nChain: Ethereum Sepolia\nLast paid: 3 TXTC on {}",
+                resolved,
+                chrono::Utc::now().format("%Y-%m-%d")
+            )
+        );
+        assert_eq!(
+            processor.process(&phone, "CONTACT ghost").await,
+            "GHOST\nENS: ghost.eth\nAddress: name doesn't resolve\nLast paid: never"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_contact_details() {
+        let Some((processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        let phone = random_phone();
+        let alice_phone = random_phone();
+        let alice = UserRepository::new(pool.clone()).seed_user(&alice_phone).await.unwrap();
+        let book = AddressBookRepository::new(pool.clone());
+        book.seed_contact(&phone, "ALICE", &alice.phone).await.unwrap();
+        book.seed_contact(&phone, "ALICIA", "+15550001234").await.unwrap();
+        book.seed_contact(&phone, "ALI", "+15550005678").await.unwrap();
+
+        let transfers = TransferRepository::new(pool);
+        let id = transfers.create_pending(&phone, "0xfrom", &alice.wallet_address, 5.0, "TXTC").await.unwrap();
+        transfers.mark_queued(id, None).await.unwrap();
+        let failed = transfers.create_pending(&phone, "0xfrom", &alice.wallet_address, 9.0, "TXTC").await.unwrap();
+        transfers.mark_failed(failed, "rejected").await.unwrap();
+
+        let reply = processor.process(&phone, "CONTACT alice").await;
+        let today = chrono::Utc::now().format("%Y-%m-%d");
+        assert_eq!(
+            reply,
+            format!(
+                "ALICE\nPhone: {}\nAddress: {}\nChain: Ethereum Sepolia\nLast paid: 5 TXTC on {}",
+                mask_phone(&alice.phone),
+                alice.wallet_address,
+                today
+            )
+        );
+
+        // Exact name beats partial matches; unregistered phones have no address
+        assert_eq!(
+            processor.process(&phone, "CONTACT ali").await,
+            "ALI\nPhone: +1***5678\nAddress: not on TextChain\nLast paid: never"
+        );
+        assert_eq!(
+            processor.process(&phone, "CONTACT alic").await,
+            "Several contacts match ALIC:\nALICE, ALICIA\nReply CONTACT <full name>."
+        );
+        assert_eq!(
+            processor.process(&phone, "CONTACT bob").await,
+            "No contact named BOB.\nReply CONTACTS to list."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_stores_address_and_ens_as_wallet() {
+        let Some((processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        let phone = random_phone();
+        let address = "0x71C7656EC7ab88b098defB751B7401B5f6d8976F";
+
+        assert_eq!(processor.process(&phone, "SAVE alice +15551234567").await, "Saved +15551234567 as ALICE.");
+        assert_eq!(processor.process(&phone, &format!("SAVE bob {}", address)).await, "Saved 0x71C7...976F as BOB.");
+        assert_eq!(processor.process(&phone, "SAVE carol carol.ttcip.eth").await, "Saved carol.ttcip.eth as CAROL.");
+
+        let contacts = AddressBookRepository::new(pool).list_all(&phone).await.unwrap();
+        let saved: Vec<_> = contacts
+            .iter()
+            .map(|c| (c.name.as_str(), c.contact_phone.as_deref(), c.wallet_address.as_deref()))
+            .collect();
+        assert_eq!(
+            saved,
+            vec![
+                ("ALICE", Some("+15551234567"), None),
+                ("BOB", None, Some(address)),
+                ("CAROL", None, Some("carol.ttcip.eth")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_saveall_reports_failures() {
+        let Some((processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        let phone = random_phone();
+
+        let reply = processor
+            .process(&phone, "SAVEALL alice +1 555 123 4567, bob 12345, carol, dave +15559876543")
+            .await;
+        assert_eq!(reply, "Saved 2 of 4 contacts.\nFailed: BOB (invalid phone), CAROL (invalid phone)");
+
+        let contacts = AddressBookRepository::new(pool).list_all(&phone).await.unwrap();
+        let mut saved: Vec<_> = contacts.iter().map(|c| (c.name.as_str(), c.contact_phone.as_deref())).collect();
+        saved.sort();
+        assert_eq!(saved, vec![("ALICE", Some("+15551234567")), ("DAVE", Some("+15559876543"))]);
+    }
+
+    #[tokio::test]
+    async fn test_cashout_cooldown_leaves_balance_alone() {
+        use axum::{routing::post, Json, Router};
+        let backend = Router::new().route(
+            "/api/arc/cashout",
+            post(|| async { Json(serde_json::json!({ "success": true })) }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.cooldowns = CooldownStore::new(&std::collections::HashMap::from([("CASHOUT".to_string(), 300)]));
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        assert!(processor.process(&phone, "CASHOUT 1 ETH").await.starts_with("Cashing out 1 ETH"));
+        assert_eq!(
+            processor.process(&phone, "CASHOUT 1 ETH").await,
+            "You just did that. Wait 5 min before another CASHOUT."
+        );
+
+        for _ in 0..2 {
+            assert!(!processor.process(&phone, "BALANCE").await.starts_with("You just did that"));
+        }
+        // Other users aren't affected
+        let other = random_phone();
+        assert!(!processor.process(&other, "CASHOUT 1 ETH").await.starts_with("You just did that"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_cashout_replies_failure_without_cooldown() {
+        use axum::{routing::post, Json, Router};
+        let backend = Router::new().route(
+            "/api/arc/cashout",
+            post(|| async { Json(serde_json::json!({ "success": false, "error": "bridge down" })) }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.cooldowns = CooldownStore::new(&std::collections::HashMap::from([("CASHOUT".to_string(), 300)]));
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        for _ in 0..2 {
+            assert_eq!(processor.process(&phone, "CASHOUT 1 ETH").await, "Cashout failed. Try later.");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rapid_identical_buys_call_backend_once() {
+        use axum::{routing::post, Json, Router};
+        let buys = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = buys.clone();
+        let backend = Router::new().route(
+            "/api/buy",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                recorded.lock().unwrap().push(body);
+                Json(serde_json::json!({ "success": true }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.cooldowns = CooldownStore::new(&std::collections::HashMap::new());
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        let (first, second) = tokio::join!(processor.process(&phone, "BUY 10"), processor.process(&phone, "BUY 10"));
+        let mut replies = [first, second];
+        replies.sort();
+        assert!(replies[0].starts_with("Already buying TXTC with €10"), "{:?}", replies);
+        assert!(replies[1].starts_with("Buying TXTC with €10"), "{:?}", replies);
+        assert_eq!(buys.lock().unwrap().len(), 1);
+        let request_id = buys.lock().unwrap()[0]["requestId"].as_str().unwrap().to_string();
+        assert_eq!(request_id.len(), 32);
+
+        // A different amount is a separate purchase, shown to the cent
+        processor.config.buy_decimals = 2;
+        assert!(processor.process(&phone, "BUY 12.5").await.starts_with("Buying TXTC with €12.50 airtime"));
+        let buys = buys.lock().unwrap();
+        assert_eq!(buys.len(), 2);
+        assert_ne!(buys[1]["requestId"], request_id.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_welcome_bonus_granted_once_per_phone() {
+        use axum::{routing::post, Json, Router};
+        let grants = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = grants.clone();
+        let backend = Router::new().route(
+            "/api/bonus",
+            post(move |headers: axum::http::HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                assert_eq!(headers["X-Bonus-Token"], "bonus-secret");
+                recorded.lock().unwrap().push(body);
+                Json(serde_json::json!({ "success": true }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.welcome_bonus_txtc = 5.0;
+        processor.config.welcome_bonus_token = "bonus-secret".to_string();
+        let phone = random_phone();
+
+        let reply = processor.process(&phone, "JOIN").await;
+        assert!(reply.contains("+5 TXTC welcome bonus!"), "{}", reply);
+        assert_eq!(grants.lock().unwrap().len(), 1);
+        assert_eq!(grants.lock().unwrap()[0]["amount"], "5");
+
+        assert!(processor.process(&phone, "JOIN").await.starts_with("Welcome back!"));
+        assert!(processor.process(&phone, "HISTORY").await.contains("5 TXTC welcome bonus"));
+
+        // A wallet re-created for the same phone doesn't earn it again
+        sqlx::query("DELETE FROM users WHERE phone = $1").bind(&phone).execute(&pool).await.unwrap();
+        let reply = processor.process(&phone, "JOIN").await;
+        assert!(reply.starts_with("Wallet created!") && !reply.contains("bonus"), "{}", reply);
+        assert_eq!(grants.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_swap_requotes_before_executing() {
+        let swaps = Arc::new(std::sync::Mutex::new(Vec::new()));
+        // Price drops 5% between the quote and the execute-time re-quote
+        let url = crate::test_support::spawn_mock_backend(quote_backend(&["2.0", "1.9"], swaps.clone())).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let phone = "+15550100004";
+        UserRepository::new(pool).seed_user(phone).await.unwrap();
+
+        assert_eq!(processor.process(phone, "SWAP 5 TXTC").await, "Price moved, try again.");
+        assert!(swaps.lock().unwrap().is_empty());
+
+        // Within the default 1% tolerance: executes with the slippage-adjusted floor
+        let url = crate::test_support::spawn_mock_backend(quote_backend(&["2.0", "1.995"], swaps.clone())).await;
+        let processor = processor.with_backend_url(&url);
+        assert!(processor.process(phone, "SWAP 5 TXTC").await.starts_with("Swapping 5 TXTC"));
+        let swaps = swaps.lock().unwrap();
+        assert_eq!(swaps.len(), 1);
+        assert_eq!(swaps[0]["minEthOut"], "1.98");
+    }
+
+    #[tokio::test]
+    async fn test_swap_and_cashout_carry_configured_protection() {
+        use axum::{routing::post, Json};
+        let swaps = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = swaps.clone();
+        let backend = quote_backend(&["2.0"], swaps.clone()).route(
+            "/api/arc/cashout",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                recorded.lock().unwrap().push(body);
+                Json(serde_json::json!({ "success": true }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.swap_slippage_bps = 250;
+        processor.config.swap_deadline_secs = 120;
+        processor.cooldowns = CooldownStore::new(&std::collections::HashMap::new());
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        let before = chrono::Utc::now().timestamp();
+        assert!(processor.process(&phone, "SWAP 5 TXTC").await.starts_with("Swapping 5 TXTC"));
+        assert!(processor.process(&phone, "CASHOUT 5 TXTC").await.starts_with("Cashing out 5 TXTC"));
+        let after = chrono::Utc::now().timestamp();
+
+        let swaps = swaps.lock().unwrap();
+        assert_eq!(swaps.len(), 2);
+        for payload in swaps.iter() {
+            assert_eq!(payload["slippageBps"], 250, "{}", payload);
+            let deadline = payload["deadline"].as_i64().unwrap();
+            assert!((before + 120..=after + 120).contains(&deadline), "{}", payload);
+        }
+        assert_eq!(swaps[0]["minEthOut"], "1.95");
+    }
+
+    #[tokio::test]
+    async fn test_rapid_commands_are_throttled() {
+        let mut processor = test_processor();
+        processor.rate_limiter = RateLimiter::new(5, std::time::Duration::from_millis(300));
+        let from = "+15550002222";
+
+        for _ in 0..5 {
+            assert_ne!(processor.process(from, "FOO").await, "Too many requests, wait a moment.");
+        }
+        assert_eq!(processor.process(from, "FOO").await, "Too many requests, wait a moment.");
+        // STOP still gets through
+        assert_ne!(processor.process(from, "STOP").await, "Too many requests, wait a moment.");
+
+        tokio::time::sleep(std::time::Duration::from_millis(320)).await;
+        assert_ne!(processor.process(from, "FOO").await, "Too many requests, wait a moment.");
+    }
+
+    #[tokio::test]
+    async fn test_keyword_confirmation_of_large_send() {
+        let mut processor = test_processor();
+        processor.config.confirm_above = 100.0;
+        processor.config.confirm_by_keyword = true;
+        processor.rate_limiter = RateLimiter::new(0, std::time::Duration::ZERO);
+        let from = "+15550001111";
+
+        // Small sends go straight through (and hit the missing DB)
+        assert_eq!(processor.process(from, "SEND 5 TXTC TO alice").await, "DB offline. Try later.");
+
+        let prompt = processor.process(from, "SEND 500 TXTC TO alice").await;
+        assert!(prompt.contains("send 500 TXTC to alice") && prompt.contains("Reply YES"), "{}", prompt);
+        assert_eq!(processor.process(from, "YES").await, "DB offline. Try later.");
+        assert_eq!(processor.process(from, "yes").await, "Nothing to confirm.");
+
+        // Two pending: a bare YES lists them and executes nothing
+        processor.process(from, "SEND 500 TXTC TO alice").await;
+        processor.process(from, "SEND 200 ETH TO bob").await;
+        let list = processor.process(from, "YES").await;
+        assert!(list.contains("1. send 500 TXTC to alice") && list.contains("2. send 200 ETH to bob"), "{}", list);
+        assert_eq!(processor.process(from, "NO 1").await, "Cancelled: send 500 TXTC to alice.");
+        assert_eq!(processor.process(from, "YES 2").await, "No pending send #2.\nReply YES to list.");
+        assert_eq!(processor.process(from, "YES").await, "DB offline. Try later.");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_balance_asks_user_to_wait() {
+        use axum::http::StatusCode;
+        let router = axum::Router::new().route(
+            "/api/balance/:address",
+            axum::routing::get(|| async { (StatusCode::TOO_MANY_REQUESTS, [("retry-after", "90")], "slow down") }),
+        );
+        let url = crate::test_support::spawn_mock_backend(router).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        assert_eq!(processor.process(&phone, "BALANCE").await, "Busy right now. Try again in 2 min.");
+    }
+
+    #[tokio::test]
+    async fn test_vouchers_lists_redeemed_codes() {
+        use axum::{routing::post, Json, Router};
+        let backend = Router::new().route(
+            "/api/redeem",
+            post(|| async { Json(serde_json::json!({ "success": true, "tokenAmount": "90", "ethAmount": "0.001", "txHash": "0xabc" })) }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.page_size = 2;
+        processor.config.max_page_size = 5;
+        processor.rate_limiter = RateLimiter::new(0, std::time::Duration::ZERO);
+        let vouchers = VoucherRepository::new(pool.clone());
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        assert_eq!(
+            processor.process(&phone, "VOUCHERS").await,
+            "No vouchers redeemed yet.\nReply REDEEM <code> to add funds."
+        );
+
+        // The first code was only ever minted on chain, so it has no local amount
+        let codes: Vec<String> = (0..3).map(|i| format!("VL{}{}", i, rand::random::<u32>() % 1_000_000)).collect();
+        for code in &codes[1..] {
+            vouchers.seed_voucher(code, 5_000_000).await.unwrap();
+        }
+        for code in &codes {
+            let reply = processor.process(&phone, &format!("REDEEM {}", code)).await;
+            assert!(reply.starts_with("Voucher redeemed!"), "{}", reply);
+        }
+
+        let reply = processor.process(&phone, "VOUCHERS").await;
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        let newest = &codes[2];
+        assert!(reply.starts_with("Redeemed vouchers:\nVL"), "{}", reply);
+        assert!(reply.contains(&format!("*{} $5.00 {}", &newest[newest.len() - 2..], today)), "{}", reply);
+        assert!(!reply.contains(newest.as_str()), "{}", reply);
+        assert!(reply.ends_with("Reply VOUCHERS 5 for more."), "{}", reply);
+        let all = processor.process(&phone, "VOUCHERS 5").await;
+        assert_eq!(all.lines().count(), 4);
+        let oldest = &codes[0];
+        assert!(all.ends_with(&format!("*{} {}", &oldest[oldest.len() - 2..], today)), "{}", all);
+    }
+
+    #[tokio::test]
+    async fn test_help_lists_only_commands_enabled_in_region() {
+        let mut processor = test_processor();
+        processor.config.disabled_commands =
+            std::collections::HashMap::from([("+44".to_string(), vec!["BRIDGE".to_string(), "BUY".to_string()])]);
+
+        let us = processor.process("+15550003333", "MENU").await;
+        assert!(us.contains("BRIDGE 10 USDC") && us.contains("BUY 10"), "{}", us);
+
+        let uk = processor.process("+447700900123", "MENU").await;
+        assert!(!uk.contains("BRIDGE") && !uk.contains("BUY"), "{}", uk);
+        assert!(uk.starts_with("Text-to-Chain Commands:\nJOIN") && uk.contains("SWAP 10 TXTC"), "{}", uk);
+        assert_eq!(
+            processor.process("+447700900123", "BRIDGE 10 USDC FROM POLYGON TO BASE").await,
+            "BRIDGE isn't available in your region.\nReply MENU for commands."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_join_refuses_blocked_names() {
+        let Some((mut processor, _pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        processor.config.blocked_name_words = vec!["scam".to_string(), "admin".to_string()];
+        let phone = random_phone();
+        let refused = "That name isn't available.\n\nTry another: JOIN <name>";
+
+        assert_eq!(processor.process(&phone, "JOIN bestscam").await, refused);
+        assert_eq!(processor.process(&phone, "JOIN 5C4M").await, refused);
+        assert_eq!(processor.process(&phone, "JOIN adm1n99").await, refused);
+        assert!(!is_blocked_name("alice", &processor.config.blocked_name_words));
     }
 
     #[test]
-    fn test_parse_send() {
+    fn test_parse_currency() {
         let processor = test_processor();
-        
-        let cmd = processor.parse("SEND 10 USDC TO +917123456789");
-        assert!(matches!(cmd, Command::Send { amount, token, recipient } 
-            if amount == 10.0 && token == "USDC" && recipient == "+917123456789"));
+        assert_eq!(processor.parse("CURRENCY"), Command::Currency { code: None });
+        assert_eq!(processor.parse("currency kes"), Command::Currency { code: Some("KES".to_string()) });
+        assert_eq!(processor.parse("CURRENCY NGN"), Command::Currency { code: Some("NGN".to_string()) });
+        assert_eq!(processor.parse("CURRENCY off"), Command::Currency { code: Some("OFF".to_string()) });
+        assert!(matches!(processor.parse("CURRENCY XYZ"), Command::Invalid { hint, .. } if hint.starts_with("Supported: USD")));
     }
 
     #[test]
-    fn test_parse_pin() {
+    fn test_parse_approve() {
         let processor = test_processor();
-        
-        let cmd = processor.parse("PIN 1234");
-        assert!(matches!(cmd, Command::Pin { new_pin: Some(pin) } if pin == "1234"));
-        
-        let cmd = processor.parse("PIN");
-        assert!(matches!(cmd, Command::Pin { new_pin: None }));
+        let above = |amount: f64, token: &str| Command::Approve {
+            limit: Some(ApprovalLimit::Above { amount, token: token.to_string() }),
+        };
+        assert_eq!(processor.parse("APPROVE"), Command::Approve { limit: None });
+        assert_eq!(processor.parse("approve above 100 txtc"), above(100.0, "TXTC"));
+        assert_eq!(processor.parse("APPROVE 0.5 ETH"), above(0.5, "ETH"));
+        assert_eq!(processor.parse("APPROVE ABOVE 100"), above(100.0, "TXTC"));
+        assert_eq!(processor.parse("APPROVE OFF"), Command::Approve { limit: Some(ApprovalLimit::Off) });
+        for bad in ["APPROVE ABOVE", "APPROVE ABOVE -5", "APPROVE lots", "APPROVE 5 TXTC now"] {
+            assert!(matches!(processor.parse(bad), Command::Invalid { hint, .. } if hint.starts_with("Usage: APPROVE")), "{}", bad);
+        }
     }
 
-    #[test]
-    fn test_parse_unknown() {
+    #[tokio::test]
+    async fn test_incoming_transfer_above_approval_limit_is_held() {
+        use axum::{routing::post, Json, Router};
+        let backend = Router::new().route(
+            "/api/send-yellow",
+            post(|Json(body): Json<serde_json::Value>| async move {
+                Json(serde_json::json!({ "success": true, "holdId": body["hold"].as_bool().map(|_| "hold-1") }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let sender = random_phone();
+        let recipient = random_phone();
+        let users = UserRepository::new(pool.clone());
+        users.seed_user(&sender).await.unwrap();
+        users.seed_user(&recipient).await.unwrap();
+        let inbound = InboundTransferRepository::new(pool);
+
+        assert_eq!(
+            processor.process(&recipient, "APPROVE").await,
+            "Incoming transfers are credited right away.\nExample: APPROVE ABOVE 100 TXTC"
+        );
+        assert_eq!(
+            processor.process(&recipient, "APPROVE ABOVE 50 TXTC").await,
+            "Incoming transfers above 50 TXTC will wait for your ACCEPT."
+        );
+
+        // A small transfer is credited as usual
+        let reply = processor.process(&sender, &format!("SEND 10 TXTC TO {}", recipient)).await;
+        assert!(reply.starts_with("Sending 10 TXTC"), "{}", reply);
+        assert!(inbound.list_pending(&recipient).await.unwrap().is_empty());
+
+        // A large one waits for ACCEPT, as does one in another token only past its own limit
+        let reply = processor.process(&sender, &format!("SEND 100 TXTC TO {}", recipient)).await;
+        assert!(reply.contains("Held until they reply ACCEPT."), "{}", reply);
+        let pending = inbound.list_pending(&recipient).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!((pending[0].amount, pending[0].token.as_str()), (100.0, "TXTC"));
+        let reply = processor.process(&sender, &format!("SEND 100 ETH TO {}", recipient)).await;
+        assert!(reply.starts_with("Sending 100 ETH"), "{}", reply);
+
+        assert_eq!(
+            processor.process(&recipient, "APPROVE OFF").await,
+            "Large incoming transfers will be credited right away."
+        );
+        let reply = processor.process(&sender, &format!("SEND 90 TXTC TO {}", recipient)).await;
+        assert!(reply.starts_with("Sending 90 TXTC"), "{}", reply);
+        assert_eq!(inbound.list_pending(&recipient).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_held_send_without_a_hold_id_fails() {
+        use axum::{routing::post, Json, Router};
+        // A backend that paid straight out instead of opening a hold
+        let backend = Router::new().route("/api/send-yellow", post(|| async { Json(serde_json::json!({ "success": true })) }));
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let (sender, recipient) = (random_phone(), random_phone());
+        let users = UserRepository::new(pool.clone());
+        users.seed_user(&sender).await.unwrap();
+        users.seed_user(&recipient).await.unwrap();
+        processor.process(&recipient, "APPROVE ABOVE 50 TXTC").await;
+
+        let reply = processor.process(&sender, &format!("SEND 100 TXTC TO {}", recipient)).await;
+        assert_eq!(reply, "Transfer failed. Try later.");
+        assert!(InboundTransferRepository::new(pool).list_pending(&recipient).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_balance_shows_value_in_chosen_currency() {
+        use axum::{routing::get, Json, Router};
+        let backend = Router::new().route(
+            "/api/balance/:address",
+            get(|| async { Json(serde_json::json!({ "success": true, "balances": { "txtc": "10", "eth": "0" } })) }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let processor = processor.with_price_source(Arc::new(StubPrice(Some(130.0))));
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        // Without a CURRENCY only the total is shown, in USD
+        let reply = processor.process(&phone, "BALANCE").await;
+        assert!(reply.contains("10 TXTC\n0 ETH\n≈ $1,300.00\n"), "{}", reply);
+        assert_eq!(processor.process(&phone, "CURRENCY KES").await, "Amounts will show approx. KES values.");
+        let reply = processor.process(&phone, "BALANCE").await;
+        assert!(reply.contains("10 TXTC (~KES 1,300)\n0 ETH\n≈ 1,300.00 KES\n"), "{}", reply);
+
+        // Feed down: the fiat values are left out, the balance still shows
+        let down = processor.with_price_source(Arc::new(StubPrice(None)));
+        let reply = down.process(&phone, "BALANCE").await;
+        assert!(reply.contains("10 TXTC\n0 ETH\n\n") && !reply.contains('≈'), "{}", reply);
+    }
+
+    #[tokio::test]
+    async fn test_pin_session_skips_reentry_until_locked() {
+        use axum::{routing::post, Json, Router};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let sends = Arc::new(AtomicUsize::new(0));
+        let counter = sends.clone();
+        let backend = Router::new().route(
+            "/api/send-yellow",
+            post(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Json(serde_json::json!({ "success": true }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.rate_limiter = RateLimiter::new(0, std::time::Duration::ZERO);
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+        let to = "0x1234567890abcdef1234567890abcdef12345678";
+        let needed = "PIN needed for SEND.\nReply UNLOCK <your PIN> to continue.";
+
+        // No PIN yet: nothing to enter
+        assert!(processor.process(&phone, &format!("SEND 1 TXTC TO {}", to)).await.starts_with("Sending 1 TXTC"));
+        assert_eq!(processor.process(&phone, "PIN 1234").await, "PIN set!");
+
+        assert_eq!(processor.process(&phone, &format!("SEND 2 TXTC TO {}", to)).await, needed);
+        assert_eq!(
+            processor.process(&phone, "UNLOCK 9999").await,
+            "Wrong PIN.\nSend your command again, then UNLOCK <PIN>."
+        );
+        assert_eq!(processor.process(&phone, "UNLOCK 1234").await, "Unlocked for 5 min.\nReply LOCK to lock now.");
+        assert_eq!(sends.load(Ordering::SeqCst), 1);
+        assert!(processor.process(&phone, &format!("SEND 3 TXTC TO {}", to)).await.starts_with("Sending 3 TXTC"));
+
+        // LOCK forces re-entry; the held send runs on UNLOCK and the next one
+        // within the window goes straight through
+        assert!(processor.process(&phone, "LOCK").await.starts_with("Locked."));
+        assert_eq!(processor.process(&phone, &format!("SEND 4 TXTC TO {}", to)).await, needed);
+        assert_eq!(sends.load(Ordering::SeqCst), 2);
+        assert!(processor.process(&phone, "UNLOCK 1234").await.starts_with("Sending 4 TXTC"));
+        assert!(processor.process(&phone, &format!("SEND 5 TXTC TO {}", to)).await.starts_with("Sending 5 TXTC"));
+        assert_eq!(sends.load(Ordering::SeqCst), 4);
+        // Read-only commands never ask
+        assert!(!processor.process(&phone, "HISTORY").await.starts_with("PIN needed"));
+
+        // A PIN typed with the send covers just that send
+        assert!(processor.process(&phone, "LOCK").await.starts_with("Locked."));
+        assert_eq!(
+            processor.process(&phone, &format!("SEND 6 TXTC TO {} PIN 9999", to)).await,
+            "Wrong PIN. Nothing was sent.\nPIN required: SEND 10 TXTC alice PIN 1234"
+        );
+        assert_eq!(sends.load(Ordering::SeqCst), 4);
+        assert!(processor.process(&phone, &format!("SEND 7 TXTC TO {} PIN 1234", to)).await.starts_with("Sending 7 TXTC"));
+        assert_eq!(sends.load(Ordering::SeqCst), 5);
+        assert_eq!(processor.process(&phone, &format!("SEND 8 TXTC TO {}", to)).await, needed);
+    }
+
+    #[tokio::test]
+    async fn test_send_to_tagged_payee_carries_default_note() {
+        use axum::{routing::post, Json, Router};
+        let notes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = notes.clone();
+        let backend = Router::new().route(
+            "/api/send-yellow",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                seen.lock().unwrap().push(body["note"].clone());
+                Json(serde_json::json!({ "success": true }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+        let to = "0xABCDEF1234567890abcdef1234567890abcdef12";
+
+        assert!(processor.process(&phone, &format!("TAG {} rent", to)).await.starts_with("Tagged"));
+        let reply = processor.process(&phone, &format!("SEND 1 TXTC TO {}", to.to_lowercase())).await;
+        assert!(reply.starts_with(&format!("Sending 1 TXTC to {}...\nNote: rent\n", to.to_lowercase())), "{}", reply);
+        // An explicit note wins over the tag
+        processor.process(&phone, &format!("SEND 2 TXTC TO {} NOTE deposit", to)).await;
+        assert_eq!(processor.process(&phone, &format!("TAG {} OFF", to)).await, format!("Tag on {} removed.", to));
+        processor.process(&phone, &format!("SEND 3 TXTC TO {}", to)).await;
+
+        assert_eq!(*notes.lock().unwrap(), [serde_json::json!("rent"), serde_json::json!("deposit"), serde_json::Value::Null]);
+    }
+
+    #[tokio::test]
+    async fn test_send_reuses_open_channel() {
+        use axum::{routing::post, Json, Router};
+        let channel = format!("0x{}", hex::encode(rand::random::<[u8; 32]>()));
+        let requested = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = requested.clone();
+        let opened = channel.clone();
+        let opens = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counted = opens.clone();
+        let backend = Router::new()
+            .route(
+                "/api/send-yellow",
+                post(move |Json(body): Json<serde_json::Value>| async move {
+                    seen.lock().unwrap().push(body["channelId"].clone());
+                    Json(serde_json::json!({ "success": true, "channelId": body["channelId"], "channelBalance": "40" }))
+                }),
+            )
+            .route(
+                "/channel/open",
+                post(move || async move {
+                    counted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    Json(serde_json::json!({ "success": true, "channelId": opened }))
+                }),
+            );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        let phone = random_phone();
+        UserRepository::new(pool.clone()).seed_user(&phone).await.unwrap();
+        let to = "0x1234567890abcdef1234567890abcdef12345678";
+
+        processor.process(&phone, &format!("SEND 1 TXTC TO {}", to)).await;
+        let reply = processor.process(&phone, &format!("SEND 2 TXTC TO {}", to)).await;
+        assert!(reply.starts_with("Sending 2 TXTC"), "{}", reply);
+        // Opened once, for the first send, then reused
+        assert_eq!(*requested.lock().unwrap(), [serde_json::json!(channel), serde_json::json!(channel)]);
+        assert_eq!(opens.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let channels = ChannelRepository::new(pool);
+        assert_eq!(channels.get_open_channel(&phone).await.unwrap().unwrap().balance, Some(40.0));
+        channels.mark_closed(&channel).await.unwrap();
+        assert!(channels.get_open_channel(&phone).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_new_wallet_sends_after_min_age_or_deposit() {
+        use axum::{routing::post, Json, Router};
+        let backend = Router::new().route("/api/send-yellow", post(|| async { Json(serde_json::json!({ "success": true })) }));
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.min_wallet_age_secs = 24 * 3600;
+        let users = UserRepository::new(pool.clone());
+        let to = "0x1234567890abcdef1234567890abcdef12345678";
+        let send = format!("SEND 1 TXTC TO {}", to);
+
+        let fresh = random_phone();
+        users.seed_user(&fresh).await.unwrap();
+        assert_eq!(
+            processor.process(&fresh, &send).await,
+            "New wallets can't send yet.\nSending opens in 1440 min, or as soon as you DEPOSIT funds."
+        );
+        // A welcome bonus isn't the user's own money
+        DepositRepository::new(pool.clone()).create_welcome_bonus(&fresh, 1_000_000).await.unwrap();
+        assert!(processor.process(&fresh, &send).await.starts_with("New wallets can't send yet."));
+
+        let funded = random_phone();
+        users.seed_user(&funded).await.unwrap();
+        DepositRepository::new(pool.clone()).seed_deposit(&funded, 1_000_000).await.unwrap();
+        assert!(processor.process(&funded, &send).await.starts_with("Sending 1 TXTC"));
+
+        let aged = random_phone();
+        users.seed_user(&aged).await.unwrap();
+        sqlx::query("UPDATE users SET created_at = NOW() - INTERVAL '2 days' WHERE phone = $1")
+            .bind(&aged)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(processor.process(&aged, &send).await.starts_with("Sending 1 TXTC"));
+
+        // Trusted segments skip the rule
+        processor.config.wallet_age_exempt = vec![fresh.clone()];
+        assert!(processor.process(&fresh, &format!("SEND 2 TXTC TO {}", to)).await.starts_with("Sending 2 TXTC"));
+    }
+
+    #[tokio::test]
+    async fn test_freeze_blocks_send_until_unfreeze() {
+        use axum::{routing::post, Json, Router};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let sends = Arc::new(AtomicUsize::new(0));
+        let counter = sends.clone();
+        let backend = Router::new().route(
+            "/api/send-yellow",
+            post(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Json(serde_json::json!({ "success": true }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.rate_limiter = RateLimiter::new(0, std::time::Duration::ZERO);
+        let users = UserRepository::new(pool);
+        let phone = random_phone();
+        let victim = users.seed_user(&phone).await.unwrap();
+        let friend = random_phone();
+        users.seed_user(&friend).await.unwrap();
+        let to = "0x1234567890abcdef1234567890abcdef12345678";
+
+        assert_eq!(processor.process(&phone, "PIN 1234").await, "PIN set!");
+        assert!(processor.process(&phone, "UNLOCK 1234").await.starts_with("Unlocked"));
+        assert!(processor.process(&phone, &format!("SEND 1 TXTC TO {}", to)).await.starts_with("Sending 1 TXTC"));
+
+        // No PIN needed to freeze, and the open session ends with it
+        assert_eq!(
+            processor.process(&phone, "FREEZE").await,
+            "Account frozen. Sends, swaps and cashouts are blocked; you can still receive.\nReply UNFREEZE <your PIN> to restore."
+        );
+        assert_eq!(
+            processor.process(&phone, &format!("SEND 2 TXTC TO {}", to)).await,
+            "Account frozen. SEND is blocked.\nReply UNFREEZE <your PIN> to restore."
+        );
+        assert!(processor.process(&phone, "PIN 5678").await.starts_with("Account frozen. PIN is blocked."));
+        assert_eq!(sends.load(Ordering::SeqCst), 1);
+
+        // Still receives
+        let incoming = format!("SEND 3 TXTC TO {}", victim.wallet_address);
+        assert!(processor.process(&friend, &incoming).await.starts_with("Sending 3 TXTC"));
+        assert_eq!(sends.load(Ordering::SeqCst), 2);
+
+        assert_eq!(processor.process(&phone, "UNFREEZE 9999").await, "Wrong PIN. Account stays frozen.");
+        assert_eq!(processor.process(&phone, "UNFREEZE 1234").await, "Account unfrozen. Sends are back on.");
+        assert_eq!(processor.process(&phone, "UNFREEZE 1234").await, "Account isn't frozen.");
+        assert!(processor.process(&phone, &format!("SEND 4 TXTC TO {}", to)).await.starts_with("PIN needed"));
+        assert!(processor.process(&phone, "UNLOCK 1234").await.starts_with("Sending 4 TXTC"));
+        assert_eq!(sends.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_pins_lock_pin_entry() {
+        let Some((mut processor, pool)) = crate::test_support::db_processor("http://127.0.0.1:9").await else { return };
+        processor.rate_limiter = RateLimiter::new(0, std::time::Duration::ZERO);
+        processor.config.pin_max_attempts = 3;
+        processor.config.pin_lockout_secs = 900;
+        let phone = random_phone();
+        UserRepository::new(pool.clone()).seed_user(&phone).await.unwrap();
+        let expire_lockout = || async {
+            sqlx::query("UPDATE users SET pin_locked_until = NOW() WHERE phone = $1")
+                .bind(&phone)
+                .execute(&pool)
+                .await
+                .unwrap();
+        };
+        assert_eq!(processor.process(&phone, "PIN 1234").await, "PIN set!");
+
+        assert!(processor.process(&phone, "UNLOCK 0000").await.starts_with("Wrong PIN."));
+        assert_eq!(processor.process(&phone, "FREEZE").await.lines().next(), Some("Account frozen. Sends, swaps and cashouts are blocked; you can still receive."));
+        assert_eq!(processor.process(&phone, "UNFREEZE 0000").await, "Wrong PIN. Account stays frozen.");
+        // The third wrong PIN in a row, whichever command it came with, locks PIN entry
+        assert_eq!(processor.process(&phone, "UNFREEZE 0000").await, "Too many wrong PINs. Try again in 15 min.");
+        assert_eq!(processor.process(&phone, "UNFREEZE 1234").await, "Too many wrong PINs. Try again in 15 min.");
+
+        // Each further miss doubles the lockout
+        expire_lockout().await;
+        assert_eq!(processor.process(&phone, "UNFREEZE 0000").await, "Too many wrong PINs. Try again in 30 min.");
+
+        // The right PIN once the lockout ends clears the count
+        expire_lockout().await;
+        assert_eq!(processor.process(&phone, "UNFREEZE 1234").await, "Account unfrozen. Sends are back on.");
+        assert!(processor.process(&phone, "UNLOCK 0000").await.starts_with("Wrong PIN."));
+        assert!(processor.process(&phone, "UNLOCK 1234").await.starts_with("Unlocked"));
+    }
+
+    #[tokio::test]
+    async fn test_completed_send_emits_events_to_webhook() {
+        use axum::{http::HeaderMap, routing::post, Json, Router};
+        let backend = Router::new().route(
+            "/api/send-yellow",
+            post(|| async { Json(serde_json::json!({ "success": true, "txHash": "0xfeed" })) }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+
+        let posts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = posts.clone();
+        let hook = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: String| async move {
+                let signature = headers[crate::events::SIGNATURE_HEADER].to_str().unwrap().to_string();
+                recorded.lock().unwrap().push((signature, body));
+            }),
+        );
+        let hook_url = crate::test_support::spawn_mock_backend(hook).await;
+        crate::events::spawn_webhook(processor.subscribe_events(), format!("{}/hook", hook_url), "hook-secret".to_string());
+        let mut events = processor.subscribe_events();
+
+        let phone = random_phone();
+        let sender = UserRepository::new(pool).seed_user(&phone).await.unwrap();
+        let to = "0x1234567890abcdef1234567890abcdef12345678";
+        assert!(processor.process(&phone, &format!("SEND 3 TXTC TO {}", to)).await.starts_with("Sending 3 TXTC"));
+
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WalletEvent::TransferCompleted {
+                from_address: sender.wallet_address.clone(),
+                to_address: to.to_string(),
+                amount: 3.0,
+                token: "TXTC".to_string(),
+                tx_hash: Some("0xfeed".to_string()),
+            }
+        );
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WalletEvent::BalanceChanged { address: sender.wallet_address.clone(), token: "TXTC".to_string(), delta: -3.0 }
+        );
+        assert_eq!(
+            events.try_recv().unwrap(),
+            WalletEvent::BalanceChanged { address: to.to_string(), token: "TXTC".to_string(), delta: 3.0 }
+        );
+
+        for _ in 0..50 {
+            if posts.lock().unwrap().len() == 3 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let posts = posts.lock().unwrap();
+        assert_eq!(posts.len(), 3);
+        let (signature, body) = &posts[0];
+        assert_eq!(*signature, crate::events::sign("hook-secret", body.as_bytes()));
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["event"]["type"], "transfer_completed");
+        assert_eq!(body["event"]["txHash"], "0xfeed");
+    }
+
+    #[tokio::test]
+    async fn test_completed_send_links_signed_receipt() {
+        use axum::{routing::post, Json, Router};
+        let backend = Router::new().route(
+            "/api/send-yellow",
+            post(|| async { Json(serde_json::json!({ "success": true, "transactionId": "yellow-42" })) }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.receipt_signing_key = "service-key".to_string();
+        processor.config.public_url = "https://pay.example".to_string();
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+        let to = "0x1234567890abcdef1234567890abcdef12345678";
+
+        let reply = processor.process(&phone, &format!("SEND 3 TXTC TO {}", to)).await;
+        let reference = reply.split("https://pay.example/receipt/").nth(1).expect(&reply);
+
+        let (receipt, verified) = processor.find_receipt(&reference.to_lowercase()).await.unwrap();
+        assert!(verified);
+        assert_eq!((receipt.amount, receipt.token.as_str(), receipt.to_address.as_str()), (3.0, "TXTC", to));
+        assert_eq!(receipt.tx_hash.as_deref(), Some("yellow-42"));
+        assert_eq!(
+            processor.receipt_explorer_url(&receipt).as_deref(),
+            Some("https://sepolia.etherscan.io/tx/yellow-42")
+        );
+        processor.config.send_chain = "base".to_string();
+        assert_eq!(processor.receipt_explorer_url(&receipt).as_deref(), Some("https://basescan.org/tx/yellow-42"));
+
+        // Signed under a different key: fails verification on read
+        processor.config.receipt_signing_key = "rotated-key".to_string();
+        assert!(!processor.find_receipt(reference).await.unwrap().1);
+        assert!(processor.find_receipt("NOSUCHREF").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rapid_identical_send_held_for_confirmation() {
         let processor = test_processor();
-        
-        let cmd = processor.parse("FOOBAR");
-        assert!(matches!(cmd, Command::Unknown(_)));
+        let from = "+15550002222";
+
+        assert_eq!(processor.process(from, "SEND 5 TXTC TO alice").await, "DB offline. Try later.");
+        // A different send goes straight through
+        assert_eq!(processor.process(from, "SEND 6 TXTC TO alice").await, "DB offline. Try later.");
+
+        let prompt = processor.process(from, "send 5 txtc to ALICE").await;
+        assert!(prompt.starts_with("You just did this. Again: send 5 TXTC to ALICE?"), "{}", prompt);
+        let code = prompt.split_whitespace().skip_while(|w| *w != "CONFIRM").nth(1).unwrap();
+        assert_eq!(processor.process(from, &format!("CONFIRM {}", code)).await, "DB offline. Try later.");
+    }
+
+    #[tokio::test]
+    async fn test_token_confirmation_requires_code() {
+        let mut processor = test_processor();
+        processor.config.confirm_above = 100.0;
+        processor.config.confirm_by_keyword = false;
+        let from = "+15550002222";
+
+        let prompt = processor.process(from, "SEND 500 TXTC TO alice").await;
+        let code = prompt.split_whitespace().skip_while(|w| *w != "CONFIRM").nth(1).unwrap().to_string();
+        assert_eq!(processor.process(from, "YES").await, "Usage: CONFIRM <code>");
+        assert_eq!(processor.process(from, "CONFIRM 000000X").await, "Code not found or expired.");
+        assert_eq!(processor.process(from, &format!("confirm {}", code.to_lowercase())).await, "DB offline. Try later.");
+        assert_eq!(processor.process(from, &format!("CONFIRM {}", code)).await, "Already confirmed.");
+    }
+
+    #[tokio::test]
+    async fn test_expired_send_is_reported_not_sent() {
+        let mut processor = test_processor();
+        processor.config.confirm_above = 100.0;
+        processor.config.confirm_by_keyword = true;
+        processor.pending = PendingStore::new(std::time::Duration::ZERO);
+        let from = "+15550003333";
+
+        processor.process(from, "SEND 500 TXTC TO alice").await;
+        assert_eq!(
+            processor.process(from, "YES").await,
+            "That send expired and wasn't made.\nSend it again if you still want to."
+        );
+        assert_eq!(processor.process(from, "YES").await, "Nothing to confirm.");
+        processor.process(from, "SEND 500 TXTC TO alice").await;
+        assert_eq!(processor.process(from, "NO").await, "That send had already expired. Nothing was sent.");
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_confirms_execute_send_once() {
+        use axum::{routing::post, Json, Router};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let sends = Arc::new(AtomicUsize::new(0));
+        let counter = sends.clone();
+        let backend = Router::new().route(
+            "/api/send-yellow",
+            post(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Json(serde_json::json!({ "success": true })) }
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.confirm_above = 1.0;
+        processor.config.confirm_by_keyword = false;
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+
+        let prompt = processor.process(&phone, "SEND 5 TXTC TO 0x1234567890abcdef1234567890abcdef12345678").await;
+        let code = prompt.split_whitespace().skip_while(|w| *w != "CONFIRM").nth(1).unwrap().to_string();
+        let confirm = format!("CONFIRM {}", code);
+
+        let (first, second) = tokio::join!(processor.process(&phone, &confirm), processor.process(&phone, &confirm));
+        let mut replies = [first, second];
+        replies.sort();
+        assert_eq!(replies[0], "Already confirmed.");
+        assert!(replies[1].starts_with("Sending 5 TXTC"), "{}", replies[1]);
+        assert_eq!(sends.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_first_payment_to_recipient_needs_confirming() {
+        use axum::{routing::post, Json, Router};
+        let backend = Router::new().route(
+            "/api/send-yellow",
+            post(|| async { Json(serde_json::json!({ "success": true })) }),
+        );
+        let url = crate::test_support::spawn_mock_backend(backend).await;
+        let Some((mut processor, pool)) = crate::test_support::db_processor(&url).await else { return };
+        processor.config.confirm_new_recipients = true;
+        processor.config.confirm_by_keyword = false;
+        let phone = random_phone();
+        UserRepository::new(pool).seed_user(&phone).await.unwrap();
+        let recipient = format!("0x{:040x}", rand::random::<u64>());
+
+        let prompt = processor.process(&phone, &format!("SEND 5 TXTC TO {}", recipient)).await;
+        assert!(
+            prompt.starts_with(&format!("First time paying {}. Confirm: send 5 TXTC to {}?", recipient, recipient)),
+            "{}",
+            prompt
+        );
+        let code = prompt.split_whitespace().skip_while(|w| *w != "CONFIRM").nth(1).unwrap().to_string();
+        assert!(processor.process(&phone, &format!("CONFIRM {}", code)).await.starts_with("Sending 5 TXTC"));
+
+        // Paid before: goes straight through
+        let reply = processor.process(&phone, &format!("SEND 6 TXTC TO {}", recipient)).await;
+        assert!(reply.starts_with("Sending 6 TXTC"), "{}", reply);
+
+        // Off: no gate even for someone new
+        processor.config.confirm_new_recipients = false;
+        let other = format!("0x{:040x}", rand::random::<u64>());
+        let reply = processor.process(&phone, &format!("SEND 5 TXTC TO {}", other)).await;
+        assert!(reply.starts_with("Sending 5 TXTC"), "{}", reply);
     }
 }
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::db::{PendingTx, PendingTxRepository};
+use crate::wallet::format_display_amount;
+
+/// Action waiting for the user to confirm it by SMS
+#[derive(Debug, Clone, PartialEq)]
+pub enum PendingKind {
+    Send {
+        amount: f64,
+        token: String,
+        recipient: String,
+        note: Option<String>,
+    },
+}
+
+impl PendingKind {
+    /// Format for SMS display
+    pub fn to_sms_string(&self, precision: usize) -> String {
+        match self {
+            PendingKind::Send { amount, token, recipient, note } => {
+                let note = note.as_ref().map(|note| format!(" ({})", note)).unwrap_or_default();
+                format!("send {} {} to {}{}", format_display_amount(*amount, precision), token.to_uppercase(), recipient, note)
+            }
+        }
+    }
+}
+
+impl From<&PendingTx> for PendingKind {
+    fn from(tx: &PendingTx) -> Self {
+        PendingKind::Send {
+            amount: tx.amount,
+            token: tx.token.clone(),
+            recipient: tx.recipient.clone(),
+            note: tx.note.clone(),
+        }
+    }
+}
+
+/// Pending action with the code that confirms it
+#[derive(Debug, Clone)]
+pub struct PendingAction {
+    pub code: String,
+    pub kind: PendingKind,
+    created_at: Instant,
+}
+
+impl From<&PendingTx> for PendingAction {
+    fn from(tx: &PendingTx) -> Self {
+        PendingAction { code: tx.code.clone(), kind: tx.into(), created_at: Instant::now() }
+    }
+}
+
+/// Why a pending action couldn't be picked
+#[derive(Debug, PartialEq)]
+pub enum PickError {
+    /// Nothing pending
+    Empty,
+    /// What would have been picked expired before it was confirmed
+    Expired,
+    /// No pending action matches the code/number
+    NotFound,
+    /// Several actions pending and none was chosen
+    Ambiguous(Vec<PendingKind>),
+    /// The code was already used to confirm (true) or cancel (false)
+    AlreadyUsed { confirmed: bool },
+    /// The database behind the store couldn't be reached
+    Unavailable,
+}
+
+fn unavailable(e: sqlx::Error) -> PickError {
+    tracing::error!("Pending send storage failed: {}", e);
+    PickError::Unavailable
+}
+
+/// Index of the n-th (1-based) action, or of the only one when `index` is None
+fn pick(kinds: &[PendingKind], index: Option<usize>) -> Result<usize, PickError> {
+    match index {
+        Some(n) if n >= 1 && n <= kinds.len() => Ok(n - 1),
+        Some(_) => Err(PickError::NotFound),
+        None if kinds.len() == 1 => Ok(0),
+        None => Err(PickError::Ambiguous(kinds.to_vec())),
+    }
+}
+
+/// Code that resolved an action, kept until the TTL runs out to refuse replays
+#[derive(Debug)]
+struct UsedCode {
+    code: String,
+    confirmed: bool,
+    used_at: Instant,
+}
+
+#[derive(Default)]
+struct PendingState {
+    actions: HashMap<String, Vec<PendingAction>>,
+    used: HashMap<String, Vec<UsedCode>>,
+}
+
+/// Per-phone store of actions awaiting confirmation. Entries expire after
+/// the TTL; clones share the same store. Kept in memory unless given a
+/// `PendingTxRepository`, which survives restarts and is shared between
+/// instances. Either way taking an action consumes its code atomically, so
+/// a replayed or concurrent second CONFIRM of the same code never gets it.
+#[derive(Clone)]
+pub struct PendingStore {
+    state: Arc<Mutex<PendingState>>,
+    ttl: Duration,
+    repo: Option<PendingTxRepository>,
+}
+
+impl PendingStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(PendingState::default())),
+            ttl,
+            repo: None,
+        }
+    }
+
+    /// Keep pending actions in the database instead of memory
+    pub fn with_repo(mut self, repo: PendingTxRepository) -> Self {
+        self.repo = Some(repo);
+        self
+    }
+
+    /// Queue an action for a phone and return its confirmation code
+    pub async fn add(&self, phone: &str, kind: PendingKind) -> Result<String, sqlx::Error> {
+        let code = hex::encode_upper(rand::random::<[u8; 3]>());
+        if let Some(ref repo) = self.repo {
+            let PendingKind::Send { amount, ref token, ref recipient, ref note } = kind;
+            repo.create(phone, &code, amount, token, recipient, note.as_deref()).await?;
+            return Ok(code);
+        }
+        let mut state = self.state.lock().unwrap();
+        let list = state.actions.entry(phone.to_string()).or_default();
+        list.retain(|a| a.created_at.elapsed() < self.ttl);
+        list.push(PendingAction { code: code.clone(), kind, created_at: Instant::now() });
+        Ok(code)
+    }
+
+    /// How many unexpired actions the phone has waiting
+    pub async fn count(&self, phone: &str) -> Result<usize, sqlx::Error> {
+        if let Some(ref repo) = self.repo {
+            return Ok(repo.live(phone, self.ttl).await?.0.len());
+        }
+        let state = self.state.lock().unwrap();
+        Ok(state.actions.get(phone).map_or(0, |list| list.iter().filter(|a| a.created_at.elapsed() < self.ttl).count()))
+    }
+
+    /// Remove and return the n-th (1-based) action, or the only one when `index` is None.
+    /// Never guesses: with several pending and no index, nothing is removed.
+    /// `confirmed` records whether the action is being confirmed or cancelled.
+    pub async fn take(&self, phone: &str, index: Option<usize>, confirmed: bool) -> Result<PendingAction, PickError> {
+        let Some(ref repo) = self.repo else {
+            return self.take_where(phone, confirmed, |list| {
+                pick(&list.iter().map(|a| a.kind.clone()).collect::<Vec<_>>(), index)
+            });
+        };
+        let (live, expired) = repo.live(phone, self.ttl).await.map_err(unavailable)?;
+        if live.is_empty() {
+            return Err(if expired > 0 { PickError::Expired } else { PickError::Empty });
+        }
+        let kinds: Vec<PendingKind> = live.iter().map(PendingKind::from).collect();
+        Self::claim(repo, &live[pick(&kinds, index)?], confirmed).await
+    }
+
+    /// Remove and return the action with this confirmation code
+    pub async fn take_by_code(&self, phone: &str, code: &str, confirmed: bool) -> Result<PendingAction, PickError> {
+        let Some(ref repo) = self.repo else {
+            return self.take_by_code_in_memory(phone, code, confirmed);
+        };
+        match repo.find_code(phone, code).await.map_err(unavailable)? {
+            Some(tx) if tx.used_at.is_some() && !tx.is_older_than(self.ttl) => {
+                Err(PickError::AlreadyUsed { confirmed: tx.confirmed.unwrap_or(false) })
+            }
+            Some(tx) if tx.used_at.is_none() && tx.is_older_than(self.ttl) => Err(PickError::Expired),
+            Some(tx) if tx.used_at.is_none() => Self::claim(repo, &tx, confirmed).await,
+            _ => {
+                let (live, expired) = repo.live(phone, self.ttl).await.map_err(unavailable)?;
+                Err(match (live.is_empty(), expired) {
+                    (false, _) => PickError::NotFound,
+                    (true, 0) => PickError::Empty,
+                    (true, _) => PickError::Expired,
+                })
+            }
+        }
+    }
+
+    /// Mark a stored action used; whoever loses a race is told how it went
+    async fn claim(repo: &PendingTxRepository, tx: &PendingTx, confirmed: bool) -> Result<PendingAction, PickError> {
+        if repo.claim(tx.id, confirmed).await.map_err(unavailable)? {
+            return Ok(tx.into());
+        }
+        let used = repo.find_code(&tx.phone, &tx.code).await.map_err(unavailable)?;
+        Err(PickError::AlreadyUsed { confirmed: used.and_then(|u| u.confirmed).unwrap_or(confirmed) })
+    }
+
+    fn take_by_code_in_memory(&self, phone: &str, code: &str, confirmed: bool) -> Result<PendingAction, PickError> {
+        let mut state = self.state.lock().unwrap();
+        let ttl = self.ttl;
+        if let Some(used) = state.used.get_mut(phone) {
+            used.retain(|u| u.used_at.elapsed() < ttl);
+            if let Some(u) = used.iter().find(|u| u.code.eq_ignore_ascii_case(code)) {
+                return Err(PickError::AlreadyUsed { confirmed: u.confirmed });
+            }
+        }
+        let expired = state.actions.get(phone).is_some_and(|list| {
+            list.iter().any(|a| a.code.eq_ignore_ascii_case(code) && a.created_at.elapsed() >= ttl)
+        });
+        if expired {
+            return Err(PickError::Expired);
+        }
+        self.take_locked(&mut state, phone, confirmed, |list| {
+            list.iter()
+                .position(|a| a.code.eq_ignore_ascii_case(code))
+                .ok_or(PickError::NotFound)
+        })
+    }
+
+    fn take_where(
+        &self,
+        phone: &str,
+        confirmed: bool,
+        pick: impl FnOnce(&[PendingAction]) -> Result<usize, PickError>,
+    ) -> Result<PendingAction, PickError> {
+        let mut state = self.state.lock().unwrap();
+        self.take_locked(&mut state, phone, confirmed, pick)
+    }
+
+    fn take_locked(
+        &self,
+        state: &mut PendingState,
+        phone: &str,
+        confirmed: bool,
+        pick: impl FnOnce(&[PendingAction]) -> Result<usize, PickError>,
+    ) -> Result<PendingAction, PickError> {
+        let Some(list) = state.actions.get_mut(phone) else {
+            return Err(PickError::Empty);
+        };
+        let before = list.len();
+        list.retain(|a| a.created_at.elapsed() < self.ttl);
+        if list.is_empty() {
+            return Err(if before > 0 { PickError::Expired } else { PickError::Empty });
+        }
+        let action = list.remove(pick(list)?);
+
+        let used = state.used.entry(phone.to_string()).or_default();
+        used.retain(|u| u.used_at.elapsed() < self.ttl);
+        used.push(UsedCode { code: action.code.clone(), confirmed, used_at: Instant::now() });
+        Ok(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(amount: f64) -> PendingKind {
+        PendingKind::Send { amount, token: "txtc".to_string(), recipient: "alice".to_string(), note: None }
+    }
+
+    /// An in-memory store, plus a database-backed one when TEST_DATABASE_URL
+    /// is set, each with fresh phones: both must behave the same
+    async fn stores(ttl: Duration) -> Vec<(PendingStore, String, String)> {
+        let phone = || crate::test_support::random_phone();
+        let mut stores = vec![(PendingStore::new(ttl), phone(), phone())];
+        if let Some(pool) = crate::db::test_pool().await {
+            stores.push((PendingStore::new(ttl).with_repo(PendingTxRepository::new(pool)), phone(), phone()));
+        }
+        stores
+    }
+
+    #[tokio::test]
+    async fn test_take_never_guesses_between_actions() {
+        for (store, phone, _) in stores(Duration::from_secs(60)).await {
+            assert_eq!(store.take(&phone, None, true).await.unwrap_err(), PickError::Empty);
+
+            store.add(&phone, send(1.0)).await.unwrap();
+            let code = store.add(&phone, send(2.0)).await.unwrap();
+            assert!(matches!(store.take(&phone, None, true).await, Err(PickError::Ambiguous(kinds)) if kinds.len() == 2));
+            assert_eq!(store.take(&phone, Some(3), true).await.unwrap_err(), PickError::NotFound);
+
+            assert_eq!(store.take_by_code(&phone, &code.to_lowercase(), true).await.unwrap().kind, send(2.0));
+            assert_eq!(store.take(&phone, None, true).await.unwrap().kind, send(1.0));
+            assert_eq!(store.take(&phone, None, true).await.unwrap_err(), PickError::Empty);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_used_code_is_refused() {
+        for (store, phone, other) in stores(Duration::from_secs(60)).await {
+            let confirmed = store.add(&phone, send(1.0)).await.unwrap();
+            let cancelled = store.add(&phone, send(2.0)).await.unwrap();
+
+            assert!(store.take_by_code(&phone, &confirmed, true).await.is_ok());
+            assert!(store.take_by_code(&phone, &cancelled, false).await.is_ok());
+            assert_eq!(store.take_by_code(&phone, &confirmed, true).await.unwrap_err(), PickError::AlreadyUsed { confirmed: true });
+            assert_eq!(store.take_by_code(&phone, &cancelled, true).await.unwrap_err(), PickError::AlreadyUsed { confirmed: false });
+            // Codes are per phone
+            assert_eq!(store.take_by_code(&other, &confirmed, true).await.unwrap_err(), PickError::Empty);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expired_actions_are_dropped() {
+        for (store, phone, _) in stores(Duration::ZERO).await {
+            store.add(&phone, send(1.0)).await.unwrap();
+            assert_eq!(store.take(&phone, None, true).await.unwrap_err(), PickError::Expired);
+            // Reported once; after that there's nothing pending
+            assert_eq!(store.take(&phone, None, true).await.unwrap_err(), PickError::Empty);
+
+            let code = store.add(&phone, send(1.0)).await.unwrap();
+            assert_eq!(store.take_by_code(&phone, &code, true).await.unwrap_err(), PickError::Expired);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stored_send_survives_a_restart() {
+        let Some(pool) = crate::db::test_pool().await else { return };
+        let phone = crate::test_support::random_phone();
+        let ttl = Duration::from_secs(60);
+        let before = PendingStore::new(ttl).with_repo(PendingTxRepository::new(pool.clone()));
+        let code = before.add(&phone, send(5.0)).await.unwrap();
+        drop(before);
+
+        // A new process (or another instance) sees the same send, once
+        let after = PendingStore::new(ttl).with_repo(PendingTxRepository::new(pool.clone()));
+        let other = PendingStore::new(ttl).with_repo(PendingTxRepository::new(pool));
+        assert_eq!(after.take_by_code(&phone, &code, true).await.unwrap().kind, send(5.0));
+        assert_eq!(other.take_by_code(&phone, &code, true).await.unwrap_err(), PickError::AlreadyUsed { confirmed: true });
+    }
+}
@@ -0,0 +1,94 @@
+use dashmap::DashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Token bucket per phone number: `capacity` commands, refilled evenly over
+/// `window`, so a flood of SMS can't turn into unlimited backend calls.
+/// Buckets live in a sharded map, so senders don't wait on each other.
+/// Clones share the same buckets.
+#[derive(Clone)]
+pub struct RateLimiter {
+    capacity: u32,
+    window: Duration,
+    buckets: Arc<DashMap<String, Bucket>>,
+    /// When idle buckets were last cleared out
+    swept_at: Arc<Mutex<Instant>>,
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// `capacity` commands per `window` for each phone (0 = unlimited)
+    pub fn new(capacity: u32, window: Duration) -> Self {
+        Self {
+            capacity,
+            window,
+            buckets: Arc::new(DashMap::new()),
+            swept_at: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Take a token for `phone`; false when their bucket is empty
+    pub fn allow(&self, phone: &str) -> bool {
+        if self.capacity == 0 || self.window.is_zero() {
+            return true;
+        }
+        let now = Instant::now();
+        self.sweep(now);
+
+        let capacity = f64::from(self.capacity);
+        let mut bucket = self
+            .buckets
+            .entry(phone.to_string())
+            .or_insert(Bucket { tokens: capacity, refilled_at: now });
+        let refill = now.duration_since(bucket.refilled_at).as_secs_f64() / self.window.as_secs_f64() * capacity;
+        bucket.tokens = (bucket.tokens + refill).min(capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    /// Drop buckets idle for a whole window, which are full again, same as
+    /// no bucket. Runs at most once per window, and is skipped while another
+    /// call is already at it.
+    fn sweep(&self, now: Instant) {
+        let Ok(mut swept_at) = self.swept_at.try_lock() else { return };
+        if now.duration_since(*swept_at) < self.window {
+            return;
+        }
+        *swept_at = now;
+        self.buckets.retain(|_, bucket| now.duration_since(bucket.refilled_at) < self.window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sixth_rapid_command_is_throttled_until_refill() {
+        let limiter = RateLimiter::new(5, Duration::from_millis(300));
+        for _ in 0..5 {
+            assert!(limiter.allow("+1"));
+        }
+        assert!(!limiter.allow("+1"));
+        assert!(limiter.allow("+2"), "buckets are per phone");
+
+        tokio::time::sleep(Duration::from_millis(320)).await;
+        for _ in 0..5 {
+            assert!(limiter.allow("+1"));
+        }
+        // The idle +2 bucket was swept on the way
+        assert_eq!(limiter.buckets.len(), 1);
+
+        assert!(RateLimiter::new(0, Duration::from_secs(60)).allow("+1"));
+    }
+}
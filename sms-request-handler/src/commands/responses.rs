@@ -0,0 +1,83 @@
+/// Replies in the user's language. English is the source text: each table
+/// maps a fixed English phrase to its translation, and phrases a table
+/// doesn't cover are left in English.
+const TRANSLATIONS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "fr",
+        &[
+            ("Balance:", "Solde :"),
+            ("TXTC available", "TXTC disponibles"),
+            ("In channel:", "En canal :"),
+            ("Pending out:", "Envoi en cours :"),
+            ("Held:", "Bloqué :"),
+            ("Total:", "Total :"),
+            ("Other chains:", "Autres réseaux :"),
+            ("Reply DEPOSIT to fund wallet.", "Répondez DEPOSER pour alimenter votre portefeuille."),
+            ("No wallet. Reply JOIN first.", "Pas de portefeuille. Répondez INSCRIRE d'abord."),
+            ("Network error. Try later.", "Erreur réseau. Réessayez plus tard."),
+            ("Error. Try later.", "Erreur. Réessayez plus tard."),
+            ("Invalid amount", "Montant invalide"),
+        ],
+    ),
+    (
+        "es",
+        &[
+            ("Balance:", "Saldo:"),
+            ("TXTC available", "TXTC disponibles"),
+            ("In channel:", "En canal:"),
+            ("Pending out:", "Envío pendiente:"),
+            ("Held:", "Retenido:"),
+            ("Other chains:", "Otras redes:"),
+            ("Reply DEPOSIT to fund wallet.", "Responda DEPOSITAR para cargar su billetera."),
+            ("No wallet. Reply JOIN first.", "Sin billetera. Responda REGISTRAR primero."),
+            ("Network error. Try later.", "Error de red. Intente más tarde."),
+            ("Error. Try later.", "Error. Intente más tarde."),
+            ("Invalid amount", "Monto inválido"),
+        ],
+    ),
+    (
+        "pt",
+        &[
+            ("Balance:", "Saldo:"),
+            ("TXTC available", "TXTC disponíveis"),
+            ("In channel:", "No canal:"),
+            ("Pending out:", "Envio pendente:"),
+            ("Held:", "Retido:"),
+            ("Other chains:", "Outras redes:"),
+            ("Reply DEPOSIT to fund wallet.", "Responda DEPOSITAR para abastecer a carteira."),
+            ("No wallet. Reply JOIN first.", "Sem carteira. Responda CADASTRAR primeiro."),
+            ("Network error. Try later.", "Erro de rede. Tente mais tarde."),
+            ("Error. Try later.", "Erro. Tente mais tarde."),
+            ("Invalid amount", "Valor inválido"),
+        ],
+    ),
+];
+
+/// `reply` with every phrase `language` has a translation for replaced
+pub fn localize(reply: &str, language: &str) -> String {
+    let Some((_, table)) = TRANSLATIONS.iter().find(|(l, _)| l.eq_ignore_ascii_case(language)) else {
+        return reply.to_string();
+    };
+    table
+        .iter()
+        .fold(reply.to_string(), |reply, (english, translated)| reply.replace(english, translated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_localize_known_phrases() {
+        assert_eq!(
+            localize("Balance:\n12.5 TXTC\n0.01 ETH\n\nSepolia testnet", "pt"),
+            "Saldo:\n12.5 TXTC\n0.01 ETH\n\nSepolia testnet"
+        );
+        assert_eq!(localize("No wallet. Reply JOIN first.", "fr"), "Pas de portefeuille. Répondez INSCRIRE d'abord.");
+        // The longer phrase isn't clobbered by the shorter one inside it
+        assert_eq!(localize("Network error. Try later.", "es"), "Error de red. Intente más tarde.");
+        // Untranslated text and unknown languages stay English
+        assert_eq!(localize("Unlocked for 5 min.", "fr"), "Unlocked for 5 min.");
+        assert_eq!(localize("Balance:", "de"), "Balance:");
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Unlocked PIN sessions per phone, plus the command each user sent while
+/// locked so it can run once they UNLOCK. Clones share the same store.
+#[derive(Clone)]
+pub struct PinSessions {
+    window: Duration,
+    held_ttl: Duration,
+    unlocked: Arc<Mutex<HashMap<String, Instant>>>,
+    held: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+}
+
+impl PinSessions {
+    /// Sessions last `window` (zero = PIN every time); held commands `held_ttl`
+    pub fn new(window: Duration, held_ttl: Duration) -> Self {
+        Self {
+            window,
+            held_ttl,
+            unlocked: Arc::new(Mutex::new(HashMap::new())),
+            held: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Whether `phone` entered their PIN within the window
+    pub fn is_unlocked(&self, phone: &str) -> bool {
+        let unlocked = self.unlocked.lock().unwrap();
+        unlocked.get(phone).is_some_and(|at| at.elapsed() < self.window)
+    }
+
+    /// Start a session after a correct PIN
+    pub fn unlock(&self, phone: &str) {
+        if self.window.is_zero() {
+            return;
+        }
+        let mut unlocked = self.unlocked.lock().unwrap();
+        unlocked.retain(|_, at| at.elapsed() < self.window);
+        unlocked.insert(phone.to_string(), Instant::now());
+    }
+
+    /// End the session and drop any held command (LOCK or a wrong PIN)
+    pub fn lock(&self, phone: &str) {
+        self.unlocked.lock().unwrap().remove(phone);
+        self.held.lock().unwrap().remove(phone);
+    }
+
+    /// Keep the text of a command waiting on the PIN, replacing an older one
+    pub fn hold(&self, phone: &str, text: &str) {
+        let mut held = self.held.lock().unwrap();
+        held.retain(|_, (_, at)| at.elapsed() < self.held_ttl);
+        held.insert(phone.to_string(), (text.to_string(), Instant::now()));
+    }
+
+    /// The command held for `phone`, if it hasn't expired
+    pub fn take_held(&self, phone: &str) -> Option<String> {
+        let (text, at) = self.held.lock().unwrap().remove(phone)?;
+        (at.elapsed() < self.held_ttl).then_some(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_unlocks_until_locked() {
+        let sessions = PinSessions::new(Duration::from_secs(300), Duration::from_secs(300));
+        assert!(!sessions.is_unlocked("+1"));
+
+        sessions.hold("+1", "SEND 5 TXTC TO bob");
+        sessions.unlock("+1");
+        assert!(sessions.is_unlocked("+1"));
+        assert!(!sessions.is_unlocked("+2"));
+        assert_eq!(sessions.take_held("+1").as_deref(), Some("SEND 5 TXTC TO bob"));
+        assert_eq!(sessions.take_held("+1"), None);
+
+        sessions.hold("+1", "CASHOUT 1 ETH");
+        sessions.lock("+1");
+        assert!(!sessions.is_unlocked("+1"));
+        assert_eq!(sessions.take_held("+1"), None);
+
+        // No window: every sensitive command needs the PIN
+        let strict = PinSessions::new(Duration::ZERO, Duration::from_secs(300));
+        strict.unlock("+1");
+        assert!(!strict.is_unlocked("+1"));
+    }
+}
@@ -0,0 +1,122 @@
+/// Command words per language, mapped to the English keyword the parser
+/// knows. A synonym must not be an English keyword itself, or it would shadow it.
+const LOCALES: &[(&str, &[(&str, &str)])] = &[
+    (
+        "fr",
+        &[
+            ("SOLDE", "BALANCE"),
+            ("ENVOYER", "SEND"),
+            ("AIDE", "HELP"),
+            ("HISTORIQUE", "HISTORY"),
+            ("DEPOSER", "DEPOSIT"),
+            ("DÉPOSER", "DEPOSIT"),
+            ("ACHETER", "BUY"),
+            ("ECHANGER", "SWAP"),
+            ("ÉCHANGER", "SWAP"),
+            ("RETIRER", "CASHOUT"),
+            ("INSCRIRE", "JOIN"),
+            ("ENREGISTRER", "SAVE"),
+            ("VERROUILLER", "LOCK"),
+            ("DEVERROUILLER", "UNLOCK"),
+            ("DÉVERROUILLER", "UNLOCK"),
+            ("OUI", "YES"),
+            ("NON", "NO"),
+            ("ANNULER", "CANCEL"),
+        ],
+    ),
+    (
+        "es",
+        &[
+            ("SALDO", "BALANCE"),
+            ("ENVIAR", "SEND"),
+            ("AYUDA", "HELP"),
+            ("HISTORIAL", "HISTORY"),
+            ("DEPOSITAR", "DEPOSIT"),
+            ("COMPRAR", "BUY"),
+            ("CAMBIAR", "SWAP"),
+            ("RETIRAR", "CASHOUT"),
+            ("REGISTRAR", "JOIN"),
+            ("GUARDAR", "SAVE"),
+            ("BLOQUEAR", "LOCK"),
+            ("DESBLOQUEAR", "UNLOCK"),
+            ("SI", "YES"),
+            ("SÍ", "YES"),
+            ("CANCELAR", "CANCEL"),
+        ],
+    ),
+    (
+        "pt",
+        &[
+            ("SALDO", "BALANCE"),
+            ("ENVIAR", "SEND"),
+            ("AJUDA", "HELP"),
+            ("HISTORICO", "HISTORY"),
+            ("HISTÓRICO", "HISTORY"),
+            ("DEPOSITAR", "DEPOSIT"),
+            ("COMPRAR", "BUY"),
+            ("TROCAR", "SWAP"),
+            ("SACAR", "CASHOUT"),
+            ("CADASTRAR", "JOIN"),
+            ("SALVAR", "SAVE"),
+            ("BLOQUEAR", "LOCK"),
+            ("DESBLOQUEAR", "UNLOCK"),
+            ("SIM", "YES"),
+            ("NAO", "NO"),
+            ("NÃO", "NO"),
+            ("CANCELAR", "CANCEL"),
+        ],
+    ),
+];
+
+/// Languages with a synonym table
+pub fn supported_languages() -> impl Iterator<Item = &'static str> {
+    LOCALES.iter().map(|(language, _)| *language)
+}
+
+/// English keyword for an (uppercased) command word in one of `languages`,
+/// in the order given; None if it isn't a synonym there
+pub fn resolve(word: &str, languages: &[String]) -> Option<&'static str> {
+    languages.iter().find_map(|language| {
+        let (_, table) = LOCALES.iter().find(|(l, _)| l.eq_ignore_ascii_case(language))?;
+        table.iter().find(|(synonym, _)| *synonym == word).map(|(_, keyword)| *keyword)
+    })
+}
+
+/// First of `languages` that `word` is a synonym in
+pub fn language_of(word: &str, languages: &[String]) -> Option<&'static str> {
+    languages.iter().find_map(|language| {
+        let (l, table) = LOCALES.iter().find(|(l, _)| l.eq_ignore_ascii_case(language))?;
+        table.iter().any(|(synonym, _)| *synonym == word).then_some(*l)
+    })
+}
+
+/// Whether `language` has its own word for the English `keyword`
+pub fn has_synonym_for(keyword: &str, language: &str) -> bool {
+    LOCALES
+        .iter()
+        .find(|(l, _)| l.eq_ignore_ascii_case(language))
+        .is_some_and(|(_, table)| table.iter().any(|(_, english)| *english == keyword))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_only_in_enabled_languages() {
+        let french = ["fr".to_string()];
+        assert_eq!(resolve("SOLDE", &french), Some("BALANCE"));
+        assert_eq!(resolve("ÉCHANGER", &french), Some("SWAP"));
+        assert_eq!(resolve("SALDO", &french), None);
+        assert_eq!(resolve("SOLDE", &[]), None);
+        assert_eq!(resolve("SALDO", &["FR".to_string(), "es".to_string()]), Some("BALANCE"));
+        assert!(supported_languages().eq(["fr", "es", "pt"]));
+
+        let both = ["es".to_string(), "pt".to_string()];
+        assert_eq!(language_of("SACAR", &both), Some("pt"));
+        assert_eq!(language_of("SALDO", &both), Some("es"));
+        assert_eq!(language_of("SOLDE", &both), None);
+        assert!(has_synonym_for("BALANCE", "pt"));
+        assert!(!has_synonym_for("PIN", "fr"));
+    }
+}
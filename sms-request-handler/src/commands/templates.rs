@@ -0,0 +1,35 @@
+/// Default SEND acknowledgement. `{fiat}` is " (~KES 1,300)" or empty and
+/// `{note}` is a "\nNote: ..." line or empty.
+pub const DEFAULT_SEND_ACK: &str =
+    "Sending {amount} {token}{fiat} to {recipient}...{note}\n\nQueued via Yellow Network.\nYou'll get SMS when complete.";
+
+/// Default CASHOUT acknowledgement. `{currency}` and `{chain}` are where it settles.
+pub const DEFAULT_CASHOUT_ACK: &str =
+    "Cashing out {amount} {token}...\n\n{token} → {currency} on {chain}.\nYou'll get an SMS when complete.\n\nThis may take {eta}.";
+
+/// Default BRIDGE acknowledgement. `{route}` is the backend's route summary
+/// and `{fee}` a "Fee: ...\n" line or empty.
+pub const DEFAULT_BRIDGE_ACK: &str = "Bridge started!\n{route}\n{fee}SMS when done.";
+
+/// Fill `{name}` placeholders in an operator-edited template. Unknown
+/// placeholders are left as typed so a typo shows up in testing.
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_fills_known_placeholders() {
+        let values = [("amount", "10"), ("token", "TXTC"), ("recipient", "alice"), ("eta", "3 min")];
+        assert_eq!(
+            render("{amount} {token} to {recipient} in {eta}, {amount} again", &values),
+            "10 TXTC to alice in 3 min, 10 again"
+        );
+        assert_eq!(render("{amount} {tokne}", &values), "10 {tokne}");
+    }
+}
@@ -1,5 +1,11 @@
+use std::collections::HashMap;
 use std::env;
 
+use crate::commands::synonyms::supported_languages;
+use crate::commands::templates::{DEFAULT_BRIDGE_ACK, DEFAULT_CASHOUT_ACK, DEFAULT_SEND_ACK};
+use crate::sms::{LengthLimit, Overflow};
+use crate::wallet::Chain;
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub twilio: TwilioConfig,
@@ -24,15 +30,647 @@ pub struct ServerConfig {
 #[derive(Debug, Clone)]
 pub struct AaConfig {
     pub bundler_url: String,
+    /// Empty means the canonical address of `entry_point_version`
     pub entry_point_address: String,
+    /// EntryPoint release the bundler runs: 0.6 or 0.7
+    pub entry_point_version: String,
     pub simple_account_factory_address: String,
 }
 
+/// A way SEND turns what the user typed into a wallet address. They are
+/// tried in RECIPIENT_RESOLVERS order and the first that matches wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecipientResolver {
+    /// A raw 0x address
+    Address,
+    /// The TextChain wallet of a registered phone
+    Phone,
+    /// An ENS name
+    Ens,
+    /// A saved contact's address or phone
+    Contact,
+}
+
+impl RecipientResolver {
+    pub const DEFAULT_ORDER: [RecipientResolver; 4] = [
+        RecipientResolver::Address,
+        RecipientResolver::Phone,
+        RecipientResolver::Ens,
+        RecipientResolver::Contact,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecipientResolver::Address => "address",
+            RecipientResolver::Phone => "phone",
+            RecipientResolver::Ens => "ens",
+            RecipientResolver::Contact => "contact",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::DEFAULT_ORDER.into_iter().find(|r| r.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Currencies CASHOUT can settle in, and the chain each one settles on
+pub const CASHOUT_SETTLEMENTS: &[(&str, &str)] = &[("USDC", "Arc"), ("USDT", "Polygon")];
+
+/// Chain a settlement currency is paid out on (None if unsupported)
+pub fn settlement_chain(currency: &str) -> Option<&'static str> {
+    CASHOUT_SETTLEMENTS
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(currency))
+        .map(|(_, chain)| *chain)
+}
+
+/// Tunables for SMS command handling
+#[derive(Debug, Clone)]
+pub struct CommandConfig {
+    /// Hold transfers from senders the recipient hasn't saved until they ACCEPT
+    pub hold_unknown_senders: bool,
+    /// Entries shown by HISTORY/CONTACTS when no count is given
+    pub page_size: usize,
+    /// Upper bound for a requested count (keeps replies within SMS limits)
+    pub max_page_size: usize,
+    /// Days an undated HOLD stays locked before it is refunded to the payer
+    pub escrow_max_days: i64,
+    /// SENDs above this amount wait for the user to confirm (0 = never ask)
+    pub confirm_above: f64,
+    /// Confirm with a plain YES/NO reply instead of CONFIRM <code>
+    pub confirm_by_keyword: bool,
+    /// Seconds a send waits for confirmation before it is dropped
+    pub confirm_ttl_secs: u64,
+    /// JOIN creates a receive-only account; the Arc wallet is set up on the first SEND
+    pub receive_only_join: bool,
+    /// Blocks before an on-chain entry shows as confirmed in HISTORY
+    pub min_confirmations: u64,
+    /// Public base URL of this service, used for EXPORT download links
+    pub public_url: String,
+    /// CONTACTS line format: {name}, {phone} (masked), {address}, {contact}
+    pub contact_template: String,
+    /// SEND acknowledgement: {amount}, {token}, {recipient}, {eta}, {fiat}, {note}
+    pub send_ack_template: String,
+    /// CASHOUT acknowledgement: {amount}, {token}, {recipient}, {eta}
+    pub cashout_ack_template: String,
+    /// BRIDGE acknowledgement: {amount}, {token}, {recipient} (destination chain), {eta}, {route}, {fee}
+    pub bridge_ack_template: String,
+    /// Slippage tolerated on SWAP and TXTC CASHOUT, in basis points of the
+    /// quoted output (1-5000)
+    pub swap_slippage_bps: u32,
+    /// Seconds a submitted swap stays valid on chain before it reverts (30-3600)
+    pub swap_deadline_secs: u64,
+    /// TXTC credited to each new wallet, once per phone (0 = no bonus)
+    pub welcome_bonus_txtc: f64,
+    /// Shared secret the backend requires on /api/bonus (X-Bonus-Token)
+    pub welcome_bonus_token: String,
+    /// Seconds to wait for Yellow to accept a SEND before reporting it as still processing
+    pub send_timeout_secs: u64,
+    /// Per-user cooldown seconds by command keyword, e.g. BUY=60,CASHOUT=300
+    pub command_cooldowns: HashMap<String, u64>,
+    /// Commands a phone may send per `rate_limit_window_secs` (0 = unlimited)
+    pub rate_limit_commands: u32,
+    /// Window over which a phone's command allowance refills
+    pub rate_limit_window_secs: u64,
+    /// Decimal places shown for token amounts in replies (trailing zeros trimmed)
+    pub display_precision: usize,
+    /// Seconds within which an identical SEND needs confirming (0 = never ask)
+    pub duplicate_send_window_secs: u64,
+    /// Commands turned off by phone prefix ("+44") or "*" for everyone,
+    /// e.g. *=EXPORT;+44=BRIDGE,BUY
+    pub disabled_commands: HashMap<String, Vec<String>>,
+    /// Key signing SEND receipts (empty = no receipts)
+    pub receipt_signing_key: String,
+    /// Secret users' private keys are encrypted under (ENCRYPTION_KEY;
+    /// empty = keys can't be stored or read)
+    pub encryption_key: String,
+    /// BIP-39 phrase new wallets are derived from (MASTER_MNEMONIC); set,
+    /// JOIN stores only each user's index so keys survive a database loss
+    pub master_mnemonic: String,
+    /// Chain SENDs settle on, for explorer links on receipts (as in CHAIN <name>)
+    pub send_chain: String,
+    /// Words JOIN refuses in ENS names (matched after undoing leetspeak)
+    pub blocked_name_words: Vec<String>,
+    /// Seconds without a send before a Yellow channel is closed (0 = never)
+    pub channel_idle_close_secs: u64,
+    /// Yellow channels with less than this balance are closed (0 = never)
+    pub channel_min_balance: f64,
+    /// How long an auto-close waits for the channel to settle before the
+    /// user is told it's still settling
+    pub channel_settle_wait_secs: u64,
+    /// A SEND to someone the user has never paid waits for confirmation
+    pub confirm_new_recipients: bool,
+    /// Seconds after UNLOCK <pin> that PIN-protected commands don't ask again (0 = every time)
+    pub pin_session_secs: u64,
+    /// Wrong PINs in a row before PIN entry is locked out (0 = never)
+    pub pin_max_attempts: u32,
+    /// First PIN lockout; each further wrong PIN doubles it, up to a day
+    pub pin_lockout_secs: u64,
+    /// A wallet younger than this can't SEND until it receives a deposit (0 = off)
+    pub min_wallet_age_secs: u64,
+    /// Phone prefixes (trusted segments) the wallet-age rule doesn't apply to
+    pub wallet_age_exempt: Vec<String>,
+    /// Smallest BUY, in euros of airtime
+    pub buy_min: f64,
+    /// Largest BUY, in euros of airtime
+    pub buy_max: f64,
+    /// Decimal places a BUY amount may have (0 = whole euros only)
+    pub buy_decimals: u32,
+    /// Languages whose command words are understood besides English ("fr", "es")
+    pub languages: Vec<String>,
+    /// Largest share of a deposit AUTOSWAP may swap to gas, in percent
+    pub autoswap_max_pct: u32,
+    /// Seconds an ENS name's resolved address is reused (0 = always resolve again)
+    pub ens_cache_secs: u64,
+    /// Chain whose ENS registry resolves names before asking the backend
+    /// (None = always ask the backend)
+    pub ens_chain: Option<Chain>,
+    /// Order SEND tries recipient resolvers in (RECIPIENT_RESOLVERS)
+    pub recipient_resolvers: Vec<RecipientResolver>,
+    /// Seconds a user looked up by phone is reused (0 = always ask the database)
+    pub user_cache_secs: u64,
+    /// Chains BALANCE also lists native and USDC balances on (empty = none)
+    pub balance_chains: Vec<Chain>,
+    /// Seconds an address's per-chain balances are reused, to spare public RPCs
+    pub chain_balance_cache_secs: u64,
+    /// Currency CASHOUT settles in when the user doesn't pick one
+    pub cashout_currency: String,
+    /// Currencies a user may pick with CASHOUT <amount> <token> TO <currency>
+    pub cashout_currencies: Vec<String>,
+}
+
+impl CommandConfig {
+    /// Load command settings from environment variables, falling back to defaults
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            hold_unknown_senders: env_flag("HOLD_UNKNOWN_SENDERS", defaults.hold_unknown_senders),
+            page_size: env_parse("PAGE_SIZE", defaults.page_size),
+            max_page_size: env_parse("MAX_PAGE_SIZE", defaults.max_page_size),
+            escrow_max_days: env_parse("ESCROW_MAX_DAYS", defaults.escrow_max_days),
+            confirm_above: env_parse("CONFIRM_ABOVE", defaults.confirm_above),
+            confirm_by_keyword: env_flag("CONFIRM_BY_KEYWORD", defaults.confirm_by_keyword),
+            confirm_ttl_secs: env_parse("CONFIRM_TTL_SECS", defaults.confirm_ttl_secs),
+            receive_only_join: env_flag("RECEIVE_ONLY_JOIN", defaults.receive_only_join),
+            min_confirmations: env_parse("MIN_CONFIRMATIONS", defaults.min_confirmations),
+            public_url: env::var("PUBLIC_URL").unwrap_or(defaults.public_url),
+            contact_template: env::var("CONTACT_TEMPLATE").unwrap_or(defaults.contact_template),
+            send_ack_template: env_template("SEND_ACK_TEMPLATE", defaults.send_ack_template),
+            cashout_ack_template: env_template("CASHOUT_ACK_TEMPLATE", defaults.cashout_ack_template),
+            bridge_ack_template: env_template("BRIDGE_ACK_TEMPLATE", defaults.bridge_ack_template),
+            swap_slippage_bps: env_parse_in("SWAP_SLIPPAGE_BPS", 1..=5_000, defaults.swap_slippage_bps),
+            swap_deadline_secs: env_parse_in("SWAP_DEADLINE_SECS", 30..=3_600, defaults.swap_deadline_secs),
+            welcome_bonus_txtc: env_parse("WELCOME_BONUS_TXTC", defaults.welcome_bonus_txtc),
+            welcome_bonus_token: env::var("BONUS_TOKEN").unwrap_or(defaults.welcome_bonus_token),
+            send_timeout_secs: env_parse("SEND_TIMEOUT_SECS", defaults.send_timeout_secs),
+            command_cooldowns: env::var("COMMAND_COOLDOWNS")
+                .map(|v| parse_cooldowns(&v))
+                .unwrap_or(defaults.command_cooldowns),
+            rate_limit_commands: env_parse("RATE_LIMIT_COMMANDS", defaults.rate_limit_commands),
+            rate_limit_window_secs: env_parse("RATE_LIMIT_WINDOW_SECS", defaults.rate_limit_window_secs),
+            display_precision: env_parse("DISPLAY_PRECISION", defaults.display_precision),
+            duplicate_send_window_secs: env_parse("DUPLICATE_SEND_WINDOW_SECS", defaults.duplicate_send_window_secs),
+            disabled_commands: env::var("DISABLED_COMMANDS")
+                .map(|v| parse_disabled_commands(&v))
+                .unwrap_or(defaults.disabled_commands),
+            receipt_signing_key: env::var("RECEIPT_SIGNING_KEY").unwrap_or(defaults.receipt_signing_key),
+            encryption_key: env::var("ENCRYPTION_KEY").unwrap_or(defaults.encryption_key),
+            master_mnemonic: env::var("MASTER_MNEMONIC").unwrap_or(defaults.master_mnemonic),
+            send_chain: env::var("SEND_CHAIN").unwrap_or(defaults.send_chain),
+            blocked_name_words: env::var("BLOCKED_NAME_WORDS")
+                .map(|v| v.split(',').map(|w| w.trim().to_string()).filter(|w| !w.is_empty()).collect())
+                .unwrap_or(defaults.blocked_name_words),
+            channel_idle_close_secs: env_parse("CHANNEL_IDLE_CLOSE_SECS", defaults.channel_idle_close_secs),
+            channel_min_balance: env_parse("CHANNEL_MIN_BALANCE", defaults.channel_min_balance),
+            channel_settle_wait_secs: env_parse("CHANNEL_SETTLE_WAIT_SECS", defaults.channel_settle_wait_secs),
+            confirm_new_recipients: env_flag("CONFIRM_NEW_RECIPIENTS", defaults.confirm_new_recipients),
+            pin_session_secs: env_parse("PIN_SESSION_SECS", defaults.pin_session_secs),
+            pin_max_attempts: env_parse("PIN_MAX_ATTEMPTS", defaults.pin_max_attempts),
+            pin_lockout_secs: env_parse("PIN_LOCKOUT_SECS", defaults.pin_lockout_secs),
+            min_wallet_age_secs: env_parse("MIN_WALLET_AGE_SECS", defaults.min_wallet_age_secs),
+            wallet_age_exempt: env::var("WALLET_AGE_EXEMPT")
+                .map(|v| v.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect())
+                .unwrap_or(defaults.wallet_age_exempt),
+            buy_min: env_parse("BUY_MIN", defaults.buy_min),
+            buy_max: env_parse("BUY_MAX", defaults.buy_max),
+            buy_decimals: env_parse_in("BUY_DECIMALS", 0..=6, defaults.buy_decimals),
+            languages: env::var("COMMAND_LANGUAGES")
+                .map(|v| {
+                    v.split(',')
+                        .map(|l| l.trim().to_lowercase())
+                        .filter(|l| {
+                            let known = supported_languages().any(|s| s == l);
+                            if !known && !l.is_empty() {
+                                tracing::warn!("COMMAND_LANGUAGES: no synonyms for '{}', ignoring", l);
+                            }
+                            known
+                        })
+                        .collect()
+                })
+                .unwrap_or(defaults.languages),
+            autoswap_max_pct: env_parse_in("AUTOSWAP_MAX_PCT", 1..=100, defaults.autoswap_max_pct),
+            ens_cache_secs: env_parse("ENS_CACHE_SECS", defaults.ens_cache_secs),
+            ens_chain: env::var("ENS_CHAIN").map(|v| env_ens_chain(&v)).unwrap_or(defaults.ens_chain),
+            recipient_resolvers: env::var("RECIPIENT_RESOLVERS")
+                .map(|v| parse_resolvers(&v))
+                .unwrap_or(defaults.recipient_resolvers),
+            user_cache_secs: env_parse("USER_CACHE_SECS", defaults.user_cache_secs),
+            balance_chains: env::var("BALANCE_CHAINS")
+                .map(|v| parse_chains("BALANCE_CHAINS", &v))
+                .unwrap_or(defaults.balance_chains),
+            chain_balance_cache_secs: env_parse("CHAIN_BALANCE_CACHE_SECS", defaults.chain_balance_cache_secs),
+            cashout_currency: env::var("CASHOUT_CURRENCY")
+                .ok()
+                .and_then(|v| env_settlement("CASHOUT_CURRENCY", &v))
+                .unwrap_or(defaults.cashout_currency),
+            cashout_currencies: env::var("CASHOUT_CURRENCIES")
+                .map(|v| v.split(',').filter_map(|c| env_settlement("CASHOUT_CURRENCIES", c)).collect())
+                .unwrap_or(defaults.cashout_currencies),
+        }
+    }
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        Self {
+            hold_unknown_senders: false,
+            page_size: 5,
+            max_page_size: 10,
+            escrow_max_days: 30,
+            confirm_above: 0.0,
+            confirm_by_keyword: false,
+            confirm_ttl_secs: 300,
+            receive_only_join: false,
+            min_confirmations: 12,
+            public_url: "http://localhost:3000".to_string(),
+            contact_template: "{name}: {contact}".to_string(),
+            send_ack_template: DEFAULT_SEND_ACK.to_string(),
+            cashout_ack_template: DEFAULT_CASHOUT_ACK.to_string(),
+            bridge_ack_template: DEFAULT_BRIDGE_ACK.to_string(),
+            swap_slippage_bps: 100,
+            swap_deadline_secs: 300,
+            welcome_bonus_txtc: 0.0,
+            welcome_bonus_token: String::new(),
+            send_timeout_secs: 30,
+            command_cooldowns: HashMap::from([("BUY".to_string(), 60), ("CASHOUT".to_string(), 60)]),
+            rate_limit_commands: 5,
+            rate_limit_window_secs: 60,
+            display_precision: 4,
+            duplicate_send_window_secs: 60,
+            disabled_commands: HashMap::new(),
+            receipt_signing_key: String::new(),
+            encryption_key: default_encryption_key(),
+            master_mnemonic: String::new(),
+            send_chain: "sepolia".to_string(),
+            blocked_name_words: ["admin", "support", "official", "textchain", "ttcip"].map(String::from).to_vec(),
+            channel_idle_close_secs: 7 * 24 * 3600,
+            channel_min_balance: 0.0,
+            channel_settle_wait_secs: 30,
+            confirm_new_recipients: false,
+            pin_session_secs: 300,
+            pin_max_attempts: 5,
+            pin_lockout_secs: 900,
+            min_wallet_age_secs: 0,
+            wallet_age_exempt: Vec::new(),
+            buy_min: 1.0,
+            buy_max: 100.0,
+            buy_decimals: 2,
+            languages: Vec::new(),
+            autoswap_max_pct: 50,
+            ens_cache_secs: 300,
+            ens_chain: Some(Chain::EthereumMainnet),
+            recipient_resolvers: RecipientResolver::DEFAULT_ORDER.to_vec(),
+            user_cache_secs: 30,
+            balance_chains: Vec::new(),
+            chain_balance_cache_secs: 15,
+            cashout_currency: "USDC".to_string(),
+            cashout_currencies: CASHOUT_SETTLEMENTS.iter().map(|(c, _)| c.to_string()).collect(),
+        }
+    }
+}
+
+/// On-chain deposit watcher settings
+#[derive(Debug, Clone)]
+pub struct DepositWatcherConfig {
+    /// Poll the chain for incoming USDC transfers to user wallets
+    pub enabled: bool,
+    /// Chain to watch (as accepted by the CHAIN command)
+    pub chain: String,
+    /// TXTC contract on that chain; TXTC deposits are only watched when set
+    pub txtc_address: String,
+    /// Blocks a deposit must be buried under before it is credited
+    pub confirmations: u64,
+    /// Seconds between polls
+    pub poll_secs: u64,
+}
+
+impl DepositWatcherConfig {
+    /// Load watcher settings from environment variables, falling back to defaults
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            enabled: env_flag("DEPOSIT_WATCHER", defaults.enabled),
+            chain: env::var("DEPOSIT_CHAIN").unwrap_or(defaults.chain),
+            txtc_address: env::var("DEPOSIT_TXTC_ADDRESS").unwrap_or(defaults.txtc_address),
+            confirmations: env_parse("DEPOSIT_CONFIRMATIONS", defaults.confirmations),
+            poll_secs: env_parse("DEPOSIT_POLL_SECS", defaults.poll_secs),
+        }
+    }
+}
+
+impl Default for DepositWatcherConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            chain: "amoy".to_string(),
+            txtc_address: String::new(),
+            confirmations: 12,
+            poll_secs: 15,
+        }
+    }
+}
+
+/// Outbound webhook for wallet events
+#[derive(Debug, Clone, Default)]
+pub struct EventWebhookConfig {
+    /// Where events are POSTed (empty = no webhook)
+    pub url: String,
+    /// Shared secret for the X-TextChain-Signature HMAC
+    pub secret: String,
+}
+
+impl EventWebhookConfig {
+    pub fn from_env() -> Self {
+        Self {
+            url: env::var("EVENT_WEBHOOK_URL").unwrap_or_default(),
+            secret: env::var("EVENT_WEBHOOK_SECRET").unwrap_or_default(),
+        }
+    }
+}
+
+/// Outbound SMS queue settings
+#[derive(Debug, Clone)]
+pub struct SmsQueueConfig {
+    /// Messages handed to the provider per second
+    pub rate_per_sec: f64,
+    /// Messages held before the least urgent are dropped
+    pub capacity: usize,
+    /// Most characters in one outbound SMS (0 = unlimited)
+    pub max_chars: usize,
+    /// What happens to a longer one: truncate, segment or summarize
+    pub overflow: Overflow,
+    /// Link a truncated SMS ends with, for the full details (empty = none)
+    pub overflow_link: String,
+}
+
+impl SmsQueueConfig {
+    /// Load queue settings from environment variables, falling back to defaults
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            rate_per_sec: env_parse("SMS_RATE_PER_SEC", defaults.rate_per_sec),
+            capacity: env_parse("SMS_QUEUE_CAPACITY", defaults.capacity),
+            max_chars: env_parse("SMS_MAX_CHARS", defaults.max_chars),
+            overflow: env_parse("SMS_OVERFLOW", defaults.overflow),
+            overflow_link: env::var("SMS_OVERFLOW_LINK").unwrap_or(defaults.overflow_link),
+        }
+    }
+
+    pub fn length_limit(&self) -> LengthLimit {
+        LengthLimit {
+            max_chars: self.max_chars,
+            overflow: self.overflow,
+            link: self.overflow_link.clone(),
+        }
+    }
+}
+
+impl Default for SmsQueueConfig {
+    fn default() -> Self {
+        // Twilio long codes send about one message per second
+        Self {
+            rate_per_sec: 1.0,
+            capacity: 1000,
+            max_chars: 0,
+            overflow: Overflow::Truncate,
+            overflow_link: String::new(),
+        }
+    }
+}
+
+/// Field mapping for the generic `/inbound` gateway webhook
+#[derive(Debug, Clone)]
+pub struct InboundGatewayConfig {
+    /// JSON field holding the sender's number (dots reach into objects: "msg.from")
+    pub from_field: String,
+    /// JSON field holding the message text
+    pub text_field: String,
+    /// Field the reply text is returned under
+    pub reply_field: String,
+    /// Shared secret the gateway sends in X-Inbound-Token (empty = /inbound is off)
+    pub token: String,
+}
+
+impl InboundGatewayConfig {
+    /// Load the mapping from environment variables, falling back to `{from, text}`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            from_field: env::var("INBOUND_FROM_FIELD").unwrap_or(defaults.from_field),
+            text_field: env::var("INBOUND_TEXT_FIELD").unwrap_or(defaults.text_field),
+            reply_field: env::var("INBOUND_REPLY_FIELD").unwrap_or(defaults.reply_field),
+            token: env::var("INBOUND_TOKEN").unwrap_or(defaults.token),
+        }
+    }
+}
+
+impl Default for InboundGatewayConfig {
+    fn default() -> Self {
+        Self {
+            from_field: "from".to_string(),
+            text_field: "text".to_string(),
+            reply_field: "reply".to_string(),
+            token: String::new(),
+        }
+    }
+}
+
+/// Signature check on Twilio's `/sms/incoming` webhook
+#[derive(Debug, Clone)]
+pub struct TwilioWebhookConfig {
+    /// Reject webhooks whose X-Twilio-Signature doesn't match (off for local testing)
+    pub verify_signature: bool,
+    /// Key Twilio signs webhooks with
+    pub auth_token: String,
+    /// Public base URL Twilio posts to; signatures cover the full URL, so this
+    /// must match the webhook URL configured in the Twilio console
+    pub public_url: String,
+}
+
+impl TwilioWebhookConfig {
+    /// Load the check from environment variables (off unless VERIFY_TWILIO_SIGNATURE is set)
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            verify_signature: env_flag("VERIFY_TWILIO_SIGNATURE", defaults.verify_signature),
+            auth_token: env::var("TWILIO_AUTH_TOKEN").unwrap_or(defaults.auth_token),
+            public_url: env::var("PUBLIC_URL").unwrap_or(defaults.public_url),
+        }
+    }
+}
+
+impl Default for TwilioWebhookConfig {
+    fn default() -> Self {
+        Self {
+            verify_signature: false,
+            auth_token: String::new(),
+            public_url: CommandConfig::default().public_url,
+        }
+    }
+}
+
+/// Comma-separated chains as CHAIN takes them ("amoy,base-t"); unknown ones
+/// are ignored with a warning
+fn parse_chains(name: &str, value: &str) -> Vec<Chain> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .filter_map(|c| {
+            let chain = Chain::from_input(c);
+            if chain.is_none() {
+                tracing::warn!("{}: unknown chain '{}', ignoring", name, c);
+            }
+            chain
+        })
+        .collect()
+}
+
+/// RECIPIENT_RESOLVERS, e.g. "ens,contact,phone,address". Unknown and
+/// repeated names are ignored with a warning; resolvers left out aren't
+/// tried. Nothing usable keeps the default order.
+fn parse_resolvers(value: &str) -> Vec<RecipientResolver> {
+    let mut resolvers = Vec::new();
+    for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        match RecipientResolver::from_name(name) {
+            Some(r) if !resolvers.contains(&r) => resolvers.push(r),
+            Some(_) => tracing::warn!("RECIPIENT_RESOLVERS: '{}' listed twice, ignoring", name),
+            None => tracing::warn!("RECIPIENT_RESOLVERS: unknown resolver '{}', ignoring", name),
+        }
+    }
+    if resolvers.is_empty() {
+        tracing::warn!("RECIPIENT_RESOLVERS: no known resolvers, using the default order");
+        return RecipientResolver::DEFAULT_ORDER.to_vec();
+    }
+    resolvers
+}
+
+/// ENS_CHAIN as CHAIN takes it; "off" or empty turns native resolution off
+/// and an unknown chain falls back to mainnet with a warning
+fn env_ens_chain(value: &str) -> Option<Chain> {
+    let value = value.trim();
+    if value.is_empty() || value.eq_ignore_ascii_case("off") {
+        return None;
+    }
+    Chain::from_input(value).or_else(|| {
+        tracing::warn!("ENS_CHAIN: unknown chain '{}', using Ethereum", value);
+        Some(Chain::EthereumMainnet)
+    })
+}
+
+/// No built-in key outside tests: one in the source would protect nothing
+#[cfg(not(test))]
+fn default_encryption_key() -> String {
+    String::new()
+}
+
+#[cfg(test)]
+fn default_encryption_key() -> String {
+    "textchain-test-encryption-key".to_string()
+}
+
+/// Multi-line reply template; `\n` in the value starts a new line
+fn env_template(name: &str, default: String) -> String {
+    env::var(name).map(|v| v.replace("\\n", "\n")).unwrap_or(default)
+}
+
+/// Settlement currency from the environment, uppercased; unknown ones are
+/// ignored with a warning
+fn env_settlement(name: &str, value: &str) -> Option<String> {
+    let currency = value.trim().to_uppercase();
+    if settlement_chain(&currency).is_none() {
+        if !currency.is_empty() {
+            tracing::warn!("{}: can't settle cashouts in '{}', ignoring", name, currency);
+        }
+        return None;
+    }
+    Some(currency)
+}
+
+/// Read a boolean flag ("true"/"1"/"yes") from the environment
+fn env_flag(name: &str, default: bool) -> bool {
+    match env::var(name) {
+        Ok(v) => matches!(v.to_lowercase().as_str(), "true" | "1" | "yes" | "on"),
+        Err(_) => default,
+    }
+}
+
+/// Parse a value from the environment, ignoring unset or malformed values
+fn env_parse<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+/// Like `env_parse`, but an out-of-range value is ignored with a warning
+fn env_parse_in<T>(name: &str, range: std::ops::RangeInclusive<T>, default: T) -> T
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+{
+    match env::var(name).ok().and_then(|v| v.parse::<T>().ok()) {
+        Some(value) if range.contains(&value) => value,
+        Some(value) => {
+            tracing::warn!(
+                "{}={} is outside {}..={}, using {}",
+                name, value, range.start(), range.end(), default
+            );
+            default
+        }
+        None => default,
+    }
+}
+
+/// Parse "BUY=60,CASHOUT=300", skipping malformed entries
+fn parse_cooldowns(value: &str) -> HashMap<String, u64> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (command, secs) = entry.split_once('=')?;
+            Some((command.trim().to_uppercase(), secs.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parse "*=EXPORT;+44=BRIDGE,BUY" into command keywords by phone prefix
+fn parse_disabled_commands(value: &str) -> HashMap<String, Vec<String>> {
+    value
+        .split(';')
+        .filter_map(|entry| {
+            let (prefix, commands) = entry.split_once('=')?;
+            let commands = commands.split(',').map(|c| c.trim().to_uppercase()).filter(|c| !c.is_empty()).collect();
+            Some((prefix.trim().to_string(), commands))
+        })
+        .collect()
+}
+
 impl Config {
     /// Load configuration from environment variables
     pub fn from_env() -> Result<Self, ConfigError> {
         dotenvy::dotenv().ok();
 
+        // Read by CommandConfig; required here so a deploy without it never starts
+        if env::var("ENCRYPTION_KEY").unwrap_or_default().is_empty() {
+            return Err(ConfigError::Missing("ENCRYPTION_KEY"));
+        }
+
         Ok(Config {
             twilio: TwilioConfig {
                 account_sid: env::var("TWILIO_ACCOUNT_SID")
@@ -52,6 +690,7 @@ impl Config {
             aa: AaConfig {
                 bundler_url: env::var("BUNDLER_URL").unwrap_or_else(|_| "".to_string()),
                 entry_point_address: env::var("ENTRY_POINT_ADDRESS").unwrap_or_else(|_| "".to_string()),
+                entry_point_version: env::var("ENTRY_POINT_VERSION").unwrap_or_else(|_| "0.6".to_string()),
                 simple_account_factory_address: env::var("SIMPLE_ACCOUNT_FACTORY_ADDRESS").unwrap_or_else(|_| "".to_string()),
             },
             admin_private_key: env::var("ADMIN_PRIVATE_KEY").unwrap_or_else(|_| "".to_string()),
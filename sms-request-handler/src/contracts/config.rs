@@ -6,6 +6,14 @@ pub struct ContractConfig {
     pub rpc_url: String,
     pub private_key: String,
     pub contracts: ContractAddresses,
+    /// Blocks a redeem transaction must be buried under before it counts
+    pub redeem_confirmations: usize,
+    /// Seconds between receipt polls while waiting on a redeem
+    pub poll_interval_secs: u64,
+    /// Seconds to wait on a redeem before reporting it pending
+    pub redeem_timeout_secs: u64,
+    /// Headroom on the suggested EIP-1559 fees (1.2 = 20% over the node's figures)
+    pub gas_fee_multiplier: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +38,14 @@ impl ContractConfig {
                 entry_point: "0x6b5b8b917f3161aeb72105b988E55910e231d240".to_string(),
                 uniswap_v3_pool: "0x54fB26024019504e075B98c2834adEB29E779c7e".to_string(),
             },
+            redeem_confirmations: env_or("REDEEM_CONFIRMATIONS", 1),
+            poll_interval_secs: env_or("REDEEM_POLL_INTERVAL_SECS", 3),
+            redeem_timeout_secs: env_or("REDEEM_TIMEOUT_SECS", 60),
+            gas_fee_multiplier: env_or("GAS_FEE_MULTIPLIER", 1.2),
         })
     }
 }
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
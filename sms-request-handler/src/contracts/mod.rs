@@ -1,5 +1,6 @@
 // Contract integration module for Text-to-Chain
 pub mod config;
+mod nonce;
 pub mod service;
 
 pub use config::ContractConfig;
@@ -1,6 +1,10 @@
 use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
 use std::sync::Arc;
+use std::time::Duration;
 use super::config::ContractConfig;
+use super::nonce::NonceManager;
+use crate::db::TransferRepository;
 
 // ABI definitions (simplified - use full ABIs in production)
 abigen!(
@@ -28,8 +32,52 @@ abigen!(
 pub struct ContractService {
     provider: Arc<Provider<Http>>,
     wallet: LocalWallet,
-    entry_point: EntryPointV3<SignerMiddleware<Provider<Http>, LocalWallet>>,
-    token_xyz: TokenXYZ<SignerMiddleware<Provider<Http>, LocalWallet>>,
+    entry_point: EntryPointV3<SignerMiddleware<Arc<Provider<Http>>, LocalWallet>>,
+    token_xyz: TokenXYZ<SignerMiddleware<Arc<Provider<Http>>, LocalWallet>>,
+    redeem_wait: RedeemWait,
+    gas_fee_multiplier: f64,
+    /// Nonces of the shared backend wallet, handed out one per transaction
+    nonces: NonceManager,
+    /// Redeems still unconfirmed at the timeout are recorded here for reconciliation
+    transfer_repo: Option<TransferRepository>,
+}
+
+/// How long and how often to wait on a redeem transaction
+#[derive(Debug, Clone, Copy)]
+pub struct RedeemWait {
+    pub confirmations: usize,
+    pub poll_interval: Duration,
+    pub timeout: Duration,
+}
+
+/// Outcome of waiting on a sent transaction
+#[derive(Debug)]
+pub enum Waited {
+    Mined(TransactionReceipt),
+    /// Still unconfirmed when the timeout passed; it may yet be mined
+    TimedOut,
+}
+
+impl RedeemWait {
+    pub fn from_config(config: &ContractConfig) -> Self {
+        Self {
+            confirmations: config.redeem_confirmations.max(1),
+            poll_interval: Duration::from_secs(config.poll_interval_secs.max(1)),
+            timeout: Duration::from_secs(config.redeem_timeout_secs),
+        }
+    }
+
+    /// Wait for `tx` to get the configured confirmations, giving up at the timeout
+    pub async fn wait<P: JsonRpcClient>(
+        &self,
+        tx: PendingTransaction<'_, P>,
+    ) -> Result<Waited, Box<dyn std::error::Error>> {
+        let tx = tx.confirmations(self.confirmations).interval(self.poll_interval);
+        match tokio::time::timeout(self.timeout, tx).await {
+            Ok(receipt) => Ok(Waited::Mined(receipt?.ok_or("Transaction failed")?)),
+            Err(_) => Ok(Waited::TimedOut),
+        }
+    }
 }
 
 impl ContractService {
@@ -55,26 +103,95 @@ impl ContractService {
         
         Ok(Self {
             provider,
+            nonces: NonceManager::new(wallet.address()),
             wallet,
             entry_point,
             token_xyz,
+            redeem_wait: RedeemWait::from_config(&config),
+            gas_fee_multiplier: config.gas_fee_multiplier,
+            transfer_repo: None,
         })
     }
+
+    /// EIP-1559 `(max_fee, max_priority_fee)` for the next block, with the
+    /// configured multiplier's headroom
+    pub async fn suggest_fees(&self) -> Result<(U256, U256), Box<dyn std::error::Error>> {
+        suggest_fees(&*self.provider, self.gas_fee_multiplier).await
+    }
+
+    /// Price `tx` from `suggest_fees`, keeping ethers' defaults if the node
+    /// can't say (legacy transactions get the max fee as their gas price)
+    async fn apply_fees(&self, tx: &mut TypedTransaction) {
+        let (max_fee, max_priority) = match self.suggest_fees().await {
+            Ok(fees) => fees,
+            Err(e) => {
+                tracing::warn!("Fee suggestion failed, using defaults: {}", e);
+                return;
+            }
+        };
+        match tx.as_eip1559_mut() {
+            Some(tx) => {
+                tx.max_fee_per_gas = Some(max_fee);
+                tx.max_priority_fee_per_gas = Some(max_priority);
+            }
+            None => {
+                tx.set_gas_price(max_fee);
+            }
+        }
+    }
+
+    /// Give `tx` the backend wallet's next nonce
+    async fn claim_nonce(&self, tx: &mut TypedTransaction) -> Result<(), Box<dyn std::error::Error>> {
+        let nonce = self.nonces.next(&*self.provider).await?;
+        tx.set_nonce(nonce);
+        Ok(())
+    }
+
+    /// Record redeems that time out, for reconciliation
+    pub fn with_transfer_repo(mut self, repo: TransferRepository) -> Self {
+        self.transfer_repo = Some(repo);
+        self
+    }
     
     /// Redeem voucher for user
     /// SMS Command: REDEEM <code>
+    ///
+    /// Waits for the configured confirmations; past the timeout the result is
+    /// `pending` (the user is notified later) and the tx is recorded.
     pub async fn redeem_voucher(
         &self,
+        phone: &str,
         voucher_code: &str,
         user_address: Address,
         auto_swap_to_eth: bool,
     ) -> Result<RedeemResult, Box<dyn std::error::Error>> {
-        let tx = self.entry_point
-            .redeem_voucher(voucher_code.to_string(), user_address, auto_swap_to_eth)
-            .send()
-            .await?;
-        
-        let receipt = tx.await?.ok_or("Transaction failed")?;
+        let mut call = self.entry_point
+            .redeem_voucher(voucher_code.to_string(), user_address, auto_swap_to_eth);
+        self.apply_fees(&mut call.tx).await;
+        self.claim_nonce(&mut call.tx).await?;
+        let tx = match call.send().await {
+            Ok(tx) => tx,
+            Err(e) => {
+                self.nonces.resync().await;
+                return Err(e.into());
+            }
+        };
+        let tx_hash = format!("{:?}", tx.tx_hash());
+
+        let receipt = match self.redeem_wait.wait(tx).await? {
+            Waited::Mined(receipt) => receipt,
+            Waited::TimedOut => {
+                tracing::warn!(tx = %tx_hash, "Redeem unconfirmed after {:?}, reporting pending", self.redeem_wait.timeout);
+                self.record_unconfirmed(phone, voucher_code, user_address, &tx_hash).await;
+                return Ok(RedeemResult {
+                    success: true,
+                    pending: true,
+                    token_amount: "0".to_string(),
+                    eth_amount: "0".to_string(),
+                    tx_hash,
+                });
+            }
+        };
         
         // Parse events
         for log in receipt.logs {
@@ -85,6 +202,7 @@ impl ContractService {
             ) {
                 return Ok(RedeemResult {
                     success: true,
+                    pending: false,
                     token_amount: format_ether(event.token_amount),
                     eth_amount: format_ether(event.eth_amount),
                     tx_hash: format!("{:?}", receipt.transaction_hash),
@@ -94,11 +212,29 @@ impl ContractService {
         
         Ok(RedeemResult {
             success: true,
+            pending: false,
             token_amount: "0".to_string(),
             eth_amount: "0".to_string(),
             tx_hash: format!("{:?}", receipt.transaction_hash),
         })
     }
+
+    /// Leave a timed-out redeem pending in `outgoing_transfers` so the
+    /// reconciliation report follows it up
+    async fn record_unconfirmed(&self, phone: &str, voucher_code: &str, user_address: Address, tx_hash: &str) {
+        let Some(ref repo) = self.transfer_repo else {
+            return;
+        };
+        let from = format!("{:?}", self.wallet.address());
+        let to = format!("{:?}", user_address);
+        let recorded = match repo.create_pending(phone, &from, &to, 0.0, "TXTC").await {
+            Ok(id) => repo.note_unresolved(id, &format!("redeem {} unconfirmed, tx {}", voucher_code, tx_hash)).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = recorded {
+            tracing::error!("Failed to record unconfirmed redeem {}: {}", tx_hash, e);
+        }
+    }
     
     /// Swap tokens for ETH
     /// SMS Command: SWAP <amount> TXTC
@@ -108,10 +244,17 @@ impl ContractService {
         token_amount: U256,
         min_eth_out: U256,
     ) -> Result<SwapResult, Box<dyn std::error::Error>> {
-        let tx = self.entry_point
-            .swap_token_for_eth(user_address, token_amount, min_eth_out)
-            .send()
-            .await?;
+        let mut call = self.entry_point
+            .swap_token_for_eth(user_address, token_amount, min_eth_out);
+        self.apply_fees(&mut call.tx).await;
+        self.claim_nonce(&mut call.tx).await?;
+        let tx = match call.send().await {
+            Ok(tx) => tx,
+            Err(e) => {
+                self.nonces.resync().await;
+                return Err(e.into());
+            }
+        };
         
         let receipt = tx.await?.ok_or("Transaction failed")?;
         
@@ -172,6 +315,8 @@ impl ContractService {
 #[derive(Debug, Clone)]
 pub struct RedeemResult {
     pub success: bool,
+    /// Sent but not confirmed in time; the user hears back when it is
+    pub pending: bool,
     pub token_amount: String,
     pub eth_amount: String,
     pub tx_hash: String,
@@ -184,7 +329,107 @@ pub struct SwapResult {
     pub tx_hash: String,
 }
 
+/// Blocks of fee history the base fee is read from
+const FEE_HISTORY_BLOCKS: u64 = 5;
+
+/// EIP-1559 fees from the node: the next block's base fee (from
+/// `eth_feeHistory`) plus the node's tip (`eth_maxPriorityFeePerGas`), both
+/// scaled by `multiplier`. Returns `(max_fee, max_priority_fee)`.
+pub async fn suggest_fees<M: Middleware>(
+    provider: &M,
+    multiplier: f64,
+) -> Result<(U256, U256), Box<dyn std::error::Error>>
+where
+    M::Error: 'static,
+{
+    let history = provider
+        .fee_history(FEE_HISTORY_BLOCKS, BlockNumber::Latest, &[])
+        .await?;
+    // The last entry is the base fee of the block after `latest`
+    let next_base_fee = *history.base_fee_per_gas.last().ok_or("node returned no base fees")?;
+    let priority: U256 = provider
+        .provider()
+        .request("eth_maxPriorityFeePerGas", ())
+        .await?;
+
+    let max_priority = scale(priority, multiplier);
+    Ok((scale(next_base_fee, multiplier) + max_priority, max_priority))
+}
+
+/// `value * multiplier`, to a hundredth of a percent
+fn scale(value: U256, multiplier: f64) -> U256 {
+    let bps = (multiplier.max(0.0) * 10_000.0).round() as u64;
+    value * U256::from(bps) / U256::from(10_000u64)
+}
+
 fn format_ether(value: U256) -> String {
     let eth = ethers::utils::format_ether(value);
     eth
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait(confirmations: usize, timeout_ms: u64) -> RedeemWait {
+        RedeemWait {
+            confirmations,
+            poll_interval: Duration::from_millis(5),
+            timeout: Duration::from_millis(timeout_ms),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redeem_waits_for_configured_confirmations() {
+        let (provider, mock) = Provider::mocked();
+        let tx_hash = H256::random();
+        let mined = TransactionReceipt { transaction_hash: tx_hash, block_number: Some(10.into()), ..Default::default() };
+        // Responses are served last-pushed first
+        mock.push(U64::from(13)).unwrap();
+        mock.push(U64::from(12)).unwrap();
+        mock.push(mined).unwrap();
+        mock.push(Transaction { hash: tx_hash, block_number: Some(10.into()), ..Default::default() }).unwrap();
+
+        let waited = wait(3, 1_000).wait(PendingTransaction::new(tx_hash, &provider)).await.unwrap();
+        assert!(matches!(waited, Waited::Mined(receipt) if receipt.transaction_hash == tx_hash));
+        // Block 12 isn't deep enough for 3 confirmations, so the head was asked again
+        mock.assert_request("eth_getTransactionByHash", [tx_hash]).unwrap();
+        mock.assert_request("eth_getTransactionReceipt", [tx_hash]).unwrap();
+        mock.assert_request("eth_blockNumber", ()).unwrap();
+        mock.assert_request("eth_blockNumber", ()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_suggest_fees_applies_multiplier() {
+        let (provider, mock) = Provider::mocked();
+        let gwei = U256::exp10(9);
+        // Served last-pushed first: fee history, then the tip
+        mock.push(gwei * 2).unwrap();
+        mock.push(FeeHistory {
+            base_fee_per_gas: vec![gwei * 8, gwei * 9, gwei * 10],
+            gas_used_ratio: vec![0.5, 0.6],
+            oldest_block: 100.into(),
+            reward: Vec::new(),
+        })
+        .unwrap();
+
+        let (max_fee, max_priority) = suggest_fees(&provider, 1.5).await.unwrap();
+        // Tip 2 gwei * 1.5; next base fee 10 gwei * 1.5 plus that tip
+        assert_eq!(max_priority, gwei * 3);
+        assert_eq!(max_fee, gwei * 18);
+        mock.assert_request("eth_feeHistory", (U256::from(FEE_HISTORY_BLOCKS), BlockNumber::Latest, Vec::<f64>::new()))
+            .unwrap();
+        mock.assert_request("eth_maxPriorityFeePerGas", ()).unwrap();
+
+        assert_eq!(scale(U256::from(1_000u64), 1.0), U256::from(1_000u64));
+        assert_eq!(scale(U256::from(1_000u64), 1.2345), U256::from(1_234u64));
+    }
+
+    #[tokio::test]
+    async fn test_redeem_times_out_as_pending() {
+        // The node never reports the transaction
+        let (provider, _mock) = Provider::mocked();
+        let waited = wait(1, 50).wait(PendingTransaction::new(H256::random(), &provider)).await.unwrap();
+        assert!(matches!(waited, Waited::TimedOut));
+    }
+}
@@ -14,16 +14,53 @@ pub struct Contact {
 }
 
 impl Contact {
-    /// Format for SMS display
-    pub fn to_sms_string(&self) -> String {
-        match (&self.contact_phone, &self.wallet_address) {
-            (Some(phone), _) => format!("{}: {}", self.name, phone),
-            (_, Some(addr)) => format!("{}: {}...{}", self.name, &addr[..6], &addr[38..]),
-            _ => self.name.clone(),
-        }
+    /// Format for SMS display using a template with `{name}`, `{phone}` (masked),
+    /// `{address}` (shortened, or the ENS name it was saved as) and `{contact}`
+    /// (phone if known, else address)
+    pub fn render(&self, template: &str) -> String {
+        let phone = self.contact_phone.as_deref().map(mask_phone).unwrap_or_default();
+        let address = self
+            .wallet_address
+            .as_deref()
+            .map(|a| if a.len() == 42 { format!("{}...{}", &a[..6], &a[38..]) } else { a.to_string() })
+            .unwrap_or_default();
+        let contact = if phone.is_empty() { address.clone() } else { phone.clone() };
+
+        template
+            .replace("{name}", &self.name)
+            .replace("{phone}", &phone)
+            .replace("{address}", &address)
+            .replace("{contact}", &contact)
+            // Nothing known beyond the name: drop the dangling separator
+            .trim_end_matches([' ', ':', '-'])
+            .to_string()
+    }
+}
+
+/// E.164 form of a typed phone number ("+1 555-123-4567" -> "+15551234567"),
+/// or None if it isn't one
+pub fn normalize_phone(input: &str) -> Option<String> {
+    let compact: String = input.chars().filter(|c| !matches!(c, ' ' | '-' | '(' | ')' | '.')).collect();
+    let digits = compact.strip_prefix('+')?;
+    if (8..=15).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit()) {
+        Some(compact)
+    } else {
+        None
     }
 }
 
+/// Hide all but the country prefix and last 4 digits: +15551234567 -> +1***4567
+pub fn mask_phone(phone: &str) -> String {
+    let chars: Vec<char> = phone.chars().collect();
+    let prefix = if phone.starts_with('+') { 2 } else { 0 };
+    if chars.len() <= prefix + 4 {
+        return phone.to_string();
+    }
+    let head: String = chars[..prefix].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}***{}", head, tail)
+}
+
 /// Address book repository for database operations
 #[derive(Clone)]
 pub struct AddressBookRepository {
@@ -131,3 +168,49 @@ impl AddressBookRepository {
         })
     }
 }
+
+#[cfg(test)]
+impl AddressBookRepository {
+    /// Save a contact pointing at a phone number (idempotent)
+    pub async fn seed_contact(&self, user_phone: &str, name: &str, contact_phone: &str) -> Result<Contact, sqlx::Error> {
+        self.add_contact(user_phone, name, Some(contact_phone), None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(phone: Option<&str>, address: Option<&str>) -> Contact {
+        Contact {
+            id: Uuid::new_v4(),
+            user_phone: "+15550000000".to_string(),
+            name: "mom".to_string(),
+            contact_phone: phone.map(str::to_string),
+            wallet_address: address.map(str::to_string),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_contact_rendered_from_template() {
+        let address = "0x1234567890abcdef1234567890abcdef12345678";
+        let both = contact(Some("+15551234567"), Some(address));
+
+        assert_eq!(both.render("{name}: {contact}"), "mom: +1***4567");
+        assert_eq!(both.render("{name} ({address})"), "mom (0x1234...5678)");
+        assert_eq!(contact(None, Some(address)).render("{name}: {contact}"), "mom: 0x1234...5678");
+        assert_eq!(contact(None, Some("mom.eth")).render("{name}: {contact}"), "mom: mom.eth");
+        assert_eq!(contact(None, None).render("{name}: {contact}"), "mom");
+        assert_eq!(mask_phone("5551234"), "***1234");
+        assert_eq!(mask_phone("+1234"), "+1234");
+    }
+
+    #[test]
+    fn test_normalize_phone() {
+        assert_eq!(normalize_phone("+1 (555) 123-4567").as_deref(), Some("+15551234567"));
+        assert_eq!(normalize_phone("5551234567"), None);
+        assert_eq!(normalize_phone("+1555"), None);
+        assert_eq!(normalize_phone("+1555ABC4567"), None);
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// One processed SMS command, kept for dispute resolution.
+/// Holds no raw phone number and no secrets (PINs, passphrases, voucher codes).
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub phone_hash: String,
+    pub command: String,
+    pub args: String,          // Redacted arguments
+    pub outcome: String,       // "ok", "error", "invalid", "unknown", "suppressed", "simulated"
+    pub correlation_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filters for the admin audit query
+#[derive(Debug, Default)]
+pub struct AuditQuery {
+    pub phone: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
+
+/// Hex SHA-256 of a phone number, as stored in the audit log
+pub fn hash_phone(phone: &str) -> String {
+    hex::encode(Sha256::digest(phone.trim().as_bytes()))
+}
+
+/// Audit log repository
+#[derive(Clone)]
+pub struct AuditRepository {
+    pool: PgPool,
+}
+
+impl AuditRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Append an entry for a processed command
+    pub async fn record(
+        &self,
+        phone: &str,
+        command: &str,
+        args: &str,
+        outcome: &str,
+        correlation_id: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO audit_log (id, phone_hash, command, args, outcome, correlation_id)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(hash_phone(phone))
+        .bind(command)
+        .bind(args)
+        .bind(outcome)
+        .bind(correlation_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Entries matching the filters, newest first
+    pub async fn query(&self, filter: &AuditQuery) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        sqlx::query_as::<_, AuditEntry>(
+            "SELECT id, phone_hash, command, args, outcome, correlation_id, created_at
+             FROM audit_log
+             WHERE ($1::text IS NULL OR phone_hash = $1)
+               AND ($2::timestamptz IS NULL OR created_at >= $2)
+               AND ($3::timestamptz IS NULL OR created_at < $3)
+             ORDER BY created_at DESC
+             LIMIT $4",
+        )
+        .bind(filter.phone.as_deref().map(hash_phone))
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(filter.limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
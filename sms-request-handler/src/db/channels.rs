@@ -0,0 +1,173 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// A user's Yellow state channel, as last reported by the send path
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct YellowChannel {
+    pub channel_id: String,
+    pub user_phone: String,
+    pub balance: Option<f64>, // Unknown until the backend reports it
+    pub last_activity_at: DateTime<Utc>,
+}
+
+/// Why an open channel should be closed and settled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CloseReason {
+    Idle,
+    LowBalance,
+}
+
+impl YellowChannel {
+    /// Whether the channel is due for auto-close. `idle_secs` and
+    /// `min_balance` of 0 turn the respective trigger off.
+    pub fn close_reason(&self, now: DateTime<Utc>, idle_secs: u64, min_balance: f64) -> Option<CloseReason> {
+        let idle_for = now.signed_duration_since(self.last_activity_at).num_seconds();
+        if idle_secs > 0 && idle_for >= idle_secs as i64 {
+            return Some(CloseReason::Idle);
+        }
+        match self.balance {
+            Some(balance) if min_balance > 0.0 && balance < min_balance => Some(CloseReason::LowBalance),
+            _ => None,
+        }
+    }
+}
+
+/// Yellow channel repository for database operations
+#[derive(Clone)]
+pub struct ChannelRepository {
+    pool: PgPool,
+}
+
+impl ChannelRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Note a send through a channel, with its remaining balance when known
+    pub async fn record_activity(&self, user_phone: &str, channel_id: &str, balance: Option<f64>) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO yellow_channels (channel_id, user_phone, balance)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (channel_id) DO UPDATE
+             SET balance = COALESCE($3, yellow_channels.balance), last_activity_at = NOW(), status = 'open'",
+        )
+        .bind(channel_id)
+        .bind(user_phone)
+        .bind(balance)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Record a channel just opened for the user. Returns false if they
+    /// already have an open one (at most one is open per phone).
+    pub async fn insert_open(&self, user_phone: &str, channel_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO yellow_channels (channel_id, user_phone) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(channel_id)
+        .bind(user_phone)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The user's open channel, for the next send to reuse
+    pub async fn get_open_channel(&self, user_phone: &str) -> Result<Option<YellowChannel>, sqlx::Error> {
+        sqlx::query_as::<_, YellowChannel>(
+            "SELECT channel_id, user_phone, balance, last_activity_at
+             FROM yellow_channels WHERE user_phone = $1 AND status = 'open'
+             ORDER BY last_activity_at DESC LIMIT 1",
+        )
+        .bind(user_phone)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Channels still open, least recently used first
+    pub async fn list_open(&self) -> Result<Vec<YellowChannel>, sqlx::Error> {
+        sqlx::query_as::<_, YellowChannel>(
+            "SELECT channel_id, user_phone, balance, last_activity_at
+             FROM yellow_channels WHERE status = 'open' ORDER BY last_activity_at",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// What a user has locked in open channels (unknown balances count as 0)
+    pub async fn open_balance(&self, user_phone: &str) -> Result<f64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COALESCE(SUM(balance), 0) FROM yellow_channels WHERE user_phone = $1 AND status = 'open'",
+        )
+        .bind(user_phone)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Claim an open channel for closing. Returns false if someone else did.
+    pub async fn claim_close(&self, channel_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE yellow_channels SET status = 'closing' WHERE channel_id = $1 AND status = 'open'")
+            .bind(channel_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The close settled on chain
+    pub async fn mark_closed(&self, channel_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE yellow_channels SET status = 'closed', closed_at = NOW() WHERE channel_id = $1")
+            .bind(channel_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Put a claimed channel back to open (e.g. the close call failed)
+    pub async fn reopen(&self, channel_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE yellow_channels SET status = 'open' WHERE channel_id = $1 AND status = 'closing'")
+            .bind(channel_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_close_triggers() {
+        let now = Utc::now();
+        let channel = |idle_secs: i64, balance: Option<f64>| YellowChannel {
+            channel_id: "0xabc".to_string(),
+            user_phone: "+15550001111".to_string(),
+            balance,
+            last_activity_at: now - chrono::Duration::seconds(idle_secs),
+        };
+
+        assert_eq!(channel(3_600, Some(5.0)).close_reason(now, 1_800, 1.0), Some(CloseReason::Idle));
+        assert_eq!(channel(60, Some(0.5)).close_reason(now, 1_800, 1.0), Some(CloseReason::LowBalance));
+        assert_eq!(channel(60, Some(5.0)).close_reason(now, 1_800, 1.0), None);
+        // Unknown balance never counts as low; 0 turns a trigger off
+        assert_eq!(channel(60, None).close_reason(now, 1_800, 1.0), None);
+        assert_eq!(channel(3_600, Some(0.5)).close_reason(now, 0, 0.0), None);
+    }
+
+    #[tokio::test]
+    async fn test_one_open_channel_per_phone() {
+        let Some(pool) = crate::db::test_pool().await else { return };
+        let repo = ChannelRepository::new(pool);
+        let phone = crate::test_support::random_phone();
+        let channel = || format!("0x{}", uuid::Uuid::new_v4().simple());
+        let (first, second) = (channel(), channel());
+
+        assert!(repo.insert_open(&phone, &first).await.unwrap());
+        assert!(!repo.insert_open(&phone, &second).await.unwrap());
+        assert_eq!(repo.get_open_channel(&phone).await.unwrap().unwrap().channel_id, first);
+
+        // Once it's closed the next one can open
+        repo.mark_closed(&first).await.unwrap();
+        assert!(repo.insert_open(&phone, &second).await.unwrap());
+    }
+}
@@ -8,6 +8,7 @@ pub enum DepositSource {
     Voucher,
     OnChain,
     Partner,
+    Bonus,
 }
 
 impl std::fmt::Display for DepositSource {
@@ -16,6 +17,7 @@ impl std::fmt::Display for DepositSource {
             DepositSource::Voucher => write!(f, "voucher"),
             DepositSource::OnChain => write!(f, "onchain"),
             DepositSource::Partner => write!(f, "partner"),
+            DepositSource::Bonus => write!(f, "bonus"),
         }
     }
 }
@@ -25,10 +27,12 @@ impl std::fmt::Display for DepositSource {
 pub struct Deposit {
     pub id: Uuid,
     pub user_phone: String,
-    pub amount: i64,          // Amount in micro USDC (6 decimals)
-    pub source: String,       // "voucher", "onchain", "partner"
+    pub amount: i64,          // Amount in micro units of `token` (6 decimals)
+    pub token: String,        // "USDC" or "TXTC"
+    pub source: String,       // "voucher", "onchain", "partner", "bonus"
     pub source_ref: Option<String>,  // voucher code, tx hash, or partner ref
     pub chain: Option<String>,
+    pub block_number: Option<i64>,  // Inclusion block for on-chain deposits
     pub created_at: DateTime<Utc>,
 }
 
@@ -63,7 +67,7 @@ impl DepositRepository {
             r#"
             INSERT INTO deposits (id, user_phone, amount, source, source_ref)
             VALUES ($1, $2, $3, 'voucher', $4)
-            RETURNING id, user_phone, amount, source, source_ref, chain, created_at
+            RETURNING id, user_phone, amount, token, source, source_ref, chain, block_number, created_at
             "#
         )
         .bind(id)
@@ -88,7 +92,7 @@ impl DepositRepository {
             r#"
             INSERT INTO deposits (id, user_phone, amount, source, source_ref, chain)
             VALUES ($1, $2, $3, 'onchain', $4, $5)
-            RETURNING id, user_phone, amount, source, source_ref, chain, created_at
+            RETURNING id, user_phone, amount, token, source, source_ref, chain, block_number, created_at
             "#
         )
         .bind(id)
@@ -100,21 +104,144 @@ impl DepositRepository {
         .await
     }
 
+    /// Record the one-time welcome bonus for a phone.
+    /// Returns None if this phone has already been granted one.
+    pub async fn create_welcome_bonus(&self, phone: &str, amount: i64) -> Result<Option<Deposit>, sqlx::Error> {
+        let id = Uuid::new_v4();
+
+        sqlx::query_as::<_, Deposit>(
+            r#"
+            INSERT INTO deposits (id, user_phone, amount, source, source_ref)
+            VALUES ($1, $2, $3, 'bonus', 'welcome')
+            ON CONFLICT (user_phone) WHERE source = 'bonus' DO NOTHING
+            RETURNING id, user_phone, amount, token, source, source_ref, chain, block_number, created_at
+            "#
+        )
+        .bind(id)
+        .bind(phone)
+        .bind(amount)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Drop a welcome bonus record whose grant failed
+    pub async fn remove_welcome_bonus(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM deposits WHERE id = $1 AND source = 'bonus'")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Record an on-chain deposit that still needs confirmations.
+    /// Returns None if the transaction is already tracked on this chain.
+    pub async fn create_provisional(
+        &self,
+        phone: &str,
+        amount: i64,
+        token: &str,
+        tx_hash: &str,
+        chain: &str,
+        block_number: i64,
+    ) -> Result<Option<Deposit>, sqlx::Error> {
+        let id = Uuid::new_v4();
+
+        sqlx::query_as::<_, Deposit>(
+            r#"
+            INSERT INTO deposits (id, user_phone, amount, token, source, source_ref, chain, block_number, status)
+            SELECT $1, $2, $3, $7, 'onchain', $4, $5, $6, 'provisional'
+            WHERE NOT EXISTS (
+                SELECT 1 FROM deposits WHERE source = 'onchain' AND source_ref = $4 AND chain = $5
+            )
+            RETURNING id, user_phone, amount, token, source, source_ref, chain, block_number, created_at
+            "#
+        )
+        .bind(id)
+        .bind(phone)
+        .bind(amount)
+        .bind(tx_hash)
+        .bind(chain)
+        .bind(block_number)
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Get on-chain deposits of a token on a chain still waiting for confirmations
+    pub async fn list_provisional(&self, chain: &str, token: &str) -> Result<Vec<Deposit>, sqlx::Error> {
+        sqlx::query_as::<_, Deposit>(
+            "SELECT id, user_phone, amount, token, source, source_ref, chain, block_number, created_at 
+             FROM deposits WHERE chain = $1 AND token = $2 AND status = 'provisional'
+             ORDER BY block_number"
+        )
+        .bind(chain)
+        .bind(token)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Move a provisional deposit to a new inclusion block (re-mined after a reorg)
+    pub async fn update_block(&self, id: Uuid, block_number: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE deposits SET block_number = $1 WHERE id = $2 AND status = 'provisional'")
+            .bind(block_number)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Mark a provisional deposit confirmed. Returns false if it wasn't provisional.
+    pub async fn confirm(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE deposits SET status = 'confirmed' WHERE id = $1 AND status = 'provisional'")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Claim a deposit for AUTOSWAP. True only the first time, so a deposit is swapped at most once.
+    pub async fn claim_autoswap(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE deposits SET autoswapped = TRUE WHERE id = $1 AND NOT autoswapped")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Drop a provisional deposit whose transaction was reorged out
+    pub async fn remove_provisional(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM deposits WHERE id = $1 AND status = 'provisional'")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     /// Get all deposits for a user
     pub async fn find_by_user(&self, phone: &str) -> Result<Vec<Deposit>, sqlx::Error> {
         sqlx::query_as::<_, Deposit>(
-            "SELECT id, user_phone, amount, source, source_ref, chain, created_at 
-             FROM deposits WHERE user_phone = $1 ORDER BY created_at DESC"
+            "SELECT id, user_phone, amount, token, source, source_ref, chain, block_number, created_at 
+             FROM deposits WHERE user_phone = $1 AND status = 'confirmed' ORDER BY created_at DESC"
         )
         .bind(phone)
         .fetch_all(&self.pool)
         .await
     }
 
+    /// Whether the user has received a confirmed deposit of their own (welcome bonuses don't count)
+    pub async fn has_funded(&self, phone: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM deposits WHERE user_phone = $1 AND status = 'confirmed' AND source <> 'bonus')"
+        )
+        .bind(phone)
+        .fetch_one(&self.pool)
+        .await
+    }
+
     /// Get total USDC balance for a user (from all deposits)
     pub async fn get_balance(&self, phone: &str) -> Result<i64, sqlx::Error> {
         let result = sqlx::query_scalar::<_, i64>(
-            "SELECT COALESCE(SUM(amount), 0) FROM deposits WHERE user_phone = $1"
+            "SELECT COALESCE(SUM(amount), 0)::BIGINT FROM deposits WHERE user_phone = $1 AND status = 'confirmed' AND token = 'USDC'"
         )
         .bind(phone)
         .fetch_one(&self.pool)
@@ -131,10 +258,11 @@ impl DepositRepository {
     }
 
     /// Get recent deposits (last N)
+    /// Includes provisional deposits, so HISTORY can show them as pending
     pub async fn get_recent(&self, phone: &str, limit: i64) -> Result<Vec<Deposit>, sqlx::Error> {
         sqlx::query_as::<_, Deposit>(
-            "SELECT id, user_phone, amount, source, source_ref, chain, created_at 
-             FROM deposits WHERE user_phone = $1 
+            "SELECT id, user_phone, amount, token, source, source_ref, chain, block_number, created_at 
+             FROM deposits WHERE user_phone = $1
              ORDER BY created_at DESC LIMIT $2"
         )
         .bind(phone)
@@ -143,3 +271,23 @@ impl DepositRepository {
         .await
     }
 }
+
+#[cfg(test)]
+impl DepositRepository {
+    /// Record a confirmed on-chain deposit with a unique fake tx hash
+    pub async fn seed_deposit(&self, phone: &str, amount: i64) -> Result<Deposit, sqlx::Error> {
+        let tx_hash = format!("0x{}", Uuid::new_v4().simple());
+        self.create_from_chain(phone, amount, &tx_hash, "Polygon Amoy").await
+    }
+
+    /// Record a confirmed on-chain deposit of `token`
+    pub async fn seed_token_deposit(&self, phone: &str, amount: i64, token: &str) -> Result<Deposit, sqlx::Error> {
+        let tx_hash = format!("0x{}", Uuid::new_v4().simple());
+        let deposit = self
+            .create_provisional(phone, amount, token, &tx_hash, "Polygon Amoy", 1)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)?;
+        self.confirm(deposit.id).await?;
+        Ok(deposit)
+    }
+}
@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const ESCROW_COLUMNS: &str =
+    "id, payer_phone, payer_address, recipient, recipient_address, amount, token, hold_ref, release_at";
+
+/// Funds locked by a payer until a release date or a manual RELEASE
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Escrow {
+    pub id: Uuid,
+    pub payer_phone: String,
+    pub payer_address: String,
+    pub recipient: String,          // As typed by the payer (name, phone, ENS or address)
+    pub recipient_address: String,
+    pub amount: f64,
+    pub token: String,
+    pub hold_ref: Option<String>,   // Backend escrow reference for release/refund
+    pub release_at: Option<DateTime<Utc>>,
+}
+
+impl Escrow {
+    /// Short reference shown in SMS and accepted by RELEASE
+    pub fn short_id(&self) -> String {
+        self.id.simple().to_string()[..6].to_uppercase()
+    }
+}
+
+/// Escrow repository for database operations
+#[derive(Clone)]
+pub struct EscrowRepository {
+    pool: PgPool,
+}
+
+impl EscrowRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record newly locked funds
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create(
+        &self,
+        payer_phone: &str,
+        payer_address: &str,
+        recipient: &str,
+        recipient_address: &str,
+        amount: f64,
+        token: &str,
+        hold_ref: Option<&str>,
+        release_at: Option<DateTime<Utc>>,
+    ) -> Result<Escrow, sqlx::Error> {
+        let id = Uuid::new_v4();
+
+        sqlx::query_as::<_, Escrow>(&format!(
+            "INSERT INTO escrows (id, payer_phone, payer_address, recipient, recipient_address, amount, token, hold_ref, release_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             RETURNING {}",
+            ESCROW_COLUMNS
+        ))
+        .bind(id)
+        .bind(payer_phone)
+        .bind(payer_address)
+        .bind(recipient)
+        .bind(recipient_address)
+        .bind(amount)
+        .bind(token)
+        .bind(hold_ref)
+        .bind(release_at)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Find a payer's held escrow by its short reference
+    pub async fn find_held(&self, payer_phone: &str, short_id: &str) -> Result<Option<Escrow>, sqlx::Error> {
+        sqlx::query_as::<_, Escrow>(&format!(
+            "SELECT {} FROM escrows
+             WHERE payer_phone = $1 AND status = 'held' AND REPLACE(id::text, '-', '') LIKE $2
+             ORDER BY created_at
+             LIMIT 1",
+            ESCROW_COLUMNS
+        ))
+        .bind(payer_phone)
+        .bind(format!("{}%", short_id.to_lowercase()))
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Held escrows whose release date has passed
+    pub async fn due_for_release(&self, now: DateTime<Utc>) -> Result<Vec<Escrow>, sqlx::Error> {
+        sqlx::query_as::<_, Escrow>(&format!(
+            "SELECT {} FROM escrows
+             WHERE status = 'held' AND release_at IS NOT NULL AND release_at <= $1
+             ORDER BY release_at",
+            ESCROW_COLUMNS
+        ))
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Total a payer still has held in escrow for one token
+    pub async fn held_total(&self, payer_phone: &str, token: &str) -> Result<f64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COALESCE(SUM(amount), 0) FROM escrows
+             WHERE payer_phone = $1 AND UPPER(token) = UPPER($2) AND status = 'held'",
+        )
+        .bind(payer_phone)
+        .bind(token)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Undated held escrows created before the cutoff (never released)
+    pub async fn stale(&self, cutoff: DateTime<Utc>) -> Result<Vec<Escrow>, sqlx::Error> {
+        sqlx::query_as::<_, Escrow>(&format!(
+            "SELECT {} FROM escrows
+             WHERE status = 'held' AND release_at IS NULL AND created_at <= $1
+             ORDER BY created_at",
+            ESCROW_COLUMNS
+        ))
+        .bind(cutoff)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Resolve a held escrow. Returns false if it was already resolved.
+    pub async fn resolve(&self, id: Uuid, status: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE escrows SET status = $1, resolved_at = NOW()
+             WHERE id = $2 AND status = 'held'"
+        )
+        .bind(status)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Give up on a claimed escrow the backend can't settle, for support to review
+    pub async fn mark_failed(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE escrows SET status = 'failed', resolved_at = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Put a claimed escrow back to held (e.g. the backend call failed)
+    pub async fn reopen(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE escrows SET status = 'held', resolved_at = NULL WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Incoming transfer held until the recipient accepts or declines it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct InboundTransfer {
+    pub id: Uuid,
+    pub recipient_phone: String,
+    pub sender_phone: String,
+    pub sender_address: String,
+    pub amount: f64,
+    pub token: String,
+    pub hold_ref: Option<String>,  // Backend escrow reference for release/refund
+}
+
+impl InboundTransfer {
+    /// Format for SMS display
+    pub fn to_sms_string(&self) -> String {
+        format!("{} {} from {}", self.amount, self.token, self.sender_phone)
+    }
+}
+
+/// Inbound transfer repository for database operations
+#[derive(Clone)]
+pub struct InboundTransferRepository {
+    pool: PgPool,
+}
+
+impl InboundTransferRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a transfer waiting on the recipient's decision
+    pub async fn create_pending(
+        &self,
+        recipient_phone: &str,
+        sender_phone: &str,
+        sender_address: &str,
+        amount: f64,
+        token: &str,
+        hold_ref: Option<&str>,
+    ) -> Result<InboundTransfer, sqlx::Error> {
+        let id = Uuid::new_v4();
+
+        sqlx::query_as::<_, InboundTransfer>(
+            r#"
+            INSERT INTO inbound_transfers (id, recipient_phone, sender_phone, sender_address, amount, token, hold_ref)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, recipient_phone, sender_phone, sender_address, amount, token, hold_ref
+            "#
+        )
+        .bind(id)
+        .bind(recipient_phone)
+        .bind(sender_phone)
+        .bind(sender_address)
+        .bind(amount)
+        .bind(token)
+        .bind(hold_ref)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Get pending transfers for a recipient, oldest first
+    pub async fn list_pending(&self, recipient_phone: &str) -> Result<Vec<InboundTransfer>, sqlx::Error> {
+        sqlx::query_as::<_, InboundTransfer>(
+            "SELECT id, recipient_phone, sender_phone, sender_address, amount, token, hold_ref
+             FROM inbound_transfers
+             WHERE recipient_phone = $1 AND status = 'pending'
+             ORDER BY created_at"
+        )
+        .bind(recipient_phone)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Sender phone, amount, token, status and time of every transfer sent
+    /// to a phone, newest first
+    pub async fn list_received(
+        &self,
+        recipient_phone: &str,
+    ) -> Result<Vec<(String, f64, String, String, DateTime<Utc>)>, sqlx::Error> {
+        sqlx::query_as::<_, (String, f64, String, String, DateTime<Utc>)>(
+            "SELECT sender_phone, amount, token, status, created_at
+             FROM inbound_transfers WHERE recipient_phone = $1
+             ORDER BY created_at DESC"
+        )
+        .bind(recipient_phone)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Resolve a pending transfer. Returns false if it was already resolved.
+    pub async fn resolve(&self, id: Uuid, status: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE inbound_transfers SET status = $1, resolved_at = NOW()
+             WHERE id = $2 AND status = 'pending'"
+        )
+        .bind(status)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Give up on a claimed transfer the backend can't settle, for support to review
+    pub async fn mark_failed(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE inbound_transfers SET status = 'failed', resolved_at = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Put a claimed transfer back to pending (e.g. the backend call failed)
+    pub async fn reopen(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE inbound_transfers SET status = 'pending', resolved_at = NULL WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
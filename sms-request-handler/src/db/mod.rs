@@ -1,10 +1,28 @@
 pub mod address_book;
+pub mod audit;
+pub mod channels;
 pub mod deposits;
+pub mod escrows;
+pub mod inbound;
+pub mod pending_txs;
+pub mod receipts;
+pub mod tags;
+pub mod transactions;
+pub mod transfers;
 pub mod users;
 pub mod vouchers;
 
 pub use address_book::*;
+pub use audit::*;
+pub use channels::*;
 pub use deposits::*;
+pub use escrows::*;
+pub use inbound::*;
+pub use pending_txs::*;
+pub use receipts::*;
+pub use tags::*;
+pub use transactions::*;
+pub use transfers::*;
 pub use users::*;
 pub use vouchers::*;
 
@@ -19,8 +37,48 @@ pub async fn create_pool(database_url: &str) -> Result<PgPool, sqlx::Error> {
         .await
 }
 
-/// Run database migrations
+/// Schema version this build migrates to. Bump it whenever `run_migrations`
+/// gains a step, so an older build refuses to run against the newer schema.
+pub const SCHEMA_VERSION: i32 = 8;
+
+/// Highest schema version recorded in the database (0 before versioning)
+pub async fn schema_version(pool: &PgPool) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar::<_, Option<i32>>("SELECT MAX(version) FROM schema_version")
+        .fetch_one(pool)
+        .await
+        .map(|version| version.unwrap_or(0))
+}
+
+/// Run database migrations. Refuses a database migrated by a newer build,
+/// since this build's migrations and queries may not match its schema.
 pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            applied_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    let applied = schema_version(pool).await?;
+    if applied > SCHEMA_VERSION {
+        return Err(sqlx::Error::Configuration(
+            format!(
+                "database schema is at version {} but this build expects {}; refusing to start (deploy a newer build)",
+                applied, SCHEMA_VERSION
+            )
+            .into(),
+        ));
+    }
+    tracing::info!(
+        applied,
+        pending = SCHEMA_VERSION - applied,
+        "Schema at version {}, migrating to {}",
+        applied,
+        SCHEMA_VERSION
+    );
+
     tracing::info!("Creating users table...");
     // Users table
     sqlx::query(
@@ -38,6 +96,72 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // ERC-4337 account mode per user
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS account_mode VARCHAR(10) NOT NULL DEFAULT 'eoa'")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS smart_account_address VARCHAR(42)")
+        .execute(pool)
+        .await?;
+
+    // Receive-only users get their Arc wallet on first send
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS receive_only BOOLEAN NOT NULL DEFAULT FALSE")
+        .execute(pool)
+        .await?;
+
+    // Carrier STOP/START opt-out
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS opted_out BOOLEAN NOT NULL DEFAULT FALSE")
+        .execute(pool)
+        .await?;
+
+    // CURRENCY preference for approximate fiat values
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS display_currency VARCHAR(3)")
+        .execute(pool)
+        .await?;
+
+    // APPROVE: incoming transfers above this amount of this token wait for ACCEPT
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS approve_above DOUBLE PRECISION")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS approve_above_token VARCHAR(10)")
+        .execute(pool)
+        .await?;
+
+    // FREEZE: outgoing operations blocked until UNFREEZE <pin>
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS frozen BOOLEAN NOT NULL DEFAULT FALSE")
+        .execute(pool)
+        .await?;
+
+    // Wrong PINs in a row, and when the resulting lockout ends
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS pin_failures INTEGER NOT NULL DEFAULT 0")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS pin_locked_until TIMESTAMPTZ")
+        .execute(pool)
+        .await?;
+
+    // AUTOSWAP: percent of each on-chain deposit swapped to gas
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS autoswap_pct INTEGER")
+        .execute(pool)
+        .await?;
+
+    // Language the user writes commands in, for localized replies (NULL = English)
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS language VARCHAR(5)")
+        .execute(pool)
+        .await?;
+
+    // MASTER_MNEMONIC mode: wallets derived at m/44'/60'/0'/0/{index}, key not stored
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS derivation_index INTEGER UNIQUE")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE SEQUENCE IF NOT EXISTS wallet_derivation_index_seq MINVALUE 0 START WITH 0")
+        .execute(pool)
+        .await?;
+
     tracing::info!("Creating indices for users...");
     sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_phone ON users(phone)")
         .execute(pool)
@@ -73,6 +197,10 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_vouchers_redeemed_by ON vouchers(redeemed_by)")
+        .execute(pool)
+        .await?;
+
     tracing::info!("Creating deposits table...");
     // Deposits table
     sqlx::query(
@@ -89,6 +217,25 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Provisional on-chain deposits wait for confirmations before counting
+    sqlx::query("ALTER TABLE deposits ADD COLUMN IF NOT EXISTS block_number BIGINT")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE deposits ADD COLUMN IF NOT EXISTS status VARCHAR(20) NOT NULL DEFAULT 'confirmed'")
+        .execute(pool)
+        .await?;
+
+    // Set once AUTOSWAP has acted on the deposit, so it never swaps twice
+    sqlx::query("ALTER TABLE deposits ADD COLUMN IF NOT EXISTS autoswapped BOOLEAN NOT NULL DEFAULT FALSE")
+        .execute(pool)
+        .await?;
+
+    // Token the deposit arrived in; rows from before the column are all USDC
+    sqlx::query("ALTER TABLE deposits ADD COLUMN IF NOT EXISTS token VARCHAR(10) NOT NULL DEFAULT 'USDC'")
+        .execute(pool)
+        .await?;
+
     tracing::info!("Creating indices for deposits...");
     sqlx::query("CREATE INDEX IF NOT EXISTS idx_deposits_user ON deposits(user_phone)")
         .execute(pool)
@@ -98,6 +245,15 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_deposits_chain_ref ON deposits(chain, source_ref)")
+        .execute(pool)
+        .await?;
+
+    // At most one welcome bonus per phone, even if the user is re-created
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_deposits_welcome_bonus ON deposits(user_phone) WHERE source = 'bonus'")
+        .execute(pool)
+        .await?;
+
     tracing::info!("Creating address_book table...");
     // Address book table
     sqlx::query(
@@ -136,7 +292,304 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
-    tracing::info!("Database migrations completed");
+    tracing::info!("Creating inbound_transfers table...");
+    // Inbound transfers awaiting recipient acceptance
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS inbound_transfers (
+            id UUID PRIMARY KEY,
+            recipient_phone VARCHAR(20) NOT NULL,
+            sender_phone VARCHAR(20) NOT NULL,
+            sender_address VARCHAR(42) NOT NULL,
+            amount DOUBLE PRECISION NOT NULL,
+            token VARCHAR(10) NOT NULL,
+            hold_ref VARCHAR(255),
+            status VARCHAR(20) NOT NULL DEFAULT 'pending',
+            resolved_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_inbound_recipient ON inbound_transfers(recipient_phone, status)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating outgoing_transfers table...");
+    // SENDs submitted to Yellow, kept until their outcome is known
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS outgoing_transfers (
+            id UUID PRIMARY KEY,
+            sender_phone VARCHAR(20) NOT NULL,
+            from_address VARCHAR(42) NOT NULL,
+            to_address VARCHAR(42) NOT NULL,
+            amount DOUBLE PRECISION NOT NULL,
+            token VARCHAR(10) NOT NULL,
+            status VARCHAR(20) NOT NULL DEFAULT 'pending',
+            error TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_outgoing_transfers_status ON outgoing_transfers(status, created_at)")
+        .execute(pool)
+        .await?;
+
+    // Hash reported by Yellow, listed in EXPORT HISTORY
+    sqlx::query("ALTER TABLE outgoing_transfers ADD COLUMN IF NOT EXISTS tx_hash VARCHAR(255)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating escrows table...");
+    // Escrowed payments released on a date or by the payer
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS escrows (
+            id UUID PRIMARY KEY,
+            payer_phone VARCHAR(20) NOT NULL,
+            payer_address VARCHAR(42) NOT NULL,
+            recipient VARCHAR(255) NOT NULL,
+            recipient_address VARCHAR(42) NOT NULL,
+            amount DOUBLE PRECISION NOT NULL,
+            token VARCHAR(10) NOT NULL,
+            hold_ref VARCHAR(255),
+            release_at TIMESTAMP WITH TIME ZONE,
+            status VARCHAR(20) NOT NULL DEFAULT 'held',
+            resolved_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_escrows_payer ON escrows(payer_phone, status)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_escrows_release ON escrows(status, release_at)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating audit_log table...");
+    // Per-command audit trail (hashed phone, redacted args)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id UUID PRIMARY KEY,
+            phone_hash CHAR(64) NOT NULL,
+            command VARCHAR(20) NOT NULL,
+            args TEXT NOT NULL DEFAULT '',
+            outcome VARCHAR(20) NOT NULL,
+            correlation_id UUID NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_phone ON audit_log(phone_hash, created_at)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_created ON audit_log(created_at)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating receipts table...");
+    // Signed receipts for completed SENDs, looked up by reference
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS receipts (
+            reference VARCHAR(16) PRIMARY KEY,
+            amount DOUBLE PRECISION NOT NULL,
+            token VARCHAR(10) NOT NULL,
+            from_address VARCHAR(42) NOT NULL,
+            to_address VARCHAR(42) NOT NULL,
+            tx_hash VARCHAR(255),
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            signature CHAR(64) NOT NULL
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating linked_phones table...");
+    // Extra numbers attached to a user's wallet with LINK
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS linked_phones (
+            phone VARCHAR(20) PRIMARY KEY,
+            primary_phone VARCHAR(20) NOT NULL REFERENCES users(phone) ON DELETE CASCADE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating yellow_channels table...");
+    // Users' Yellow state channels, for auto-closing idle or drained ones
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS yellow_channels (
+            channel_id VARCHAR(66) PRIMARY KEY,
+            user_phone VARCHAR(20) NOT NULL,
+            balance DOUBLE PRECISION,
+            status VARCHAR(20) NOT NULL DEFAULT 'open',
+            last_activity_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            closed_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_yellow_channels_status ON yellow_channels(status, last_activity_at)")
+        .execute(pool)
+        .await?;
+
+    // At most one open channel per phone: older duplicates from concurrent
+    // sends are marked closed before the index goes on
+    sqlx::query(
+        "UPDATE yellow_channels SET status = 'closed', closed_at = NOW()
+         WHERE status = 'open' AND channel_id NOT IN (
+             SELECT DISTINCT ON (user_phone) channel_id FROM yellow_channels
+             WHERE status = 'open' ORDER BY user_phone, last_activity_at DESC
+         )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_yellow_channels_one_open
+         ON yellow_channels(user_phone) WHERE status = 'open'",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating payee_tags table...");
+    // TAG: default note for a payer's sends to a payee
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS payee_tags (
+            payer_phone VARCHAR(20) NOT NULL,
+            payee VARCHAR(255) NOT NULL,
+            tag VARCHAR(64) NOT NULL,
+            updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (payer_phone, payee)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating transactions table...");
+    // Sends, swaps, cashouts and bridges the backend accepted, for HISTORY
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS transactions (
+            id UUID PRIMARY KEY,
+            phone VARCHAR(20) NOT NULL,
+            kind VARCHAR(20) NOT NULL,
+            amount DOUBLE PRECISION NOT NULL,
+            token VARCHAR(20) NOT NULL,
+            counterparty VARCHAR(255),
+            tx_hash VARCHAR(255),
+            status VARCHAR(20) NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_transactions_phone ON transactions(phone, created_at)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating pending_txs table...");
+    // Sends awaiting CONFIRM/CANCEL; used rows keep their code to refuse replays
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS pending_txs (
+            id UUID PRIMARY KEY,
+            phone VARCHAR(20) NOT NULL,
+            code VARCHAR(16) NOT NULL,
+            amount DOUBLE PRECISION NOT NULL,
+            token VARCHAR(20) NOT NULL,
+            recipient VARCHAR(255) NOT NULL,
+            note TEXT,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            used_at TIMESTAMP WITH TIME ZONE,
+            confirmed BOOLEAN
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_pending_txs_phone ON pending_txs(phone, created_at)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("INSERT INTO schema_version (version) VALUES ($1) ON CONFLICT (version) DO NOTHING")
+        .bind(SCHEMA_VERSION)
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Database migrations completed (schema version {})", SCHEMA_VERSION);
     Ok(())
 }
 
+/// Connect to the test database named by `TEST_DATABASE_URL`, migrating it once per run.
+/// Returns None when the variable isn't set so DB-backed tests can skip locally;
+/// under CI (`CI` set) a missing database fails the test instead of passing it.
+#[cfg(test)]
+pub async fn test_pool() -> Option<PgPool> {
+    static MIGRATED: tokio::sync::OnceCell<()> = tokio::sync::OnceCell::const_new();
+
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        assert!(
+            std::env::var_os("CI").is_none(),
+            "CI is set but TEST_DATABASE_URL isn't: database-backed tests would pass without running"
+        );
+        return None;
+    };
+    let pool = PgPoolOptions::new()
+        .max_connections(2)
+        .connect(&url)
+        .await
+        .expect("TEST_DATABASE_URL is set but unreachable");
+    MIGRATED
+        .get_or_init(|| async { run_migrations(&pool).await.expect("test migrations failed") })
+        .await;
+    Some(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::postgres::PgConnectOptions;
+
+    #[tokio::test]
+    async fn test_newer_schema_version_refuses_to_start() {
+        let Some(admin) = test_pool().await else { return };
+        let url = std::env::var("TEST_DATABASE_URL").unwrap();
+        // A schema of its own, so the shared test tables keep their version
+        let schema = format!("migrate_{}", rand::random::<u32>());
+        sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&admin).await.unwrap();
+        let options: PgConnectOptions = url.parse().unwrap();
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(options.options([("search_path", schema.as_str())]))
+            .await
+            .unwrap();
+
+        run_migrations(&pool).await.unwrap();
+        assert_eq!(schema_version(&pool).await.unwrap(), SCHEMA_VERSION);
+        // Re-running at the same version is fine
+        run_migrations(&pool).await.unwrap();
+
+        // A later build migrated it, then the deploy was rolled back
+        sqlx::query("INSERT INTO schema_version (version) VALUES ($1)")
+            .bind(SCHEMA_VERSION + 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let err = run_migrations(&pool).await.unwrap_err();
+        assert!(err.to_string().contains("refusing to start"), "{}", err);
+
+        pool.close().await;
+        sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema)).execute(&admin).await.unwrap();
+    }
+}
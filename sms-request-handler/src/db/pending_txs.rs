@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A SEND waiting for CONFIRM/CANCEL. Rows outlive a restart and are
+/// shared by every instance behind the webhook.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PendingTx {
+    pub id: Uuid,
+    pub phone: String,
+    pub code: String,
+    pub amount: f64,
+    pub token: String,
+    pub recipient: String,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub confirmed: Option<bool>, // Set with used_at: confirmed (true) or cancelled
+}
+
+impl PendingTx {
+    /// Whether this was created (or used) `ttl` or longer ago
+    pub fn is_older_than(&self, ttl: Duration) -> bool {
+        self.used_at.unwrap_or(self.created_at) <= cutoff(ttl)
+    }
+}
+
+/// Rows stamped at or before this are `ttl` old
+fn cutoff(ttl: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(ttl)
+        .ok()
+        .and_then(|ttl| Utc::now().checked_sub_signed(ttl))
+        .unwrap_or(DateTime::UNIX_EPOCH)
+}
+
+#[derive(Clone)]
+pub struct PendingTxRepository {
+    pool: PgPool,
+}
+
+impl PendingTxRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store a send awaiting confirmation under `code`
+    pub async fn create(
+        &self,
+        phone: &str,
+        code: &str,
+        amount: f64,
+        token: &str,
+        recipient: &str,
+        note: Option<&str>,
+    ) -> Result<Uuid, sqlx::Error> {
+        let id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO pending_txs (id, phone, code, amount, token, recipient, note, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(id)
+        .bind(phone)
+        .bind(code)
+        .bind(amount)
+        .bind(token)
+        .bind(recipient)
+        .bind(note)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    /// The phone's unused sends younger than `ttl`, oldest first, and how
+    /// many had expired. Expired sends and codes used more than `ttl` ago
+    /// are deleted on the way.
+    pub async fn live(&self, phone: &str, ttl: Duration) -> Result<(Vec<PendingTx>, usize), sqlx::Error> {
+        let expired: Vec<bool> = sqlx::query_scalar(
+            "DELETE FROM pending_txs
+             WHERE phone = $1 AND COALESCE(used_at, created_at) <= $2
+             RETURNING used_at IS NULL",
+        )
+        .bind(phone)
+        .bind(cutoff(ttl))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let live = sqlx::query_as::<_, PendingTx>(
+            "SELECT id, phone, code, amount, token, recipient, note, created_at, used_at, confirmed
+             FROM pending_txs WHERE phone = $1 AND used_at IS NULL
+             ORDER BY created_at",
+        )
+        .bind(phone)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok((live, expired.into_iter().filter(|unused| *unused).count()))
+    }
+
+    /// The phone's send with this code (case-insensitive), used or not
+    pub async fn find_code(&self, phone: &str, code: &str) -> Result<Option<PendingTx>, sqlx::Error> {
+        sqlx::query_as::<_, PendingTx>(
+            "SELECT id, phone, code, amount, token, recipient, note, created_at, used_at, confirmed
+             FROM pending_txs WHERE phone = $1 AND UPPER(code) = UPPER($2)
+             ORDER BY created_at DESC LIMIT 1",
+        )
+        .bind(phone)
+        .bind(code)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Mark a send confirmed or cancelled. False if it was already used, so
+    /// of two concurrent CONFIRMs only one gets it.
+    pub async fn claim(&self, id: Uuid, confirmed: bool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE pending_txs SET used_at = $2, confirmed = $3 WHERE id = $1 AND used_at IS NULL",
+        )
+        .bind(id)
+        .bind(Utc::now())
+        .bind(confirmed)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+
+    #[tokio::test]
+    async fn test_claimed_once_and_expired_rows_dropped() {
+        let Some(pool) = test_pool().await else { return };
+        let repo = PendingTxRepository::new(pool);
+        let phone = crate::test_support::random_phone();
+
+        let id = repo.create(&phone, "A1B2C3", 5.0, "TXTC", "alice", Some("rent")).await.unwrap();
+        let (live, expired) = repo.live(&phone, Duration::from_secs(60)).await.unwrap();
+        assert_eq!((live.len(), expired), (1, 0));
+        assert_eq!(live[0].note.as_deref(), Some("rent"));
+
+        assert!(repo.claim(id, true).await.unwrap());
+        assert!(!repo.claim(id, false).await.unwrap());
+        let used = repo.find_code(&phone, "a1b2c3").await.unwrap().unwrap();
+        assert_eq!(used.confirmed, Some(true));
+
+        repo.create(&phone, "D4E5F6", 1.0, "TXTC", "bob", None).await.unwrap();
+        let (live, expired) = repo.live(&phone, Duration::ZERO).await.unwrap();
+        assert_eq!((live.len(), expired), (0, 1));
+        assert!(repo.find_code(&phone, "A1B2C3").await.unwrap().is_none());
+    }
+}
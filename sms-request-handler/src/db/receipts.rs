@@ -0,0 +1,145 @@
+use chrono::{DateTime, SubsecRound, Utc};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signed proof of a completed SEND, served at /receipt/{reference}
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Receipt {
+    pub reference: String,
+    pub amount: f64,
+    pub token: String,
+    pub from_address: String,
+    pub to_address: String,
+    pub tx_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub signature: String, // hex HMAC-SHA256 over everything above
+}
+
+impl Receipt {
+    /// Unsigned receipt for a send happening now, with a fresh reference
+    pub fn new(amount: f64, token: &str, from_address: &str, to_address: &str, tx_hash: Option<&str>) -> Self {
+        const ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+        let mut rng = rand::thread_rng();
+        let reference = (0..10).map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char).collect();
+        Self {
+            reference,
+            amount,
+            token: token.to_uppercase(),
+            from_address: from_address.to_string(),
+            to_address: to_address.to_string(),
+            tx_hash: tx_hash.map(str::to_string),
+            // Postgres keeps microseconds; sign what will be read back
+            created_at: Utc::now().trunc_subsecs(6),
+            signature: String::new(),
+        }
+    }
+
+    /// The receipt signed with the service key
+    pub fn signed(mut self, key: &str) -> Self {
+        self.signature = hex::encode(self.mac(key).finalize().into_bytes());
+        self
+    }
+
+    /// Whether the signature matches the contents under the service key
+    pub fn verify(&self, key: &str) -> bool {
+        match hex::decode(&self.signature) {
+            Ok(signature) => self.mac(key).verify_slice(&signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn mac(&self, key: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+        let payload = format!(
+            "{}|{}|{}|{}|{}|{}|{}",
+            self.reference,
+            self.amount,
+            self.token,
+            self.from_address.to_lowercase(),
+            self.to_address.to_lowercase(),
+            self.tx_hash.as_deref().unwrap_or(""),
+            self.created_at.timestamp_micros()
+        );
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+/// Receipt repository for database operations
+#[derive(Clone)]
+pub struct ReceiptRepository {
+    pool: PgPool,
+}
+
+impl ReceiptRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store a signed receipt
+    pub async fn insert(&self, receipt: &Receipt) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO receipts (reference, amount, token, from_address, to_address, tx_hash, created_at, signature)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(&receipt.reference)
+        .bind(receipt.amount)
+        .bind(&receipt.token)
+        .bind(&receipt.from_address)
+        .bind(&receipt.to_address)
+        .bind(&receipt.tx_hash)
+        .bind(receipt.created_at)
+        .bind(&receipt.signature)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Find a receipt by reference (case-insensitive)
+    pub async fn find(&self, reference: &str) -> Result<Option<Receipt>, sqlx::Error> {
+        sqlx::query_as::<_, Receipt>(
+            "SELECT reference, amount, token, from_address, to_address, tx_hash, created_at, signature
+             FROM receipts WHERE reference = UPPER($1)",
+        )
+        .bind(reference)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_receipt_verifies_and_tampered_one_fails() {
+        let receipt = Receipt::new(
+            5.0,
+            "txtc",
+            "0x1111111111111111111111111111111111111111",
+            "0x2222222222222222222222222222222222222222",
+            Some("0xabc"),
+        )
+        .signed("service-key");
+        assert_eq!(receipt.reference.len(), 10);
+        assert!(receipt.verify("service-key"));
+        assert!(!receipt.verify("other-key"));
+
+        let mut tampered = receipt.clone();
+        tampered.amount = 50.0;
+        assert!(!tampered.verify("service-key"));
+
+        let mut redirected = receipt.clone();
+        redirected.to_address = "0x3333333333333333333333333333333333333333".to_string();
+        assert!(!redirected.verify("service-key"));
+
+        let mut garbled = receipt;
+        garbled.signature = "not hex".to_string();
+        assert!(!garbled.verify("service-key"));
+    }
+}
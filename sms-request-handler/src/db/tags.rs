@@ -0,0 +1,47 @@
+use sqlx::PgPool;
+
+/// Default send notes per payer and payee, set with TAG
+#[derive(Clone)]
+pub struct PayeeTagRepository {
+    pool: PgPool,
+}
+
+impl PayeeTagRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Tag `payee` (as the payer types it, case-insensitive), replacing any earlier tag
+    pub async fn set(&self, payer_phone: &str, payee: &str, tag: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO payee_tags (payer_phone, payee, tag)
+             VALUES ($1, LOWER($2), $3)
+             ON CONFLICT (payer_phone, payee) DO UPDATE SET tag = $3, updated_at = NOW()",
+        )
+        .bind(payer_phone)
+        .bind(payee)
+        .bind(tag)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Drop the tag on `payee`. Returns false if there was none.
+    pub async fn remove(&self, payer_phone: &str, payee: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM payee_tags WHERE payer_phone = $1 AND payee = LOWER($2)")
+            .bind(payer_phone)
+            .bind(payee)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The default note for sends from `payer_phone` to `payee`, if tagged
+    pub async fn get(&self, payer_phone: &str, payee: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, String>("SELECT tag FROM payee_tags WHERE payer_phone = $1 AND payee = LOWER($2)")
+            .bind(payer_phone)
+            .bind(payee.trim())
+            .fetch_optional(&self.pool)
+            .await
+    }
+}
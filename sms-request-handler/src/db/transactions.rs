@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A send, swap, cashout or bridge the backend accepted, for HISTORY
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Transaction {
+    pub id: Uuid,
+    pub phone: String,
+    pub kind: String,                 // "send", "swap", "cashout", "bridge"
+    pub amount: f64,
+    pub token: String,
+    pub counterparty: Option<String>, // recipient, payout currency or destination chain
+    pub tx_hash: Option<String>,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Transaction {
+    /// One HISTORY line, e.g. "Sent 5 TXTC to +15550100002"
+    pub fn summary(&self, amount: &str) -> String {
+        let verb = match self.kind.as_str() {
+            "send" => "Sent",
+            "swap" => "Swapped",
+            "cashout" => "Cashed out",
+            "bridge" => "Bridged",
+            other => other,
+        };
+        let target = match (self.kind.as_str(), self.counterparty.as_deref()) {
+            (_, None) => String::new(),
+            ("swap", Some(to)) => format!(" for {}", to),
+            (_, Some(to)) => format!(" to {}", to),
+        };
+        format!("{} {} {}{}", verb, amount, self.token, target)
+    }
+}
+
+/// User-facing record of outgoing activity, written when the backend
+/// reports success
+#[derive(Clone)]
+pub struct TransactionRepository {
+    pool: PgPool,
+}
+
+impl TransactionRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a transaction for `phone`
+    #[allow(clippy::too_many_arguments)]
+    pub async fn record(
+        &self,
+        phone: &str,
+        kind: &str,
+        amount: f64,
+        token: &str,
+        counterparty: Option<&str>,
+        tx_hash: Option<&str>,
+        status: &str,
+    ) -> Result<Uuid, sqlx::Error> {
+        let id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO transactions (id, phone, kind, amount, token, counterparty, tx_hash, status)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(id)
+        .bind(phone)
+        .bind(kind)
+        .bind(amount)
+        .bind(token.to_uppercase())
+        .bind(counterparty)
+        .bind(tx_hash)
+        .bind(status)
+        .execute(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    /// The user's `limit` most recent transactions, newest first
+    pub async fn recent(&self, phone: &str, limit: i64) -> Result<Vec<Transaction>, sqlx::Error> {
+        sqlx::query_as::<_, Transaction>(
+            "SELECT id, phone, kind, amount, token, counterparty, tx_hash, status, created_at
+             FROM transactions WHERE phone = $1
+             ORDER BY created_at DESC LIMIT $2",
+        )
+        .bind(phone)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+
+    #[tokio::test]
+    async fn test_recorded_transactions_come_back_newest_first() {
+        let Some(pool) = test_pool().await else { return };
+        let repo = TransactionRepository::new(pool);
+        let phone = crate::test_support::random_phone();
+
+        repo.record(&phone, "swap", 10.0, "txtc", Some("ETH"), None, "submitted").await.unwrap();
+        repo.record(&phone, "send", 2.5, "TXTC", Some("+15550100002"), Some("0xabc"), "queued").await.unwrap();
+
+        let recent = repo.recent(&phone, 5).await.unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].summary("2.5"), "Sent 2.5 TXTC to +15550100002");
+        assert_eq!(recent[0].tx_hash.as_deref(), Some("0xabc"));
+        assert_eq!(recent[1].summary("10"), "Swapped 10 TXTC for ETH");
+        assert_eq!(repo.recent(&phone, 1).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,173 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Tracked outgoing transfer, as read back by tests
+#[cfg(test)]
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OutgoingTransfer {
+    pub to_address: String,
+    pub amount: f64,
+    pub status: String,        // "pending", "queued", "failed"
+    pub error: Option<String>, // Why it failed, or why the outcome is unknown
+}
+
+/// Outgoing transfer as listed in EXPORT HISTORY
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TransferRecord {
+    pub to_address: String,
+    pub amount: f64,
+    pub token: String,
+    pub status: String,
+    pub tx_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Outgoing transfer as checked by reconciliation
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TrackedTransfer {
+    pub id: Uuid,
+    pub from_address: String,
+    pub to_address: String,
+    pub amount: f64,
+    pub token: String,
+    pub status: String,
+    pub tx_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// SENDs handed to Yellow. Rows left "pending" have an unknown outcome
+/// (e.g. the call timed out) and are for reconciliation to follow up.
+#[derive(Clone)]
+pub struct TransferRepository {
+    pool: PgPool,
+}
+
+impl TransferRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a transfer about to be submitted
+    pub async fn create_pending(
+        &self,
+        sender_phone: &str,
+        from_address: &str,
+        to_address: &str,
+        amount: f64,
+        token: &str,
+    ) -> Result<Uuid, sqlx::Error> {
+        let id = Uuid::new_v4();
+
+        sqlx::query(
+            "INSERT INTO outgoing_transfers (id, sender_phone, from_address, to_address, amount, token)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(id)
+        .bind(sender_phone)
+        .bind(from_address)
+        .bind(to_address)
+        .bind(amount)
+        .bind(token)
+        .execute(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    /// Yellow accepted the transfer, with its tx hash when it reported one
+    pub async fn mark_queued(&self, id: Uuid, tx_hash: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE outgoing_transfers SET tx_hash = $1 WHERE id = $2")
+            .bind(tx_hash)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        self.set_status(id, "queued", None).await
+    }
+
+    /// Yellow rejected the transfer
+    pub async fn mark_failed(&self, id: Uuid, error: &str) -> Result<(), sqlx::Error> {
+        self.set_status(id, "failed", Some(error)).await
+    }
+
+    /// Keep the transfer pending but note why its outcome is unknown
+    pub async fn note_unresolved(&self, id: Uuid, reason: &str) -> Result<(), sqlx::Error> {
+        self.set_status(id, "pending", Some(reason)).await
+    }
+
+    /// Amount, token and time of the latest non-failed transfer from a phone to an address
+    pub async fn last_to(
+        &self,
+        sender_phone: &str,
+        to_address: &str,
+    ) -> Result<Option<(f64, String, DateTime<Utc>)>, sqlx::Error> {
+        sqlx::query_as::<_, (f64, String, DateTime<Utc>)>(
+            "SELECT amount, token, created_at FROM outgoing_transfers
+             WHERE sender_phone = $1 AND LOWER(to_address) = LOWER($2) AND status <> 'failed'
+             ORDER BY created_at DESC
+             LIMIT 1",
+        )
+        .bind(sender_phone)
+        .bind(to_address)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Every transfer a phone has sent, newest first
+    pub async fn list_by_sender(&self, sender_phone: &str) -> Result<Vec<TransferRecord>, sqlx::Error> {
+        sqlx::query_as::<_, TransferRecord>(
+            "SELECT to_address, amount, token, status, tx_hash, created_at
+             FROM outgoing_transfers WHERE sender_phone = $1 ORDER BY created_at DESC",
+        )
+        .bind(sender_phone)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Total of a phone's sends of one token whose outcome is still unknown
+    pub async fn pending_total(&self, sender_phone: &str, token: &str) -> Result<f64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COALESCE(SUM(amount), 0) FROM outgoing_transfers
+             WHERE sender_phone = $1 AND UPPER(token) = UPPER($2) AND status = 'pending'",
+        )
+        .bind(sender_phone)
+        .bind(token)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Every transfer recorded in `[since, until)`, oldest first
+    pub async fn list_between(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<TrackedTransfer>, sqlx::Error> {
+        sqlx::query_as::<_, TrackedTransfer>(
+            "SELECT id, from_address, to_address, amount, token, status, tx_hash, created_at
+             FROM outgoing_transfers WHERE created_at >= $1 AND created_at < $2 ORDER BY created_at",
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn set_status(&self, id: Uuid, status: &str, error: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE outgoing_transfers SET status = $1, error = $2, updated_at = NOW() WHERE id = $3")
+            .bind(status)
+            .bind(error)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl TransferRepository {
+    /// Every transfer a phone has sent, oldest first
+    pub async fn find_by_sender(&self, sender_phone: &str) -> Result<Vec<OutgoingTransfer>, sqlx::Error> {
+        sqlx::query_as::<_, OutgoingTransfer>(
+            "SELECT to_address, amount, status, error
+             FROM outgoing_transfers WHERE sender_phone = $1 ORDER BY created_at",
+        )
+        .bind(sender_phone)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -10,9 +10,21 @@ pub struct User {
     pub encrypted_private_key: String,
     pub pin_hash: Option<String>,
     pub ens_name: Option<String>,
+    pub account_mode: String,                   // "eoa" or "smart" (ERC-4337)
+    pub smart_account_address: Option<String>,  // Counterfactual SimpleAccount address, once resolved
+    pub receive_only: bool,                     // Joined without Arc provisioning; upgraded on first send
+    pub opted_out: bool,                        // Replied STOP: no outbound SMS until START
+    pub derivation_index: Option<i32>,          // HD index under MASTER_MNEMONIC; no stored key when set
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+impl User {
+    /// Whether the user transacts from their ERC-4337 smart account
+    pub fn uses_smart_account(&self) -> bool {
+        self.account_mode == "smart"
+    }
+}
+
 /// User repository for database operations
 #[derive(Clone)]
 pub struct UserRepository {
@@ -24,65 +36,380 @@ impl UserRepository {
         Self { pool }
     }
 
-    /// Find user by phone number
+    /// Find user by phone number, including numbers LINKed to their wallet.
+    /// `phone` on the result is always the wallet's main number.
     pub async fn find_by_phone(&self, phone: &str) -> Result<Option<User>, sqlx::Error> {
         sqlx::query_as::<_, User>(
-            "SELECT id, phone, wallet_address, encrypted_private_key, pin_hash, ens_name, created_at 
-             FROM users WHERE phone = $1"
+            "SELECT id, phone, wallet_address, encrypted_private_key, pin_hash, ens_name, account_mode, smart_account_address, receive_only, opted_out, derivation_index, created_at 
+             FROM users
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)"
         )
         .bind(phone)
         .fetch_optional(&self.pool)
         .await
     }
 
+    /// Find user by wallet or smart account address (case-insensitive)
+    pub async fn find_by_wallet(&self, wallet_address: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            "SELECT id, phone, wallet_address, encrypted_private_key, pin_hash, ens_name, account_mode, smart_account_address, receive_only, opted_out, derivation_index, created_at 
+             FROM users WHERE LOWER(wallet_address) = LOWER($1) OR LOWER(smart_account_address) = LOWER($1)"
+        )
+        .bind(wallet_address)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
     /// Create a new user
     pub async fn create(
         &self,
         phone: &str,
         wallet_address: &str,
         encrypted_private_key: &str,
+    ) -> Result<User, sqlx::Error> {
+        self.insert(phone, wallet_address, encrypted_private_key, None, false).await
+    }
+
+    /// Create a receive-only user (no Arc wallet until their first send)
+    pub async fn create_receive_only(
+        &self,
+        phone: &str,
+        wallet_address: &str,
+        encrypted_private_key: &str,
+    ) -> Result<User, sqlx::Error> {
+        self.insert(phone, wallet_address, encrypted_private_key, None, true).await
+    }
+
+    /// Next unused HD derivation index (never handed out twice)
+    pub async fn next_derivation_index(&self) -> Result<i32, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>("SELECT nextval('wallet_derivation_index_seq')")
+            .fetch_one(&self.pool)
+            .await
+            .map(|index| index as i32)
+    }
+
+    /// Create a user whose wallet is derived from the master mnemonic at
+    /// `derivation_index`; only the index is stored, never the key
+    pub async fn create_derived(
+        &self,
+        phone: &str,
+        wallet_address: &str,
+        derivation_index: i32,
+        receive_only: bool,
+    ) -> Result<User, sqlx::Error> {
+        self.insert(phone, wallet_address, "", Some(derivation_index), receive_only).await
+    }
+
+    async fn insert(
+        &self,
+        phone: &str,
+        wallet_address: &str,
+        encrypted_private_key: &str,
+        derivation_index: Option<i32>,
+        receive_only: bool,
     ) -> Result<User, sqlx::Error> {
         let id = Uuid::new_v4();
         
         sqlx::query_as::<_, User>(
             r#"
-            INSERT INTO users (id, phone, wallet_address, encrypted_private_key)
-            VALUES ($1, $2, $3, $4)
-            RETURNING id, phone, wallet_address, encrypted_private_key, pin_hash, ens_name, created_at
+            INSERT INTO users (id, phone, wallet_address, encrypted_private_key, receive_only, derivation_index)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, phone, wallet_address, encrypted_private_key, pin_hash, ens_name, account_mode, smart_account_address, receive_only, opted_out, derivation_index, created_at
             "#
         )
         .bind(id)
         .bind(phone)
         .bind(wallet_address)
         .bind(encrypted_private_key)
+        .bind(receive_only)
+        .bind(derivation_index)
         .fetch_one(&self.pool)
         .await
     }
 
+    /// Upgrade a receive-only user to a full account
+    pub async fn mark_provisioned(&self, phone: &str) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET receive_only = FALSE
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)"
+        )
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        updated_one(result)
+    }
+
     /// Update user's PIN hash
     pub async fn update_pin(&self, phone: &str, pin_hash: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE users SET pin_hash = $1 WHERE phone = $2")
-            .bind(pin_hash)
-            .bind(phone)
-            .execute(&self.pool)
-            .await?;
-        Ok(())
+        let result = sqlx::query(
+            "UPDATE users SET pin_hash = $1
+             WHERE phone = $2 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $2)"
+        )
+        .bind(pin_hash)
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        updated_one(result)
     }
 
     /// Update user's ENS name
     pub async fn update_ens_name(&self, phone: &str, ens_name: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE users SET ens_name = $1 WHERE phone = $2")
-            .bind(ens_name)
+        let result = sqlx::query(
+            "UPDATE users SET ens_name = $1
+             WHERE phone = $2 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $2)"
+        )
+        .bind(ens_name)
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        updated_one(result)
+    }
+
+    /// Switch between EOA and smart-account mode, caching the smart account address
+    pub async fn update_account_mode(
+        &self,
+        phone: &str,
+        account_mode: &str,
+        smart_account_address: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET account_mode = $1, smart_account_address = COALESCE($2, smart_account_address)
+             WHERE phone = $3 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $3)"
+        )
+        .bind(account_mode)
+        .bind(smart_account_address)
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        updated_one(result)
+    }
+
+    /// Record a carrier STOP (true) or START (false) on the wallet behind `phone`.
+    /// Fails with `RowNotFound` if there is no such wallet.
+    pub async fn set_opted_out(&self, phone: &str, opted_out: bool) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET opted_out = $1
+             WHERE phone = $2 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $2)"
+        )
+        .bind(opted_out)
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        updated_one(result)
+    }
+
+    /// Whether the user has opted out of SMS
+    pub async fn is_opted_out(&self, phone: &str) -> Result<bool, sqlx::Error> {
+        let opted_out = sqlx::query_scalar::<_, bool>(
+            "SELECT opted_out FROM users
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)"
+        )
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(opted_out.unwrap_or(false))
+    }
+
+    /// Currency the user wants approximate values shown in, if any
+    pub async fn display_currency(&self, phone: &str) -> Result<Option<String>, sqlx::Error> {
+        let currency = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT display_currency FROM users
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)"
+        )
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(currency.flatten())
+    }
+
+    /// Set (or clear with None) the display currency. Returns false if the user doesn't exist.
+    pub async fn set_display_currency(&self, phone: &str, currency: Option<&str>) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET display_currency = $1
+             WHERE phone = $2 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $2)"
+        )
+        .bind(currency)
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Language the user's replies are localized to (None = English)
+    pub async fn language(&self, phone: &str) -> Result<Option<String>, sqlx::Error> {
+        let language = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT language FROM users
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)"
+        )
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(language.flatten())
+    }
+
+    /// Set (or clear with None) the reply language. Returns false if the user doesn't exist.
+    pub async fn set_language(&self, phone: &str, language: Option<&str>) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET language = $1
+             WHERE phone = $2 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $2)"
+        )
+        .bind(language)
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Amount and token above which incoming transfers wait for ACCEPT, if set
+    pub async fn approval_limit(&self, phone: &str) -> Result<Option<(f64, String)>, sqlx::Error> {
+        let limit = sqlx::query_as::<_, (Option<f64>, Option<String>)>(
+            "SELECT approve_above, approve_above_token FROM users
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)"
+        )
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(match limit {
+            Some((Some(amount), Some(token))) => Some((amount, token)),
+            _ => None,
+        })
+    }
+
+    /// Set (or clear with None) the approval limit. Returns false if the user doesn't exist.
+    pub async fn set_approval_limit(&self, phone: &str, limit: Option<(f64, &str)>) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET approve_above = $1, approve_above_token = $2
+             WHERE phone = $3 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $3)"
+        )
+        .bind(limit.map(|(amount, _)| amount))
+        .bind(limit.map(|(_, token)| token))
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// AUTOSWAP percent, None when off
+    pub async fn autoswap_pct(&self, phone: &str) -> Result<Option<u32>, sqlx::Error> {
+        let pct = sqlx::query_scalar::<_, Option<i32>>(
+            "SELECT autoswap_pct FROM users
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)"
+        )
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(pct.flatten().map(|p| p as u32))
+    }
+
+    /// Set (or clear with None) the AUTOSWAP percent. Returns false if the user doesn't exist.
+    pub async fn set_autoswap_pct(&self, phone: &str, pct: Option<u32>) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET autoswap_pct = $1
+             WHERE phone = $2 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $2)"
+        )
+        .bind(pct.map(|p| p as i32))
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Whether the wallet behind `phone` (directly or through a linked number) is frozen
+    pub async fn is_frozen(&self, phone: &str) -> Result<bool, sqlx::Error> {
+        let frozen = sqlx::query_scalar::<_, bool>(
+            "SELECT frozen FROM users
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)"
+        )
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(frozen.unwrap_or(false))
+    }
+
+    /// Freeze or unfreeze the wallet behind `phone`. Returns false if the user doesn't exist.
+    pub async fn set_frozen(&self, phone: &str, frozen: bool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET frozen = $1
+             WHERE phone = $2 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $2)"
+        )
+        .bind(frozen)
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// When the PIN lockout on the wallet behind `phone` ends, if it's locked now
+    pub async fn pin_locked_until(&self, phone: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>, sqlx::Error> {
+        let until = sqlx::query_scalar::<_, Option<chrono::DateTime<chrono::Utc>>>(
+            "SELECT pin_locked_until FROM users
+             WHERE (phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1))
+               AND pin_locked_until > NOW()"
+        )
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(until.flatten())
+    }
+
+    /// Count a wrong PIN. From the `max_attempts`th in a row the PIN is
+    /// locked for `lockout_secs`, doubling with each further miss up to
+    /// `max_lockout_secs`. Returns when the lockout ends, if one started.
+    pub async fn record_pin_failure(
+        &self,
+        phone: &str,
+        max_attempts: u32,
+        lockout_secs: u64,
+        max_lockout_secs: u64,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, sqlx::Error> {
+        let until = sqlx::query_scalar::<_, Option<chrono::DateTime<chrono::Utc>>>(
+            "UPDATE users SET
+                 pin_failures = pin_failures + 1,
+                 pin_locked_until = CASE
+                     WHEN $2 > 0 AND pin_failures + 1 >= $2 THEN NOW() + make_interval(
+                         secs => LEAST($3 * POWER(2, LEAST(pin_failures + 1 - $2, 30)), $4)
+                     )
+                     ELSE pin_locked_until
+                 END
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)
+             RETURNING pin_locked_until"
+        )
+        .bind(phone)
+        .bind(max_attempts as i32)
+        .bind(lockout_secs as f64)
+        .bind(max_lockout_secs as f64)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(until.flatten().filter(|until| *until > chrono::Utc::now()))
+    }
+
+    /// Forget past wrong PINs once the right one is given
+    pub async fn reset_pin_failures(&self, phone: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE users SET pin_failures = 0, pin_locked_until = NULL
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)"
+        )
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Attach `phone` to the wallet of `primary_phone`
+    pub async fn link_phone(&self, phone: &str, primary_phone: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO linked_phones (phone, primary_phone) VALUES ($1, $2)")
             .bind(phone)
+            .bind(primary_phone)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
-    /// Check if user exists
+    /// Check if user exists (directly or through a linked number)
     pub async fn exists(&self, phone: &str) -> Result<bool, sqlx::Error> {
         let result = sqlx::query_scalar::<_, i64>(
-            "SELECT COUNT(*) FROM users WHERE phone = $1"
+            "SELECT COUNT(*) FROM users
+             WHERE phone = $1 OR phone = (SELECT primary_phone FROM linked_phones WHERE phone = $1)"
         )
         .bind(phone)
         .fetch_one(&self.pool)
@@ -91,3 +418,75 @@ impl UserRepository {
         Ok(result > 0)
     }
 }
+
+/// An update that must have hit a user row; none means there is no such user
+fn updated_one(result: sqlx::postgres::PgQueryResult) -> Result<(), sqlx::Error> {
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+impl UserRepository {
+    /// Insert (or return) a user whose wallet is derived from the phone number,
+    /// so the same phone always seeds the same address
+    pub async fn seed_user(&self, phone: &str) -> Result<User, sqlx::Error> {
+        if let Some(user) = self.find_by_phone(phone).await? {
+            return Ok(user);
+        }
+        let wallet = seed_wallet(phone);
+        self.create(phone, &wallet.address_string(), &hex::encode(wallet.private_key_bytes())).await
+    }
+}
+
+/// Deterministic test wallet for a phone number
+#[cfg(test)]
+pub fn seed_wallet(phone: &str) -> crate::wallet::UserWallet {
+    use sha2::{Digest, Sha256};
+    let key: [u8; 32] = Sha256::digest(format!("textchain-seed:{}", phone)).into();
+    crate::wallet::UserWallet::from_private_key(&key).expect("seed key is a valid secp256k1 scalar")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+
+    #[tokio::test]
+    async fn test_display_currency_round_trip() {
+        let Some(pool) = test_pool().await else { return };
+        let repo = UserRepository::new(pool);
+        let phone = crate::test_support::random_phone();
+        repo.seed_user(&phone).await.unwrap();
+
+        assert_eq!(repo.display_currency(&phone).await.unwrap(), None);
+        assert!(repo.set_display_currency(&phone, Some("NGN")).await.unwrap());
+        assert_eq!(repo.display_currency(&phone).await.unwrap().as_deref(), Some("NGN"));
+        assert!(repo.set_display_currency(&phone, None).await.unwrap());
+        assert_eq!(repo.display_currency(&phone).await.unwrap(), None);
+
+        // No such user
+        assert!(!repo.set_display_currency("+19990000000000", Some("USD")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_linked_number_updates_the_main_row() {
+        let Some(pool) = test_pool().await else { return };
+        let repo = UserRepository::new(pool);
+        let main = crate::test_support::random_phone();
+        let second = crate::test_support::random_phone();
+        repo.seed_user(&main).await.unwrap();
+        repo.link_phone(&second, &main).await.unwrap();
+
+        repo.set_opted_out(&second, true).await.unwrap();
+        assert!(repo.is_opted_out(&main).await.unwrap());
+        assert!(repo.is_opted_out(&second).await.unwrap());
+        repo.update_pin(&second, "hash").await.unwrap();
+        assert_eq!(repo.find_by_phone(&main).await.unwrap().unwrap().pin_hash.as_deref(), Some("hash"));
+
+        // No such user
+        assert!(matches!(repo.set_opted_out("+19990000000000", true).await, Err(sqlx::Error::RowNotFound)));
+        assert!(matches!(repo.update_pin("+19990000000000", "hash").await, Err(sqlx::Error::RowNotFound)));
+    }
+}
@@ -43,6 +43,17 @@ impl Voucher {
         self.usdc_amount as f64 / 1_000_000.0
     }
 
+    /// Code with all but the first and last two characters hidden: TX******56
+    pub fn masked_code(&self) -> String {
+        let chars: Vec<char> = self.code.chars().collect();
+        if chars.len() <= 4 {
+            return "*".repeat(chars.len());
+        }
+        let head: String = chars[..2].iter().collect();
+        let tail: String = chars[chars.len() - 2..].iter().collect();
+        format!("{}{}{}", head, "*".repeat(chars.len() - 4), tail)
+    }
+
     /// Check if voucher is valid for redemption
     pub fn is_valid(&self) -> bool {
         self.status == "unused" && 
@@ -72,6 +83,37 @@ impl VoucherRepository {
         .await
     }
 
+    /// Vouchers redeemed by a phone, most recent first
+    pub async fn find_redeemed_by(&self, phone: &str, limit: i64) -> Result<Vec<Voucher>, sqlx::Error> {
+        sqlx::query_as::<_, Voucher>(
+            "SELECT id, code, usdc_amount, status, redeemed_by, redeemed_at, expires_at, created_at 
+             FROM vouchers WHERE redeemed_by = $1 AND status = 'redeemed'
+             ORDER BY redeemed_at DESC
+             LIMIT $2"
+        )
+        .bind(phone)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record a redemption the backend has already made on chain. Codes
+    /// that were never loaded here are added as redeemed, with no amount.
+    pub async fn record_redemption(&self, code: &str, phone: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO vouchers (id, code, usdc_amount, status, redeemed_by, redeemed_at)
+             VALUES ($1, $2, 0, 'redeemed', $3, NOW())
+             ON CONFLICT (code) DO UPDATE SET status = 'redeemed', redeemed_by = $3, redeemed_at = NOW()
+             WHERE vouchers.status <> 'redeemed'"
+        )
+        .bind(Uuid::new_v4())
+        .bind(code.to_uppercase())
+        .bind(phone)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
     /// Redeem a voucher for a user
     pub async fn redeem(&self, code: &str, phone: &str) -> Result<Voucher, VoucherError> {
         // First, find and validate the voucher
@@ -170,3 +212,56 @@ impl std::fmt::Display for VoucherError {
 }
 
 impl std::error::Error for VoucherError {}
+
+#[cfg(test)]
+impl VoucherRepository {
+    /// Insert an unused voucher, replacing any earlier one with the same code
+    pub async fn seed_voucher(&self, code: &str, usdc_amount: i64) -> Result<Voucher, sqlx::Error> {
+        sqlx::query("DELETE FROM vouchers WHERE code = $1")
+            .bind(code.to_uppercase())
+            .execute(&self.pool)
+            .await?;
+        let mut vouchers = self.create_batch(&[code.to_string()], usdc_amount, None).await?;
+        Ok(vouchers.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+
+    #[test]
+    fn test_masked_code() {
+        let mut voucher = Voucher {
+            id: Uuid::new_v4(),
+            code: "TXTC123456".to_string(),
+            usdc_amount: 5_000_000,
+            status: "redeemed".to_string(),
+            redeemed_by: None,
+            redeemed_at: None,
+            expires_at: None,
+            created_at: Utc::now(),
+        };
+        assert_eq!(voucher.masked_code(), "TX******56");
+        voucher.code = "AB12".to_string();
+        assert_eq!(voucher.masked_code(), "****");
+    }
+
+    #[tokio::test]
+    async fn test_seeded_voucher_redeems_once() {
+        let Some(pool) = test_pool().await else { return };
+        let repo = VoucherRepository::new(pool);
+
+        let code = format!("SEED{}", rand::random::<u32>() % 1_000_000);
+        let voucher = repo.seed_voucher(&code, 5_000_000).await.unwrap();
+        assert!(voucher.is_valid());
+
+        let redeemed = repo.redeem(&code, "+15550100003").await.unwrap();
+        assert_eq!(redeemed.status, "redeemed");
+        assert!(repo.redeem(&code, "+15550100003").await.is_err());
+
+        // Re-seeding resets the code for the next run
+        assert!(repo.seed_voucher(&code, 5_000_000).await.unwrap().is_valid());
+    }
+}
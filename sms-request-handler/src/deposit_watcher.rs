@@ -0,0 +1,349 @@
+use ethers::prelude::*;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::db::{Deposit, DepositRepository, UserRepository};
+use crate::wallet::{Chain, ChainProvider};
+
+/// Incoming token transfer seen on chain
+#[derive(Debug, Clone)]
+pub struct ObservedTransfer {
+    pub tx_hash: String,
+    pub to_address: String,
+    pub amount: i64,  // Micro units of the scanned token (6 decimals)
+    pub block_number: u64,
+}
+
+/// Chain reads the watcher needs (mocked in tests)
+pub trait ChainScanner {
+    /// Symbol of the token being scanned ("USDC" or "TXTC")
+    fn token(&self) -> &'static str;
+    /// Latest block number
+    async fn head(&self) -> Result<u64, String>;
+    /// Token transfers in an inclusive block range
+    async fn transfers(&self, from_block: u64, to_block: u64) -> Result<Vec<ObservedTransfer>, String>;
+    /// Block the transaction is currently included in, None if a reorg dropped it
+    async fn inclusion_block(&self, tx_hash: &str) -> Result<Option<u64>, String>;
+}
+
+/// Scans an ERC-20's `Transfer` logs over JSON-RPC
+pub struct TokenScanner {
+    provider: Arc<ChainProvider>,
+    address: Address,
+    symbol: &'static str,
+    /// Raw units per micro unit
+    scale: U256,
+}
+
+impl TokenScanner {
+    /// USDC (6 decimals). None if USDC isn't deployed on the chain
+    pub fn usdc(provider: Arc<ChainProvider>, chain: Chain) -> Option<Self> {
+        Some(Self { provider, address: chain.usdc_address()?, symbol: "USDC", scale: U256::one() })
+    }
+
+    /// TXTC (18 decimals) at `address`
+    pub fn txtc(provider: Arc<ChainProvider>, address: Address) -> Self {
+        Self { provider, address, symbol: "TXTC", scale: U256::exp10(12) }
+    }
+}
+
+impl ChainScanner for TokenScanner {
+    fn token(&self) -> &'static str {
+        self.symbol
+    }
+
+    async fn head(&self) -> Result<u64, String> {
+        self.provider
+            .get_block_number()
+            .await
+            .map(|n| n.as_u64())
+            .map_err(|e| format!("Failed to get block number: {}", e))
+    }
+
+    async fn transfers(&self, from_block: u64, to_block: u64) -> Result<Vec<ObservedTransfer>, String> {
+        let filter = Filter::new()
+            .address(self.address)
+            .event("Transfer(address,address,uint256)")
+            .from_block(from_block)
+            .to_block(to_block);
+
+        let logs = self
+            .provider
+            .get_logs(&filter)
+            .await
+            .map_err(|e| format!("Failed to get logs: {}", e))?;
+
+        Ok(logs
+            .into_iter()
+            .filter_map(|log| {
+                let to = Address::from(*log.topics.get(2)?);
+                let amount = U256::from_big_endian(&log.data) / self.scale;
+                Some(ObservedTransfer {
+                    tx_hash: format!("{:?}", log.transaction_hash?),
+                    to_address: format!("{:?}", to),
+                    amount: i64::try_from(amount.as_u128()).ok()?,
+                    block_number: log.block_number?.as_u64(),
+                })
+            })
+            .collect())
+    }
+
+    async fn inclusion_block(&self, tx_hash: &str) -> Result<Option<u64>, String> {
+        let hash = H256::from_str(tx_hash).map_err(|e| format!("Invalid tx hash: {}", e))?;
+        let receipt = self
+            .provider
+            .get_transaction_receipt(hash)
+            .await
+            .map_err(|e| format!("Failed to get receipt: {}", e))?;
+        Ok(receipt.and_then(|r| r.block_number).map(|n| n.as_u64()))
+    }
+}
+
+/// Credits on-chain deposits to users once they are buried deep enough
+/// that a reorg can no longer remove them
+pub struct DepositWatcher<S> {
+    scanner: S,
+    chain: Chain,
+    user_repo: UserRepository,
+    deposit_repo: DepositRepository,
+    confirmations: u64,
+    next_block: Option<u64>,
+}
+
+impl<S: ChainScanner> DepositWatcher<S> {
+    pub fn new(
+        scanner: S,
+        chain: Chain,
+        user_repo: UserRepository,
+        deposit_repo: DepositRepository,
+        confirmations: u64,
+    ) -> Self {
+        Self {
+            scanner,
+            chain,
+            user_repo,
+            deposit_repo,
+            confirmations: confirmations.max(1),
+            next_block: None,
+        }
+    }
+
+    /// One polling round: record new transfers as provisional, confirm the ones
+    /// with enough confirmations and drop the ones a reorg removed.
+    /// Returns the deposits confirmed in this round (to notify).
+    pub async fn poll(&mut self) -> Result<Vec<Deposit>, String> {
+        let head = self.scanner.head().await?;
+        let chain = self.chain.name();
+        let token = self.scanner.token();
+
+        // Rescan the unconfirmed window so transfers re-mined after a reorg are picked up again
+        let from_block = match self.next_block {
+            Some(next) => next.saturating_sub(self.confirmations),
+            None => head,
+        };
+        if from_block <= head {
+            for transfer in self.scanner.transfers(from_block, head).await? {
+                self.record(&transfer, token, chain).await;
+            }
+            self.next_block = Some(head + 1);
+        }
+
+        let provisional = self
+            .deposit_repo
+            .list_provisional(chain, token)
+            .await
+            .map_err(|e| format!("Failed to load provisional deposits: {}", e))?;
+
+        let mut confirmed = Vec::new();
+        for mut deposit in provisional {
+            let Some(ref tx_hash) = deposit.source_ref else { continue };
+
+            let block = match self.scanner.inclusion_block(tx_hash).await? {
+                Some(block) => block,
+                None => {
+                    tracing::warn!(tx = %tx_hash, chain, "Deposit reorged out, removing");
+                    let _ = self.deposit_repo.remove_provisional(deposit.id).await;
+                    continue;
+                }
+            };
+
+            if deposit.block_number != Some(block as i64) {
+                let _ = self.deposit_repo.update_block(deposit.id, block as i64).await;
+                deposit.block_number = Some(block as i64);
+            }
+
+            let depth = (head + 1).saturating_sub(block);
+            if depth >= self.confirmations && matches!(self.deposit_repo.confirm(deposit.id).await, Ok(true)) {
+                tracing::info!(tx = %tx_hash, chain, depth, "Deposit confirmed");
+                confirmed.push(deposit);
+            }
+        }
+
+        Ok(confirmed)
+    }
+
+    /// Track a transfer if it pays one of our users
+    async fn record(&self, transfer: &ObservedTransfer, token: &str, chain: &str) {
+        let user = match self.user_repo.find_by_wallet(&transfer.to_address).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!("Failed to look up deposit recipient: {}", e);
+                return;
+            }
+        };
+
+        if let Err(e) = self
+            .deposit_repo
+            .create_provisional(&user.phone, transfer.amount, token, &transfer.tx_hash, chain, transfer.block_number as i64)
+            .await
+        {
+            tracing::error!("Failed to record deposit {}: {}", transfer.tx_hash, e);
+        }
+    }
+}
+
+/// SMS sent to the user once a deposit is confirmed
+pub fn confirmation_sms(deposit: &Deposit) -> String {
+    format!(
+        "Deposit confirmed: {:.2} {} on {}.\n\nReply BALANCE to check.",
+        deposit.amount_as_f64(),
+        deposit.token,
+        deposit.chain.as_deref().unwrap_or("chain")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Scripted chain: tests move the head and drop transactions to simulate reorgs
+    #[derive(Default)]
+    struct FakeChain {
+        head: u64,
+        transfers: Vec<ObservedTransfer>,
+        included: HashMap<String, u64>,
+    }
+
+    #[derive(Default, Clone)]
+    struct FakeScanner(Arc<Mutex<FakeChain>>);
+
+    impl FakeScanner {
+        fn set_head(&self, head: u64) {
+            self.0.lock().unwrap().head = head;
+        }
+
+        fn mine(&self, transfer: ObservedTransfer) {
+            let mut chain = self.0.lock().unwrap();
+            chain.included.insert(transfer.tx_hash.clone(), transfer.block_number);
+            chain.transfers.push(transfer);
+        }
+
+        fn reorg_out(&self, tx_hash: &str) {
+            let mut chain = self.0.lock().unwrap();
+            chain.included.remove(tx_hash);
+            chain.transfers.retain(|t| t.tx_hash != tx_hash);
+        }
+    }
+
+    impl ChainScanner for FakeScanner {
+        fn token(&self) -> &'static str {
+            "USDC"
+        }
+
+        async fn head(&self) -> Result<u64, String> {
+            Ok(self.0.lock().unwrap().head)
+        }
+
+        async fn transfers(&self, from_block: u64, to_block: u64) -> Result<Vec<ObservedTransfer>, String> {
+            let chain = self.0.lock().unwrap();
+            Ok(chain
+                .transfers
+                .iter()
+                .filter(|t| (from_block..=to_block).contains(&t.block_number))
+                .cloned()
+                .collect())
+        }
+
+        async fn inclusion_block(&self, tx_hash: &str) -> Result<Option<u64>, String> {
+            Ok(self.0.lock().unwrap().included.get(tx_hash).copied())
+        }
+    }
+
+    /// Watcher for a fresh user. Each test watches its own chain because
+    /// provisional deposits are listed per chain.
+    async fn setup(chain: Chain) -> Option<(DepositWatcher<FakeScanner>, FakeScanner, DepositRepository, String, String)> {
+        let pool = test_pool().await?;
+        let user_repo = UserRepository::new(pool.clone());
+        let deposit_repo = DepositRepository::new(pool);
+
+        let phone = crate::test_support::random_phone();
+        let address = format!("0x{}", hex::encode(rand::random::<[u8; 20]>()));
+        user_repo.create(&phone, &address, "key").await.unwrap();
+
+        let scanner = FakeScanner::default();
+        scanner.set_head(100);
+        let watcher = DepositWatcher::new(scanner.clone(), chain, user_repo, deposit_repo.clone(), 3);
+        Some((watcher, scanner, deposit_repo, phone, address))
+    }
+
+    fn transfer(to: &str, block: u64) -> ObservedTransfer {
+        ObservedTransfer {
+            tx_hash: format!("{:?}", H256::random()),
+            to_address: to.to_string(),
+            amount: 2_500_000,
+            block_number: block,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deposit_waits_for_confirmations() {
+        let Some((mut watcher, scanner, deposits, phone, address)) = setup(Chain::BaseSepolia).await else { return };
+        watcher.poll().await.unwrap();
+
+        scanner.set_head(101);
+        scanner.mine(transfer(&address, 101));
+
+        // 1 and 2 confirmations: tracked but not credited
+        assert!(watcher.poll().await.unwrap().is_empty());
+        scanner.set_head(102);
+        assert!(watcher.poll().await.unwrap().is_empty());
+        assert_eq!(deposits.get_balance(&phone).await.unwrap(), 0);
+
+        // 3 confirmations: credited exactly once
+        scanner.set_head(103);
+        let confirmed = watcher.poll().await.unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(confirmed[0].user_phone, phone);
+        assert_eq!(deposits.get_balance(&phone).await.unwrap(), 2_500_000);
+
+        scanner.set_head(104);
+        assert!(watcher.poll().await.unwrap().is_empty());
+        assert_eq!(deposits.get_balance(&phone).await.unwrap(), 2_500_000);
+    }
+
+    #[tokio::test]
+    async fn test_reorg_removes_provisional_deposit() {
+        let Some((mut watcher, scanner, deposits, phone, address)) = setup(Chain::EthereumSepolia).await else { return };
+        watcher.poll().await.unwrap();
+
+        let dropped = transfer(&address, 101);
+        scanner.set_head(101);
+        scanner.mine(dropped.clone());
+        watcher.poll().await.unwrap();
+        assert_eq!(deposits.list_provisional(Chain::EthereumSepolia.name(), "USDC").await.unwrap().len(), 1);
+
+        // The block is replaced before the deposit is confirmed
+        scanner.reorg_out(&dropped.tx_hash);
+        scanner.set_head(102);
+        assert!(watcher.poll().await.unwrap().is_empty());
+        assert!(deposits.list_provisional(Chain::EthereumSepolia.name(), "USDC").await.unwrap().is_empty());
+
+        scanner.set_head(110);
+        assert!(watcher.poll().await.unwrap().is_empty());
+        assert_eq!(deposits.get_balance(&phone).await.unwrap(), 0);
+    }
+}
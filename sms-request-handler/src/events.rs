@@ -0,0 +1,120 @@
+//! Wallet events for integrators: an in-process broadcast bus, and an optional
+//! webhook that forwards each event signed with HMAC-SHA256
+
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header carrying the hex HMAC-SHA256 of the webhook body
+pub const SIGNATURE_HEADER: &str = "X-TextChain-Signature";
+
+/// Something that changed a wallet's balance
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case", rename_all_fields = "camelCase")]
+pub enum WalletEvent {
+    /// `delta` of `token` added to `address` (negative when taken from it)
+    BalanceChanged { address: String, token: String, delta: f64 },
+    /// A SEND was accepted for delivery
+    TransferCompleted {
+        from_address: String,
+        to_address: String,
+        amount: f64,
+        token: String,
+        tx_hash: Option<String>,
+    },
+}
+
+/// Fan-out of wallet events to any number of subscribers. Clones share the bus.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<WalletEvent>,
+}
+
+impl EventBus {
+    /// Subscribers more than `capacity` events behind skip ahead
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Publish to current subscribers (dropped when there are none)
+    pub fn emit(&self, event: WalletEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<WalletEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+/// Hex HMAC-SHA256 of a webhook body under the shared secret
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// POST every event from `events` to `url` until the bus closes.
+/// Delivery is best effort: failures are logged, not retried.
+pub fn spawn_webhook(
+    mut events: broadcast::Receiver<WalletEvent>,
+    url: String,
+    secret: String,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "Event webhook fell behind, events dropped");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            let body = serde_json::json!({ "event": event, "sentAt": chrono::Utc::now().to_rfc3339() }).to_string();
+            let result = client
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, sign(&secret, body.as_bytes()))
+                .body(body)
+                .timeout(std::time::Duration::from_secs(5))
+                .send()
+                .await;
+            match result {
+                Ok(resp) if resp.status().is_success() => {}
+                Ok(resp) => tracing::warn!(status = %resp.status(), "Event webhook rejected event"),
+                Err(e) => tracing::warn!("Event webhook unreachable: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json_and_signature() {
+        let event = WalletEvent::BalanceChanged { address: "0xabc".to_string(), token: "TXTC".to_string(), delta: -5.0 };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({ "type": "balance_changed", "address": "0xabc", "token": "TXTC", "delta": -5.0 })
+        );
+
+        let signature = sign("secret", b"{}");
+        assert_eq!(signature.len(), 64);
+        assert_eq!(signature, sign("secret", b"{}"));
+        assert_ne!(signature, sign("other", b"{}"));
+    }
+}
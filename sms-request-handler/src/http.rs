@@ -0,0 +1,128 @@
+//! Outbound HTTP calls to the backend, Yellow and Arc services, with
+//! handling for rate-limit (429) responses
+
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+/// Longest `Retry-After` waited out before the caller is told to back off
+const MAX_TRANSPARENT_WAIT: Duration = Duration::from_secs(3);
+
+/// Why an HTTP call produced no usable response
+#[derive(Debug)]
+pub enum HttpError {
+    /// The service answered 429 and asked for a longer wait than we absorb
+    RateLimited { retry_after: Option<Duration> },
+    /// Connection failure, timeout, ...
+    Request(reqwest::Error),
+}
+
+impl HttpError {
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, HttpError::Request(e) if e.is_timeout())
+    }
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HttpError::RateLimited { retry_after: Some(wait) } => {
+                write!(f, "rate limited, retry after {}s", wait.as_secs())
+            }
+            HttpError::RateLimited { retry_after: None } => write!(f, "rate limited"),
+            HttpError::Request(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Send a request. On 429 with a short `Retry-After` the request is retried
+/// once after waiting; otherwise the wait is returned for the caller to report.
+pub async fn send(request: RequestBuilder) -> Result<Response, HttpError> {
+    send_with_max_wait(request, MAX_TRANSPARENT_WAIT).await
+}
+
+async fn send_with_max_wait(request: RequestBuilder, max_wait: Duration) -> Result<Response, HttpError> {
+    // Streaming bodies can't be cloned; those calls just don't get the retry
+    let retry = request.try_clone();
+    let response = request.send().await.map_err(HttpError::Request)?;
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return Ok(response);
+    }
+
+    let wait = retry_after(&response);
+    match (retry, wait) {
+        (Some(retry), Some(wait)) if wait <= max_wait => {
+            tracing::info!(url = %response.url(), ?wait, "Rate limited, retrying after Retry-After");
+            tokio::time::sleep(wait).await;
+            let response = retry.send().await.map_err(HttpError::Request)?;
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                return Err(HttpError::RateLimited { retry_after: retry_after(&response) });
+            }
+            Ok(response)
+        }
+        _ => {
+            tracing::warn!(url = %response.url(), ?wait, "Rate limited");
+            Err(HttpError::RateLimited { retry_after: wait })
+        }
+    }
+}
+
+/// `Retry-After` as delta-seconds or an HTTP date
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::HeaderMap, routing::get, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Backend answering 429 with `Retry-After: retry_after` to the first `limited` calls
+    async fn limited_backend(limited: usize, retry_after: &'static str) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let router = Router::new().route(
+            "/api/balance",
+            get(move || {
+                let counter = counter.clone();
+                async move {
+                    let mut headers = HeaderMap::new();
+                    if counter.fetch_add(1, Ordering::SeqCst) < limited {
+                        headers.insert("retry-after", retry_after.parse().unwrap());
+                        return (StatusCode::TOO_MANY_REQUESTS, headers, "slow down");
+                    }
+                    (StatusCode::OK, headers, "ok")
+                }
+            }),
+        );
+        (crate::test_support::spawn_mock_backend(router).await, calls)
+    }
+
+    #[tokio::test]
+    async fn test_short_retry_after_is_waited_out() {
+        let (url, calls) = limited_backend(1, "1").await;
+        let started = std::time::Instant::now();
+
+        let response = send(reqwest::Client::new().get(format!("{}/api/balance", url))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_long_retry_after_is_reported() {
+        let (url, calls) = limited_backend(usize::MAX, "120").await;
+
+        match send(reqwest::Client::new().get(format!("{}/api/balance", url))).await {
+            Err(HttpError::RateLimited { retry_after }) => assert_eq!(retry_after, Some(Duration::from_secs(120))),
+            other => panic!("expected rate limit, got {:?}", other.map(|r| r.status())),
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -3,17 +3,26 @@ mod admin_wallet;
 mod commands;
 mod config;
 mod db;
+mod deposit_watcher;
+mod events;
+mod http;
+mod price;
+mod reconcile;
 mod routes;
+mod selftest;
 mod sms;
+#[cfg(test)]
+mod test_support;
 mod wallet;
 mod yellow_client;
 
-use config::Config;
+use config::{Config, DepositWatcherConfig, EventWebhookConfig, InboundGatewayConfig, SmsQueueConfig, TwilioWebhookConfig};
 use commands::CommandProcessor;
-use db::{create_pool, run_migrations, UserRepository, VoucherRepository, DepositRepository, AddressBookRepository};
+use db::{create_pool, run_migrations, UserRepository, VoucherRepository, DepositRepository, AddressBookRepository, InboundTransferRepository, EscrowRepository, AuditRepository, TransferRepository, ReceiptRepository, ChannelRepository, PayeeTagRepository, TransactionRepository, PendingTxRepository};
 use routes::{create_router, create_router_with_admin};
-use sms::TwilioClient;
-use wallet::create_shared_provider;
+use sms::{Priority, SmsQueue, TwilioClient};
+use wallet::{create_chain_provider, create_shared_provider, Chain};
+use deposit_watcher::{confirmation_sms, ChainScanner, DepositWatcher, TokenScanner};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -36,8 +45,8 @@ async fn main() -> anyhow::Result<()> {
         "Starting TextChain SMS backend"
     );
 
-    // Get admin token from env (defaults to "admin123" for dev)
-    let admin_token = std::env::var("ADMIN_TOKEN").unwrap_or_else(|_| "admin123".to_string());
+    // Admin token from env (the admin routes fall back to a dev token without it)
+    let admin_token = std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty());
 
     // Initialize database (optional - will work without if DATABASE_URL not set)
     let db_pool = if let Ok(database_url) = std::env::var("DATABASE_URL") {
@@ -56,6 +65,9 @@ async fn main() -> anyhow::Result<()> {
 
     // Initialize services
     let twilio = TwilioClient::new(&config.twilio);
+    let queue_config = SmsQueueConfig::from_env();
+    let sms_queue = SmsQueue::new(queue_config.capacity).with_length_limit(queue_config.length_limit());
+    sms_queue.spawn_dispatcher(std::sync::Arc::new(twilio), queue_config.rate_per_sec);
 
     // Build router based on whether database is available
     let app = if let Some(ref pool) = db_pool {
@@ -70,16 +82,72 @@ async fn main() -> anyhow::Result<()> {
             Some(deposit_repo),
             Some(address_book_repo),
             provider,
+        )
+        .with_inbound_repo(InboundTransferRepository::new(pool.clone()))
+        .with_escrow_repo(EscrowRepository::new(pool.clone()))
+        .with_audit_repo(AuditRepository::new(pool.clone()))
+        .with_transfer_repo(TransferRepository::new(pool.clone()))
+        .with_receipt_repo(ReceiptRepository::new(pool.clone()))
+        .with_channel_repo(ChannelRepository::new(pool.clone()))
+        .with_tag_repo(PayeeTagRepository::new(pool.clone()))
+        .with_transaction_repo(TransactionRepository::new(pool.clone()))
+        .with_pending_tx_repo(PendingTxRepository::new(pool.clone()))
+        .with_aa_config(config.aa.clone());
+        spawn_event_webhook(&command_processor);
+        spawn_deposit_watcher(
+            &sms_queue,
+            &UserRepository::new(pool.clone()),
+            &DepositRepository::new(pool.clone()),
+            &command_processor,
         );
 
+        // Release due escrows and refund abandoned ones in the background
+        let escrow_processor = command_processor.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                let settled = escrow_processor.run_escrow_sweep().await;
+                if settled > 0 {
+                    tracing::info!(settled, "Escrow sweep finished");
+                }
+            }
+        });
+
+        // Close idle or drained Yellow channels and tell their owners
+        let channel_processor = command_processor.clone();
+        let channel_queue = sms_queue.clone();
+        let channel_users = UserRepository::new(pool.clone());
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(300));
+            loop {
+                interval.tick().await;
+                for (phone, sms) in channel_processor.run_channel_sweep().await {
+                    if channel_users.is_opted_out(&phone).await.unwrap_or(false) {
+                        continue;
+                    }
+                    channel_queue.enqueue(&phone, &sms, Priority::Normal);
+                }
+            }
+        });
+
         tracing::info!("Admin routes enabled at /admin/*");
-        create_router_with_admin(twilio, command_processor, voucher_repo, admin_token, pool.clone())
+        create_router_with_admin(
+            sms_queue,
+            command_processor,
+            voucher_repo,
+            admin_token,
+            pool.clone(),
+            InboundGatewayConfig::from_env(),
+            TwilioWebhookConfig::from_env(),
+        )
     } else {
         let command_processor = CommandProcessor::new(
             None, 
             provider,
         );
-        create_router(twilio, command_processor)
+        spawn_event_webhook(&command_processor);
+        create_router(sms_queue, command_processor, InboundGatewayConfig::from_env(), TwilioWebhookConfig::from_env())
     };
 
     // Start server
@@ -95,4 +163,84 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Forward wallet events to EVENT_WEBHOOK_URL if set
+fn spawn_event_webhook(command_processor: &CommandProcessor) {
+    let webhook = EventWebhookConfig::from_env();
+    if webhook.url.is_empty() {
+        return;
+    }
+    if webhook.secret.is_empty() {
+        tracing::warn!("EVENT_WEBHOOK_SECRET not set - webhook events are signed with an empty key");
+    }
+    tracing::info!(url = %webhook.url, "Event webhook enabled");
+    events::spawn_webhook(command_processor.subscribe_events(), webhook.url, webhook.secret);
+}
+
+/// Start the on-chain deposit watchers if enabled: one for USDC and, when
+/// DEPOSIT_TXTC_ADDRESS is set, one for TXTC. Confirmed deposits are
+/// announced and handed to AUTOSWAP.
+fn spawn_deposit_watcher(
+    sms_queue: &SmsQueue,
+    user_repo: &UserRepository,
+    deposit_repo: &DepositRepository,
+    command_processor: &CommandProcessor,
+) {
+    let watcher_config = DepositWatcherConfig::from_env();
+    if !watcher_config.enabled {
+        return;
+    }
 
+    let Some(chain) = Chain::from_input(&watcher_config.chain) else {
+        tracing::warn!(chain = %watcher_config.chain, "Unknown DEPOSIT_CHAIN - deposit watcher disabled");
+        return;
+    };
+    let mut scanners = Vec::new();
+    match TokenScanner::usdc(create_chain_provider(chain), chain) {
+        Some(scanner) => scanners.push(scanner),
+        None => tracing::warn!(%chain, "No USDC on chain - USDC deposits not watched"),
+    }
+    if !watcher_config.txtc_address.is_empty() {
+        match watcher_config.txtc_address.parse() {
+            Ok(address) => scanners.push(TokenScanner::txtc(create_chain_provider(chain), address)),
+            Err(_) => tracing::warn!(address = %watcher_config.txtc_address, "Invalid DEPOSIT_TXTC_ADDRESS - TXTC deposits not watched"),
+        }
+    }
+
+    for scanner in scanners {
+        let token = scanner.token();
+        let mut watcher = DepositWatcher::new(
+            scanner,
+            chain,
+            user_repo.clone(),
+            deposit_repo.clone(),
+            watcher_config.confirmations,
+        );
+        let sms_queue = sms_queue.clone();
+        let user_repo = user_repo.clone();
+        let command_processor = command_processor.clone();
+        let poll_secs = watcher_config.poll_secs;
+
+        tracing::info!(%chain, token, confirmations = watcher_config.confirmations, "Deposit watcher started");
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(poll_secs));
+            loop {
+                interval.tick().await;
+                match watcher.poll().await {
+                    Ok(confirmed) => {
+                        for deposit in confirmed {
+                            let autoswap = command_processor.autoswap_deposit(&deposit).await;
+                            if user_repo.is_opted_out(&deposit.user_phone).await.unwrap_or(false) {
+                                continue;
+                            }
+                            sms_queue.enqueue(&deposit.user_phone, &confirmation_sms(&deposit), Priority::Normal);
+                            if let Some(sms) = autoswap {
+                                sms_queue.enqueue(&deposit.user_phone, &sms, Priority::Normal);
+                            }
+                        }
+                    }
+                    Err(e) => tracing::warn!(token, "Deposit watcher poll failed: {}", e),
+                }
+            }
+        });
+    }
+}
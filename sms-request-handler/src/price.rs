@@ -0,0 +1,369 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Default CoinGecko-compatible price feed
+const DEFAULT_PRICE_FEED_URL: &str = "https://api.coingecko.com/api/v3";
+
+/// Default allowed spread between two price feeds, in percent
+const DEFAULT_PRICE_TOLERANCE_PCT: f64 = 2.0;
+
+/// Start of the error returned when cross-checked feeds are too far apart
+pub const PRICES_DISAGREE: &str = "Price sources disagree";
+
+/// Fiat currencies accepted for quoting (ISO-4217, all supported by the feed)
+pub const SUPPORTED_CURRENCIES: &[&str] = &["USD", "EUR", "GBP", "NGN", "KES", "INR"];
+
+/// Map a currency symbol to its ISO code: $ -> USD
+pub fn currency_for_symbol(symbol: char) -> Option<&'static str> {
+    match symbol {
+        '$' => Some("USD"),
+        '€' => Some("EUR"),
+        '£' => Some("GBP"),
+        '₦' => Some("NGN"),
+        '₹' => Some("INR"),
+        _ => None,
+    }
+}
+
+/// Normalize a currency code if it's one we can quote in
+pub fn supported_currency(code: &str) -> Option<&'static str> {
+    SUPPORTED_CURRENCIES
+        .iter()
+        .find(|c| c.eq_ignore_ascii_case(code))
+        .copied()
+}
+
+/// Format a fiat amount for SMS: $10.00, 10.00 KES
+pub fn format_fiat(amount: f64, currency: &str) -> String {
+    match currency {
+        "USD" => format!("${:.2}", amount),
+        "EUR" => format!("€{:.2}", amount),
+        "GBP" => format!("£{:.2}", amount),
+        _ => format!("{:.2} {}", amount, currency),
+    }
+}
+
+/// Approximate fiat value for display next to a token amount: ~KES 1,300, ~USD 0.42
+pub fn format_approx_fiat(amount: f64, currency: &str) -> String {
+    let decimals = if amount.abs() >= 100.0 { 0 } else { 2 };
+    let sign = if amount < 0.0 { "-" } else { "" };
+    format!("~{} {}{}", currency, sign, group_thousands(&format!("{:.*}", decimals, amount.abs())))
+}
+
+/// Unit price for PRICE: $3,210.44, 5,130,000.00 NGN. Sub-cent prices keep
+/// up to 6 decimals so they don't show as zero.
+pub fn format_price(price: f64, currency: &str) -> String {
+    let formatted = if price.abs() >= 0.01 {
+        format!("{:.2}", price)
+    } else {
+        let precise = format!("{:.6}", price);
+        precise.trim_end_matches('0').trim_end_matches('.').to_string()
+    };
+    let grouped = group_thousands(&formatted);
+    match currency {
+        "USD" => format!("${}", grouped),
+        "EUR" => format!("€{}", grouped),
+        "GBP" => format!("£{}", grouped),
+        _ => format!("{} {}", grouped, currency),
+    }
+}
+
+/// "1234567.5" as "1,234,567.5"
+fn group_thousands(number: &str) -> String {
+    let (whole, fraction) = number.split_once('.').map_or((number, None), |(w, f)| (w, Some(f)));
+    let mut grouped = String::new();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    match fraction {
+        Some(fraction) => format!("{}.{}", grouped, fraction),
+        None => grouped,
+    }
+}
+
+/// Source of token prices. Pluggable so tests can inject a stub.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    /// Price of one `token` (TXTC, ETH) in `currency` (USD, EUR, ...)
+    async fn price(&self, token: &str, currency: &str) -> Result<f64, String>;
+
+    /// Price to move money at. Sources that cross-check refuse unless every
+    /// feed answered; for the rest this is `price`.
+    async fn firm_price(&self, token: &str, currency: &str) -> Result<f64, String> {
+        self.price(token, currency).await
+    }
+}
+
+/// Prices ETH from a CoinGecko-style feed and TXTC from the backend's
+/// pool price (ETH per TXTC) multiplied by the ETH price
+pub struct HttpPriceSource {
+    client: reqwest::Client,
+    feed_url: String,
+    backend_url: String,
+}
+
+impl HttpPriceSource {
+    pub fn new(feed_url: impl Into<String>, backend_url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            feed_url: feed_url.into(),
+            backend_url: backend_url.into(),
+        }
+    }
+
+    /// Feed from `PRICE_FEED_URL`, pool price from the backend
+    pub fn from_env(backend_url: &str) -> Self {
+        let feed_url = std::env::var("PRICE_FEED_URL").unwrap_or_else(|_| DEFAULT_PRICE_FEED_URL.to_string());
+        Self::new(feed_url, backend_url)
+    }
+
+    async fn get_json(&self, url: &str) -> Result<serde_json::Value, String> {
+        crate::http::send(self.client.get(url).timeout(std::time::Duration::from_secs(10)))
+            .await
+            .map_err(|e| format!("Price request failed: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid price response: {}", e))
+    }
+
+    async fn eth_price(&self, currency: &str) -> Result<f64, String> {
+        let vs = currency.to_lowercase();
+        let json = self
+            .get_json(&format!("{}/simple/price?ids=ethereum&vs_currencies={}", self.feed_url, vs))
+            .await?;
+        json["ethereum"][vs.as_str()]
+            .as_f64()
+            .ok_or_else(|| format!("No ETH/{} price", currency))
+    }
+
+    /// TXTC priced in ETH from the liquidity pool
+    async fn txtc_eth_price(&self) -> Result<f64, String> {
+        let json = self.get_json(&format!("{}/api/price", self.backend_url)).await?;
+        if !json["success"].as_bool().unwrap_or(false) {
+            return Err("Pool price unavailable".to_string());
+        }
+        // The backend may send the price as a number or a decimal string
+        json["price"]
+            .as_f64()
+            .or_else(|| json["price"].as_str().and_then(|s| s.parse().ok()))
+            .filter(|p| *p > 0.0)
+            .ok_or_else(|| "Invalid pool price".to_string())
+    }
+}
+
+#[async_trait]
+impl PriceSource for HttpPriceSource {
+    async fn price(&self, token: &str, currency: &str) -> Result<f64, String> {
+        match token.to_uppercase().as_str() {
+            "ETH" => self.eth_price(currency).await,
+            "TXTC" => Ok(self.txtc_eth_price().await? * self.eth_price(currency).await?),
+            other => Err(format!("No price for {}", other)),
+        }
+    }
+}
+
+/// Asks two sources and refuses a price when they are more than
+/// `tolerance_pct` apart, so a lagging feed can't misprice a send during
+/// volatility. If only one source answers, its price is still shown, but
+/// no firm price is given.
+pub struct CrossCheckedPriceSource {
+    primary: Arc<dyn PriceSource>,
+    secondary: Arc<dyn PriceSource>,
+    tolerance_pct: f64,
+}
+
+impl CrossCheckedPriceSource {
+    pub fn new(primary: Arc<dyn PriceSource>, secondary: Arc<dyn PriceSource>, tolerance_pct: f64) -> Self {
+        Self { primary, secondary, tolerance_pct }
+    }
+
+    async fn check(&self, token: &str, currency: &str, firm: bool) -> Result<f64, String> {
+        let (primary, secondary) =
+            tokio::join!(self.primary.price(token, currency), self.secondary.price(token, currency));
+        match (primary, secondary) {
+            (Ok(a), Ok(b)) => {
+                let spread_pct = (a - b).abs() / a.min(b) * 100.0;
+                if spread_pct.is_finite() && spread_pct <= self.tolerance_pct {
+                    Ok(a)
+                } else {
+                    Err(format!("{}: {}/{} {} vs {}", PRICES_DISAGREE, token, currency, a, b))
+                }
+            }
+            (Ok(_), Err(e)) | (Err(e), Ok(_)) if firm => Err(format!("Price not cross-checked, one source failed: {}", e)),
+            (Ok(price), Err(e)) | (Err(e), Ok(price)) => {
+                tracing::warn!("One price source failed, using the other: {}", e);
+                Ok(price)
+            }
+            (Err(e), Err(_)) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceSource for CrossCheckedPriceSource {
+    async fn price(&self, token: &str, currency: &str) -> Result<f64, String> {
+        self.check(token, currency, false).await
+    }
+
+    async fn firm_price(&self, token: &str, currency: &str) -> Result<f64, String> {
+        self.check(token, currency, true).await
+    }
+}
+
+/// Price source for the processor: `PRICE_FEED_URL`, cross-checked against
+/// `PRICE_FEED_URL_2` within `PRICE_TOLERANCE_PCT` when that is set, cached for a minute.
+///
+/// Both feeds take TXTC's ETH price from the same backend pool, so for TXTC
+/// only the ETH leg is cross-checked; a bad pool price gets through both.
+pub fn price_source_from_env(backend_url: &str) -> Arc<dyn PriceSource> {
+    let mut source: Arc<dyn PriceSource> = Arc::new(HttpPriceSource::from_env(backend_url));
+    if let Some(second_feed) = std::env::var("PRICE_FEED_URL_2").ok().filter(|u| !u.is_empty()) {
+        let tolerance_pct = std::env::var("PRICE_TOLERANCE_PCT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_PRICE_TOLERANCE_PCT);
+        let second: Arc<dyn PriceSource> = Arc::new(HttpPriceSource::new(second_feed, backend_url));
+        source = Arc::new(CrossCheckedPriceSource::new(source, second, tolerance_pct));
+    }
+    Arc::new(CachedPriceSource::new(source, Duration::from_secs(60)))
+}
+
+/// Token, currency and whether the price is firm
+type PriceKey = (String, String, bool);
+
+/// Remembers prices for a while so every BALANCE doesn't hit the feed.
+/// Failures aren't cached, and firm prices are cached apart from the rest.
+pub struct CachedPriceSource {
+    inner: Arc<dyn PriceSource>,
+    ttl: Duration,
+    prices: Mutex<HashMap<PriceKey, (f64, Instant)>>,
+}
+
+impl CachedPriceSource {
+    pub fn new(inner: Arc<dyn PriceSource>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            prices: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn lookup(&self, token: &str, currency: &str, firm: bool) -> Result<f64, String> {
+        let key = (token.to_uppercase(), currency.to_uppercase(), firm);
+        if let Some((price, at)) = self.prices.lock().unwrap().get(&key) {
+            if at.elapsed() < self.ttl {
+                return Ok(*price);
+            }
+        }
+        let price = if firm {
+            self.inner.firm_price(token, currency).await?
+        } else {
+            self.inner.price(token, currency).await?
+        };
+        self.prices.lock().unwrap().insert(key, (price, Instant::now()));
+        Ok(price)
+    }
+}
+
+#[async_trait]
+impl PriceSource for CachedPriceSource {
+    async fn price(&self, token: &str, currency: &str) -> Result<f64, String> {
+        self.lookup(token, currency, false).await
+    }
+
+    async fn firm_price(&self, token: &str, currency: &str) -> Result<f64, String> {
+        self.lookup(token, currency, true).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Json, Router};
+
+    #[test]
+    fn test_currency_lookup() {
+        assert_eq!(currency_for_symbol('$'), Some("USD"));
+        assert_eq!(currency_for_symbol('x'), None);
+        assert_eq!(supported_currency("eur"), Some("EUR"));
+        assert_eq!(supported_currency("XYZ"), None);
+        assert_eq!(format_fiat(10.0, "USD"), "$10.00");
+        assert_eq!(format_fiat(1300.0, "KES"), "1300.00 KES");
+        assert_eq!(format_approx_fiat(1300.0, "KES"), "~KES 1,300");
+        assert_eq!(format_approx_fiat(1234567.4, "NGN"), "~NGN 1,234,567");
+        assert_eq!(format_approx_fiat(0.42, "USD"), "~USD 0.42");
+        assert_eq!(format_price(3210.444, "USD"), "$3,210.44");
+        assert_eq!(format_price(5_130_000.0, "NGN"), "5,130,000.00 NGN");
+        assert_eq!(format_price(0.00031, "EUR"), "€0.00031");
+    }
+
+    #[tokio::test]
+    async fn test_cached_prices_hit_the_feed_once() {
+        struct Counting(std::sync::atomic::AtomicUsize);
+
+        #[async_trait]
+        impl PriceSource for Counting {
+            async fn price(&self, _token: &str, _currency: &str) -> Result<f64, String> {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(130.0)
+            }
+        }
+
+        let inner = Arc::new(Counting(Default::default()));
+        let cached = CachedPriceSource::new(inner.clone(), Duration::from_secs(60));
+        assert_eq!(cached.price("TXTC", "KES").await.unwrap(), 130.0);
+        assert_eq!(cached.price("txtc", "kes").await.unwrap(), 130.0);
+        assert_eq!(inner.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        cached.price("ETH", "KES").await.unwrap();
+        assert_eq!(inner.0.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cross_checked_sources() {
+        struct Fixed(Option<f64>);
+
+        #[async_trait]
+        impl PriceSource for Fixed {
+            async fn price(&self, _token: &str, _currency: &str) -> Result<f64, String> {
+                self.0.ok_or_else(|| "feed down".to_string())
+            }
+        }
+        let checked = |a, b| CrossCheckedPriceSource::new(Arc::new(Fixed(a)), Arc::new(Fixed(b)), 2.0);
+
+        assert_eq!(checked(Some(100.0), Some(101.5)).price("ETH", "USD").await, Ok(100.0));
+        let err = checked(Some(100.0), Some(105.0)).price("ETH", "USD").await.unwrap_err();
+        assert!(err.starts_with(PRICES_DISAGREE), "{}", err);
+
+        // One feed down: the other is shown on its own, but gives no firm price
+        assert_eq!(checked(None, Some(99.0)).price("ETH", "USD").await, Ok(99.0));
+        assert!(checked(None, Some(99.0)).firm_price("ETH", "USD").await.is_err());
+        assert!(checked(Some(99.0), None).firm_price("ETH", "USD").await.is_err());
+        assert_eq!(checked(Some(100.0), Some(101.5)).firm_price("ETH", "USD").await, Ok(100.0));
+        assert!(checked(None, None).price("ETH", "USD").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_txtc_priced_through_pool() {
+        let router = Router::new()
+            .route(
+                "/simple/price",
+                get(|| async { Json(serde_json::json!({ "ethereum": { "usd": 3000.0 } })) }),
+            )
+            .route(
+                "/api/price",
+                get(|| async { Json(serde_json::json!({ "success": true, "price": "0.0001" })) }),
+            );
+        let url = crate::test_support::spawn_mock_backend(router).await;
+        let source = HttpPriceSource::new(url.clone(), url);
+
+        assert_eq!(source.price("ETH", "USD").await.unwrap(), 3000.0);
+        assert!((source.price("txtc", "USD").await.unwrap() - 0.3).abs() < 1e-9);
+        assert!(source.price("ETH", "EUR").await.is_err());
+    }
+}
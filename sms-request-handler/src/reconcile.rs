@@ -0,0 +1,238 @@
+//! Operator reconciliation: SENDs tracked in `outgoing_transfers` checked
+//! against the transfers the backend reports having made
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::db::{TrackedTransfer, TransferRepository};
+use crate::http;
+
+/// How a tracked transfer and the backend disagree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscrepancyKind {
+    /// Still "pending" (outcome unknown) well after it was submitted
+    PendingTooLong,
+    /// Recorded as sent, but the backend has no such transaction
+    RecordedButMissing,
+    /// The backend made the transfer, but it isn't recorded as sent
+    MissingButOnChain,
+}
+
+/// One transfer needing an operator's attention
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Discrepancy {
+    pub kind: DiscrepancyKind,
+    /// Our `outgoing_transfers` id, if we recorded the transfer
+    pub transfer_id: Option<Uuid>,
+    pub tx_hash: Option<String>,
+    pub from_address: String,
+    pub to_address: String,
+    pub amount: f64,
+    pub token: String,
+    /// Status we recorded, if we recorded the transfer at all
+    pub recorded_status: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// Transfer as the backend's `/api/transfers` reports it
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendTransfer {
+    pub tx_hash: String,
+    pub from_address: String,
+    pub to_address: String,
+    #[serde(deserialize_with = "amount_from_str_or_number")]
+    pub amount: f64,
+    pub token: String,
+    pub at: DateTime<Utc>,
+}
+
+/// Result of reconciling one time window
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileReport {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    /// Transfers recorded in the window
+    pub checked: usize,
+    /// False when the backend couldn't be asked: only stuck transfers are reported
+    pub backend_checked: bool,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+/// Reconcile the transfers recorded in `[since, until)` with the backend's.
+/// Pending transfers older than `stale_after` are reported stuck.
+pub async fn reconcile(
+    transfers: &TransferRepository,
+    backend_url: &str,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    stale_after: Duration,
+) -> Result<ReconcileReport, sqlx::Error> {
+    let recorded = transfers.list_between(since, until).await?;
+    let backend = match fetch_backend_transfers(backend_url, since, until).await {
+        Ok(backend) => Some(backend),
+        Err(e) => {
+            tracing::warn!("Reconciliation without backend data: {}", e);
+            None
+        }
+    };
+
+    Ok(ReconcileReport {
+        since,
+        until,
+        checked: recorded.len(),
+        backend_checked: backend.is_some(),
+        discrepancies: compare(&recorded, backend.as_deref(), Utc::now(), stale_after),
+    })
+}
+
+/// Discrepancies between recorded transfers and the backend's (None = backend unknown)
+pub fn compare(
+    recorded: &[TrackedTransfer],
+    backend: Option<&[BackendTransfer]>,
+    now: DateTime<Utc>,
+    stale_after: Duration,
+) -> Vec<Discrepancy> {
+    let mut found: Vec<Discrepancy> = recorded
+        .iter()
+        .filter(|t| t.status == "pending" && now - t.created_at > stale_after)
+        .map(|t| recorded_discrepancy(DiscrepancyKind::PendingTooLong, t))
+        .collect();
+
+    let Some(backend) = backend else {
+        return found;
+    };
+    let made: HashSet<String> = backend.iter().map(|t| t.tx_hash.to_lowercase()).collect();
+    let sent: HashSet<String> = recorded
+        .iter()
+        .filter(|t| t.status == "queued")
+        .filter_map(|t| t.tx_hash.as_ref().map(|h| h.to_lowercase()))
+        .collect();
+
+    // Only transfers with a hash can be looked up
+    found.extend(
+        recorded
+            .iter()
+            .filter(|t| t.status == "queued")
+            .filter(|t| t.tx_hash.as_ref().is_some_and(|h| !made.contains(&h.to_lowercase())))
+            .map(|t| recorded_discrepancy(DiscrepancyKind::RecordedButMissing, t)),
+    );
+    found.extend(backend.iter().filter(|t| !sent.contains(&t.tx_hash.to_lowercase())).map(|t| {
+        let ours = recorded
+            .iter()
+            .find(|r| r.tx_hash.as_ref().is_some_and(|h| h.eq_ignore_ascii_case(&t.tx_hash)));
+        Discrepancy {
+            kind: DiscrepancyKind::MissingButOnChain,
+            transfer_id: ours.map(|r| r.id),
+            tx_hash: Some(t.tx_hash.clone()),
+            from_address: t.from_address.clone(),
+            to_address: t.to_address.clone(),
+            amount: t.amount,
+            token: t.token.clone(),
+            recorded_status: ours.map(|r| r.status.clone()),
+            at: t.at,
+        }
+    }));
+    found
+}
+
+fn recorded_discrepancy(kind: DiscrepancyKind, transfer: &TrackedTransfer) -> Discrepancy {
+    Discrepancy {
+        kind,
+        transfer_id: Some(transfer.id),
+        tx_hash: transfer.tx_hash.clone(),
+        from_address: transfer.from_address.clone(),
+        to_address: transfer.to_address.clone(),
+        amount: transfer.amount,
+        token: transfer.token.clone(),
+        recorded_status: Some(transfer.status.clone()),
+        at: transfer.created_at,
+    }
+}
+
+/// Transfers the backend made in the window
+async fn fetch_backend_transfers(
+    backend_url: &str,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<Vec<BackendTransfer>, String> {
+    let request = reqwest::Client::new()
+        .get(format!("{}/api/transfers", backend_url))
+        .query(&[("since", since.to_rfc3339()), ("until", until.to_rfc3339())])
+        .timeout(std::time::Duration::from_secs(10));
+    let json: serde_json::Value = http::send(request)
+        .await
+        .map_err(|e| format!("Transfers request failed: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid transfers response: {}", e))?;
+
+    if !json["success"].as_bool().unwrap_or(false) {
+        return Err(json["error"].as_str().unwrap_or("Transfers lookup failed").to_string());
+    }
+    serde_json::from_value(json["transfers"].clone()).map_err(|e| format!("Invalid transfers list: {}", e))
+}
+
+/// The backend sends amounts as decimal strings
+fn amount_from_str_or_number<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(n) => n.as_f64().ok_or_else(|| serde::de::Error::custom("amount out of range")),
+        serde_json::Value::String(s) => s.parse().map_err(serde::de::Error::custom),
+        other => Err(serde::de::Error::custom(format!("invalid amount: {}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracked(status: &str, tx_hash: Option<&str>, age_mins: i64, now: DateTime<Utc>) -> TrackedTransfer {
+        TrackedTransfer {
+            id: Uuid::new_v4(),
+            from_address: "0xfrom".to_string(),
+            to_address: "0xto".to_string(),
+            amount: 5.0,
+            token: "TXTC".to_string(),
+            status: status.to_string(),
+            tx_hash: tx_hash.map(str::to_string),
+            created_at: now - Duration::minutes(age_mins),
+        }
+    }
+
+    #[test]
+    fn test_compare_finds_each_discrepancy() {
+        let now = Utc::now();
+        let recorded = [
+            tracked("pending", None, 90, now),
+            tracked("pending", None, 5, now),
+            tracked("queued", Some("0xAAA"), 60, now),
+            tracked("queued", Some("0xbbb"), 60, now),
+            tracked("failed", Some("0xccc"), 60, now),
+        ];
+        let backend: Vec<BackendTransfer> = serde_json::from_value(serde_json::json!([
+            { "txHash": "0xaaa", "fromAddress": "0xfrom", "toAddress": "0xto", "amount": "5", "token": "TXTC", "at": now },
+            { "txHash": "0xccc", "fromAddress": "0xfrom", "toAddress": "0xto", "amount": 5.0, "token": "TXTC", "at": now },
+        ]))
+        .unwrap();
+
+        let found = compare(&recorded, Some(&backend), now, Duration::minutes(30));
+        let kinds: Vec<_> = found.iter().map(|d| (d.kind, d.tx_hash.as_deref())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (DiscrepancyKind::PendingTooLong, None),
+                (DiscrepancyKind::RecordedButMissing, Some("0xbbb")),
+                (DiscrepancyKind::MissingButOnChain, Some("0xccc")),
+            ]
+        );
+        assert_eq!(found[2].recorded_status.as_deref(), Some("failed"));
+
+        // Without the backend only stuck transfers can be told
+        assert_eq!(compare(&recorded, None, now, Duration::minutes(30)).len(), 1);
+    }
+}
@@ -1,82 +1,238 @@
 use axum::{
-    routing::{get, post},
-    Router,
+    body::Body,
+    extract::{Path, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, MethodRouter},
+    Json, Router,
 };
+use std::collections::HashMap;
 use std::sync::Arc;
 use tower_http::trace::TraceLayer;
 
 use crate::admin::{admin_routes, AdminState};
 use crate::admin_wallet::admin_wallet_routes;
 use crate::commands::CommandProcessor;
-use crate::db::VoucherRepository;
-use crate::sms::{incoming_sms_handler, incoming_sms_json_handler, TwilioClient};
+use crate::selftest::selftest_routes;
+use crate::db::{schema_version, AuditRepository, TransferRepository, VoucherRepository, SCHEMA_VERSION};
+use crate::config::{InboundGatewayConfig, TwilioWebhookConfig};
+use crate::sms::{gateway_routes, has_inbound_token, incoming_sms_handler, incoming_sms_json_handler, validate_twilio_signature, SmsQueue};
 use crate::sms::webhook::AppState;
 use sqlx::PgPool;
 
 /// Build the application router with all routes
-pub fn create_router(twilio: TwilioClient, command_processor: CommandProcessor) -> Router {
+pub fn create_router(
+    sms_queue: SmsQueue,
+    command_processor: CommandProcessor,
+    gateway: InboundGatewayConfig,
+    twilio: TwilioWebhookConfig,
+) -> Router {
     let state = AppState {
-        twilio: Arc::new(twilio),
+        sms_queue,
         command_processor: Arc::new(command_processor),
     };
+    let json_router = json_webhook(&gateway.token);
+    let gateway_router = gateway_routes(state.command_processor.clone(), gateway);
 
     Router::new()
         // SMS webhook endpoint - Twilio sends incoming messages here (form-encoded)
-        .route("/sms/incoming", post(incoming_sms_handler))
+        .route("/sms/incoming", twilio_webhook(twilio))
         // SMS webhook endpoint - SMSCountry/generic JSON webhooks
-        .route("/webhook/sms", post(incoming_sms_json_handler))
+        .merge(json_router)
+        // One-time EXPORT keystore and history downloads
+        .route("/export/:token", get(export_download))
+        .route("/export/history/:token", get(history_download))
+        // Signed SEND receipts
+        .route("/receipt/:reference", get(receipt_lookup))
         // Health check endpoint
         .route("/health", get(health_check))
         // Ready check endpoint
         .route("/ready", get(ready_check))
-        // Add tracing middleware
-        .layer(TraceLayer::new_for_http())
         // Add shared state
         .with_state(state)
+        // Generic gateways posting their own JSON (field mapping from config)
+        .merge(gateway_router)
+        // Add tracing middleware
+        .layer(TraceLayer::new_for_http())
 
 }
 
+/// Admin token used when ADMIN_TOKEN isn't set (development only)
+const DEV_ADMIN_TOKEN: &str = "admin123";
+
 /// Build router with admin routes (requires voucher repo and db pool)
 pub fn create_router_with_admin(
-    twilio: TwilioClient, 
+    sms_queue: SmsQueue,
     command_processor: CommandProcessor,
     voucher_repo: VoucherRepository,
-    admin_token: String,
+    admin_token: Option<String>,
     db_pool: PgPool,
+    gateway: InboundGatewayConfig,
+    twilio: TwilioWebhookConfig,
 ) -> Router {
     let sms_state = AppState {
-        twilio: Arc::new(twilio),
+        sms_queue,
         command_processor: Arc::new(command_processor),
     };
 
+    // The self-test runs real commands, so it's never served on the dev token
+    let selftest_router = match admin_token {
+        Some(ref token) => selftest_routes(sms_state.command_processor.clone(), token.clone()),
+        None => {
+            tracing::warn!("ADMIN_TOKEN not set - /admin/selftest is disabled");
+            Router::new()
+        }
+    };
+    let admin_token = admin_token.unwrap_or_else(|| DEV_ADMIN_TOKEN.to_string());
+    let json_router = json_webhook(&gateway.token);
+    let gateway_router = gateway_routes(sms_state.command_processor.clone(), gateway);
+
     let admin_state = AdminState {
         voucher_repo: Arc::new(voucher_repo),
+        audit_repo: Arc::new(AuditRepository::new(db_pool.clone())),
+        transfer_repo: Arc::new(TransferRepository::new(db_pool.clone())),
+        backend_url: sms_state.command_processor.backend_url().to_string(),
+        lookup_caches: sms_state.command_processor.lookup_caches().clone(),
         admin_token,
     };
 
     // Create SMS routes with their state
     let sms_routes = Router::new()
-        .route("/sms/incoming", post(incoming_sms_handler))
-        .route("/webhook/sms", post(incoming_sms_json_handler))
+        .route("/sms/incoming", twilio_webhook(twilio))
+        .merge(json_router)
+        .route("/export/:token", get(export_download))
+        .route("/export/history/:token", get(history_download))
+        .route("/receipt/:reference", get(receipt_lookup))
         .with_state(sms_state);
 
 
     // Create admin routes with their state (already has state applied)
     let admin_router = admin_routes(admin_state);
     
+    // Readiness reports the schema version, so needs its own pool handle
+    let ready = get(ready_with_schema).with_state(db_pool.clone());
+
     // Create admin wallet routes
     let wallet_admin_router = admin_wallet_routes(Arc::new(db_pool));
 
     // Merge all routes together
     Router::new()
         .merge(sms_routes)
+        .merge(gateway_router)
         .nest("/admin", admin_router)
         .nest("/admin", wallet_admin_router)
+        .nest("/admin", selftest_router)
         .route("/health", get(health_check))
-        .route("/ready", get(ready_check))
+        .route("/ready", ready)
         .layer(TraceLayer::new_for_http())
 }
 
+/// Largest Twilio webhook body read for the signature check
+const MAX_TWILIO_BODY: usize = 64 * 1024;
+
+/// Twilio's webhook route, behind the signature check when it's enabled
+fn twilio_webhook(config: TwilioWebhookConfig) -> MethodRouter<AppState> {
+    let route = post(incoming_sms_handler);
+    if !config.verify_signature {
+        return route;
+    }
+    route.layer(middleware::from_fn_with_state(Arc::new(config), verify_twilio_signature))
+}
+
+/// Reject webhooks whose X-Twilio-Signature isn't Twilio's signature of the
+/// public URL and form body, so nobody else can post inbound messages
+async fn verify_twilio_signature(
+    State(config): State<Arc<TwilioWebhookConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, MAX_TWILIO_BODY).await else {
+        return (StatusCode::PAYLOAD_TOO_LARGE, "Request too large").into_response();
+    };
+    let params: HashMap<String, String> = serde_urlencoded::from_bytes(&bytes).unwrap_or_default();
+    let path = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let url = format!("{}{}", config.public_url.trim_end_matches('/'), path);
+    let signature = parts
+        .headers
+        .get("x-twilio-signature")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+
+    if !validate_twilio_signature(&config.auth_token, &url, &params, signature) {
+        tracing::warn!(url = %url, "Rejected Twilio webhook with an invalid signature");
+        return (StatusCode::FORBIDDEN, "Invalid signature").into_response();
+    }
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
+
+/// SMSCountry/generic JSON webhook. Like /inbound it needs INBOUND_TOKEN in
+/// X-Inbound-Token, and isn't served until that is set.
+fn json_webhook(token: &str) -> Router<AppState> {
+    if token.is_empty() {
+        tracing::warn!("INBOUND_TOKEN not set - /webhook/sms is disabled");
+        return Router::new();
+    }
+    let route = post(incoming_sms_json_handler)
+        .layer(middleware::from_fn_with_state(Arc::new(token.to_string()), require_inbound_token));
+    Router::new().route("/webhook/sms", route)
+}
+
+async fn require_inbound_token(State(token): State<Arc<String>>, request: Request, next: Next) -> Response {
+    if !has_inbound_token(request.headers(), &token) {
+        tracing::warn!("Rejected /webhook/sms without a valid X-Inbound-Token");
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({ "error": "invalid token" }))).into_response();
+    }
+    next.run(request).await
+}
+
+/// Serve an EXPORT keystore once, as a file standard wallets can import
+async fn export_download(State(state): State<AppState>, Path(token): Path<String>) -> impl IntoResponse {
+    match state.command_processor.take_export(&token) {
+        Some(keystore) => (
+            [(header::CONTENT_DISPOSITION, "attachment; filename=\"textchain-keystore.json\"")],
+            Json(keystore),
+        )
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, "Link expired or already used.").into_response(),
+    }
+}
+
+/// Serve an EXPORT HISTORY statement once, as a CSV file
+async fn history_download(State(state): State<AppState>, Path(token): Path<String>) -> impl IntoResponse {
+    match state.command_processor.take_history_export(&token) {
+        Some(csv) => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (header::CONTENT_DISPOSITION, "attachment; filename=\"textchain-history.csv\""),
+            ],
+            csv,
+        )
+            .into_response(),
+        None => (StatusCode::NOT_FOUND, "Link expired or already used.").into_response(),
+    }
+}
+
+/// Show a SEND receipt, after checking its signature
+async fn receipt_lookup(State(state): State<AppState>, Path(reference): Path<String>) -> impl IntoResponse {
+    match state.command_processor.find_receipt(&reference).await {
+        Some((receipt, true)) => {
+            let explorer_url = state.command_processor.receipt_explorer_url(&receipt);
+            Json(serde_json::json!({ "verified": true, "receipt": receipt, "explorerUrl": explorer_url })).into_response()
+        }
+        Some((receipt, false)) => {
+            tracing::warn!(reference = %receipt.reference, "Receipt failed signature check");
+            (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({ "verified": false, "error": "Receipt signature does not match" })),
+            )
+                .into_response()
+        }
+        None => (StatusCode::NOT_FOUND, "Receipt not found.").into_response(),
+    }
+}
+
 /// Health check handler
 async fn health_check() -> &'static str {
     "OK"
@@ -87,4 +243,134 @@ async fn ready_check() -> &'static str {
     "READY"
 }
 
+/// Ready check with the database's schema version; not ready while the database is unreachable
+async fn ready_with_schema(State(pool): State<PgPool>) -> Response {
+    match schema_version(&pool).await {
+        Ok(version) => Json(serde_json::json!({
+            "status": "ready",
+            "schemaVersion": version,
+            "expectedSchemaVersion": SCHEMA_VERSION,
+        }))
+        .into_response(),
+        Err(e) => {
+            tracing::warn!("Readiness check failed: {}", e);
+            (StatusCode::SERVICE_UNAVAILABLE, "Database unavailable").into_response()
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sms::twilio::twilio_signature;
+    use crate::wallet::create_shared_provider;
+
+    #[tokio::test]
+    async fn test_twilio_webhook_requires_valid_signature() {
+        let twilio = TwilioWebhookConfig {
+            verify_signature: true,
+            auth_token: "12345".to_string(),
+            public_url: "https://sms.example.com/".to_string(),
+        };
+        let processor = CommandProcessor::new(None, create_shared_provider());
+        let router = create_router(SmsQueue::new(10), processor, InboundGatewayConfig::default(), twilio);
+        let url = crate::test_support::spawn_mock_backend(router).await;
+        let client = reqwest::Client::new();
 
+        let params: HashMap<String, String> = [("From", "+15550000001"), ("Body", "MENU")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let signature = twilio_signature("12345", "https://sms.example.com/sms/incoming", &params);
+
+        let signed = client
+            .post(format!("{}/sms/incoming", url))
+            .header("X-Twilio-Signature", &signature)
+            .form(&params)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(signed.status(), 200);
+
+        let unsigned = client.post(format!("{}/sms/incoming", url)).form(&params).send().await.unwrap();
+        assert_eq!(unsigned.status(), 403);
+
+        // Same signature, different body
+        let mut spoofed = params.clone();
+        spoofed.insert("Body".to_string(), "SEND 100 TXTC mallory".to_string());
+        let spoofed = client
+            .post(format!("{}/sms/incoming", url))
+            .header("X-Twilio-Signature", &signature)
+            .form(&spoofed)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(spoofed.status(), 403);
+    }
+
+    #[tokio::test]
+    async fn test_json_webhook_requires_inbound_token() {
+        let processor = || CommandProcessor::new(None, create_shared_provider());
+        let message = serde_json::json!({ "From": "+15550000001", "Body": "MENU" });
+        let client = reqwest::Client::new();
+
+        let gateway = InboundGatewayConfig { token: "gw-secret".to_string(), ..Default::default() };
+        let router = create_router(SmsQueue::new(10), processor(), gateway, TwilioWebhookConfig::default());
+        let url = crate::test_support::spawn_mock_backend(router).await;
+
+        let authorized = client
+            .post(format!("{}/webhook/sms", url))
+            .header("X-Inbound-Token", "gw-secret")
+            .json(&message)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(authorized.status(), 200);
+        let unauthorized = client.post(format!("{}/webhook/sms", url)).json(&message).send().await.unwrap();
+        assert_eq!(unauthorized.status(), 401);
+
+        // Without a token configured the route isn't there at all
+        let router = create_router(SmsQueue::new(10), processor(), InboundGatewayConfig::default(), TwilioWebhookConfig::default());
+        let url = crate::test_support::spawn_mock_backend(router).await;
+        let open = client.post(format!("{}/webhook/sms", url)).json(&message).send().await.unwrap();
+        assert_eq!(open.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_selftest_needs_admin_token_set() {
+        let Some(pool) = crate::db::test_pool().await else { return };
+        let router = |admin_token: Option<&str>| {
+            create_router_with_admin(
+                SmsQueue::new(10),
+                CommandProcessor::new(None, create_shared_provider()),
+                VoucherRepository::new(pool.clone()),
+                admin_token.map(str::to_string),
+                pool.clone(),
+                InboundGatewayConfig::default(),
+                TwilioWebhookConfig::default(),
+            )
+        };
+        let client = reqwest::Client::new();
+
+        // Not even the dev token opens it
+        let url = crate::test_support::spawn_mock_backend(router(None)).await;
+        let response = client
+            .post(format!("{}/admin/selftest", url))
+            .header("X-Admin-Token", DEV_ADMIN_TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let url = crate::test_support::spawn_mock_backend(router(Some("secret"))).await;
+        let response = client
+            .post(format!("{}/admin/selftest", url))
+            .header("X-Admin-Token", "secreT")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+    }
+}
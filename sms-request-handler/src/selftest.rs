@@ -0,0 +1,156 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::admin::has_admin_token;
+use crate::commands::CommandProcessor;
+
+/// Default number used for self-tests (Twilio's magic "valid" test number)
+const DEFAULT_SELFTEST_PHONE: &str = "+15005550006";
+
+/// Predicate deciding whether a step's reply counts as a pass
+type StepCheck = fn(&str) -> bool;
+
+/// Self-test routes state
+#[derive(Clone)]
+pub struct SelfTestState {
+    pub command_processor: Arc<CommandProcessor>,
+    pub admin_token: String,
+}
+
+/// Outcome of one scripted step
+#[derive(Debug, Serialize)]
+pub struct SelfTestStep {
+    pub step: &'static str,
+    pub command: String,
+    pub passed: bool,
+    pub response: String,
+}
+
+/// Self-test response
+#[derive(Debug, Serialize)]
+pub struct SelfTestResponse {
+    pub success: bool,
+    pub phone: String,
+    pub steps: Vec<SelfTestStep>,
+}
+
+/// Create self-test routes
+pub fn selftest_routes(command_processor: Arc<CommandProcessor>, admin_token: String) -> Router {
+    let state = SelfTestState { command_processor, admin_token };
+
+    Router::new()
+        .route("/selftest", post(run_selftest))
+        .with_state(state)
+}
+
+/// Run the scripted self-test (requires `X-Admin-Token`)
+async fn run_selftest(
+    State(state): State<SelfTestState>,
+    headers: HeaderMap,
+) -> Result<Json<SelfTestResponse>, StatusCode> {
+    if !has_admin_token(&headers, &state.admin_token) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let phone = std::env::var("SELFTEST_PHONE").unwrap_or_else(|_| DEFAULT_SELFTEST_PHONE.to_string());
+    let steps = run_steps(&state.command_processor, &phone).await;
+
+    for step in &steps {
+        tracing::info!(step = step.step, passed = step.passed, "Self-test step");
+    }
+
+    Ok(Json(SelfTestResponse {
+        success: steps.iter().all(|s| s.passed),
+        phone,
+        steps,
+    }))
+}
+
+/// Drive a simulated processor through JOIN, PIN, BALANCE, SEND and UNLOCK.
+/// Stops at the first failing step since later steps depend on it.
+/// Steps the PIN holds back (a rerun, or no open session) run on UNLOCK.
+pub async fn run_steps(processor: &CommandProcessor, phone: &str) -> Vec<SelfTestStep> {
+    let processor = processor.simulated();
+
+    let script: [(&'static str, String, StepCheck); 5] = [
+        ("join", "JOIN".to_string(), |r| {
+            r.starts_with("Wallet created") || r.starts_with("Welcome back")
+        }),
+        ("pin", "PIN 1234".to_string(), |r| r == "PIN set!" || r.starts_with("PIN needed")),
+        ("balance", "BALANCE".to_string(), |r| r.starts_with("Balance")),
+        ("send", format!("SEND 1 TXTC TO {}", phone), |r| {
+            r.starts_with("[SIMULATED]") || r.starts_with("PIN needed")
+        }),
+        ("unlock", "UNLOCK 1234".to_string(), |r| {
+            r.starts_with("[SIMULATED]") || r.starts_with("Unlocked") || r.starts_with("PIN correct")
+        }),
+    ];
+
+    let mut steps = Vec::new();
+    for (step, command, check) in script {
+        let response = processor.process(phone, &command).await;
+        let passed = check(&response);
+        steps.push(SelfTestStep { step, command, passed, response });
+        if !passed {
+            break;
+        }
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_pool, UserRepository};
+    use crate::wallet::create_shared_provider;
+    use axum::routing::get;
+
+    #[tokio::test]
+    async fn test_selftest_all_green() {
+        let Some(pool) = test_pool().await else { return };
+
+        let backend = Router::new().route(
+            "/api/balance/:address",
+            get(|| async {
+                Json(serde_json::json!({
+                    "success": true,
+                    "balances": { "txtc": "5", "eth": "0.01" }
+                }))
+            }),
+        );
+        let backend_url = crate::test_support::spawn_mock_backend(backend).await;
+
+        let processor = CommandProcessor::with_repos(
+            Some(UserRepository::new(pool)),
+            None,
+            None,
+            None,
+            create_shared_provider(),
+        )
+        .with_backend_url(backend_url);
+
+        let phone = crate::test_support::random_phone();
+        let steps = run_steps(&processor, &phone).await;
+
+        assert_eq!(steps.len(), 5);
+        for step in &steps {
+            assert!(step.passed, "{} failed: {}", step.step, step.response);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_selftest_stops_on_failure() {
+        // Without a database JOIN fails and nothing else runs
+        let processor = CommandProcessor::new(None, create_shared_provider());
+        let steps = run_steps(&processor, DEFAULT_SELFTEST_PHONE).await;
+
+        assert_eq!(steps.len(), 1);
+        assert!(!steps[0].passed);
+    }
+}
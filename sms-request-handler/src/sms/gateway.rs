@@ -0,0 +1,149 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use serde_json::Value;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+use crate::commands::CommandProcessor;
+use crate::config::InboundGatewayConfig;
+
+/// Generic gateway routes state
+#[derive(Clone)]
+pub struct GatewayState {
+    pub command_processor: Arc<CommandProcessor>,
+    pub config: Arc<InboundGatewayConfig>,
+}
+
+/// Routes for SMPP and other gateways that POST their own JSON shape.
+/// The reply is returned in the response for the gateway to send back.
+/// Without an INBOUND_TOKEN nothing is served: anyone could post as any phone.
+pub fn gateway_routes(command_processor: Arc<CommandProcessor>, config: InboundGatewayConfig) -> Router {
+    if config.token.is_empty() {
+        tracing::warn!("INBOUND_TOKEN not set - /inbound is disabled");
+        return Router::new();
+    }
+    let state = GatewayState { command_processor, config: Arc::new(config) };
+
+    Router::new()
+        .route("/inbound", post(generic_inbound_handler))
+        .with_state(state)
+}
+
+/// Handler for incoming SMS in a gateway's own JSON, located through the configured field mapping
+async fn generic_inbound_handler(
+    State(state): State<GatewayState>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> (StatusCode, Json<Value>) {
+    let config = &state.config;
+    if !has_inbound_token(&headers, &config.token) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({ "error": "invalid token" })));
+    }
+
+    let (Some(from), Some(text)) = (field(&payload, &config.from_field), field(&payload, &config.text_field)) else {
+        tracing::warn!(
+            from_field = %config.from_field,
+            text_field = %config.text_field,
+            "Inbound payload missing mapped fields"
+        );
+        let error = format!("expected fields '{}' and '{}'", config.from_field, config.text_field);
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": error })));
+    };
+
+    tracing::info!(from = %from, body = %text, "Received SMS (generic gateway)");
+    let response_text = state.command_processor.process(&from, &text).await;
+
+    let mut reply = serde_json::Map::new();
+    reply.insert(config.reply_field.clone(), Value::String(response_text));
+    (StatusCode::OK, Json(Value::Object(reply)))
+}
+
+/// Whether the request carries `token` in X-Inbound-Token (compared in constant time)
+pub fn has_inbound_token(headers: &HeaderMap, token: &str) -> bool {
+    let given = headers.get("x-inbound-token").map(|v| v.as_bytes()).unwrap_or_default();
+    bool::from(given.ct_eq(token.as_bytes()))
+}
+
+/// String at a dotted `path` ("message.sender"); numbers are accepted for phone fields
+fn field(payload: &Value, path: &str) -> Option<String> {
+    let value = path.split('.').try_fold(payload, |value, key| value.get(key))?;
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::create_shared_provider;
+
+    #[tokio::test]
+    async fn test_generic_payload_gets_reply() {
+        let config = InboundGatewayConfig {
+            from_field: "message.sender".to_string(),
+            text_field: "message.body".to_string(),
+            reply_field: "answer".to_string(),
+            token: "gw-secret".to_string(),
+        };
+        let processor = Arc::new(CommandProcessor::new(None, create_shared_provider()));
+        let url = crate::test_support::spawn_mock_backend(gateway_routes(processor, config)).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("{}/inbound", url))
+            .header("X-Inbound-Token", "gw-secret")
+            .json(&serde_json::json!({ "message": { "sender": 15550000001u64, "body": "MENU" } }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert!(body["answer"].as_str().unwrap().contains("BALANCE"), "{}", body);
+
+        let missing = client
+            .post(format!("{}/inbound", url))
+            .header("X-Inbound-Token", "gw-secret")
+            .json(&serde_json::json!({ "from": "+15550000001", "text": "MENU" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), 400);
+
+        let unauthorized = client
+            .post(format!("{}/inbound", url))
+            .json(&serde_json::json!({ "message": { "sender": "+15550000001", "body": "MENU" } }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unauthorized.status(), 401);
+
+        let wrong = client
+            .post(format!("{}/inbound", url))
+            .header("X-Inbound-Token", "gw-secreT")
+            .json(&serde_json::json!({ "message": { "sender": "+15550000001", "body": "MENU" } }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(wrong.status(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_no_token_serves_nothing() {
+        let processor = Arc::new(CommandProcessor::new(None, create_shared_provider()));
+        let url = crate::test_support::spawn_mock_backend(gateway_routes(processor, InboundGatewayConfig::default())).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/inbound", url))
+            .json(&serde_json::json!({ "from": "+15550000001", "text": "MENU" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -0,0 +1,175 @@
+use std::str::FromStr;
+
+/// What happens to an outbound SMS longer than the limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Cut it short, ending with a link to the full details if one is set
+    Truncate,
+    /// Send all of it, split over several numbered messages
+    Segment,
+    /// Keep the lines that fit and say how many were left out
+    Summarize,
+}
+
+impl FromStr for Overflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "truncate" | "truncate-with-link" => Ok(Self::Truncate),
+            "segment" => Ok(Self::Segment),
+            "summarize" => Ok(Self::Summarize),
+            other => Err(format!("unknown SMS overflow policy: {}", other)),
+        }
+    }
+}
+
+/// Bound on the characters sent for one message
+#[derive(Debug, Clone)]
+pub struct LengthLimit {
+    /// Most characters per message (0 = unlimited)
+    pub max_chars: usize,
+    pub overflow: Overflow,
+    /// Where truncated messages point for the rest (empty = no link)
+    pub link: String,
+}
+
+impl Default for LengthLimit {
+    fn default() -> Self {
+        Self { max_chars: 0, overflow: Overflow::Truncate, link: String::new() }
+    }
+}
+
+impl LengthLimit {
+    /// The SMS to send for `body`: just `body` when it fits, several parts only when segmenting
+    pub fn apply(&self, body: &str) -> Vec<String> {
+        if self.max_chars == 0 || char_len(body) <= self.max_chars {
+            return vec![body.to_string()];
+        }
+        match self.overflow {
+            Overflow::Truncate => vec![self.truncate(body)],
+            Overflow::Segment => segment(body, self.max_chars),
+            Overflow::Summarize => vec![self.summarize(body)],
+        }
+    }
+
+    fn truncate(&self, body: &str) -> String {
+        let suffix = if self.link.is_empty() {
+            "…".to_string()
+        } else {
+            format!("…\nMore: {}", self.link)
+        };
+        match self.max_chars.checked_sub(char_len(&suffix)) {
+            Some(room) if room > 0 => format!("{}{}", take(body, room).trim_end(), suffix),
+            _ => take(body, self.max_chars),
+        }
+    }
+
+    /// Whole leading lines plus a "(+N more lines)" footer, or a truncation
+    /// when not even the first line fits
+    fn summarize(&self, body: &str) -> String {
+        let lines: Vec<&str> = body.lines().collect();
+        for kept in (1..lines.len()).rev() {
+            let omitted = lines[kept..].iter().filter(|l| !l.trim().is_empty()).count();
+            let text = lines[..kept].join("\n");
+            let text = text.trim_end();
+            let footer = format!("\n(+{} more line{})", omitted, if omitted == 1 { "" } else { "s" });
+            if char_len(text) + char_len(&footer) <= self.max_chars {
+                return format!("{}{}", text, footer);
+            }
+        }
+        self.truncate(body)
+    }
+}
+
+/// Numbered parts of at most `max_chars`, broken at line ends or spaces where possible
+fn segment(body: &str, max_chars: usize) -> Vec<String> {
+    // Room for a "(12/12) " label
+    let room = max_chars.saturating_sub(8).max(1);
+    let mut parts = Vec::new();
+    let mut rest = body.trim();
+    while !rest.is_empty() {
+        if char_len(rest) <= room {
+            parts.push(rest.to_string());
+            break;
+        }
+        let cut = rest.char_indices().nth(room).map_or(rest.len(), |(i, _)| i);
+        let head = &rest[..cut];
+        let at = head
+            .rfind('\n')
+            .or_else(|| head.rfind(' '))
+            .filter(|&i| i > 0)
+            .unwrap_or(cut);
+        parts.push(rest[..at].trim_end().to_string());
+        rest = rest[at..].trim_start();
+    }
+
+    let total = parts.len();
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| format!("({}/{}) {}", i + 1, total, part))
+        .collect()
+}
+
+fn char_len(s: &str) -> usize {
+    s.chars().count()
+}
+
+fn take(s: &str, n: usize) -> String {
+    s.chars().take(n).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONG: &str = "Recent transfers:\n1. Sent 5 TXTC to alice\n2. Got 10 TXTC from bob\n3. Sent 2 ETH to carol\n4. Got 1 TXTC from dave";
+
+    fn limit(max_chars: usize, overflow: Overflow, link: &str) -> LengthLimit {
+        LengthLimit { max_chars, overflow, link: link.to_string() }
+    }
+
+    #[test]
+    fn test_short_messages_and_no_limit_pass_through() {
+        assert_eq!(limit(200, Overflow::Segment, "").apply(LONG), [LONG]);
+        assert_eq!(limit(0, Overflow::Truncate, "").apply(LONG), [LONG]);
+        assert_eq!("segment".parse::<Overflow>(), Ok(Overflow::Segment));
+        assert_eq!("Truncate-With-Link".parse::<Overflow>(), Ok(Overflow::Truncate));
+        assert!("drop".parse::<Overflow>().is_err());
+    }
+
+    #[test]
+    fn test_truncate_ends_with_link() {
+        let out = limit(60, Overflow::Truncate, "https://ttc.example/h").apply(LONG);
+        assert_eq!(out, ["Recent transfers:\n1. Sent 5 TXT…\nMore: https://ttc.example/h"]);
+        assert!(char_len(&out[0]) <= 60);
+
+        let out = limit(30, Overflow::Truncate, "").apply(LONG);
+        assert_eq!(out, ["Recent transfers:\n1. Sent 5 T…"]);
+    }
+
+    #[test]
+    fn test_segment_sends_everything_in_numbered_parts() {
+        let out = limit(56, Overflow::Segment, "").apply(LONG);
+        assert_eq!(
+            out,
+            [
+                "(1/3) Recent transfers:\n1. Sent 5 TXTC to alice",
+                "(2/3) 2. Got 10 TXTC from bob\n3. Sent 2 ETH to carol",
+                "(3/3) 4. Got 1 TXTC from dave",
+            ]
+        );
+        assert!(out.iter().all(|part| char_len(part) <= 56));
+    }
+
+    #[test]
+    fn test_summarize_keeps_whole_lines() {
+        let out = limit(70, Overflow::Summarize, "").apply(LONG);
+        assert_eq!(out, ["Recent transfers:\n1. Sent 5 TXTC to alice\n(+3 more lines)"]);
+
+        // Not even the first line fits: fall back to truncating
+        let out = limit(10, Overflow::Summarize, "").apply(LONG);
+        assert_eq!(out, ["Recent tr…"]);
+    }
+}
@@ -1,5 +1,11 @@
+pub mod gateway;
+pub mod length;
+pub mod queue;
 pub mod twilio;
 pub mod webhook;
 
-pub use twilio::TwilioClient;
+pub use gateway::{gateway_routes, has_inbound_token};
+pub use length::{LengthLimit, Overflow};
+pub use queue::{Priority, SmsQueue};
+pub use twilio::{validate_twilio_signature, TwilioClient};
 pub use webhook::{incoming_sms_handler, incoming_sms_json_handler};
@@ -0,0 +1,214 @@
+use async_trait::async_trait;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use super::{LengthLimit, TwilioClient};
+
+/// How urgently an outbound SMS should go out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Welcomes and other messages nobody is waiting on
+    Low,
+    /// Alerts and completion notices
+    Normal,
+    /// Direct replies to a command the user just sent
+    High,
+}
+
+/// Anything that can deliver an SMS. Pluggable so tests can record sends.
+#[async_trait]
+pub trait SmsSender: Send + Sync {
+    async fn send(&self, to: &str, body: &str) -> Result<(), String>;
+}
+
+#[async_trait]
+impl SmsSender for TwilioClient {
+    async fn send(&self, to: &str, body: &str) -> Result<(), String> {
+        let result = self.send_sms(to, body).await.map_err(|e| e.to_string())?;
+        tracing::info!(message_sid = %result.message_sid, status = %result.status, "SMS sent");
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Queued {
+    priority: Priority,
+    seq: u64,
+    to: String,
+    body: String,
+}
+
+impl Ord for Queued {
+    /// Highest priority first, then oldest first
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    heap: BinaryHeap<Queued>,
+    next_seq: u64,
+}
+
+/// Outbound SMS queue feeding the provider at a fixed rate, most urgent
+/// first. Bounded: when full, a new message displaces the least urgent
+/// queued one, or is refused if nothing queued is less urgent. Messages
+/// over the length limit are cut down or split before queueing.
+/// Clones share the same queue.
+#[derive(Clone)]
+pub struct SmsQueue {
+    state: Arc<Mutex<QueueState>>,
+    ready: Arc<Notify>,
+    capacity: usize,
+    limit: LengthLimit,
+}
+
+impl SmsQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(QueueState::default())),
+            ready: Arc::new(Notify::new()),
+            capacity: capacity.max(1),
+            limit: LengthLimit::default(),
+        }
+    }
+
+    pub fn with_length_limit(mut self, limit: LengthLimit) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Queue a message (several, if segmented). False if the queue is full
+    /// of equally or more urgent ones.
+    pub fn enqueue(&self, to: &str, body: &str, priority: Priority) -> bool {
+        let mut state = self.state.lock().unwrap();
+        for part in self.limit.apply(body) {
+            if state.heap.len() >= self.capacity && !Self::evict_below(&mut state.heap, priority) {
+                tracing::warn!(to = %to, ?priority, "SMS queue full, message dropped");
+                return false;
+            }
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.heap.push(Queued { priority, seq, to: to.to_string(), body: part });
+        }
+        drop(state);
+        self.ready.notify_one();
+        true
+    }
+
+    /// Drop the least urgent (newest among equals) message if it is below `priority`
+    fn evict_below(heap: &mut BinaryHeap<Queued>, priority: Priority) -> bool {
+        let mut items = std::mem::take(heap).into_vec();
+        let evicted = match items.iter().enumerate().min_by(|a, b| a.1.cmp(b.1)) {
+            Some((i, lowest)) if lowest.priority < priority => {
+                let dropped = items.swap_remove(i);
+                tracing::warn!(to = %dropped.to, priority = ?dropped.priority, "SMS queue full, evicted queued message");
+                true
+            }
+            _ => false,
+        };
+        *heap = items.into();
+        evicted
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().heap.len()
+    }
+
+    fn pop(&self) -> Option<Queued> {
+        self.state.lock().unwrap().heap.pop()
+    }
+
+    /// Send queued messages through `sender`, at most `rate_per_sec` per second
+    pub fn spawn_dispatcher(&self, sender: Arc<dyn SmsSender>, rate_per_sec: f64) -> tokio::task::JoinHandle<()> {
+        let queue = self.clone();
+        let gap = Duration::from_secs_f64(1.0 / rate_per_sec.max(0.01));
+        tokio::spawn(async move {
+            loop {
+                let Some(message) = queue.pop() else {
+                    queue.ready.notified().await;
+                    continue;
+                };
+                if let Err(e) = sender.send(&message.to, &message.body).await {
+                    tracing::error!(to = %message.to, error = %e, "Failed to send queued SMS");
+                }
+                tokio::time::sleep(gap).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingSender {
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl SmsSender for RecordingSender {
+        async fn send(&self, _to: &str, body: &str) -> Result<(), String> {
+            self.sent.lock().unwrap().push(body.to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_dispatched_before_queued_low() {
+        let queue = SmsQueue::new(10);
+        let sender = Arc::new(RecordingSender::default());
+
+        assert!(queue.enqueue("+1", "welcome 1", Priority::Low));
+        assert!(queue.enqueue("+1", "welcome 2", Priority::Low));
+        assert!(queue.enqueue("+2", "deposit", Priority::Normal));
+        assert!(queue.enqueue("+3", "balance", Priority::High));
+
+        queue.spawn_dispatcher(sender.clone(), 1000.0);
+        while sender.sent.lock().unwrap().len() < 4 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(*sender.sent.lock().unwrap(), ["balance", "deposit", "welcome 1", "welcome 2"]);
+    }
+
+    #[test]
+    fn test_long_message_segmented_in_order() {
+        let queue = SmsQueue::new(10).with_length_limit(LengthLimit {
+            max_chars: 20,
+            overflow: super::super::Overflow::Segment,
+            link: String::new(),
+        });
+        assert!(queue.enqueue("+1", "first line\nsecond line", Priority::High));
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop().unwrap().body, "(1/2) first line");
+        assert_eq!(queue.pop().unwrap().body, "(2/2) second line");
+    }
+
+    #[test]
+    fn test_full_queue_evicts_less_urgent() {
+        let queue = SmsQueue::new(2);
+        assert!(queue.enqueue("+1", "a", Priority::Low));
+        assert!(queue.enqueue("+1", "b", Priority::High));
+        assert!(!queue.enqueue("+1", "c", Priority::Low));
+        assert!(queue.enqueue("+1", "d", Priority::Normal));
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop().unwrap().body, "b");
+        assert_eq!(queue.pop().unwrap().body, "d");
+    }
+}
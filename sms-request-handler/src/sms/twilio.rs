@@ -4,10 +4,14 @@ use reqwest::Client;
 use sha1::Sha1;
 use std::collections::HashMap;
 
+use super::{LengthLimit, Overflow};
 use crate::config::TwilioConfig;
 
 type HmacSha1 = Hmac<Sha1>;
 
+/// Longest body Twilio accepts for one message
+pub const TWILIO_MAX_BODY: usize = 1600;
+
 /// Twilio client for sending and validating SMS messages
 #[derive(Debug, Clone)]
 pub struct TwilioClient {
@@ -15,6 +19,8 @@ pub struct TwilioClient {
     account_sid: String,
     auth_token: String,
     phone_number: String,
+    /// Twilio REST API root (overridden in tests)
+    api_base: String,
 }
 
 /// Result of sending an SMS
@@ -42,13 +48,34 @@ impl TwilioClient {
             account_sid: config.account_sid.clone(),
             auth_token: config.auth_token.clone(),
             phone_number: config.phone_number.clone(),
+            api_base: "https://api.twilio.com".to_string(),
         }
     }
 
-    /// Send an SMS message
+    /// Point the client at another API root (a mock server in tests)
+    #[cfg(test)]
+    pub(crate) fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        self.api_base = api_base.into();
+        self
+    }
+
+    /// Send an SMS message. Bodies over Twilio's 1600-character limit go out
+    /// as numbered parts; the result is the first part's.
     pub async fn send_sms(&self, to: &str, body: &str) -> Result<SendResult, TwilioError> {
+        let limit = LengthLimit { max_chars: TWILIO_MAX_BODY, overflow: Overflow::Segment, link: String::new() };
+        let mut first = None;
+        for part in limit.apply(body) {
+            let result = self.send_message(to, &part).await?;
+            first.get_or_insert(result);
+        }
+        Ok(first.expect("a body always yields at least one part"))
+    }
+
+    /// POST one message to Twilio's Messages resource
+    async fn send_message(&self, to: &str, body: &str) -> Result<SendResult, TwilioError> {
         let url = format!(
-            "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
+            "{}/2010-04-01/Accounts/{}/Messages.json",
+            self.api_base.trim_end_matches('/'),
             self.account_sid
         );
 
@@ -87,28 +114,7 @@ impl TwilioClient {
         url: &str,
         params: &HashMap<String, String>,
     ) -> bool {
-        // Build the string to sign: URL + sorted params
-        let mut data = url.to_string();
-        
-        let mut sorted_params: Vec<_> = params.iter().collect();
-        sorted_params.sort_by(|a, b| a.0.cmp(b.0));
-        
-        for (key, value) in sorted_params {
-            data.push_str(key);
-            data.push_str(value);
-        }
-
-        // Calculate HMAC-SHA1
-        let mut mac = HmacSha1::new_from_slice(self.auth_token.as_bytes())
-            .expect("HMAC can take key of any size");
-        mac.update(data.as_bytes());
-        let result = mac.finalize();
-        
-        // Base64 encode
-        let calculated = base64::engine::general_purpose::STANDARD.encode(result.into_bytes());
-        
-        // Compare
-        calculated == signature
+        validate_twilio_signature(&self.auth_token, url, params, signature)
     }
 
     /// Get the Twilio phone number
@@ -117,10 +123,128 @@ impl TwilioClient {
     }
 }
 
+/// HMAC-SHA1 of the URL Twilio posted to followed by every form param as
+/// `key + value` in key order, base64 encoded (Twilio's webhook signature)
+fn twilio_mac(auth_token: &str, url: &str, params: &HashMap<String, String>) -> HmacSha1 {
+    let mut sorted_params: Vec<_> = params.iter().collect();
+    sorted_params.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut mac = HmacSha1::new_from_slice(auth_token.as_bytes())
+        .expect("HMAC can take key of any size");
+    mac.update(url.as_bytes());
+    for (key, value) in sorted_params {
+        mac.update(key.as_bytes());
+        mac.update(value.as_bytes());
+    }
+    mac
+}
+
+/// X-Twilio-Signature Twilio would send for this request
+pub fn twilio_signature(auth_token: &str, url: &str, params: &HashMap<String, String>) -> String {
+    let digest = twilio_mac(auth_token, url, params).finalize().into_bytes();
+    base64::engine::general_purpose::STANDARD.encode(digest)
+}
+
+/// Whether `header_sig` is Twilio's signature of this request under
+/// `auth_token` (compared in constant time)
+pub fn validate_twilio_signature(
+    auth_token: &str,
+    url: &str,
+    params: &HashMap<String, String>,
+    header_sig: &str,
+) -> bool {
+    let Ok(signature) = base64::engine::general_purpose::STANDARD.decode(header_sig.trim()) else {
+        return false;
+    };
+    twilio_mac(auth_token, url, params).verify_slice(&signature).is_ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_send_sms_posts_form_with_basic_auth() {
+        use axum::{extract::Path, http::HeaderMap, routing::post, Form, Json, Router};
+        use std::sync::{Arc, Mutex};
+
+        /// Account SID in the path, Authorization header, form fields
+        type Posted = (String, String, HashMap<String, String>);
+        let seen: Arc<Mutex<Vec<Posted>>> = Arc::default();
+        let recorded = seen.clone();
+        let api = Router::new().route(
+            "/2010-04-01/Accounts/:sid/Messages.json",
+            post(move |Path(sid): Path<String>, headers: HeaderMap, Form(form): Form<HashMap<String, String>>| async move {
+                let auth = headers["authorization"].to_str().unwrap().to_string();
+                let mut seen = recorded.lock().unwrap();
+                seen.push((sid, auth, form));
+                Json(serde_json::json!({ "sid": format!("SM{}", seen.len()), "status": "queued" }))
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(api).await;
+        let config = TwilioConfig {
+            account_sid: "AC123".to_string(),
+            auth_token: "secret".to_string(),
+            phone_number: "+18005550100".to_string(),
+        };
+        let client = TwilioClient::new(&config).with_api_base(url);
+
+        let result = client.send_sms("+15550000001", "Sent 5 TXTC to alice").await.unwrap();
+        assert_eq!(result.message_sid, "SM1");
+        assert_eq!(result.status, "queued");
+        {
+            let seen = seen.lock().unwrap();
+            let (sid, auth, form) = &seen[0];
+            assert_eq!(sid, "AC123");
+            let expected = base64::engine::general_purpose::STANDARD.encode("AC123:secret");
+            assert_eq!(auth, &format!("Basic {}", expected));
+            assert_eq!(form["To"], "+15550000001");
+            assert_eq!(form["From"], "+18005550100");
+            assert_eq!(form["Body"], "Sent 5 TXTC to alice");
+        }
+
+        // Over Twilio's limit: numbered parts, each within it
+        let long = "word ".repeat(500);
+        let result = client.send_sms("+15550000001", &long).await.unwrap();
+        assert_eq!(result.message_sid, "SM2");
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 3);
+        assert!(seen[1].2["Body"].starts_with("(1/2) "));
+        assert!(seen[2].2["Body"].starts_with("(2/2) "));
+        assert!(seen[1..].iter().all(|(_, _, form)| form["Body"].chars().count() <= TWILIO_MAX_BODY));
+    }
+
+    /// Example request from Twilio's webhook security docs
+    fn documented_request() -> (&'static str, HashMap<String, String>) {
+        let params = [
+            ("CallSid", "CA1234567890ABCDE"),
+            ("Caller", "+12349013030"),
+            ("Digits", "1234"),
+            ("From", "+12349013030"),
+            ("To", "+18005551212"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        ("https://mycompany.com/myapp.php?foo=1&bar=2", params)
+    }
+
+    #[test]
+    fn test_documented_signature_vector() {
+        let (url, params) = documented_request();
+        assert_eq!(twilio_signature("12345", url, &params), "0/KCTR6DLpKmkAf8muzZqo1nDgQ=");
+        assert!(validate_twilio_signature("12345", url, &params, "0/KCTR6DLpKmkAf8muzZqo1nDgQ="));
+
+        // Wrong token, tampered param, other URL, garbage header
+        assert!(!validate_twilio_signature("54321", url, &params, "0/KCTR6DLpKmkAf8muzZqo1nDgQ="));
+        let mut tampered = params.clone();
+        tampered.insert("Digits".to_string(), "9999".to_string());
+        assert!(!validate_twilio_signature("12345", url, &tampered, "0/KCTR6DLpKmkAf8muzZqo1nDgQ="));
+        assert!(!validate_twilio_signature("12345", "https://mycompany.com/myapp.php", &params, "0/KCTR6DLpKmkAf8muzZqo1nDgQ="));
+        assert!(!validate_twilio_signature("12345", url, &params, "not base64!"));
+        assert!(!validate_twilio_signature("12345", url, &params, ""));
+    }
+
     #[test]
     fn test_signature_validation() {
         let config = TwilioConfig {
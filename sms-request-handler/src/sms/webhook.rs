@@ -7,8 +7,9 @@ use axum::{
 use serde::Deserialize;
 use std::sync::Arc;
 
+use crate::commands::parser::Command;
 use crate::commands::CommandProcessor;
-use crate::sms::TwilioClient;
+use crate::sms::{Priority, SmsQueue};
 
 /// Incoming SMS webhook payload from Twilio
 #[derive(Debug, Deserialize)]
@@ -32,10 +33,19 @@ pub struct IncomingSms {
 /// Application state shared across handlers
 #[derive(Clone)]
 pub struct AppState {
-    pub twilio: Arc<TwilioClient>,
+    pub sms_queue: SmsQueue,
     pub command_processor: Arc<CommandProcessor>,
 }
 
+/// Queue priority of the reply to `command`: welcomes can wait behind
+/// replies users are actively waiting on (BALANCE, SEND, ...)
+pub fn reply_priority(command: &Command) -> Priority {
+    match command {
+        Command::Join { ens_name: None } | Command::Start | Command::Help => Priority::Low,
+        _ => Priority::High,
+    }
+}
+
 /// TwiML response for Twilio
 struct TwimlResponse(String);
 
@@ -67,7 +77,7 @@ impl IntoResponse for JsonResponse {
 /// Handler for incoming SMS messages from Twilio (Form-encoded)
 ///
 /// Responds immediately with empty TwiML to avoid Twilio's 15s timeout,
-/// then processes the command and sends the reply via Twilio REST API.
+/// then processes the command and queues the reply for the Twilio REST API.
 pub async fn incoming_sms_handler(
     State(state): State<AppState>,
     Form(sms): Form<IncomingSms>,
@@ -81,34 +91,23 @@ pub async fn incoming_sms_handler(
     let from = sms.from.clone();
     let body = sms.body.clone();
     let processor = state.command_processor.clone();
-    let twilio = state.twilio.clone();
+    let sms_queue = state.sms_queue.clone();
 
-    // Process command in background and send reply via Twilio API
+    // Process command in background and queue the reply for Twilio
     tokio::spawn(async move {
         let response_text = processor.process(&from, &body).await;
+        if response_text.is_empty() {
+            return;
+        }
 
+        let priority = reply_priority(&processor.parse(&body));
         tracing::info!(
             to = %from,
             response = %response_text,
-            "Sending SMS response via Twilio API"
+            ?priority,
+            "Queueing SMS response"
         );
-
-        match twilio.send_sms(&from, &response_text).await {
-            Ok(result) => {
-                tracing::info!(
-                    message_sid = %result.message_sid,
-                    status = %result.status,
-                    "SMS reply sent successfully"
-                );
-            }
-            Err(e) => {
-                tracing::error!(
-                    to = %from,
-                    error = %e,
-                    "Failed to send SMS reply"
-                );
-            }
-        }
+        sms_queue.enqueue(&from, &response_text, priority);
     });
 
     // Respond immediately with empty TwiML so Twilio doesn't timeout
@@ -164,6 +163,13 @@ fn escape_xml(s: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_reply_priority() {
+        assert_eq!(reply_priority(&Command::Balance), Priority::High);
+        assert_eq!(reply_priority(&Command::Join { ens_name: None }), Priority::Low);
+        assert_eq!(reply_priority(&Command::Join { ens_name: Some("ALICE".to_string()) }), Priority::High);
+    }
+
     #[test]
     fn test_escape_xml() {
         assert_eq!(escape_xml("Hello & Goodbye"), "Hello &amp; Goodbye");
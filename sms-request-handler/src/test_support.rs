@@ -0,0 +1,55 @@
+//! Helpers shared by unit tests
+
+use axum::Router;
+use sqlx::PgPool;
+
+use crate::commands::CommandProcessor;
+use crate::db::{
+    test_pool, AddressBookRepository, AuditRepository, ChannelRepository, DepositRepository, EscrowRepository, InboundTransferRepository,
+    PayeeTagRepository, PendingTxRepository, ReceiptRepository, TransactionRepository, TransferRepository, UserRepository, VoucherRepository,
+};
+use crate::wallet::create_shared_provider;
+
+/// A phone number unlikely to be taken by another test, so DB-backed
+/// tests sharing one database don't see each other's users
+pub fn random_phone() -> String {
+    format!("+1999{}", rand::random::<u32>() % 10_000_000)
+}
+
+/// Serve `router` on an ephemeral local port and return its base URL.
+/// Used to stand in for the backend/arc services in command tests.
+pub async fn spawn_mock_backend(router: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+/// Command processor wired to every repository on the test database and
+/// pointed at `backend_url` (for the backend, Arc and Yellow services).
+/// None when `TEST_DATABASE_URL` isn't set.
+pub async fn db_processor(backend_url: &str) -> Option<(CommandProcessor, PgPool)> {
+    let pool = test_pool().await?;
+    let processor = CommandProcessor::with_repos(
+        Some(UserRepository::new(pool.clone())),
+        Some(VoucherRepository::new(pool.clone())),
+        Some(DepositRepository::new(pool.clone())),
+        Some(AddressBookRepository::new(pool.clone())),
+        create_shared_provider(),
+    )
+    .with_inbound_repo(InboundTransferRepository::new(pool.clone()))
+    .with_escrow_repo(EscrowRepository::new(pool.clone()))
+    .with_audit_repo(AuditRepository::new(pool.clone()))
+    .with_transfer_repo(TransferRepository::new(pool.clone()))
+    .with_receipt_repo(ReceiptRepository::new(pool.clone()))
+    .with_channel_repo(ChannelRepository::new(pool.clone()))
+    .with_tag_repo(PayeeTagRepository::new(pool.clone()))
+    .with_transaction_repo(TransactionRepository::new(pool.clone()))
+    .with_pending_tx_repo(PendingTxRepository::new(pool.clone()))
+    .with_backend_url(backend_url)
+    .with_arc_url(backend_url)
+    .with_yellow_url(backend_url);
+    Some((processor, pool))
+}
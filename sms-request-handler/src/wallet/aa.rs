@@ -2,7 +2,60 @@ use ethers::prelude::*;
 use ethers::types::{Address, Bytes, U256};
 use serde::{Deserialize, Serialize};
 
-/// ERC-4337 UserOperation (v0.6.0 compatible for broadest support)
+/// EntryPoint release a bundler runs; the two hash and serialize UserOps differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryPointVersion {
+    V06,
+    V07,
+}
+
+impl EntryPointVersion {
+    /// Parse "0.6" / "v0.7" style version strings
+    pub fn parse(version: &str) -> Option<Self> {
+        match version.trim().trim_start_matches(['v', 'V']) {
+            "0.6" | "0.6.0" => Some(Self::V06),
+            "0.7" | "0.7.0" => Some(Self::V07),
+            _ => None,
+        }
+    }
+
+    /// Canonical deployment address of this release
+    pub fn default_address(&self) -> Address {
+        let address = match self {
+            Self::V06 => "0x5FF137D4b0FDCD49DcA30c7CF57E578a026d2789",
+            Self::V07 => "0x0000000071727De22E5E9d8BAB0edAC6f36da032",
+        };
+        address.parse().expect("valid entry point address")
+    }
+}
+
+/// EntryPoint a bundler submits to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryPointConfig {
+    pub address: Address,
+    pub version: EntryPointVersion,
+}
+
+impl EntryPointConfig {
+    /// From config strings. An empty address means the version's canonical
+    /// deployment; an empty version means v0.6.
+    pub fn parse(address: &str, version: &str) -> Option<Self> {
+        let version = if version.trim().is_empty() {
+            EntryPointVersion::V06
+        } else {
+            EntryPointVersion::parse(version)?
+        };
+        let address = if address.trim().is_empty() {
+            version.default_address()
+        } else {
+            address.trim().parse().ok()?
+        };
+        Some(Self { address, version })
+    }
+}
+
+/// ERC-4337 UserOperation in the v0.6 layout. v0.7 EntryPoints get it
+/// repacked at hashing/submission time (see `EntryPointVersion`).
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct UserOperation {
@@ -61,22 +114,148 @@ impl UserOperation {
         ])
     }
 
+    /// The op in v0.7's PackedUserOperation layout: gas limits and fees
+    /// paired into bytes32 words (accountGasLimits, gasFees)
+    pub fn to_packed(&self) -> PackedUserOperation {
+        PackedUserOperation {
+            sender: self.sender,
+            nonce: self.nonce,
+            init_code: self.init_code.clone(),
+            call_data: self.call_data.clone(),
+            account_gas_limits: pack_u128_pair(self.verification_gas_limit, self.call_gas_limit),
+            pre_verification_gas: self.pre_verification_gas,
+            gas_fees: pack_u128_pair(self.max_priority_fee_per_gas, self.max_fee_per_gas),
+            paymaster_and_data: self.paymaster_and_data.clone(),
+            signature: self.signature.clone(),
+        }
+    }
+
     /// Calculate the UserOp hash (requestId) to sign
-    pub fn hash(&self, entry_point_address: Address, chain_id: u64) -> [u8; 32] {
-        let packed = self.pack();
-        let user_op_hash = ethers::utils::keccak256(packed);
-        
-        let enc = ethers::abi::encode(&[
-            ethers::abi::Token::FixedBytes(user_op_hash.to_vec()),
-            ethers::abi::Token::Address(entry_point_address),
-            ethers::abi::Token::Uint(U256::from(chain_id)),
-        ]);
-        
-        ethers::utils::keccak256(enc)
+    pub fn hash(&self, entry_point: &EntryPointConfig, chain_id: u64) -> [u8; 32] {
+        match entry_point.version {
+            EntryPointVersion::V06 => user_op_hash(&self.pack(), entry_point.address, chain_id),
+            EntryPointVersion::V07 => self.to_packed().hash(entry_point.address, chain_id),
+        }
+    }
+
+    /// Take the bundler's gas limits in place of the hand-filled ones
+    pub fn with_gas(self, estimate: GasEstimate) -> Self {
+        Self {
+            call_gas_limit: estimate.call_gas_limit,
+            verification_gas_limit: estimate.verification_gas_limit,
+            pre_verification_gas: estimate.pre_verification_gas,
+            ..self
+        }
+    }
+
+    /// JSON-RPC form for `eth_sendUserOperation`. v0.7 bundlers take the
+    /// unpacked fields: factory/factoryData instead of initCode, etc.
+    pub fn to_rpc(&self, version: EntryPointVersion) -> serde_json::Value {
+        match version {
+            EntryPointVersion::V06 => serde_json::to_value(self).unwrap_or_default(),
+            EntryPointVersion::V07 => {
+                let split = |data: &Bytes| {
+                    if data.len() < 20 {
+                        (serde_json::Value::Null, serde_json::Value::Null)
+                    } else {
+                        (
+                            serde_json::json!(Address::from_slice(&data[..20])),
+                            serde_json::json!(Bytes::from(data[20..].to_vec())),
+                        )
+                    }
+                };
+                let (factory, factory_data) = split(&self.init_code);
+                let (paymaster, paymaster_data) = split(&self.paymaster_and_data);
+                serde_json::json!({
+                    "sender": self.sender,
+                    "nonce": self.nonce,
+                    "factory": factory,
+                    "factoryData": factory_data,
+                    "callData": self.call_data,
+                    "callGasLimit": self.call_gas_limit,
+                    "verificationGasLimit": self.verification_gas_limit,
+                    "preVerificationGas": self.pre_verification_gas,
+                    "maxFeePerGas": self.max_fee_per_gas,
+                    "maxPriorityFeePerGas": self.max_priority_fee_per_gas,
+                    "paymaster": paymaster,
+                    "paymasterData": paymaster_data,
+                    "signature": self.signature,
+                })
+            }
+        }
     }
 }
 
-// Simple Account Factory ABI (createAccount)
+/// ERC-4337 v0.7 PackedUserOperation, as the v0.7 EntryPoint takes it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PackedUserOperation {
+    pub sender: Address,
+    pub nonce: U256,
+    pub init_code: Bytes,
+    pub call_data: Bytes,
+    /// verificationGasLimit in the high 16 bytes, callGasLimit in the low 16
+    pub account_gas_limits: H256,
+    pub pre_verification_gas: U256,
+    /// maxPriorityFeePerGas in the high 16 bytes, maxFeePerGas in the low 16
+    pub gas_fees: H256,
+    pub paymaster_and_data: Bytes,
+    pub signature: Bytes,
+}
+
+impl PackedUserOperation {
+    /// Encoding hashed for the v0.7 userOpHash: dynamic fields by their
+    /// keccak256, the signature left out
+    pub fn pack(&self) -> Vec<u8> {
+        use ethers::abi::Token;
+        ethers::abi::encode(&[
+            Token::Address(self.sender),
+            Token::Uint(self.nonce),
+            Token::FixedBytes(ethers::utils::keccak256(&self.init_code).to_vec()),
+            Token::FixedBytes(ethers::utils::keccak256(&self.call_data).to_vec()),
+            Token::FixedBytes(self.account_gas_limits.as_bytes().to_vec()),
+            Token::Uint(self.pre_verification_gas),
+            Token::FixedBytes(self.gas_fees.as_bytes().to_vec()),
+            Token::FixedBytes(ethers::utils::keccak256(&self.paymaster_and_data).to_vec()),
+        ])
+    }
+
+    /// userOpHash the v0.7 EntryPoint at `entry_point` computes on `chain_id`
+    pub fn hash(&self, entry_point: Address, chain_id: u64) -> [u8; 32] {
+        user_op_hash(&self.pack(), entry_point, chain_id)
+    }
+}
+
+/// keccak256(keccak256(packed op), entry point, chain id), the hash owners sign
+fn user_op_hash(packed: &[u8], entry_point: Address, chain_id: u64) -> [u8; 32] {
+    let enc = ethers::abi::encode(&[
+        ethers::abi::Token::FixedBytes(ethers::utils::keccak256(packed).to_vec()),
+        ethers::abi::Token::Address(entry_point),
+        ethers::abi::Token::Uint(U256::from(chain_id)),
+    ]);
+    ethers::utils::keccak256(enc)
+}
+
+/// Two 128-bit values in one bytes32 word, `high` first
+fn pack_u128_pair(high: U256, low: U256) -> H256 {
+    let mut word = [0u8; 32];
+    ((high << 128) | low).to_big_endian(&mut word);
+    H256(word)
+}
+
+/// Gas limits `eth_estimateUserOperationGas` suggests for an op
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasEstimate {
+    pub call_gas_limit: U256,
+    pub verification_gas_limit: U256,
+    pub pre_verification_gas: U256,
+}
+
+/// Well-formed ECDSA signature that recovers to no one, so an unsigned op
+/// gets through the account's validation while the bundler simulates it
+const DUMMY_SIGNATURE: &str = "0xfffffffffffffffffffffffffffffff000000000000000000000000000000007aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa1c";
+
 // Simple Account Factory ABI (createAccount)
 abigen!(
     SimpleAccountFactory,
@@ -98,16 +277,31 @@ abigen!(
 
 /// Get the nonce for a Smart Account from the EntryPoint
 pub async fn get_account_nonce(
-    entry_point_address: Address,
+    entry_point: &EntryPointConfig,
     sender: Address,
     provider: std::sync::Arc<Provider<Http>>,
 ) -> Result<U256, Box<dyn std::error::Error + Send + Sync>> {
-    let entry_point = EntryPoint::new(entry_point_address, provider);
+    // getNonce is the same in v0.6 and v0.7
+    let entry_point = EntryPoint::new(entry_point.address, provider);
     // Key is usually 0
     let nonce = entry_point.get_nonce(sender, U256::zero()).call().await?;
     Ok(nonce)
 }
 
+/// CREATE2 salt for a user's SimpleAccount, derived from their phone number.
+///
+/// keccak256("textchain:account:" ++ E.164 phone), so the account address is
+/// recoverable from the phone and owner key alone, with no stored state.
+/// Formatting ("+1 555-123-4567") doesn't change the salt; distinct numbers
+/// collide only with 2^-256 odds.
+pub fn account_salt(phone: &str) -> U256 {
+    let normalized: String = phone
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '+')
+        .collect();
+    U256::from_big_endian(&ethers::utils::keccak256(format!("textchain:account:{}", normalized)))
+}
+
 /// Helper to get the deterministic address for a user's Smart Account
 pub async fn get_smart_account_address(
     factory_address: Address,
@@ -123,6 +317,75 @@ pub async fn get_smart_account_address(
     Ok(address)
 }
 
+/// initCode that deploys a SimpleAccount on first use: factory ++ createAccount(owner, salt)
+pub fn account_init_code(factory_address: Address, owner_eoa: Address, salt: U256) -> Bytes {
+    let mut init_code = factory_address.as_bytes().to_vec();
+    init_code.extend(ethers::abi::AbiEncode::encode(CreateAccountCall { owner: owner_eoa, salt }));
+    init_code.into()
+}
+
+/// Deploy the owner's smart account through the bundler if it has no code yet.
+/// Returns the UserOp hash when a deployment was submitted, None if already deployed.
+pub async fn ensure_account_deployed(
+    owner: &super::UserWallet,
+    account: Address,
+    factory_address: Address,
+    salt: U256,
+    entry_point: &EntryPointConfig,
+    bundler: &BundlerClient,
+    provider: std::sync::Arc<Provider<Http>>,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    if !provider.get_code(account, None).await?.is_empty() {
+        return Ok(None);
+    }
+
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let gas_price = provider.get_gas_price().await?;
+
+    // Deployment-only op: no call data, gas limits sized for SimpleAccountFactory
+    let mut user_op = UserOperation {
+        sender: account,
+        nonce: U256::zero(),
+        init_code: account_init_code(factory_address, owner.address, salt),
+        call_data: Bytes::default(),
+        call_gas_limit: U256::from(35_000),
+        verification_gas_limit: U256::from(500_000),
+        pre_verification_gas: U256::from(60_000),
+        max_fee_per_gas: gas_price,
+        max_priority_fee_per_gas: gas_price,
+        paymaster_and_data: Bytes::default(),
+        signature: DUMMY_SIGNATURE.parse()?,
+    };
+    // The fixed limits above stand when the bundler can't estimate
+    match bundler.estimate_user_op_gas(&user_op, entry_point).await {
+        Ok(estimate) => user_op = user_op.with_gas(estimate),
+        Err(e) => tracing::warn!(account = ?account, "Gas estimation failed, using defaults: {}", e),
+    }
+    user_op.signature = owner.sign_user_op(&user_op, entry_point, chain_id)?;
+
+    bundler.send_user_op(user_op, entry_point).await.map(Some)
+}
+
+/// How often `wait_for_user_op` asks the bundler for a receipt
+const USER_OP_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Outcome of a UserOperation once the bundle carrying it is mined
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOpReceipt {
+    /// Whether the op's call succeeded (the bundle can land while the op reverts)
+    pub success: bool,
+    pub actual_gas_used: U256,
+    pub receipt: UserOpTransaction,
+}
+
+/// The bundle transaction a UserOperation was included in
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOpTransaction {
+    pub transaction_hash: H256,
+}
+
 /// Client to interact with an ERC-4337 Bundler
 #[derive(Clone)]
 pub struct BundlerClient {
@@ -142,16 +405,67 @@ impl BundlerClient {
     pub async fn send_user_op(
         &self,
         user_op: UserOperation,
-        entry_point: Address,
+        entry_point: &EntryPointConfig,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let params = serde_json::json!([user_op.to_rpc(entry_point.version), entry_point.address]);
+        let result = self.call("eth_sendUserOperation", params).await?;
+        Ok(result.as_str().unwrap_or("").to_string())
+    }
+
+    /// Gas limits the bundler suggests for `user_op`, simulated against `entry_point`
+    pub async fn estimate_user_op_gas(
+        &self,
+        user_op: &UserOperation,
+        entry_point: &EntryPointConfig,
+    ) -> Result<GasEstimate, Box<dyn std::error::Error + Send + Sync>> {
+        let params = serde_json::json!([user_op.to_rpc(entry_point.version), entry_point.address]);
+        let result = self.call("eth_estimateUserOperationGas", params).await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Receipt of a submitted UserOperation, None while it isn't mined yet
+    pub async fn get_user_op_receipt(
+        &self,
+        user_op_hash: &str,
+    ) -> Result<Option<UserOpReceipt>, Box<dyn std::error::Error + Send + Sync>> {
+        let result = self.call("eth_getUserOperationReceipt", serde_json::json!([user_op_hash])).await?;
+        if result.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_value(result)?))
+    }
+
+    /// Poll for a UserOperation's receipt until it appears or `timeout`
+    /// elapses; None means it still wasn't mined
+    pub async fn wait_for_user_op(
+        &self,
+        user_op_hash: &str,
+        timeout: std::time::Duration,
+    ) -> Result<Option<UserOpReceipt>, Box<dyn std::error::Error + Send + Sync>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if let Some(receipt) = self.get_user_op_receipt(user_op_hash).await? {
+                return Ok(Some(receipt));
+            }
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            tokio::time::sleep(remaining.min(USER_OP_POLL_INTERVAL)).await;
+        }
+    }
+
+    /// JSON-RPC call to the bundler, returning its `result`
+    async fn call(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         let payload = serde_json::json!({
             "jsonrpc": "2.0",
             "id": 1,
-            "method": "eth_sendUserOperation",
-            "params": [
-                user_op,
-                entry_point
-            ]
+            "method": method,
+            "params": params
         });
 
         let response = self.client
@@ -166,11 +480,7 @@ impl BundlerClient {
             return Err(format!("Bundler error: {}", error).into());
         }
 
-        if let Some(result) = body.get("result") {
-            Ok(result.as_str().unwrap_or("").to_string())
-        } else {
-            Err("Empty result from bundler".into())
-        }
+        body.get("result").cloned().ok_or_else(|| "Empty result from bundler".into())
     }
 }
 
@@ -203,4 +513,204 @@ mod tests {
         let hash = ethers::utils::keccak256(packed);
         assert_eq!(hash.len(), 32);
     }
+
+    #[test]
+    fn test_entry_point_changes_op_hash() {
+        let op = UserOperation {
+            sender: Address::from_str("0x1111111111111111111111111111111111111111").unwrap(),
+            nonce: U256::from(1),
+            init_code: Bytes::default(),
+            call_data: Bytes::from(vec![0x56, 0x78]),
+            call_gas_limit: U256::from(100000),
+            verification_gas_limit: U256::from(200000),
+            pre_verification_gas: U256::from(30000),
+            max_fee_per_gas: U256::from(1000000000),
+            max_priority_fee_per_gas: U256::from(100000000),
+            paymaster_and_data: Bytes::default(),
+            signature: Bytes::default(),
+        };
+        let v06 = EntryPointConfig::parse("", "0.6").unwrap();
+        let v07 = EntryPointConfig::parse("", "v0.7").unwrap();
+        assert_eq!(v06.address, EntryPointVersion::V06.default_address());
+        assert_eq!(v07.address, EntryPointVersion::V07.default_address());
+
+        // Same op, different entry point: different hash
+        assert_ne!(op.hash(&v06, 80002), op.hash(&v07, 80002));
+        // Same address, different version: the packing differs too
+        let v07_at_v06 = EntryPointConfig { address: v06.address, version: EntryPointVersion::V07 };
+        assert_ne!(op.hash(&v06, 80002), op.hash(&v07_at_v06, 80002));
+        assert_eq!(op.hash(&v06, 80002), op.hash(&EntryPointConfig::parse("", "").unwrap(), 80002));
+
+        assert!(EntryPointConfig::parse("", "0.8").is_none());
+        assert!(EntryPointConfig::parse("0xnope", "0.6").is_none());
+    }
+
+    #[test]
+    fn test_v07_rpc_splits_init_code() {
+        let factory = Address::from_str("0x9406Cc6185a346906296840746125a0E44976454").unwrap();
+        let owner = Address::from_str("0x1111111111111111111111111111111111111111").unwrap();
+        let op = UserOperation {
+            sender: owner,
+            nonce: U256::zero(),
+            init_code: account_init_code(factory, owner, U256::zero()),
+            call_data: Bytes::default(),
+            call_gas_limit: U256::zero(),
+            verification_gas_limit: U256::zero(),
+            pre_verification_gas: U256::zero(),
+            max_fee_per_gas: U256::zero(),
+            max_priority_fee_per_gas: U256::zero(),
+            paymaster_and_data: Bytes::default(),
+            signature: Bytes::default(),
+        };
+        let rpc = op.to_rpc(EntryPointVersion::V07);
+        assert_eq!(rpc["factory"], serde_json::json!(factory));
+        assert!(rpc["paymaster"].is_null());
+        assert!(rpc.get("initCode").is_none());
+        assert!(op.to_rpc(EntryPointVersion::V06).get("initCode").is_some());
+    }
+
+    #[test]
+    fn test_v07_packs_gas_pairs() {
+        let op = UserOperation {
+            sender: Address::from_str("0x1111111111111111111111111111111111111111").unwrap(),
+            nonce: U256::from(3),
+            init_code: Bytes::default(),
+            call_data: Bytes::from(vec![0x56, 0x78]),
+            call_gas_limit: U256::from(0x88b8),
+            verification_gas_limit: U256::from(0x6c2d8),
+            pre_verification_gas: U256::from(0xb3f4),
+            max_fee_per_gas: U256::from(2_000_000_000u64),
+            max_priority_fee_per_gas: U256::from(1_000_000_000u64),
+            paymaster_and_data: Bytes::default(),
+            signature: Bytes::from(vec![0xaa]),
+        };
+        let packed = op.to_packed();
+        assert_eq!(
+            packed.account_gas_limits,
+            H256::from_str("0x0000000000000000000000000006c2d8000000000000000000000000000088b8").unwrap()
+        );
+        assert_eq!(
+            packed.gas_fees,
+            H256::from_str("0x0000000000000000000000003b9aca0000000000000000000000000077359400").unwrap()
+        );
+        assert_eq!(packed.pre_verification_gas, op.pre_verification_gas);
+        assert_eq!(packed.signature, op.signature);
+
+        // Both halves use their full 128 bits without spilling into each other
+        let max = U256::from(u128::MAX);
+        let full = pack_u128_pair(max, U256::zero());
+        assert_eq!(&full.as_bytes()[..16], &[0xff; 16]);
+        assert_eq!(&full.as_bytes()[16..], &[0; 16]);
+
+        let v07 = EntryPointConfig::parse("", "0.7").unwrap();
+        assert_eq!(packed.hash(v07.address, 80002), op.hash(&v07, 80002));
+    }
+
+    #[test]
+    fn test_account_salt_is_deterministic_per_phone() {
+        let salt = account_salt("+15551234567");
+        assert_eq!(salt, account_salt("+1 555-123-4567"));
+        assert_ne!(salt, account_salt("+15551234568"));
+        assert_ne!(salt, U256::zero());
+
+        // Same salt -> same counterfactual account for the same owner
+        let factory = Address::from_str("0x9406Cc6185a346906296840746125a0E44976454").unwrap();
+        let owner = Address::from_str("0x1111111111111111111111111111111111111111").unwrap();
+        assert_eq!(
+            account_init_code(factory, owner, salt),
+            account_init_code(factory, owner, account_salt("+15551234567"))
+        );
+        assert_ne!(
+            account_init_code(factory, owner, salt),
+            account_init_code(factory, owner, account_salt("+15551234568"))
+        );
+    }
+
+    #[test]
+    fn test_account_init_code() {
+        let factory = Address::from_str("0x9406Cc6185a346906296840746125a0E44976454").unwrap();
+        let owner = Address::from_str("0x1111111111111111111111111111111111111111").unwrap();
+        let init_code = account_init_code(factory, owner, U256::zero());
+
+        // 20-byte factory, 4-byte selector, two 32-byte args
+        assert_eq!(init_code.len(), 20 + 4 + 64);
+        assert_eq!(&init_code[..20], factory.as_bytes());
+        assert_eq!(&init_code[20..24], &ethers::utils::id("createAccount(address,uint256)")[..]);
+    }
+
+    #[test]
+    fn test_gas_estimate_fills_limits() {
+        let response: serde_json::Value = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","id":1,"result":{
+                "preVerificationGas":"0xb3f4",
+                "verificationGasLimit":"0x6c2d8",
+                "callGasLimit":"0x88b8",
+                "verificationGas":"0x6c2d8"
+            }}"#,
+        )
+        .unwrap();
+        let estimate: GasEstimate = serde_json::from_value(response["result"].clone()).unwrap();
+        assert_eq!(estimate.call_gas_limit, U256::from(35_000));
+        assert_eq!(estimate.verification_gas_limit, U256::from(443_096));
+        assert_eq!(estimate.pre_verification_gas, U256::from(46_068));
+
+        let op = UserOperation {
+            sender: Address::from_str("0x1111111111111111111111111111111111111111").unwrap(),
+            nonce: U256::zero(),
+            init_code: Bytes::default(),
+            call_data: Bytes::default(),
+            call_gas_limit: U256::zero(),
+            verification_gas_limit: U256::zero(),
+            pre_verification_gas: U256::zero(),
+            max_fee_per_gas: U256::from(1_000_000_000),
+            max_priority_fee_per_gas: U256::from(100_000_000),
+            paymaster_and_data: Bytes::default(),
+            signature: DUMMY_SIGNATURE.parse().unwrap(),
+        };
+        let estimated = op.clone().with_gas(estimate);
+        assert_eq!(estimated.call_gas_limit, estimate.call_gas_limit);
+        assert_eq!(estimated.verification_gas_limit, estimate.verification_gas_limit);
+        assert_eq!(estimated.pre_verification_gas, estimate.pre_verification_gas);
+        // Everything else is untouched
+        assert_eq!(estimated.max_fee_per_gas, op.max_fee_per_gas);
+        assert_eq!(estimated.signature.len(), 65);
+    }
+
+    #[tokio::test]
+    async fn test_user_op_receipt_polling() {
+        use axum::{routing::post, Json, Router};
+        let tx_hash = format!("0x{}", "ab".repeat(32));
+        let reply_hash = tx_hash.clone();
+        let bundler = Router::new().route(
+            "/",
+            post(move |Json(request): Json<serde_json::Value>| {
+                let tx_hash = reply_hash.clone();
+                async move {
+                    assert_eq!(request["method"], "eth_getUserOperationReceipt");
+                    let result = if request["params"][0] == "0xmined" {
+                        serde_json::json!({
+                            "userOpHash": "0xmined",
+                            "success": true,
+                            "actualGasUsed": "0x5208",
+                            "receipt": { "transactionHash": tx_hash, "blockNumber": "0x10" }
+                        })
+                    } else {
+                        serde_json::Value::Null
+                    };
+                    Json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+                }
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(bundler).await;
+        let client = BundlerClient::new(format!("{}/", url));
+
+        let receipt = client.wait_for_user_op("0xmined", std::time::Duration::from_secs(10)).await.unwrap().unwrap();
+        assert!(receipt.success);
+        assert_eq!(receipt.actual_gas_used, U256::from(21_000));
+        assert_eq!(receipt.receipt.transaction_hash, H256::from_str(&tx_hash).unwrap());
+
+        assert!(client.get_user_op_receipt("0xpending").await.unwrap().is_none());
+        let waited = client.wait_for_user_op("0xpending", std::time::Duration::ZERO).await.unwrap();
+        assert!(waited.is_none());
+    }
 }
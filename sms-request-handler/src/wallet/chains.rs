@@ -1,4 +1,4 @@
-use ethers::providers::{Http, Provider};
+use ethers::providers::{Http, Middleware, Provider, ProviderError};
 use ethers::types::Address;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -91,6 +91,35 @@ impl Chain {
         }
     }
 
+    /// Decimals of the native token
+    pub fn native_decimals(&self) -> u8 {
+        match self {
+            Chain::PolygonAmoy | Chain::PolygonMainnet => 18,
+            Chain::BaseSepolia | Chain::BaseMainnet => 18,
+            Chain::EthereumSepolia | Chain::EthereumMainnet => 18,
+            Chain::ArbitrumSepolia | Chain::ArbitrumOne => 18,
+        }
+    }
+
+    /// Get block explorer base URL (no trailing slash)
+    pub fn explorer_base(&self) -> &'static str {
+        match self {
+            Chain::PolygonAmoy => "https://amoy.polygonscan.com",
+            Chain::PolygonMainnet => "https://polygonscan.com",
+            Chain::BaseSepolia => "https://sepolia.basescan.org",
+            Chain::BaseMainnet => "https://basescan.org",
+            Chain::EthereumSepolia => "https://sepolia.etherscan.io",
+            Chain::EthereumMainnet => "https://etherscan.io",
+            Chain::ArbitrumSepolia => "https://sepolia.arbiscan.io",
+            Chain::ArbitrumOne => "https://arbiscan.io",
+        }
+    }
+
+    /// Explorer page for a transaction
+    pub fn tx_url(&self, tx_hash: &str) -> String {
+        format!("{}/tx/{}", self.explorer_base(), tx_hash)
+    }
+
     /// Get USDC contract address (None if not deployed)
     pub fn usdc_address(&self) -> Option<Address> {
         let addr_str = match self {
@@ -137,6 +166,14 @@ impl Chain {
         ]
     }
 
+    /// Look up a chain by its display name (as stored with deposits)
+    pub fn from_name(name: &str) -> Option<Chain> {
+        Chain::testnets()
+            .into_iter()
+            .chain(Chain::mainnets())
+            .find(|c| c.name() == name)
+    }
+
     /// Parse chain from user input (case-insensitive)
     pub fn from_input(input: &str) -> Option<Chain> {
         match input.to_uppercase().as_str() {
@@ -166,6 +203,8 @@ pub type ChainProvider = Provider<Http>;
 #[derive(Clone)]
 pub struct MultiChainProvider {
     providers: std::collections::HashMap<Chain, Arc<ChainProvider>>,
+    /// Where ENS names are resolved (None = native resolution off)
+    ens: Option<Arc<ChainProvider>>,
 }
 
 impl MultiChainProvider {
@@ -180,7 +219,7 @@ impl MultiChainProvider {
             }
         }
 
-        Self { providers }
+        Self { providers, ens: None }
     }
 
     /// Create provider with specific chains
@@ -193,7 +232,34 @@ impl MultiChainProvider {
             }
         }
 
-        Self { providers }
+        Self { providers, ens: None }
+    }
+
+    /// Resolve ENS names against `chain`'s registry (None turns it off)
+    pub fn with_ens_chain(mut self, chain: Option<Chain>) -> Self {
+        self.ens = chain.and_then(|chain| match self.providers.get(&chain) {
+            Some(provider) => Some(provider.clone()),
+            None => Provider::<Http>::try_from(chain.rpc_url()).ok().map(Arc::new),
+        });
+        self
+    }
+
+    /// Address an ENS name points at, None when it isn't registered or has
+    /// no address set. Errors when native resolution is off or the RPC fails.
+    pub async fn resolve_ens(&self, name: &str) -> Result<Option<Address>, ProviderError> {
+        match self.ens {
+            Some(ref provider) => resolve_name(provider.as_ref(), name).await,
+            None => Err(ProviderError::CustomError("ENS resolution is off".to_string())),
+        }
+    }
+
+    /// Primary ENS name of `address` (its reverse record, checked against the
+    /// forward one), None when it has none. Errors like `resolve_ens`.
+    pub async fn lookup_address(&self, address: Address) -> Result<Option<String>, ProviderError> {
+        match self.ens {
+            Some(ref provider) => lookup_name(provider.as_ref(), address).await,
+            None => Err(ProviderError::CustomError("ENS resolution is off".to_string())),
+        }
     }
 
     /// Get provider for a specific chain
@@ -214,6 +280,13 @@ impl MultiChainProvider {
         provider
     }
 
+    /// Use `provider` for `chain` (a mock RPC in tests)
+    #[cfg(test)]
+    pub fn with_provider(mut self, chain: Chain, provider: ChainProvider) -> Self {
+        self.providers.insert(chain, Arc::new(provider));
+        self
+    }
+
     /// List available chains
     pub fn available_chains(&self) -> Vec<Chain> {
         self.providers.keys().copied().collect()
@@ -226,6 +299,34 @@ impl Default for MultiChainProvider {
     }
 }
 
+/// `name` resolved through the ENS registry `provider` talks to. A name with
+/// no resolver, or a resolver with no address, is None rather than an error.
+pub async fn resolve_name<M>(provider: &M, name: &str) -> Result<Option<Address>, ProviderError>
+where
+    M: Middleware<Error = ProviderError>,
+{
+    match provider.resolve_name(name).await {
+        Ok(address) if address.is_zero() => Ok(None),
+        Ok(address) => Ok(Some(address)),
+        Err(ProviderError::EnsError(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// `address`'s reverse record through `provider`'s registry. No record, or
+/// one naming someone else's name, is None rather than an error.
+pub async fn lookup_name<M>(provider: &M, address: Address) -> Result<Option<String>, ProviderError>
+where
+    M: Middleware<Error = ProviderError>,
+{
+    match provider.lookup_address(address).await {
+        Ok(name) if name.is_empty() => Ok(None),
+        Ok(name) => Ok(Some(name)),
+        Err(ProviderError::EnsError(_) | ProviderError::EnsNotOwned(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -245,6 +346,27 @@ mod tests {
         assert_eq!(Chain::from_input("unknown"), None);
     }
 
+    #[test]
+    fn test_explorer_and_decimals() {
+        let expected = [
+            (Chain::PolygonAmoy, "https://amoy.polygonscan.com"),
+            (Chain::PolygonMainnet, "https://polygonscan.com"),
+            (Chain::BaseSepolia, "https://sepolia.basescan.org"),
+            (Chain::BaseMainnet, "https://basescan.org"),
+            (Chain::EthereumSepolia, "https://sepolia.etherscan.io"),
+            (Chain::EthereumMainnet, "https://etherscan.io"),
+            (Chain::ArbitrumSepolia, "https://sepolia.arbiscan.io"),
+            (Chain::ArbitrumOne, "https://arbiscan.io"),
+        ];
+        for (chain, explorer) in expected {
+            assert_eq!(chain.explorer_base(), explorer);
+            assert_eq!(chain.native_decimals(), 18);
+        }
+        assert_eq!(Chain::BaseMainnet.tx_url("0xabc"), "https://basescan.org/tx/0xabc");
+        // Aliases still resolve
+        assert_eq!(Chain::from_input("sepolia").map(|c| c.explorer_base()), Some("https://sepolia.etherscan.io"));
+    }
+
     #[test]
     fn test_usdc_addresses() {
         assert!(Chain::PolygonMainnet.usdc_address().is_some());
@@ -257,4 +379,25 @@ mod tests {
         let provider = MultiChainProvider::new();
         assert!(provider.get(Chain::PolygonAmoy).is_some());
     }
+
+    #[tokio::test]
+    async fn test_resolve_name_through_registry() {
+        use ethers::types::{Bytes, H256};
+        let word = |address: Address| Bytes::from(H256::from(address).as_bytes().to_vec());
+        let owner: Address = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8".parse().unwrap();
+
+        // Served last-pushed first: registry's resolver, supportsInterface(addr), addr(node)
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Bytes, _>(word(owner)).unwrap();
+        mock.push::<Bytes, _>(Bytes::from(H256::from_low_u64_be(1).as_bytes().to_vec())).unwrap();
+        mock.push::<Bytes, _>(word(Address::repeat_byte(0x42))).unwrap();
+        assert_eq!(resolve_name(&provider, "alice.ttcip.eth").await.unwrap(), Some(owner));
+
+        // No resolver set in the registry: not registered, not an error
+        mock.push::<Bytes, _>(word(Address::zero())).unwrap();
+        assert_eq!(resolve_name(&provider, "nobody.ttcip.eth").await.unwrap(), None);
+
+        // Off unless an ENS chain is configured
+        assert!(MultiChainProvider::with_chains(&[]).resolve_ens("alice.ttcip.eth").await.is_err());
+    }
 }
@@ -0,0 +1,96 @@
+//! At-rest encryption of users' private keys: AES-256-GCM under a key
+//! derived (HKDF-SHA256) from the ENCRYPTION_KEY secret
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use super::WalletError;
+
+/// Binds derived keys to this use, so the secret can't be replayed elsewhere
+const HKDF_INFO: &[u8] = b"textchain user private key v1";
+const NONCE_LEN: usize = 12;
+
+/// Encrypt a private key; the hex result is the random nonce followed by the ciphertext and tag
+pub fn encrypt_key(plaintext: &[u8; 32], passphrase: &str) -> Result<String, WalletError> {
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let ciphertext = cipher(passphrase)?
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| encryption_error("encrypt failed"))?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(hex::encode(sealed))
+}
+
+/// Decrypt what `encrypt_key` produced; fails if it was altered or the passphrase differs
+pub fn decrypt_key(ciphertext: &str, passphrase: &str) -> Result<[u8; 32], WalletError> {
+    let sealed = hex::decode(ciphertext).map_err(|_| encryption_error("invalid hex"))?;
+    if sealed.len() <= NONCE_LEN {
+        return Err(encryption_error("ciphertext too short"));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = cipher(passphrase)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| encryption_error("authentication failed"))?;
+    plaintext.try_into().map_err(|_| encryption_error("not a 32-byte key"))
+}
+
+/// Key as stored in `users.encrypted_private_key`. Rows written before keys
+/// were encrypted hold the plain hex key and are still read.
+pub fn decrypt_stored_key(stored: &str, passphrase: &str) -> Result<[u8; 32], WalletError> {
+    let legacy = stored.trim_start_matches("0x");
+    if legacy.len() == 64 {
+        let key = hex::decode(legacy).map_err(|_| encryption_error("invalid hex"))?;
+        return key.try_into().map_err(|_| encryption_error("not a 32-byte key"));
+    }
+    decrypt_key(stored, passphrase)
+}
+
+fn cipher(passphrase: &str) -> Result<Aes256Gcm, WalletError> {
+    if passphrase.is_empty() {
+        return Err(encryption_error("ENCRYPTION_KEY is not set"));
+    }
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, passphrase.as_bytes())
+        .expand(HKDF_INFO, &mut key)
+        .map_err(|_| encryption_error("key derivation failed"))?;
+    Aes256Gcm::new_from_slice(&key).map_err(|_| encryption_error("invalid key"))
+}
+
+fn encryption_error(msg: &str) -> WalletError {
+    WalletError::CreationError(format!("key encryption: {}", msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_tamper_detection() {
+        let key: [u8; 32] = rand::random();
+        let sealed = encrypt_key(&key, "secret").unwrap();
+        assert_eq!(decrypt_key(&sealed, "secret").unwrap(), key);
+        assert_eq!(decrypt_stored_key(&sealed, "secret").unwrap(), key);
+        // A fresh nonce every time
+        assert_ne!(encrypt_key(&key, "secret").unwrap(), sealed);
+
+        // Any flipped bit fails the GCM tag check, as does the wrong secret
+        let mut tampered = hex::decode(&sealed).unwrap();
+        tampered[NONCE_LEN + 3] ^= 0x01;
+        assert!(decrypt_key(&hex::encode(tampered), "secret").is_err());
+        assert!(decrypt_key(&sealed, "other secret").is_err());
+
+        // Keys stored before encryption are still readable
+        assert_eq!(decrypt_stored_key(&format!("0x{}", hex::encode(key)), "secret").unwrap(), key);
+    }
+
+    #[test]
+    fn test_empty_secret_is_refused() {
+        let key: [u8; 32] = rand::random();
+        assert!(encrypt_key(&key, "").is_err());
+        let sealed = encrypt_key(&key, "secret").unwrap();
+        assert!(decrypt_key(&sealed, "").is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Web3 Secret Storage (keystore v3) encoding, so users can import their
+//! wallet into standard wallets without ever seeing the raw key
+
+use aes::Aes128;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use ethers::types::Address;
+use serde_json::{json, Value};
+
+use super::WalletError;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+/// scrypt cost (N = 2^13). Lighter than geth's default so an export doesn't
+/// stall the SMS handler; still far beyond brute-forcing a decent passphrase.
+const SCRYPT_LOG_N: u8 = 13;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const DKLEN: usize = 32;
+
+/// Encrypt a private key into a keystore v3 JSON document
+pub fn encrypt_keystore(private_key: &[u8; 32], address: Address, passphrase: &str) -> Result<Value, WalletError> {
+    let salt: [u8; 32] = rand::random();
+    let iv: [u8; 16] = rand::random();
+    let derived = derive_key(passphrase, &salt, SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P)?;
+
+    let mut ciphertext = private_key.to_vec();
+    Aes128Ctr::new(derived[..16].into(), (&iv).into()).apply_keystream(&mut ciphertext);
+
+    Ok(json!({
+        "version": 3,
+        "id": uuid::Uuid::new_v4().to_string(),
+        "address": hex::encode(address.as_bytes()),
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": hex::encode(iv) },
+            "ciphertext": hex::encode(&ciphertext),
+            "kdf": "scrypt",
+            "kdfparams": {
+                "dklen": DKLEN,
+                "n": 1u64 << SCRYPT_LOG_N,
+                "r": SCRYPT_R,
+                "p": SCRYPT_P,
+                "salt": hex::encode(salt)
+            },
+            "mac": hex::encode(mac(&derived, &ciphertext))
+        }
+    }))
+}
+
+/// Decrypt a keystore v3 document. Fails on a wrong passphrase (MAC mismatch).
+pub fn decrypt_keystore(keystore: &Value, passphrase: &str) -> Result<[u8; 32], WalletError> {
+    let crypto = &keystore["crypto"];
+    if crypto["kdf"] != "scrypt" || crypto["cipher"] != "aes-128-ctr" {
+        return Err(keystore_error("unsupported kdf or cipher"));
+    }
+
+    let params = &crypto["kdfparams"];
+    let n = params["n"].as_u64().filter(|n| n.is_power_of_two()).ok_or_else(|| keystore_error("invalid n"))?;
+    let r = params["r"].as_u64().and_then(|r| u32::try_from(r).ok()).ok_or_else(|| keystore_error("invalid r"))?;
+    let p = params["p"].as_u64().and_then(|p| u32::try_from(p).ok()).ok_or_else(|| keystore_error("invalid p"))?;
+    let salt = hex_field(&params["salt"])?;
+    let iv = hex_field(&crypto["cipherparams"]["iv"])?;
+    let ciphertext = hex_field(&crypto["ciphertext"])?;
+    let expected_mac = hex_field(&crypto["mac"])?;
+
+    let derived = derive_key(passphrase, &salt, n.trailing_zeros() as u8, r, p)?;
+    if mac(&derived, &ciphertext)[..] != expected_mac[..] {
+        return Err(keystore_error("wrong passphrase"));
+    }
+    if iv.len() != 16 || ciphertext.len() != 32 {
+        return Err(keystore_error("invalid ciphertext"));
+    }
+
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&ciphertext);
+    Aes128Ctr::new(derived[..16].into(), iv[..].into()).apply_keystream(&mut key);
+    Ok(key)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], log_n: u8, r: u32, p: u32) -> Result<[u8; DKLEN], WalletError> {
+    let params = scrypt::Params::new(log_n, r, p).map_err(|e| keystore_error(&e.to_string()))?;
+    let mut derived = [0u8; DKLEN];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut derived).map_err(|e| keystore_error(&e.to_string()))?;
+    Ok(derived)
+}
+
+/// keccak256(derived_key[16..32] ++ ciphertext)
+fn mac(derived: &[u8; DKLEN], ciphertext: &[u8]) -> [u8; 32] {
+    let mut input = derived[16..32].to_vec();
+    input.extend_from_slice(ciphertext);
+    ethers::utils::keccak256(input)
+}
+
+fn hex_field(value: &Value) -> Result<Vec<u8>, WalletError> {
+    value
+        .as_str()
+        .and_then(|s| hex::decode(s.trim_start_matches("0x")).ok())
+        .ok_or_else(|| keystore_error("invalid hex field"))
+}
+
+fn keystore_error(msg: &str) -> WalletError {
+    WalletError::CreationError(format!("keystore: {}", msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::UserWallet;
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let wallet = UserWallet::create_new().unwrap();
+        let keystore = encrypt_keystore(&wallet.private_key_bytes(), wallet.address, "correct horse").unwrap();
+
+        assert_eq!(keystore["version"], 3);
+        assert_eq!(keystore["address"], hex::encode(wallet.address.as_bytes()));
+        assert_eq!(decrypt_keystore(&keystore, "correct horse").unwrap(), wallet.private_key_bytes());
+        assert!(decrypt_keystore(&keystore, "wrong horse").is_err());
+    }
+}
@@ -1,5 +1,7 @@
 pub mod aa;
 pub mod chains;
+pub mod encryption;
+pub mod keystore;
 pub mod provider;
 pub mod tokens;
 pub mod wallet;
@@ -1,5 +1,7 @@
 use ethers::providers::{Http, Middleware, Provider};
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use super::chains::{Chain, MultiChainProvider};
 
@@ -32,6 +34,42 @@ pub fn create_chain_provider(chain: Chain) -> Arc<Provider<Http>> {
     Arc::new(Provider::<Http>::try_from(chain.rpc_url()).expect("Invalid RPC URL"))
 }
 
+/// Latest block per chain, cached briefly so a HISTORY reply
+/// doesn't hit the RPC once per line
+#[derive(Clone)]
+pub struct BlockHeightCache {
+    heights: Arc<Mutex<HashMap<Chain, (u64, Instant)>>>,
+    ttl: Duration,
+}
+
+impl BlockHeightCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            heights: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+        }
+    }
+
+    /// Current block height, None if the RPC can't be reached
+    pub async fn head(&self, chain: Chain) -> Option<u64> {
+        if let Some((height, at)) = self.heights.lock().unwrap().get(&chain) {
+            if at.elapsed() < self.ttl {
+                return Some(*height);
+            }
+        }
+
+        let height = create_chain_provider(chain).get_block_number().await.ok()?.as_u64();
+        self.heights.lock().unwrap().insert(chain, (height, Instant::now()));
+        Some(height)
+    }
+
+    /// Pin a chain's height (tests have no RPC)
+    #[cfg(test)]
+    pub fn set(&self, chain: Chain, height: u64) {
+        self.heights.lock().unwrap().insert(chain, (height, Instant::now()));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
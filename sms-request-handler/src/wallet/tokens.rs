@@ -1,6 +1,6 @@
 use ethers::prelude::*;
 use ethers::contract::abigen;
-use super::chains::{Chain, ChainProvider};
+use super::chains::{Chain, ChainProvider, MultiChainProvider};
 use std::sync::Arc;
 
 // Generate ERC20 contract bindings for USDC
@@ -49,6 +49,18 @@ pub fn format_token_balance(balance: U256, decimals: u8) -> String {
     format!("{}.{}", integer_part, decimal_part)
 }
 
+/// Format an amount for SMS: rounded to `precision` decimals, trailing zeros trimmed
+pub fn format_display_amount(amount: f64, precision: usize) -> String {
+    let rounded = format!("{:.*}", precision, amount);
+    let trimmed = if rounded.contains('.') {
+        rounded.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &rounded
+    };
+    // -0.00001 rounds to "-0"
+    if trimmed == "-0" { "0".to_string() } else { trimmed.to_string() }
+}
+
 /// Get USDC balance for an address on a specific chain
 pub async fn get_usdc_balance(
     provider: Arc<ChainProvider>,
@@ -91,7 +103,7 @@ pub async fn get_native_balance(
         chain,
         symbol: chain.native_token().to_string(),
         balance,
-        decimals: 18,
+        decimals: chain.native_decimals(),
     })
 }
 
@@ -137,6 +149,23 @@ pub async fn get_chain_balances(
     Ok(ChainBalances { chain, native, usdc })
 }
 
+/// Balances of `address` on each of `chains`, queried concurrently and in
+/// the order given. A chain without a provider or whose RPC fails gets an Err.
+pub async fn get_balances_all_chains(
+    providers: &MultiChainProvider,
+    chains: &[Chain],
+    address: Address,
+) -> Vec<(Chain, Result<ChainBalances, String>)> {
+    let lookups = chains.iter().map(|&chain| async move {
+        let balances = match providers.get(chain) {
+            Some(provider) => get_chain_balances(provider, chain, address).await,
+            None => Err(format!("no provider for {}", chain.name())),
+        };
+        (chain, balances)
+    });
+    futures::future::join_all(lookups).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -156,6 +185,14 @@ mod tests {
         assert_eq!(format_token_balance(one_eth, 18), "1.000000");
     }
 
+    #[test]
+    fn test_format_display_amount() {
+        assert_eq!(format_display_amount(1.23456789, 4), "1.2346");
+        assert_eq!(format_display_amount(10.0, 4), "10");
+        assert_eq!(format_display_amount(0.5, 4), "0.5");
+        assert_eq!(format_display_amount(-0.00001, 4), "0");
+    }
+
     #[test]
     fn test_chain_balances_format() {
         let balances = ChainBalances {
@@ -1,10 +1,10 @@
 use ethers::core::k256::ecdsa::SigningKey;
 use ethers::prelude::*;
-use ethers::signers::Wallet;
+use ethers::signers::{coins_bip39::English, MnemonicBuilder, Wallet};
 use rand::rngs::OsRng;
 use thiserror::Error;
 
-use super::AmoyProvider;
+use super::{AmoyProvider, EntryPointConfig, UserOperation};
 
 #[derive(Error, Debug)]
 pub enum WalletError {
@@ -35,6 +35,20 @@ impl UserWallet {
         Ok(Self { address, private_key })
     }
 
+    /// Derive the wallet at BIP-44 path `m/44'/60'/0'/0/{index}` of a master
+    /// mnemonic, the same account standard wallets show for that index
+    pub fn from_mnemonic(mnemonic: &str, index: u32) -> Result<Self, WalletError> {
+        let wallet = MnemonicBuilder::<English>::default()
+            .phrase(mnemonic.trim())
+            .derivation_path(&format!("m/44'/60'/0'/0/{}", index))
+            .and_then(|builder| builder.build())
+            .map_err(|e| WalletError::CreationError(e.to_string()))?;
+        let address = wallet.address();
+        let private_key: [u8; 32] = wallet.signer().to_bytes().into();
+
+        Ok(Self { address, private_key })
+    }
+
     /// Get the private key bytes (for encrypted storage)
     pub fn private_key_bytes(&self) -> [u8; 32] {
         self.private_key
@@ -84,15 +98,32 @@ impl UserWallet {
         }
     }
 
-    /// Get the deterministic Smart Account address for this signer
-    /// using SimpleAccountFactory
+    /// Sign a 32-byte hash as an EIP-191 personal message (what SimpleAccount verifies)
+    pub fn sign_eip191_hash(&self, hash: [u8; 32]) -> Result<Signature, WalletError> {
+        let signing_key = SigningKey::from_bytes((&self.private_key).into())
+            .map_err(|e| WalletError::CreationError(e.to_string()))?;
+        let wallet: Wallet<SigningKey> = signing_key.into();
+        wallet
+            .sign_hash(ethers::utils::hash_message(hash))
+            .map_err(|e| WalletError::CreationError(e.to_string()))
+    }
+
+    /// Signature for `op`'s `signature` field: its hash for this entry point
+    /// and chain, signed as a personal message, 65 bytes r ‖ s ‖ v
+    pub fn sign_user_op(&self, op: &UserOperation, entry_point: &EntryPointConfig, chain_id: u64) -> Result<Bytes, WalletError> {
+        let signature = self.sign_eip191_hash(op.hash(entry_point, chain_id))?;
+        Ok(signature.to_vec().into())
+    }
+
+    /// Get the deterministic Smart Account address for this signer and
+    /// phone using SimpleAccountFactory (salt from `account_salt`)
     pub async fn get_smart_account_address(
         &self,
         factory_address: Address,
+        phone: &str,
         provider: std::sync::Arc<AmoyProvider>,
     ) -> Result<Address, Box<dyn std::error::Error + Send + Sync>> {
-        // Salt = 0 for default account
-        let salt = U256::zero();
+        let salt = crate::wallet::aa::account_salt(phone);
         crate::wallet::aa::get_smart_account_address(factory_address, self.address, salt, provider).await
     }
 }
@@ -108,6 +139,26 @@ mod tests {
         assert_eq!(wallet.address_string().len(), 42);
     }
 
+    #[test]
+    fn test_derive_from_mnemonic() {
+        // Hardhat/Anvil's default accounts
+        let mnemonic = "test test test test test test test test test test test junk";
+        let expected = [
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266",
+            "0x70997970C51812dc3A010C7d01b50e0d17dc79C8",
+            "0x3C44CdDdB6a900fa2b585dd299e03d12FA4293BC",
+        ];
+        for (index, address) in expected.iter().enumerate() {
+            let wallet = UserWallet::from_mnemonic(mnemonic, index as u32).unwrap();
+            assert_eq!(format!("{:?}", wallet.address), address.to_lowercase());
+        }
+        assert_eq!(
+            hex::encode(UserWallet::from_mnemonic(mnemonic, 0).unwrap().private_key_bytes()),
+            "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+        );
+        assert!(UserWallet::from_mnemonic("not a mnemonic", 0).is_err());
+    }
+
     #[test]
     fn test_restore_wallet() {
         let wallet1 = UserWallet::create_new().unwrap();
@@ -117,6 +168,38 @@ mod tests {
         assert_eq!(wallet1.address, wallet2.address);
     }
 
+    #[test]
+    fn test_sign_eip191_hash_recovers_owner() {
+        let wallet = UserWallet::create_new().unwrap();
+        let hash = [7u8; 32];
+        let signature = wallet.sign_eip191_hash(hash).unwrap();
+        assert_eq!(signature.recover(&hash[..]).unwrap(), wallet.address);
+    }
+
+    #[test]
+    fn test_sign_user_op_recovers_owner() {
+        let wallet = UserWallet::create_new().unwrap();
+        let entry_point = EntryPointConfig::parse("", "0.7").unwrap();
+        let op = UserOperation {
+            sender: Address::repeat_byte(0x11),
+            nonce: U256::zero(),
+            init_code: Bytes::default(),
+            call_data: Bytes::default(),
+            call_gas_limit: U256::from(35_000),
+            verification_gas_limit: U256::from(500_000),
+            pre_verification_gas: U256::from(60_000),
+            max_fee_per_gas: U256::from(1_000_000_000),
+            max_priority_fee_per_gas: U256::from(1_000_000_000),
+            paymaster_and_data: Bytes::default(),
+            signature: Bytes::default(),
+        };
+
+        let signature = wallet.sign_user_op(&op, &entry_point, 80002).unwrap();
+        assert_eq!(signature.len(), 65);
+        let signature = Signature::try_from(signature.as_ref()).unwrap();
+        assert_eq!(signature.recover(&op.hash(&entry_point, 80002)[..]).unwrap(), wallet.address);
+    }
+
     #[test]
     fn test_format_balance() {
         // 1 MATIC = 10^18 wei
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use reqwest::Client;
+use reqwest::{Client, Response};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenChannelResponse {
@@ -25,11 +26,34 @@ pub struct CloseResponse {
     pub error: Option<String>,
 }
 
+/// Where a channel is in its lifecycle, as the Yellow service reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelStatus {
+    Open,
+    Settling,
+    Closed,
+    Disputed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelStatusResponse {
+    pub success: bool,
+    pub status: Option<ChannelStatus>,
+    pub balance: Option<String>,
+    pub error: Option<String>,
+}
 
 #[derive(Clone)]
 pub struct YellowClient {
     client: Client,
     base_url: String,
+    /// Extra attempts after a connection error or 5xx
+    max_retries: u32,
+    /// Wait before the first retry, doubling for each one after
+    retry_base_delay: Duration,
+    /// How often `wait_for_settlement` checks a channel
+    poll_interval: Duration,
 }
 
 impl YellowClient {
@@ -37,21 +61,55 @@ impl YellowClient {
         Self {
             client: Client::new(),
             base_url,
+            max_retries: 3,
+            retry_base_delay: Duration::from_millis(250),
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+
+    /// Override how often `wait_for_settlement` checks a channel
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Override how often and how patiently failed calls are retried
+    pub fn with_retry(mut self, max_retries: u32, base_delay: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_base_delay = base_delay;
+        self
+    }
+
+    /// POST to the Yellow service, retrying with exponential backoff when
+    /// it couldn't be reached or answered 5xx. Other errors (including
+    /// timeouts, where the call may have gone through) are returned at once.
+    async fn post_with_retry(&self, path: &str, payload: &serde_json::Value) -> Result<Response, String> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut attempt = 0;
+        loop {
+            let retryable = match self.client.post(&url).json(payload).send().await {
+                Ok(res) if res.status().is_server_error() => format!("Yellow service returned {}", res.status()),
+                Ok(res) => return Ok(res),
+                Err(e) if e.is_connect() => e.to_string(),
+                Err(e) => return Err(e.to_string()),
+            };
+            if attempt >= self.max_retries {
+                return Err(retryable);
+            }
+            let delay = self.retry_base_delay * 2u32.pow(attempt);
+            tracing::warn!(url = %url, attempt = attempt + 1, ?delay, "Yellow call failed, retrying: {}", retryable);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
         }
     }
 
     /// Open a new state channel
     pub async fn open_channel(&self, amount: String) -> Result<String, String> {
-        let url = format!("{}/channel/open", self.base_url);
         let payload = serde_json::json!({
             "amount": amount
         });
 
-        let res = self.client.post(&url)
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+        let res = self.post_with_retry("/channel/open", &payload).await?;
 
         let body: OpenChannelResponse = res.json().await.map_err(|e| e.to_string())?;
         
@@ -64,17 +122,12 @@ impl YellowClient {
 
     /// Send update within channel
     pub async fn send_update(&self, channel_id: String, amount: String) -> Result<String, String> {
-        let url = format!("{}/channel/send", self.base_url);
         let payload = serde_json::json!({
             "channelId": channel_id,
             "amount": amount
         });
 
-        let res = self.client.post(&url)
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+        let res = self.post_with_retry("/channel/send", &payload).await?;
         
         let body: SendResponse = res.json().await.map_err(|e| e.to_string())?;
 
@@ -87,16 +140,11 @@ impl YellowClient {
 
     /// Close the channel
     pub async fn close_channel(&self, channel_id: Option<String>) -> Result<String, String> {
-        let url = format!("{}/channel/close", self.base_url);
         let payload = serde_json::json!({
             "channelId": channel_id
         });
 
-        let res = self.client.post(&url)
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+        let res = self.post_with_retry("/channel/close", &payload).await?;
             
         let body: CloseResponse = res.json().await.map_err(|e| e.to_string())?;
 
@@ -106,4 +154,120 @@ impl YellowClient {
             Err(body.error.unwrap_or("Failed to close".to_string()))
         }
     }
+
+    /// Channel's status and last reported balance
+    pub async fn get_channel_status(&self, channel_id: &str) -> Result<(ChannelStatus, Option<String>), String> {
+        let url = format!("{}/channel/{}/status", self.base_url, channel_id);
+
+        let res = self.client.get(&url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let body: ChannelStatusResponse = res.json().await.map_err(|e| e.to_string())?;
+
+        match (body.success, body.status) {
+            (true, Some(status)) => Ok((status, body.balance)),
+            _ => Err(body.error.unwrap_or("Unknown channel status".to_string())),
+        }
+    }
+
+    /// Poll until the channel is closed, returning its final balance, or
+    /// None if `timeout` passes first. A disputed channel is an error: it
+    /// won't settle until the dispute is resolved.
+    pub async fn wait_for_settlement(&self, channel_id: &str, timeout: Duration) -> Result<Option<String>, String> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            match self.get_channel_status(channel_id).await? {
+                (ChannelStatus::Closed, balance) => return Ok(Some(balance.unwrap_or_default())),
+                (ChannelStatus::Disputed, _) => return Err(format!("Channel {} is disputed", channel_id)),
+                (ChannelStatus::Open | ChannelStatus::Settling, _) => {}
+            }
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            tokio::time::sleep(remaining.min(self.poll_interval)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::Path, http::StatusCode, routing::{get, post}, Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Yellow service answering `status` to the first `failures` calls
+    async fn flaky_yellow(failures: usize, status: StatusCode) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let router = Router::new().route(
+            "/channel/close",
+            post(move || {
+                let counter = counter.clone();
+                async move {
+                    if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                        return (status, Json(serde_json::json!({ "success": false, "error": "upstream" })));
+                    }
+                    (StatusCode::OK, Json(serde_json::json!({ "success": true, "txHash": "0xc105e" })))
+                }
+            }),
+        );
+        (crate::test_support::spawn_mock_backend(router).await, calls)
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() {
+        let (url, calls) = flaky_yellow(2, StatusCode::BAD_GATEWAY).await;
+        let client = YellowClient::new(url).with_retry(3, Duration::from_millis(1));
+        assert_eq!(client.close_channel(Some("0xabc".to_string())).await.unwrap(), "Channel Closed. Tx: 0xc105e");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Out of retries, the last failure is reported
+        let (url, calls) = flaky_yellow(usize::MAX, StatusCode::BAD_GATEWAY).await;
+        let client = YellowClient::new(url).with_retry(2, Duration::from_millis(1));
+        assert!(client.close_channel(None).await.unwrap_err().contains("502"));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Client errors aren't retried
+        let (url, calls) = flaky_yellow(1, StatusCode::BAD_REQUEST).await;
+        let client = YellowClient::new(url).with_retry(3, Duration::from_millis(1));
+        assert_eq!(client.close_channel(None).await.unwrap_err(), "upstream");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_settlement_polls_until_closed() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let router = Router::new().route(
+            "/channel/:id/status",
+            get(move |Path(id): Path<String>| {
+                let counter = counter.clone();
+                async move {
+                    let status = match (id.as_str(), counter.fetch_add(1, Ordering::SeqCst)) {
+                        ("0xdispute", _) => "disputed",
+                        ("0xsettle", 0) => "open",
+                        ("0xsettle", 1) => "settling",
+                        ("0xsettle", _) => "closed",
+                        _ => "open",
+                    };
+                    Json(serde_json::json!({ "success": true, "status": status, "balance": "4.5" }))
+                }
+            }),
+        );
+        let url = crate::test_support::spawn_mock_backend(router).await;
+        let client = YellowClient::new(url).with_poll_interval(Duration::from_millis(1));
+
+        assert_eq!(client.get_channel_status("0xopen").await.unwrap(), (ChannelStatus::Open, Some("4.5".to_string())));
+        calls.store(0, Ordering::SeqCst);
+        let settled = client.wait_for_settlement("0xsettle", Duration::from_secs(5)).await.unwrap();
+        assert_eq!(settled, Some("4.5".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        assert_eq!(client.wait_for_settlement("0xopen", Duration::ZERO).await.unwrap(), None);
+        assert!(client.wait_for_settlement("0xdispute", Duration::from_secs(5)).await.is_err());
+    }
 }